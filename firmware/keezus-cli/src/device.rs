@@ -0,0 +1,269 @@
+//! Request/response handling on top of a `Transport`.
+
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+
+use anyhow::{bail, Result};
+use keezus_protocol::{
+    CanvasText, DateTime, KeyAction, KeymapChunk, LatencySummary, PixelRun, Rect, Request, Response, SettingId,
+    ShortText, StatsSummary, COUNT_CHUNK, KEYMAP_CHUNK, PIXEL_RUN_LEN, REPORT_LEN,
+};
+
+use crate::transport::Transport;
+
+const RESPONSE_TIMEOUT: Duration = Duration::from_millis(1000);
+
+pub struct KeymapInfo {
+    pub layers: usize,
+    pub rows: usize,
+    pub cols: usize,
+}
+
+impl KeymapInfo {
+    pub fn len(&self) -> usize {
+        self.layers * self.rows * self.cols
+    }
+}
+
+pub struct Device<T: Transport> {
+    transport: T,
+    /// Events that arrived while waiting for a response, for `next_event`
+    events: VecDeque<Response>,
+}
+
+impl<T: Transport> Device<T> {
+    pub fn new(transport: T) -> Device<T> {
+        Device {
+            transport,
+            events: VecDeque::new(),
+        }
+    }
+
+    /// Sends a request and waits for its response. Events that arrive in the
+    /// meantime are kept for `next_event`.
+    pub fn request(&mut self, request: Request) -> Result<Response> {
+        let mut packet = [0u8; REPORT_LEN];
+        request.encode(&mut packet);
+        self.transport.write(&packet)?;
+
+        let deadline = Instant::now() + RESPONSE_TIMEOUT;
+        loop {
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            let packet = match self.transport.read(remaining)? {
+                Some(packet) => packet,
+                None => bail!("timed out waiting for a response to {:?}", request.command()),
+            };
+            let response = match Response::decode(&packet) {
+                Ok(response) => response,
+                Err(e) => bail!("malformed response: {:?}", e),
+            };
+            if response.is_event() {
+                self.events.push_back(response);
+                continue;
+            }
+            if response.command() != request.command() {
+                bail!("expected a response to {:?}, got {:?}", request.command(), response);
+            }
+            if let Response::Error(command, status) = response {
+                bail!("{:?} failed: {:?}", command, status);
+            }
+            return Ok(response);
+        }
+    }
+
+    /// Waits for the next unsolicited event
+    pub fn next_event(&mut self, timeout: Duration) -> Result<Option<Response>> {
+        if let Some(event) = self.events.pop_front() {
+            return Ok(Some(event));
+        }
+        match self.transport.read(timeout)? {
+            Some(packet) => match Response::decode(&packet) {
+                Ok(response) if response.is_event() => Ok(Some(response)),
+                Ok(_) => Ok(None),
+                Err(e) => bail!("malformed event: {:?}", e),
+            },
+            None => Ok(None),
+        }
+    }
+
+    pub fn version(&mut self) -> Result<(u8, String)> {
+        match self.request(Request::GetVersion)? {
+            Response::Version {
+                protocol,
+                major,
+                minor,
+                patch,
+            } => Ok((protocol, format!("{}.{}.{}", major, minor, patch))),
+            other => bail!("unexpected response {:?}", other),
+        }
+    }
+
+    pub fn keymap_info(&mut self) -> Result<KeymapInfo> {
+        match self.request(Request::GetKeymapInfo)? {
+            Response::KeymapInfo { layers, rows, cols } => Ok(KeymapInfo {
+                layers: layers as usize,
+                rows: rows as usize,
+                cols: cols as usize,
+            }),
+            other => bail!("unexpected response {:?}", other),
+        }
+    }
+
+    pub fn read_keymap(&mut self, len: usize) -> Result<Vec<KeyAction>> {
+        let mut keymap = Vec::with_capacity(len);
+        while keymap.len() < len {
+            let count = (len - keymap.len()).min(KEYMAP_CHUNK) as u8;
+            let offset = keymap.len() as u16;
+            match self.request(Request::ReadKeymap { offset, count })? {
+                Response::Keymap(chunk) if chunk.offset == offset && chunk.count > 0 => {
+                    keymap.extend_from_slice(chunk.actions())
+                }
+                other => bail!("unexpected response {:?}", other),
+            }
+        }
+        Ok(keymap)
+    }
+
+    /// Stages the whole keymap on the board and saves it
+    pub fn write_keymap(&mut self, keymap: &[KeyAction]) -> Result<()> {
+        for (i, actions) in keymap.chunks(KEYMAP_CHUNK).enumerate() {
+            let offset = (i * KEYMAP_CHUNK) as u16;
+            self.request(Request::WriteKeymap(KeymapChunk::new(offset, actions)))?;
+        }
+        self.request(Request::SaveKeymap)?;
+        Ok(())
+    }
+
+    pub fn reset_keymap(&mut self) -> Result<()> {
+        self.request(Request::ResetKeymap)?;
+        Ok(())
+    }
+
+    pub fn get_setting(&mut self, id: SettingId) -> Result<u16> {
+        match self.request(Request::GetSetting(id))? {
+            Response::Setting(got, value) if got == id => Ok(value),
+            other => bail!("unexpected response {:?}", other),
+        }
+    }
+
+    /// Changes and saves a setting
+    pub fn set_setting(&mut self, id: SettingId, value: u16) -> Result<()> {
+        self.request(Request::SetSetting(id, value))?;
+        self.request(Request::SaveSettings)?;
+        Ok(())
+    }
+
+    /// Changes and saves the scrolling text screensaver's text, empty for
+    /// the default
+    pub fn set_screensaver_text(&mut self, text: ShortText) -> Result<()> {
+        self.request(Request::SetScreensaverText(text))?;
+        self.request(Request::SaveSettings)?;
+        Ok(())
+    }
+
+    pub fn screensaver_text(&mut self) -> Result<ShortText> {
+        match self.request(Request::GetScreensaverText)? {
+            Response::ScreensaverText(text) => Ok(text),
+            other => bail!("unexpected response {:?}", other),
+        }
+    }
+
+    /// Pops the text up over the board's display for a moment
+    pub fn notify(&mut self, text: ShortText) -> Result<()> {
+        self.request(Request::Notify(text))?;
+        Ok(())
+    }
+
+    /// The board answers, then resets a moment later
+    pub fn bootloader(&mut self) -> Result<()> {
+        self.request(Request::Bootloader)?;
+        Ok(())
+    }
+
+    pub fn set_time(&mut self, time: DateTime) -> Result<()> {
+        self.request(Request::SetTime(time))?;
+        Ok(())
+    }
+
+    /// Fails if the board's clock hasn't been set since it powered up
+    pub fn get_time(&mut self) -> Result<DateTime> {
+        match self.request(Request::GetTime)? {
+            Response::Time(time) => Ok(time),
+            other => bail!("unexpected response {:?}", other),
+        }
+    }
+
+    pub fn stream_events(&mut self, enable: bool) -> Result<()> {
+        self.request(Request::StreamEvents(enable))?;
+        Ok(())
+    }
+
+    pub fn show_canvas(&mut self, visible: bool) -> Result<()> {
+        self.request(Request::CanvasShow(visible))?;
+        Ok(())
+    }
+
+    pub fn fill_canvas(&mut self, rect: Rect, color: u16) -> Result<()> {
+        self.request(Request::CanvasFill(rect, color))?;
+        Ok(())
+    }
+
+    /// Draws `pixels`, row by row, into `rect`
+    pub fn blit_canvas(&mut self, rect: Rect, pixels: &[u16]) -> Result<()> {
+        self.request(Request::CanvasBlit(rect))?;
+        for run in pixels.chunks(PIXEL_RUN_LEN) {
+            self.request(Request::CanvasPixels(PixelRun::new(run)))?;
+        }
+        Ok(())
+    }
+
+    pub fn canvas_text(&mut self, text: CanvasText) -> Result<()> {
+        self.request(Request::CanvasText(text))?;
+        Ok(())
+    }
+
+    pub fn stats(&mut self) -> Result<StatsSummary> {
+        match self.request(Request::GetStats)? {
+            Response::Stats(summary) => Ok(summary),
+            other => bail!("unexpected response {:?}", other),
+        }
+    }
+
+    /// Presses of each key, `row * cols + col`
+    pub fn read_key_counts(&mut self, len: usize) -> Result<Vec<u32>> {
+        self.read_counts(len, |offset, count| Request::ReadKeyCounts { offset, count })
+    }
+
+    /// Presses per day, today first
+    pub fn read_day_counts(&mut self, len: usize) -> Result<Vec<u32>> {
+        self.read_counts(len, |offset, count| Request::ReadDayCounts { offset, count })
+    }
+
+    fn read_counts(&mut self, len: usize, request: fn(u8, u8) -> Request) -> Result<Vec<u32>> {
+        let mut counts = Vec::with_capacity(len);
+        while counts.len() < len {
+            let count = (len - counts.len()).min(COUNT_CHUNK) as u8;
+            let offset = counts.len() as u8;
+            match self.request(request(offset, count))? {
+                Response::KeyCounts(chunk) | Response::DayCounts(chunk) if chunk.offset == offset && chunk.count > 0 => {
+                    counts.extend_from_slice(chunk.counts())
+                }
+                other => bail!("unexpected response {:?}", other),
+            }
+        }
+        Ok(counts)
+    }
+
+    pub fn latency(&mut self) -> Result<LatencySummary> {
+        match self.request(Request::GetLatency)? {
+            Response::Latency(latency) => Ok(latency),
+            other => bail!("unexpected response {:?}", other),
+        }
+    }
+
+    /// Clears the counts on the board and in its flash
+    pub fn reset_stats(&mut self) -> Result<()> {
+        self.request(Request::ResetStats)?;
+        Ok(())
+    }
+}
@@ -0,0 +1,333 @@
+//! An in-process stand-in for a board, answering requests the way the
+//! firmware does. Handy for trying the CLI out without hardware (`--fake`).
+
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+
+use anyhow::Result;
+use keezus_protocol::keymap::{Staging, COLS, LAYERS, ROWS};
+use keezus_protocol::{
+    Command, CountChunk, DateTime, LatencySummary, Request, Response, SettingId, Status, StatsSummary, ShortText,
+    BRIGHTNESS_LEVELS, PROTOCOL_VERSION,
+};
+
+use crate::transport::{Packet, Transport};
+
+const DAYS: usize = 7;
+
+pub struct FakeDevice {
+    staged_keymap: Staging,
+    poll_interval_ms: u16,
+    brightness: u16,
+    display_timeout_min: u16,
+    orientation: u16,
+    panel_offset: (u16, u16),
+    screensaver_min: u16,
+    screensaver: u16,
+    screensaver_text: ShortText,
+    /// When the clock was set, and to what
+    time: Option<(Instant, DateTime)>,
+    /// Typing statistics, per key and per day
+    key_counts: Vec<u32>,
+    day_counts: Vec<u32>,
+    stream_events: bool,
+    /// Position of the next fake key event
+    next_event: usize,
+    outgoing: VecDeque<Packet>,
+}
+
+impl FakeDevice {
+    pub fn new() -> FakeDevice {
+        FakeDevice {
+            staged_keymap: Staging::default(),
+            poll_interval_ms: 2,
+            brightness: BRIGHTNESS_LEVELS as u16,
+            display_timeout_min: 5,
+            orientation: 2,
+            panel_offset: (0, 0),
+            screensaver_min: 3,
+            screensaver: 0,
+            screensaver_text: ShortText::EMPTY,
+            time: None,
+            // Something that looks like a week of typing, heavier towards
+            // the home row
+            key_counts: (0..ROWS * COLS).map(|key| [120, 310, 90, 15][key / COLS] * (key % 7 + 3) as u32).collect(),
+            day_counts: vec![1830, 4210, 3975, 0, 0, 5120, 2650],
+            stream_events: false,
+            next_event: 0,
+            outgoing: VecDeque::new(),
+        }
+    }
+
+    fn handle(&mut self, request: Request) -> Response {
+        match request {
+            Request::GetVersion => Response::Version {
+                protocol: PROTOCOL_VERSION,
+                major: 0,
+                minor: 1,
+                patch: 0,
+            },
+            Request::GetKeymapInfo => Response::KeymapInfo {
+                layers: LAYERS as u8,
+                rows: ROWS as u8,
+                cols: COLS as u8,
+            },
+            Request::ReadKeymap { offset, count } => match self.staged_keymap.read(offset, count) {
+                Some(chunk) => Response::Keymap(chunk),
+                None => Response::Error(Command::ReadKeymap, Status::InvalidArgument),
+            },
+            Request::WriteKeymap(chunk) => match self.staged_keymap.write(&chunk) {
+                Some(()) => Response::Ack(Command::WriteKeymap),
+                None => Response::Error(Command::WriteKeymap, Status::InvalidArgument),
+            },
+            Request::SaveKeymap => Response::Ack(Command::SaveKeymap),
+            Request::ResetKeymap => {
+                self.staged_keymap.reset();
+                Response::Ack(Command::ResetKeymap)
+            }
+            Request::GetSetting(id) => Response::Setting(
+                id,
+                match id {
+                    SettingId::PollIntervalMs => self.poll_interval_ms,
+                    SettingId::Brightness => self.brightness,
+                    SettingId::DisplayTimeoutMin => self.display_timeout_min,
+                    SettingId::Orientation => self.orientation,
+                    SettingId::PanelOffsetX => self.panel_offset.0,
+                    SettingId::PanelOffsetY => self.panel_offset.1,
+                    SettingId::ScreensaverMin => self.screensaver_min,
+                    SettingId::Screensaver => self.screensaver,
+                },
+            ),
+            Request::SetSetting(id, value) => {
+                let Some(value) = id.check(value) else {
+                    return Response::Error(Command::SetSetting, Status::InvalidArgument);
+                };
+                let setting = match id {
+                    SettingId::PollIntervalMs => &mut self.poll_interval_ms,
+                    SettingId::Brightness => &mut self.brightness,
+                    SettingId::DisplayTimeoutMin => &mut self.display_timeout_min,
+                    SettingId::Orientation => &mut self.orientation,
+                    SettingId::PanelOffsetX => &mut self.panel_offset.0,
+                    SettingId::PanelOffsetY => &mut self.panel_offset.1,
+                    SettingId::ScreensaverMin => &mut self.screensaver_min,
+                    SettingId::Screensaver => &mut self.screensaver,
+                };
+                *setting = value.into();
+                Response::Ack(Command::SetSetting)
+            }
+            Request::SaveSettings => Response::Ack(Command::SaveSettings),
+            Request::Bootloader => Response::Ack(Command::Bootloader),
+            Request::SetTime(time) => {
+                self.time = Some((Instant::now(), time));
+                Response::Ack(Command::SetTime)
+            }
+            Request::GetTime => match self.time {
+                Some((set_at, time)) => {
+                    Response::Time(DateTime::from_unix(time.to_unix() + set_at.elapsed().as_secs() as i64))
+                }
+                None => Response::Error(Command::GetTime, Status::Failed),
+            },
+            Request::StreamEvents(enable) => {
+                self.stream_events = enable;
+                Response::Ack(Command::StreamEvents)
+            }
+            Request::GetStats => Response::Stats(StatsSummary {
+                total: self.key_counts.iter().sum(),
+                wpm: 42,
+                keys: self.key_counts.len() as u8,
+                days: DAYS as u8,
+                today: self.time.map(|(_, time)| time.to_unix().div_euclid(86_400) as u32),
+            }),
+            Request::ReadKeyCounts { offset, count } => match CountChunk::from_counts(&self.key_counts, offset, count) {
+                Some(chunk) => Response::KeyCounts(chunk),
+                None => Response::Error(Command::ReadKeyCounts, Status::InvalidArgument),
+            },
+            Request::ReadDayCounts { offset, count } => match CountChunk::from_counts(&self.day_counts, offset, count) {
+                Some(chunk) => Response::DayCounts(chunk),
+                None => Response::Error(Command::ReadDayCounts, Status::InvalidArgument),
+            },
+            Request::ResetStats => {
+                self.key_counts.fill(0);
+                self.day_counts.fill(0);
+                Response::Ack(Command::ResetStats)
+            }
+            Request::SetScreensaverText(text) => {
+                self.screensaver_text = text;
+                Response::Ack(Command::SetScreensaverText)
+            }
+            Request::GetScreensaverText => Response::ScreensaverText(self.screensaver_text),
+            // Nowhere to show it
+            Request::Notify(_) => Response::Ack(Command::Notify),
+            Request::GetLatency => Response::Latency(LatencySummary {
+                samples: 1520,
+                min_us: 5_210,
+                average_us: 6_480,
+                max_us: 9_870,
+            }),
+            // Nothing to draw on
+            request @ (Request::CanvasShow(_)
+            | Request::CanvasFill(..)
+            | Request::CanvasBlit(_)
+            | Request::CanvasPixels(_)
+            | Request::CanvasText(_)) => Response::Ack(request.command()),
+        }
+    }
+
+    /// Pretends someone is typing along the top row
+    fn fake_event(&mut self) -> Response {
+        let key = self.next_event / 2 % COLS;
+        let pressed = self.next_event & 1 == 0;
+        self.next_event += 1;
+        Response::KeyEvent {
+            row: 0,
+            col: key as u8,
+            pressed,
+        }
+    }
+}
+
+impl Transport for FakeDevice {
+    fn write(&mut self, packet: &Packet) -> Result<()> {
+        let response = match Request::decode(packet) {
+            Ok(request) => self.handle(request),
+            Err(e) => Response::Error(Command::from_u8(packet[0]).unwrap_or(Command::GetVersion), e.into()),
+        };
+        // A key event from the matrix can always beat the response
+        if self.stream_events {
+            let mut event = [0u8; keezus_protocol::REPORT_LEN];
+            self.fake_event().encode(&mut event);
+            self.outgoing.push_back(event);
+        }
+        let mut out = [0u8; keezus_protocol::REPORT_LEN];
+        response.encode(&mut out);
+        self.outgoing.push_back(out);
+        Ok(())
+    }
+
+    fn read(&mut self, timeout: Duration) -> Result<Option<Packet>> {
+        if let Some(packet) = self.outgoing.pop_front() {
+            return Ok(Some(packet));
+        }
+        if !self.stream_events {
+            return Ok(None);
+        }
+        std::thread::sleep(timeout.min(Duration::from_millis(150)));
+        let mut out = [0u8; keezus_protocol::REPORT_LEN];
+        self.fake_event().encode(&mut out);
+        Ok(Some(out))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::device::Device;
+    use keezus_protocol::keymap::{DEFAULT_KEYMAP, KEYMAP_LEN};
+    use keezus_protocol::{KeyAction, KEYMAP_CHUNK, ORIENTATIONS, SCREENSAVERS};
+
+    fn device() -> Device<FakeDevice> {
+        Device::new(FakeDevice::new())
+    }
+
+    #[test]
+    fn version() {
+        let (protocol, version) = device().version().unwrap();
+        assert_eq!(protocol, PROTOCOL_VERSION);
+        assert_eq!(version, "0.1.0");
+    }
+
+    #[test]
+    fn bootloader_is_acknowledged() {
+        device().bootloader().unwrap();
+    }
+
+    #[test]
+    fn keymap_read_write_save() {
+        let mut device = device();
+        let info = device.keymap_info().unwrap();
+        assert_eq!((info.layers, info.rows, info.cols), (LAYERS, ROWS, COLS));
+
+        // More than one chunk, and a last one that isn't full
+        let keymap = device.read_keymap(info.len()).unwrap();
+        assert_eq!(keymap, DEFAULT_KEYMAP);
+
+        let mut changed = keymap.clone();
+        changed[0] = KeyAction::Custom(2);
+        changed[KEYMAP_CHUNK] = KeyAction::Layer(1);
+        changed[info.len() - 1] = KeyAction::NoOp;
+        device.write_keymap(&changed).unwrap();
+        assert_eq!(device.read_keymap(info.len()).unwrap(), changed);
+
+        device.reset_keymap().unwrap();
+        assert_eq!(device.read_keymap(info.len()).unwrap(), DEFAULT_KEYMAP);
+    }
+
+    #[test]
+    fn keymap_out_of_range() {
+        let mut device = device();
+        assert!(device.read_keymap(KEYMAP_LEN + 1).is_err());
+        assert!(device.write_keymap(&[KeyAction::Trans; KEYMAP_LEN + 1]).is_err());
+    }
+
+    #[test]
+    fn settings() {
+        let mut device = device();
+        assert_eq!(device.get_setting(SettingId::PollIntervalMs).unwrap(), 2);
+        device.set_setting(SettingId::PollIntervalMs, 8).unwrap();
+        assert_eq!(device.get_setting(SettingId::PollIntervalMs).unwrap(), 8);
+
+        device.set_setting(SettingId::Screensaver, 3).unwrap();
+        assert_eq!(device.get_setting(SettingId::Screensaver).unwrap(), 3);
+        // Not changed by a keymap reset
+        device.reset_keymap().unwrap();
+        assert_eq!(device.get_setting(SettingId::Screensaver).unwrap(), 3);
+    }
+
+    #[test]
+    fn invalid_settings() {
+        let mut device = device();
+        let error = device.set_setting(SettingId::PollIntervalMs, 3).unwrap_err();
+        assert_eq!(error.to_string(), "SetSetting failed: InvalidArgument");
+        assert!(device.set_setting(SettingId::Brightness, 0).is_err());
+        assert!(device.set_setting(SettingId::Orientation, ORIENTATIONS as u16).is_err());
+        assert!(device.set_setting(SettingId::Screensaver, SCREENSAVERS as u16).is_err());
+        assert_eq!(device.get_setting(SettingId::PollIntervalMs).unwrap(), 2);
+    }
+
+    #[test]
+    fn screensaver_text() {
+        let mut device = device();
+        assert!(device.screensaver_text().unwrap().is_empty());
+        device.set_screensaver_text(ShortText::new("hello from keezus")).unwrap();
+        assert_eq!(device.screensaver_text().unwrap().as_str(), "hello from keezus");
+    }
+
+    #[test]
+    fn stats() {
+        let mut device = device();
+        let summary = device.stats().unwrap();
+        assert_eq!(summary.keys as usize, ROWS * COLS);
+        assert_eq!(summary.today, None);
+
+        let keys = device.read_key_counts(summary.keys as usize).unwrap();
+        assert_eq!(keys.iter().sum::<u32>(), summary.total);
+        assert_eq!(device.read_day_counts(summary.days as usize).unwrap().len(), DAYS);
+
+        device.reset_stats().unwrap();
+        assert_eq!(device.stats().unwrap().total, 0);
+    }
+
+    #[test]
+    fn events_are_kept_while_waiting_for_a_response() {
+        let mut device = device();
+        // Both responses have an event ahead of them
+        device.stream_events(true).unwrap();
+        assert_eq!(device.get_setting(SettingId::Brightness).unwrap(), BRIGHTNESS_LEVELS as u16);
+
+        let event = |col, pressed| Some(Response::KeyEvent { row: 0, col, pressed });
+        assert_eq!(device.next_event(Duration::ZERO).unwrap(), event(0, true));
+        assert_eq!(device.next_event(Duration::ZERO).unwrap(), event(0, false));
+        // Then the ones that come in on their own
+        assert_eq!(device.next_event(Duration::ZERO).unwrap(), event(1, true));
+    }
+}
@@ -0,0 +1,53 @@
+//! Turning colours and PNG files into the RGB565 pixels the canvas takes.
+
+use std::fs::File;
+use std::path::Path;
+
+use anyhow::{bail, Context, Result};
+use keezus_protocol::rgb565;
+
+/// An image converted to RGB565, row by row
+pub struct Image {
+    pub width: usize,
+    pub height: usize,
+    pub pixels: Vec<u16>,
+}
+
+/// Accepts `#rrggbb` or a raw RGB565 value like `0xf800`
+pub fn parse_color(text: &str) -> Result<u16> {
+    if let Some(hex) = text.strip_prefix('#') {
+        if hex.len() == 6 {
+            if let Ok(rgb) = u32::from_str_radix(hex, 16) {
+                return Ok(rgb565((rgb >> 16) as u8, (rgb >> 8) as u8, rgb as u8));
+            }
+        }
+    } else if let Some(hex) = text.strip_prefix("0x") {
+        if let Ok(raw) = u16::from_str_radix(hex, 16) {
+            return Ok(raw);
+        }
+    }
+    bail!("can't parse colour {:?}, expected #rrggbb or 0xNNNN", text)
+}
+
+pub fn load_png(path: &Path) -> Result<Image> {
+    let file = File::open(path).with_context(|| format!("couldn't open {}", path.display()))?;
+    let mut decoder = png::Decoder::new(file);
+    decoder.set_transformations(png::Transformations::normalize_to_color8());
+    let mut reader = decoder.read_info().context("not a PNG file")?;
+    let mut buf = vec![0; reader.output_buffer_size()];
+    let info = reader.next_frame(&mut buf).context("couldn't decode the PNG")?;
+
+    let channels = info.color_type.samples();
+    let pixels = buf[..info.buffer_size()]
+        .chunks_exact(channels)
+        .map(|px| match px.len() {
+            1 | 2 => rgb565(px[0], px[0], px[0]),
+            _ => rgb565(px[0], px[1], px[2]),
+        })
+        .collect();
+    Ok(Image {
+        width: info.width as usize,
+        height: info.height as usize,
+        pixels,
+    })
+}
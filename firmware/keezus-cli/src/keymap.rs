@@ -0,0 +1,153 @@
+//! Text format for keymap dumps.
+//!
+//! One `[layer N]` section per layer, one line per matrix row and one
+//! whitespace separated token per key. Keycodes use keyberon's names
+//! (`A`, `SColon`, `LShift`, ...) or raw hex (`0x68`). The other tokens are
+//! `Trans`, `NoOp`, `L(n)` (momentary layer), `DL(n)` (default layer),
+//! `C(n)` (custom action n) and `?` for anything the protocol can't
+//! express, which the firmware leaves untouched when uploaded.
+
+use std::fmt::Write;
+
+use anyhow::{anyhow, bail, Context, Result};
+use keezus_protocol::KeyAction;
+
+use crate::device::KeymapInfo;
+
+#[rustfmt::skip]
+const KEYCODE_NAMES: &[(u8, &str)] = &[
+    (0x00, "No"), (0x01, "ErrorRollOver"), (0x02, "PostFail"), (0x03, "ErrorUndefined"),
+    (0x04, "A"), (0x05, "B"), (0x06, "C"), (0x07, "D"), (0x08, "E"), (0x09, "F"),
+    (0x0a, "G"), (0x0b, "H"), (0x0c, "I"), (0x0d, "J"), (0x0e, "K"), (0x0f, "L"),
+    (0x10, "M"), (0x11, "N"), (0x12, "O"), (0x13, "P"), (0x14, "Q"), (0x15, "R"),
+    (0x16, "S"), (0x17, "T"), (0x18, "U"), (0x19, "V"), (0x1a, "W"), (0x1b, "X"),
+    (0x1c, "Y"), (0x1d, "Z"),
+    (0x1e, "Kb1"), (0x1f, "Kb2"), (0x20, "Kb3"), (0x21, "Kb4"), (0x22, "Kb5"),
+    (0x23, "Kb6"), (0x24, "Kb7"), (0x25, "Kb8"), (0x26, "Kb9"), (0x27, "Kb0"),
+    (0x28, "Enter"), (0x29, "Escape"), (0x2a, "BSpace"), (0x2b, "Tab"), (0x2c, "Space"),
+    (0x2d, "Minus"), (0x2e, "Equal"), (0x2f, "LBracket"), (0x30, "RBracket"), (0x31, "Bslash"),
+    (0x32, "NonUsHash"), (0x33, "SColon"), (0x34, "Quote"), (0x35, "Grave"), (0x36, "Comma"),
+    (0x37, "Dot"), (0x38, "Slash"), (0x39, "CapsLock"),
+    (0x3a, "F1"), (0x3b, "F2"), (0x3c, "F3"), (0x3d, "F4"), (0x3e, "F5"), (0x3f, "F6"),
+    (0x40, "F7"), (0x41, "F8"), (0x42, "F9"), (0x43, "F10"), (0x44, "F11"), (0x45, "F12"),
+    (0x46, "PScreen"), (0x47, "ScrollLock"), (0x48, "Pause"), (0x49, "Insert"), (0x4a, "Home"),
+    (0x4b, "PgUp"), (0x4c, "Delete"), (0x4d, "End"), (0x4e, "PgDown"), (0x4f, "Right"),
+    (0x50, "Left"), (0x51, "Down"), (0x52, "Up"), (0x53, "NumLock"),
+    (0x54, "KpSlash"), (0x55, "KpAsterisk"), (0x56, "KpMinus"), (0x57, "KpPlus"), (0x58, "KpEnter"),
+    (0x59, "Kp1"), (0x5a, "Kp2"), (0x5b, "Kp3"), (0x5c, "Kp4"), (0x5d, "Kp5"),
+    (0x5e, "Kp6"), (0x5f, "Kp7"), (0x60, "Kp8"), (0x61, "Kp9"), (0x62, "Kp0"), (0x63, "KpDot"),
+    (0x64, "NonUsBslash"), (0x65, "Application"), (0x66, "Power"), (0x67, "KpEqual"),
+    (0x68, "F13"), (0x69, "F14"), (0x6a, "F15"), (0x6b, "F16"), (0x6c, "F17"), (0x6d, "F18"),
+    (0x6e, "F19"), (0x6f, "F20"), (0x70, "F21"), (0x71, "F22"), (0x72, "F23"), (0x73, "F24"),
+    (0x7f, "Mute"), (0x80, "VolUp"), (0x81, "VolDown"),
+    (0xe0, "LCtrl"), (0xe1, "LShift"), (0xe2, "LAlt"), (0xe3, "LGui"),
+    (0xe4, "RCtrl"), (0xe5, "RShift"), (0xe6, "RAlt"), (0xe7, "RGui"),
+];
+
+pub fn format_action(action: KeyAction) -> String {
+    match action {
+        KeyAction::NoOp => "NoOp".to_owned(),
+        KeyAction::Trans => "Trans".to_owned(),
+        KeyAction::KeyCode(kc) => match KEYCODE_NAMES.iter().find(|(code, _)| *code == kc) {
+            Some((_, name)) => (*name).to_owned(),
+            None => format!("{:#04x}", kc),
+        },
+        KeyAction::Layer(layer) => format!("L({})", layer),
+        KeyAction::DefaultLayer(layer) => format!("DL({})", layer),
+        KeyAction::Custom(index) => format!("C({})", index),
+        KeyAction::Other => "?".to_owned(),
+    }
+}
+
+fn parse_action(token: &str) -> Result<KeyAction> {
+    let arg = |prefix: &str| -> Option<Result<u8>> {
+        token.strip_prefix(prefix)?.strip_suffix(')').map(|n| {
+            n.parse()
+                .with_context(|| format!("bad number in {:?}", token))
+        })
+    };
+
+    if let Some(n) = arg("L(") {
+        return Ok(KeyAction::Layer(n?));
+    }
+    if let Some(n) = arg("DL(") {
+        return Ok(KeyAction::DefaultLayer(n?));
+    }
+    if let Some(n) = arg("C(") {
+        return Ok(KeyAction::Custom(n?));
+    }
+    if let Some(hex) = token.strip_prefix("0x") {
+        return Ok(KeyAction::KeyCode(
+            u8::from_str_radix(hex, 16).with_context(|| format!("bad keycode {:?}", token))?,
+        ));
+    }
+    Ok(match token {
+        "NoOp" => KeyAction::NoOp,
+        "Trans" => KeyAction::Trans,
+        "?" => KeyAction::Other,
+        name => KEYCODE_NAMES
+            .iter()
+            .find(|(_, n)| *n == name)
+            .map(|(code, _)| KeyAction::KeyCode(*code))
+            .ok_or_else(|| anyhow!("unknown key {:?}", name))?,
+    })
+}
+
+pub fn format(info: &KeymapInfo, keymap: &[KeyAction]) -> String {
+    let mut out = String::new();
+    writeln!(
+        out,
+        "# keezus keymap, {} layer(s) of {}x{}",
+        info.layers, info.rows, info.cols
+    )
+    .unwrap();
+    for (layer, keys) in keymap.chunks(info.rows * info.cols).enumerate() {
+        writeln!(out, "\n[layer {}]", layer).unwrap();
+        for row in keys.chunks(info.cols) {
+            let tokens: Vec<String> = row.iter().map(|action| format!("{:<8}", format_action(*action))).collect();
+            writeln!(out, "{}", tokens.join(" ").trim_end()).unwrap();
+        }
+    }
+    out
+}
+
+pub fn parse(info: &KeymapInfo, text: &str) -> Result<Vec<KeyAction>> {
+    let mut keymap = Vec::with_capacity(info.len());
+    let mut layer = None;
+    for (line_number, line) in text.lines().enumerate() {
+        let line = line.split('#').next().unwrap().trim();
+        if line.is_empty() {
+            continue;
+        }
+        if let Some(header) = line.strip_prefix("[layer ").and_then(|l| l.strip_suffix(']')) {
+            let expected = keymap.len() / (info.rows * info.cols);
+            if keymap.len() % (info.rows * info.cols) != 0 || header.trim().parse::<usize>().ok() != Some(expected) {
+                bail!("line {}: expected [layer {}]", line_number + 1, expected);
+            }
+            layer = Some(expected);
+            continue;
+        }
+        if layer.is_none() {
+            bail!("line {}: keys before the first [layer N] header", line_number + 1);
+        }
+        let row: Vec<KeyAction> = line
+            .split_whitespace()
+            .map(parse_action)
+            .collect::<Result<_>>()
+            .with_context(|| format!("line {}", line_number + 1))?;
+        if row.len() != info.cols {
+            bail!("line {}: expected {} keys, found {}", line_number + 1, info.cols, row.len());
+        }
+        keymap.extend(row);
+    }
+    if keymap.len() != info.len() {
+        bail!(
+            "expected {} layer(s) of {}x{}, found {} keys",
+            info.layers,
+            info.rows,
+            info.cols,
+            keymap.len()
+        );
+    }
+    Ok(keymap)
+}
@@ -0,0 +1,355 @@
+//! Host side companion for the keezus firmware, talking to the board over
+//! its raw HID interface.
+
+mod device;
+mod fake;
+mod image;
+mod keymap;
+mod stats;
+mod time;
+mod transport;
+
+use std::fs;
+use std::path::PathBuf;
+use std::time::Duration;
+
+use anyhow::{anyhow, bail, Result};
+use clap::{Parser, Subcommand};
+use keezus_protocol::{
+    CanvasText, Rect, Response, SettingId, ShortText, CANVAS_FONT_WIDTH, CANVAS_HEIGHT, CANVAS_WIDTH, SHORT_TEXT_LEN,
+};
+
+use device::Device;
+use transport::{HidTransport, Transport};
+
+#[derive(Parser)]
+#[command(version, about)]
+struct Args {
+    /// Talk to an in-process fake board instead of real hardware
+    #[arg(long, global = true)]
+    fake: bool,
+    /// Serial number of the board to use when several are connected
+    #[arg(long, global = true)]
+    serial: Option<String>,
+    #[command(subcommand)]
+    command: Cmd,
+}
+
+#[derive(Subcommand)]
+enum Cmd {
+    /// List connected boards
+    List,
+    /// Show the firmware and protocol version
+    Version,
+    #[command(subcommand)]
+    Keymap(KeymapCmd),
+    /// Show all settings, or a single one
+    Get { name: Option<String> },
+    /// Change and save a setting
+    Set { name: String, value: u16 },
+    /// Show the scrolling text screensaver's text, or change and save it
+    /// ("" for the default)
+    ScreensaverText { text: Option<String> },
+    /// Pop a message up over the board's display for a moment
+    Notify { text: String },
+    /// Reboot into the RP2040's USB bootloader
+    Bootloader,
+    /// Print key events as they happen, until interrupted
+    Events,
+    #[command(subcommand)]
+    Display(DisplayCmd),
+    #[command(subcommand)]
+    Time(TimeCmd),
+    #[command(subcommand)]
+    Stats(StatsCmd),
+    /// Show how long key presses take from the matrix to the USB report,
+    /// since the board reset
+    Latency,
+}
+
+/// Drawing on the board's screen. Everything is drawn to a canvas that is
+/// only shown while enabled with `display show`.
+#[derive(Subcommand)]
+enum DisplayCmd {
+    /// Show the canvas instead of the firmware's own screen
+    Show,
+    /// Go back to the firmware's own screen, keeping what's on the canvas
+    Hide,
+    /// Fill the whole canvas with one colour
+    Clear {
+        #[arg(default_value = "#000000")]
+        color: String,
+    },
+    /// Fill a rectangle, colours are #rrggbb or a raw RGB565 value
+    Fill {
+        x: u8,
+        y: u8,
+        width: u8,
+        height: u8,
+        color: String,
+    },
+    /// Draw a line of text with its top left corner at x, y
+    Text {
+        x: u8,
+        y: u8,
+        text: String,
+        #[arg(long, default_value = "#ffffff")]
+        color: String,
+        #[arg(long, default_value = "#000000")]
+        background: String,
+    },
+    /// Draw a PNG image with its top left corner at x, y
+    Image {
+        file: PathBuf,
+        #[arg(default_value_t = 0)]
+        x: u8,
+        #[arg(default_value_t = 0)]
+        y: u8,
+    },
+}
+
+/// The board's clock, which keeps time until the board resets
+#[derive(Subcommand)]
+enum TimeCmd {
+    /// Set the board's clock to this computer's local time
+    Sync,
+    /// Show the time on the board
+    Get,
+    /// Set the board's clock, e.g. "2024-01-31 13:45:00"
+    Set { time: String },
+}
+
+/// Typing statistics the board keeps: presses per key, per day and words
+/// per minute
+#[derive(Subcommand)]
+enum StatsCmd {
+    /// Print the counts, keys laid out like the board
+    Show,
+    /// Print the counts as CSV, or write them to a file
+    Csv { file: Option<PathBuf> },
+    /// Clear the counts on the board
+    Reset,
+}
+
+#[derive(Subcommand)]
+enum KeymapCmd {
+    /// Print the keymap, or write it to a file
+    Dump { file: Option<PathBuf> },
+    /// Upload and save a keymap, applied on the board's next reset
+    Upload { file: PathBuf },
+    /// Go back to the keymap compiled into the firmware
+    Reset,
+}
+
+const SETTING_NAMES: &[(SettingId, &str)] = &[
+    (SettingId::PollIntervalMs, "poll-interval-ms"),
+    (SettingId::Brightness, "brightness"),
+    (SettingId::DisplayTimeoutMin, "display-timeout-min"),
+    (SettingId::Orientation, "orientation"),
+    (SettingId::PanelOffsetX, "panel-offset-x"),
+    (SettingId::PanelOffsetY, "panel-offset-y"),
+    (SettingId::ScreensaverMin, "screensaver-min"),
+    (SettingId::Screensaver, "screensaver"),
+];
+
+fn setting_by_name(name: &str) -> Result<SettingId> {
+    SETTING_NAMES
+        .iter()
+        .find(|(_, n)| *n == name)
+        .map(|(id, _)| *id)
+        .ok_or_else(|| {
+            let names: Vec<&str> = SETTING_NAMES.iter().map(|(_, n)| *n).collect();
+            anyhow!("unknown setting {:?}, expected one of {}", name, names.join(", "))
+        })
+}
+
+fn run<T: Transport>(mut device: Device<T>, command: Cmd) -> Result<()> {
+    match command {
+        // Doesn't need a device, handled in main
+        Cmd::List => unreachable!(),
+        Cmd::Version => {
+            let (protocol, version) = device.version()?;
+            println!("keezus {} (protocol {})", version, protocol);
+        }
+        Cmd::Keymap(KeymapCmd::Dump { file }) => {
+            let info = device.keymap_info()?;
+            let text = keymap::format(&info, &device.read_keymap(info.len())?);
+            match file {
+                Some(path) => fs::write(path, text)?,
+                None => print!("{}", text),
+            }
+        }
+        Cmd::Keymap(KeymapCmd::Upload { file }) => {
+            let info = device.keymap_info()?;
+            let keymap = keymap::parse(&info, &fs::read_to_string(file)?)?;
+            device.write_keymap(&keymap)?;
+            println!("keymap saved, reset the board to apply it");
+        }
+        Cmd::Keymap(KeymapCmd::Reset) => {
+            device.reset_keymap()?;
+            println!("keymap reset, reset the board to apply it");
+        }
+        Cmd::Get { name } => {
+            let ids = match name {
+                Some(name) => vec![setting_by_name(&name)?],
+                None => SettingId::ALL.to_vec(),
+            };
+            for id in ids {
+                let name = SETTING_NAMES.iter().find(|(i, _)| *i == id).map_or("?", |(_, n)| n);
+                println!("{} = {}", name, device.get_setting(id)?);
+            }
+        }
+        Cmd::Set { name, value } => {
+            device.set_setting(setting_by_name(&name)?, value)?;
+        }
+        Cmd::ScreensaverText { text: Some(text) } => {
+            if text.len() > SHORT_TEXT_LEN {
+                bail!("the screensaver text can be {} bytes at most", SHORT_TEXT_LEN);
+            }
+            device.set_screensaver_text(ShortText::new(&text))?;
+        }
+        Cmd::ScreensaverText { text: None } => println!("{}", device.screensaver_text()?.as_str()),
+        Cmd::Notify { text } => {
+            if text.len() > SHORT_TEXT_LEN {
+                bail!("notifications can be {} bytes at most", SHORT_TEXT_LEN);
+            }
+            device.notify(ShortText::new(&text))?;
+        }
+        Cmd::Bootloader => device.bootloader()?,
+        Cmd::Events => {
+            device.stream_events(true)?;
+            loop {
+                if let Some(Response::KeyEvent { row, col, pressed }) =
+                    device.next_event(Duration::from_millis(500))?
+                {
+                    let what = if pressed { "press" } else { "release" };
+                    println!("{:<7} row {} col {}", what, row, col);
+                }
+            }
+        }
+        Cmd::Display(command) => display(&mut device, command)?,
+        Cmd::Time(TimeCmd::Sync) => {
+            let now = time::local_now()?;
+            device.set_time(now)?;
+            println!("set to {}", time::format(&now));
+        }
+        Cmd::Time(TimeCmd::Get) => println!("{}", time::format(&device.get_time()?)),
+        Cmd::Time(TimeCmd::Set { time }) => device.set_time(time::parse(&time)?)?,
+        Cmd::Stats(StatsCmd::Reset) => {
+            device.reset_stats()?;
+            println!("typing statistics cleared");
+        }
+        Cmd::Latency => {
+            let latency = device.latency()?;
+            if latency.samples == 0 {
+                println!("no key presses measured yet");
+            } else {
+                println!(
+                    "{} presses: min {} us, average {} us, max {} us",
+                    latency.samples, latency.min_us, latency.average_us, latency.max_us
+                );
+            }
+        }
+        Cmd::Stats(command) => {
+            let info = device.keymap_info()?;
+            // Keys are named after the base layer
+            let layer = device.read_keymap(info.rows * info.cols)?;
+            let summary = device.stats()?;
+            let stats = stats::Stats {
+                summary,
+                keys: device.read_key_counts(summary.keys as usize)?,
+                days: device.read_day_counts(summary.days as usize)?,
+            };
+            match command {
+                StatsCmd::Csv { file: Some(path) } => fs::write(path, stats::csv(&stats, &info, &layer))?,
+                StatsCmd::Csv { file: None } => print!("{}", stats::csv(&stats, &info, &layer)),
+                _ => print!("{}", stats::format(&stats, &info, &layer)),
+            }
+        }
+    }
+    Ok(())
+}
+
+fn display<T: Transport>(device: &mut Device<T>, command: DisplayCmd) -> Result<()> {
+    match command {
+        DisplayCmd::Show => device.show_canvas(true)?,
+        DisplayCmd::Hide => device.show_canvas(false)?,
+        DisplayCmd::Clear { color } => {
+            let rect = Rect {
+                x: 0,
+                y: 0,
+                width: CANVAS_WIDTH,
+                height: CANVAS_HEIGHT,
+            };
+            device.fill_canvas(rect, image::parse_color(&color)?)?;
+        }
+        DisplayCmd::Fill {
+            x,
+            y,
+            width,
+            height,
+            color,
+        } => {
+            let rect = Rect { x, y, width, height };
+            device.fill_canvas(rect, image::parse_color(&color)?)?;
+        }
+        DisplayCmd::Text {
+            x,
+            y,
+            text,
+            color,
+            background,
+        } => {
+            let (color, background) = (image::parse_color(&color)?, image::parse_color(&background)?);
+            // One packet only carries so much text, longer lines are sent a
+            // piece at a time
+            let mut x = x as usize;
+            let mut rest = text.as_str();
+            while !rest.is_empty() && x < CANVAS_WIDTH as usize {
+                let piece = CanvasText::new(x as u8, y, color, background, rest);
+                device.canvas_text(piece)?;
+                x += piece.text().chars().count() * CANVAS_FONT_WIDTH as usize;
+                rest = &rest[piece.text().len()..];
+            }
+        }
+        DisplayCmd::Image { file, x, y } => {
+            let image = image::load_png(&file)?;
+            if x as usize + image.width > CANVAS_WIDTH as usize || y as usize + image.height > CANVAS_HEIGHT as usize {
+                bail!(
+                    "a {}x{} image doesn't fit on the {}x{} screen at {}, {}",
+                    image.width,
+                    image.height,
+                    CANVAS_WIDTH,
+                    CANVAS_HEIGHT,
+                    x,
+                    y
+                );
+            }
+            let rect = Rect {
+                x,
+                y,
+                width: image.width as u8,
+                height: image.height as u8,
+            };
+            device.blit_canvas(rect, &image.pixels)?;
+        }
+    }
+    Ok(())
+}
+
+fn main() -> Result<()> {
+    let args = Args::parse();
+
+    if let Cmd::List = args.command {
+        for board in transport::list_boards()? {
+            println!("{}  serial {}  ({})", board.product, board.serial, board.path);
+        }
+        return Ok(());
+    }
+
+    if args.fake {
+        run(Device::new(fake::FakeDevice::new()), args.command)
+    } else {
+        run(Device::new(HidTransport::open(args.serial.as_deref())?), args.command)
+    }
+}
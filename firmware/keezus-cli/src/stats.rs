@@ -0,0 +1,81 @@
+//! Typing statistics as read from the board, printed for people or as CSV
+//! for a spreadsheet.
+
+use std::fmt::Write;
+
+use keezus_protocol::{DateTime, KeyAction, StatsSummary};
+
+use crate::device::KeymapInfo;
+use crate::keymap;
+
+pub struct Stats {
+    pub summary: StatsSummary,
+    /// One per key, row by row
+    pub keys: Vec<u32>,
+    /// One per day, today first
+    pub days: Vec<u32>,
+}
+
+impl Stats {
+    /// "YYYY-MM-DD" of the day `age` days ago, or "-N" when the board's
+    /// clock hasn't been set
+    fn day_name(&self, age: usize) -> String {
+        match self.summary.today {
+            Some(today) => {
+                let date = DateTime::from_unix((today as i64 - age as i64) * 86_400);
+                format!("{:04}-{:02}-{:02}", date.year, date.month, date.day)
+            }
+            None if age == 0 => "today".to_owned(),
+            None => format!("-{}", age),
+        }
+    }
+}
+
+/// A summary, then the presses of each key laid out like the board with
+/// the base layer's names, then the daily counts
+pub fn format(stats: &Stats, info: &KeymapInfo, layer: &[KeyAction]) -> String {
+    let mut out = String::new();
+    let _ = writeln!(
+        out,
+        "{} presses, {} today, {} wpm",
+        stats.summary.total,
+        stats.days.first().copied().unwrap_or(0),
+        stats.summary.wpm
+    );
+
+    out.push('\n');
+    for row in 0..info.rows {
+        let mut names = String::new();
+        let mut counts = String::new();
+        for col in 0..info.cols {
+            let key = row * info.cols + col;
+            let name = layer.get(key).map_or_else(|| "?".to_owned(), |&action| keymap::format_action(action));
+            let _ = write!(names, "{:>8}", name);
+            let _ = write!(counts, "{:>8}", stats.keys.get(key).copied().unwrap_or(0));
+        }
+        let _ = writeln!(out, "{}\n{}", names, counts);
+    }
+
+    out.push('\n');
+    for (age, count) in stats.days.iter().enumerate() {
+        let _ = writeln!(out, "{:>10}  {}", stats.day_name(age), count);
+    }
+    out
+}
+
+/// `row,col,key,presses` for each key, then `day,presses` for each day
+pub fn csv(stats: &Stats, info: &KeymapInfo, layer: &[KeyAction]) -> String {
+    let mut out = String::from("row,col,key,presses\n");
+    for row in 0..info.rows {
+        for col in 0..info.cols {
+            let key = row * info.cols + col;
+            let name = layer.get(key).map_or_else(|| "?".to_owned(), |&action| keymap::format_action(action));
+            let _ = writeln!(out, "{},{},{},{}", row, col, name, stats.keys.get(key).copied().unwrap_or(0));
+        }
+    }
+    out.push_str("\nday,presses\n");
+    for (age, count) in stats.days.iter().enumerate() {
+        let _ = writeln!(out, "{},{}", stats.day_name(age), count);
+    }
+    out
+}
@@ -0,0 +1,71 @@
+//! Wall clock times for `keezus time`, written as "YYYY-MM-DD HH:MM:SS".
+
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use anyhow::{anyhow, bail, Result};
+use keezus_protocol::DateTime;
+
+/// The host's local time, to the second
+pub fn local_now() -> Result<DateTime> {
+    let now = SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs() as i64;
+    Ok(DateTime::from_unix(now + utc_offset(now)))
+}
+
+/// Seconds east of UTC in the host's time zone at `unix` seconds
+#[cfg(unix)]
+fn utc_offset(unix: i64) -> i64 {
+    let time = unix as libc::time_t;
+    let mut local: libc::tm = unsafe { std::mem::zeroed() };
+    if unsafe { libc::localtime_r(&time, &mut local) }.is_null() {
+        return 0;
+    }
+    local.tm_gmtoff as i64
+}
+
+/// Without a portable way to ask for the time zone, the board gets UTC
+#[cfg(not(unix))]
+fn utc_offset(_unix: i64) -> i64 {
+    0
+}
+
+pub fn parse(text: &str) -> Result<DateTime> {
+    let invalid = || anyhow!("expected a time like \"2024-01-31 13:45:00\", got {:?}", text);
+    let (date, time) = text.trim().split_once([' ', 'T']).ok_or_else(invalid)?;
+    let numbers = |text: &str, separator: char| -> Result<Vec<u16>> {
+        text.split(separator).map(|n| n.parse().map_err(|_| invalid())).collect()
+    };
+    let (date, time) = (numbers(date, '-')?, numbers(time, ':')?);
+    let (&[year, month, day], &[hour, minute, second]) = (date.as_slice(), time.as_slice()) else {
+        return Err(invalid());
+    };
+    let parsed = DateTime {
+        year,
+        month: month.min(u8::MAX as u16) as u8,
+        day: day.min(u8::MAX as u16) as u8,
+        weekday: 0,
+        hour: hour.min(u8::MAX as u16) as u8,
+        minute: minute.min(u8::MAX as u16) as u8,
+        second: second.min(u8::MAX as u16) as u8,
+    };
+    // Going through seconds fills in the weekday, and turns e.g. 31 February
+    // into a different day, which is how that gets caught
+    let time = DateTime::from_unix(parsed.to_unix());
+    if !parsed.is_valid() || (time.year, time.month, time.day) != (parsed.year, parsed.month, parsed.day) {
+        bail!("{:?} isn't a time the board can keep", text);
+    }
+    Ok(time)
+}
+
+pub fn format(time: &DateTime) -> String {
+    const WEEKDAYS: [&str; 7] = ["Sun", "Mon", "Tue", "Wed", "Thu", "Fri", "Sat"];
+    format!(
+        "{} {:04}-{:02}-{:02} {:02}:{:02}:{:02}",
+        WEEKDAYS.get(time.weekday as usize).unwrap_or(&"?"),
+        time.year,
+        time.month,
+        time.day,
+        time.hour,
+        time.minute,
+        time.second
+    )
+}
@@ -0,0 +1,81 @@
+//! Ways of getting packets to and from a board.
+
+use std::time::Duration;
+
+use anyhow::{anyhow, Context, Result};
+use hidapi::{HidApi, HidDevice};
+use keezus_protocol::{REPORT_LEN, USAGE, USAGE_PAGE};
+
+pub type Packet = [u8; REPORT_LEN];
+
+pub trait Transport {
+    fn write(&mut self, packet: &Packet) -> Result<()>;
+    /// Returns `None` if nothing arrived within `timeout`
+    fn read(&mut self, timeout: Duration) -> Result<Option<Packet>>;
+}
+
+/// A board's raw HID interface
+pub struct HidTransport {
+    device: HidDevice,
+}
+
+/// A raw HID interface found on the bus
+pub struct BoardInfo {
+    pub product: String,
+    pub serial: String,
+    pub path: String,
+}
+
+fn is_keezus(info: &hidapi::DeviceInfo) -> bool {
+    info.usage_page() == USAGE_PAGE && info.usage() == USAGE
+}
+
+/// Lists every connected board's raw HID interface
+pub fn list_boards() -> Result<Vec<BoardInfo>> {
+    let api = HidApi::new().context("couldn't initialise hidapi")?;
+    Ok(api
+        .device_list()
+        .filter(|info| is_keezus(info))
+        .map(|info| BoardInfo {
+            product: info.product_string().unwrap_or("?").to_owned(),
+            serial: info.serial_number().unwrap_or("?").to_owned(),
+            path: info.path().to_string_lossy().into_owned(),
+        })
+        .collect())
+}
+
+impl HidTransport {
+    /// Opens the first board found, or the one with the given serial number
+    pub fn open(serial: Option<&str>) -> Result<HidTransport> {
+        let api = HidApi::new().context("couldn't initialise hidapi")?;
+        let info = api
+            .device_list()
+            .filter(|info| is_keezus(info))
+            .find(|info| serial.is_none_or(|serial| info.serial_number() == Some(serial)))
+            .ok_or_else(|| match serial {
+                Some(serial) => anyhow!("no keezyboost40 with serial {} found", serial),
+                None => anyhow!("no keezyboost40 found"),
+            })?;
+        let device = info.open_device(&api).context("couldn't open the raw HID interface")?;
+        Ok(HidTransport { device })
+    }
+}
+
+impl Transport for HidTransport {
+    fn write(&mut self, packet: &Packet) -> Result<()> {
+        // hidapi wants the report ID first, the interface doesn't use any
+        let mut report = [0u8; REPORT_LEN + 1];
+        report[1..].copy_from_slice(packet);
+        self.device.write(&report).context("write to the board failed")?;
+        Ok(())
+    }
+
+    fn read(&mut self, timeout: Duration) -> Result<Option<Packet>> {
+        let mut packet = [0u8; REPORT_LEN];
+        let len = self
+            .device
+            .read_timeout(&mut packet, timeout.as_millis() as i32)
+            .context("read from the board failed")?;
+        Ok(if len == 0 { None } else { Some(packet) })
+    }
+}
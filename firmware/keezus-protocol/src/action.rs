@@ -0,0 +1,59 @@
+//! Keymap entries as they go over the wire.
+//!
+//! Only the simple keyberon actions can be represented, anything else (hold
+//! taps, multiple actions, ...) shows up as `Other` and is left untouched by
+//! the firmware when written back.
+
+const TAG_SPECIAL: u8 = 0x00;
+const TAG_KEYCODE: u8 = 0x01;
+const TAG_LAYER: u8 = 0x02;
+const TAG_DEFAULT_LAYER: u8 = 0x03;
+const TAG_CUSTOM: u8 = 0x04;
+const TAG_OTHER: u8 = 0xff;
+
+const SPECIAL_NO_OP: u8 = 0x00;
+const SPECIAL_TRANS: u8 = 0x01;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum KeyAction {
+    NoOp,
+    Trans,
+    /// HID usage ID of the key
+    KeyCode(u8),
+    /// Momentarily switch to a layer while held
+    Layer(u8),
+    DefaultLayer(u8),
+    /// Index into the firmware's custom actions
+    Custom(u8),
+    /// Something the protocol can't express
+    Other,
+}
+
+impl KeyAction {
+    pub fn to_u16(self) -> u16 {
+        let (tag, value) = match self {
+            KeyAction::NoOp => (TAG_SPECIAL, SPECIAL_NO_OP),
+            KeyAction::Trans => (TAG_SPECIAL, SPECIAL_TRANS),
+            KeyAction::KeyCode(kc) => (TAG_KEYCODE, kc),
+            KeyAction::Layer(layer) => (TAG_LAYER, layer),
+            KeyAction::DefaultLayer(layer) => (TAG_DEFAULT_LAYER, layer),
+            KeyAction::Custom(index) => (TAG_CUSTOM, index),
+            KeyAction::Other => (TAG_OTHER, 0xff),
+        };
+        u16::from_be_bytes([tag, value])
+    }
+
+    /// Unknown encodings decode to `Other`
+    pub fn from_u16(raw: u16) -> KeyAction {
+        let [tag, value] = raw.to_be_bytes();
+        match (tag, value) {
+            (TAG_SPECIAL, SPECIAL_NO_OP) => KeyAction::NoOp,
+            (TAG_SPECIAL, SPECIAL_TRANS) => KeyAction::Trans,
+            (TAG_KEYCODE, kc) => KeyAction::KeyCode(kc),
+            (TAG_LAYER, layer) => KeyAction::Layer(layer),
+            (TAG_DEFAULT_LAYER, layer) => KeyAction::DefaultLayer(layer),
+            (TAG_CUSTOM, index) => KeyAction::Custom(index),
+            _ => KeyAction::Other,
+        }
+    }
+}
@@ -0,0 +1,163 @@
+//! Payloads for drawing on the host canvas, a screen sized RGB565
+//! framebuffer the firmware shows in place of its own screens while the
+//! host has it enabled.
+//!
+//! Colours are raw RGB565 values.
+
+use crate::{Error, Reader, Writer};
+
+pub const CANVAS_WIDTH: u8 = 128;
+pub const CANVAS_HEIGHT: u8 = 160;
+/// Text is drawn in a fixed width font with cells this big
+pub const CANVAS_FONT_WIDTH: u8 = 6;
+pub const CANVAS_FONT_HEIGHT: u8 = 10;
+/// Pixels that fit in one `CanvasPixels` packet
+pub const PIXEL_RUN_LEN: usize = 15;
+/// Bytes of UTF-8 that fit in one `CanvasText` packet
+pub const CANVAS_TEXT_LEN: usize = 24;
+
+/// Converts 8 bit per channel RGB to RGB565
+pub const fn rgb565(r: u8, g: u8, b: u8) -> u16 {
+    ((r as u16 >> 3) << 11) | ((g as u16 >> 2) << 5) | (b as u16 >> 3)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Rect {
+    pub x: u8,
+    pub y: u8,
+    pub width: u8,
+    pub height: u8,
+}
+
+impl Rect {
+    pub(crate) fn write(&self, w: &mut Writer) {
+        w.u8(self.x);
+        w.u8(self.y);
+        w.u8(self.width);
+        w.u8(self.height);
+    }
+
+    pub(crate) fn read(r: &mut Reader) -> Result<Rect, Error> {
+        Ok(Rect {
+            x: r.u8()?,
+            y: r.u8()?,
+            width: r.u8()?,
+            height: r.u8()?,
+        })
+    }
+}
+
+/// Pixel data for the window opened by the last `CanvasBlit`, filled left
+/// to right, top to bottom
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PixelRun {
+    pub count: u8,
+    pub pixels: [u16; PIXEL_RUN_LEN],
+}
+
+impl PixelRun {
+    pub fn new(pixels: &[u16]) -> PixelRun {
+        let count = pixels.len().min(PIXEL_RUN_LEN);
+        let mut run = PixelRun {
+            count: count as u8,
+            pixels: [0; PIXEL_RUN_LEN],
+        };
+        run.pixels[..count].copy_from_slice(&pixels[..count]);
+        run
+    }
+
+    pub fn pixels(&self) -> &[u16] {
+        &self.pixels[..self.count as usize]
+    }
+
+    pub(crate) fn write(&self, w: &mut Writer) {
+        w.u8(self.count);
+        for &pixel in self.pixels() {
+            w.u16(pixel);
+        }
+    }
+
+    pub(crate) fn read(r: &mut Reader) -> Result<PixelRun, Error> {
+        let count = r.u8()? as usize;
+        if count > PIXEL_RUN_LEN {
+            return Err(Error::InvalidArgument);
+        }
+        let mut pixels = [0; PIXEL_RUN_LEN];
+        for pixel in pixels.iter_mut().take(count) {
+            *pixel = r.u16()?;
+        }
+        Ok(PixelRun {
+            count: count as u8,
+            pixels,
+        })
+    }
+}
+
+/// A line of text drawn with its top left corner at `x`, `y`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CanvasText {
+    pub x: u8,
+    pub y: u8,
+    pub color: u16,
+    pub background: u16,
+    len: u8,
+    bytes: [u8; CANVAS_TEXT_LEN],
+}
+
+impl CanvasText {
+    /// Text that doesn't fit is cut off at a character boundary
+    pub fn new(x: u8, y: u8, color: u16, background: u16, text: &str) -> CanvasText {
+        let mut len = text.len().min(CANVAS_TEXT_LEN);
+        while !text.is_char_boundary(len) {
+            len -= 1;
+        }
+        let mut bytes = [0; CANVAS_TEXT_LEN];
+        bytes[..len].copy_from_slice(&text.as_bytes()[..len]);
+        CanvasText {
+            x,
+            y,
+            color,
+            background,
+            len: len as u8,
+            bytes,
+        }
+    }
+
+    pub fn text(&self) -> &str {
+        core::str::from_utf8(&self.bytes[..self.len as usize]).unwrap_or("")
+    }
+
+    pub(crate) fn write(&self, w: &mut Writer) {
+        w.u8(self.x);
+        w.u8(self.y);
+        w.u16(self.color);
+        w.u16(self.background);
+        w.u8(self.len);
+        for &byte in &self.bytes[..self.len as usize] {
+            w.u8(byte);
+        }
+    }
+
+    pub(crate) fn read(r: &mut Reader) -> Result<CanvasText, Error> {
+        let (x, y, color, background) = (r.u8()?, r.u8()?, r.u16()?, r.u16()?);
+        let len = r.u8()? as usize;
+        if len > CANVAS_TEXT_LEN {
+            return Err(Error::InvalidArgument);
+        }
+        let mut bytes = [0; CANVAS_TEXT_LEN];
+        for byte in bytes.iter_mut().take(len) {
+            *byte = r.u8()?;
+        }
+        if core::str::from_utf8(&bytes[..len]).is_err() {
+            return Err(Error::InvalidArgument);
+        }
+        Ok(CanvasText {
+            x,
+            y,
+            color,
+            background,
+            len: len as u8,
+            bytes,
+        })
+    }
+}
@@ -0,0 +1,650 @@
+//! The keyboard side of USB HID: answering the HID class's control
+//! requests, the protocol mode and idle rate the host sets with them, and
+//! the boot and NKRO keyboard reports.
+//!
+//! None of this touches the USB stack, `keezus`'s `HidClass` hands
+//! `HidInterface` the requests and `Keyboard` the pressed keys, so it
+//! builds and is tested on the host along with the rest of this crate.
+
+pub const REQ_GET_REPORT: u8 = 0x01;
+pub const REQ_GET_IDLE: u8 = 0x02;
+pub const REQ_GET_PROTOCOL: u8 = 0x03;
+pub const REQ_SET_REPORT: u8 = 0x09;
+pub const REQ_SET_IDLE: u8 = 0x0a;
+pub const REQ_SET_PROTOCOL: u8 = 0x0b;
+
+/// Modifier byte, reserved byte, then up to 6 usages
+pub const BOOT_REPORT_LEN: usize = 8;
+/// Modifier byte followed by a bitmap of usages 0x00..=0xdf, the whole
+/// keyboard page below the modifiers
+pub const NKRO_REPORT_LEN: usize = 29;
+pub const NKRO_MAX_USAGE: u8 = 0xdf;
+
+const REQ_GET_DESCRIPTOR: u8 = 0x06;
+const DESC_TYPE_HID: u8 = 0x21;
+const DESC_TYPE_REPORT: u8 = 0x22;
+const HID_SPEC_1_11: [u8; 2] = [0x11, 0x01];
+const COUNTRY_UNSPECIFIED: u8 = 0x00;
+
+const USAGE_ERROR_ROLL_OVER: u8 = 0x01;
+const USAGE_ERROR_UNDEFINED: u8 = 0x03;
+const USAGE_LEFT_CTRL: u8 = 0xe0;
+const USAGE_RIGHT_GUI: u8 = 0xe7;
+
+/// Report format requested by the host with SET_PROTOCOL
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+#[repr(u8)]
+pub enum ProtocolMode {
+    Boot = 0x00,
+    Report = 0x01,
+}
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum ReportType {
+    Input,
+    Output,
+    Feature,
+    Reserved(u8),
+}
+
+impl From<u8> for ReportType {
+    fn from(val: u8) -> Self {
+        match val {
+            1 => ReportType::Input,
+            2 => ReportType::Output,
+            3 => ReportType::Feature,
+            _ => ReportType::Reserved(val),
+        }
+    }
+}
+
+/// What the host has set with SET_PROTOCOL and SET_IDLE
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub struct HidState {
+    protocol_mode: ProtocolMode,
+    /// Idle rate in units of 4ms, 0 means only report on change
+    idle_rate: u8,
+}
+
+impl HidState {
+    pub const fn new() -> HidState {
+        HidState {
+            // The HID spec says devices come up in report protocol, and only
+            // switch to boot protocol when asked
+            protocol_mode: ProtocolMode::Report,
+            idle_rate: 0,
+        }
+    }
+
+    /// Back to the defaults, on a bus reset
+    pub fn reset(&mut self) {
+        *self = HidState::new();
+    }
+
+    pub fn protocol_mode(&self) -> ProtocolMode {
+        self.protocol_mode
+    }
+
+    /// The idle rate set by the host in milliseconds, 0 meaning infinite
+    pub fn idle_ms(&self) -> u32 {
+        self.idle_rate as u32 * 4
+    }
+
+    /// Handles a class OUT request with the given `wValue`, returns false
+    /// for the ones this doesn't know
+    pub fn control_out(&mut self, request: u8, value: u16) -> bool {
+        match request {
+            REQ_SET_IDLE => {
+                // Upper byte is the duration, lower byte the report ID. We
+                // only have the one report so the ID is ignored
+                self.idle_rate = (value >> 8) as u8;
+                true
+            }
+            REQ_SET_PROTOCOL => {
+                self.protocol_mode = match value {
+                    0 => ProtocolMode::Boot,
+                    _ => ProtocolMode::Report,
+                };
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Answers a class IN request, `None` for the ones this doesn't know
+    pub fn control_in(&self, request: u8) -> Option<u8> {
+        match request {
+            REQ_GET_IDLE => Some(self.idle_rate),
+            REQ_GET_PROTOCOL => Some(self.protocol_mode as u8),
+            _ => None,
+        }
+    }
+}
+
+impl Default for HidState {
+    fn default() -> HidState {
+        HidState::new()
+    }
+}
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+#[repr(u8)]
+pub enum Subclass {
+    None = 0x00,
+    BootInterface = 0x01,
+}
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+#[repr(u8)]
+pub enum Protocol {
+    None = 0x00,
+    Keyboard = 0x01,
+}
+
+pub trait HidDevice {
+    fn subclass(&self) -> Subclass;
+    fn protocol(&self) -> Protocol;
+    fn max_packet_size(&self) -> u16;
+    fn report_descriptor(&self) -> &[u8];
+    /// Returns false for reports the device doesn't take
+    fn set_report(&mut self, report_type: ReportType, report_id: u8, data: &[u8]) -> bool;
+    /// `mode` is the protocol the host has selected, devices without a boot
+    /// report just ignore it
+    fn get_report(&mut self, mode: ProtocolMode, report_type: ReportType, report_id: u8) -> Option<&[u8]>;
+}
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum RequestType {
+    Standard,
+    Class,
+    Vendor,
+}
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum Recipient {
+    Device,
+    Interface,
+    Endpoint,
+    Other,
+}
+
+/// The setup packet of a control request, as far as HID cares
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub struct ControlRequest {
+    pub request_type: RequestType,
+    pub recipient: Recipient,
+    pub request: u8,
+    pub value: u16,
+    pub index: u16,
+}
+
+/// How to finish a control transfer
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum Reply<'a> {
+    /// Not for this interface, leave it to the other classes
+    Ignore,
+    /// Stall it
+    Reject,
+    /// Accept it, sending this back for IN requests
+    Accept(&'a [u8]),
+}
+
+/// One HID interface: a device and the state the host has set on it
+pub struct HidInterface<D: HidDevice> {
+    device: D,
+    /// Interface number, requests for any other are ignored
+    number: u8,
+    state: HidState,
+    /// Room for answers that don't live in the device
+    answer: [u8; 7],
+}
+
+impl<D: HidDevice> HidInterface<D> {
+    pub fn new(device: D, number: u8) -> HidInterface<D> {
+        HidInterface {
+            device,
+            number,
+            state: HidState::new(),
+            answer: [0; 7],
+        }
+    }
+
+    pub fn device(&self) -> &D {
+        &self.device
+    }
+
+    pub fn device_mut(&mut self) -> &mut D {
+        &mut self.device
+    }
+
+    pub fn state(&self) -> &HidState {
+        &self.state
+    }
+
+    /// Back to the defaults, on a bus reset
+    pub fn reset(&mut self) {
+        self.state.reset();
+    }
+
+    /// The device's current input report in whichever format the host has
+    /// selected
+    pub fn input_report(&mut self) -> Option<&[u8]> {
+        self.device.get_report(self.state.protocol_mode(), ReportType::Input, 0)
+    }
+
+    /// The HID descriptor that goes in the configuration descriptor
+    pub fn hid_descriptor(&self) -> [u8; 7] {
+        let len = self.device.report_descriptor().len() as u16;
+        let [len_lo, len_hi] = len.to_le_bytes();
+        [
+            HID_SPEC_1_11[0],
+            HID_SPEC_1_11[1],
+            COUNTRY_UNSPECIFIED,
+            1, // number of class descriptors
+            DESC_TYPE_REPORT,
+            len_lo,
+            len_hi,
+        ]
+    }
+
+    fn is_for_us(&self, req: &ControlRequest) -> bool {
+        req.recipient == Recipient::Interface && req.index == self.number as u16
+    }
+
+    pub fn control_in(&mut self, req: &ControlRequest) -> Reply<'_> {
+        if !self.is_for_us(req) {
+            return Reply::Ignore;
+        }
+        match req.request_type {
+            RequestType::Standard if req.request == REQ_GET_DESCRIPTOR => match (req.value >> 8) as u8 {
+                DESC_TYPE_REPORT => Reply::Accept(self.device.report_descriptor()),
+                DESC_TYPE_HID => {
+                    self.answer = self.hid_descriptor();
+                    Reply::Accept(&self.answer)
+                }
+                _ => Reply::Ignore,
+            },
+            RequestType::Class => match req.request {
+                REQ_GET_REPORT => {
+                    let [report_id, report_type] = req.value.to_le_bytes();
+                    let mode = self.state.protocol_mode();
+                    match self.device.get_report(mode, report_type.into(), report_id) {
+                        Some(data) => Reply::Accept(data),
+                        None => Reply::Reject,
+                    }
+                }
+                request => match self.state.control_in(request) {
+                    Some(value) => {
+                        self.answer[0] = value;
+                        Reply::Accept(&self.answer[..1])
+                    }
+                    None => Reply::Reject,
+                },
+            },
+            _ => Reply::Ignore,
+        }
+    }
+
+    /// `data` is what came with the request
+    pub fn control_out(&mut self, req: &ControlRequest, data: &[u8]) -> Reply<'static> {
+        if req.request_type != RequestType::Class || !self.is_for_us(req) {
+            return Reply::Ignore;
+        }
+        let done = match req.request {
+            REQ_SET_REPORT => {
+                let [report_id, report_type] = req.value.to_le_bytes();
+                self.device.set_report(report_type.into(), report_id, data)
+            }
+            request => self.state.control_out(request, req.value),
+        };
+        if done {
+            Reply::Accept(&[])
+        } else {
+            Reply::Reject
+        }
+    }
+}
+
+/// The pressed keys in both report formats
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct KeyboardReports {
+    boot: [u8; BOOT_REPORT_LEN],
+    nkro: [u8; NKRO_REPORT_LEN],
+}
+
+impl KeyboardReports {
+    pub const fn new() -> KeyboardReports {
+        KeyboardReports {
+            boot: [0; BOOT_REPORT_LEN],
+            nkro: [0; NKRO_REPORT_LEN],
+        }
+    }
+
+    /// Updates both reports from the usages of the pressed keys. Returns
+    /// true if anything changed.
+    pub fn set_usages(&mut self, usages: impl IntoIterator<Item = u8>) -> bool {
+        let mut boot = [0u8; BOOT_REPORT_LEN];
+        let mut nkro = [0u8; NKRO_REPORT_LEN];
+        for usage in usages {
+            if (USAGE_LEFT_CTRL..=USAGE_RIGHT_GUI).contains(&usage) {
+                boot[0] |= 1 << (usage - USAGE_LEFT_CTRL);
+                nkro[0] |= 1 << (usage - USAGE_LEFT_CTRL);
+                continue;
+            }
+            boot_press(&mut boot, usage);
+            if usage > USAGE_ERROR_UNDEFINED && usage <= NKRO_MAX_USAGE {
+                nkro[1 + (usage / 8) as usize] |= 1 << (usage % 8);
+            }
+        }
+
+        let changed = boot != self.boot || nkro != self.nkro;
+        self.boot = boot;
+        self.nkro = nkro;
+        changed
+    }
+
+    pub fn report(&self, mode: ProtocolMode) -> &[u8] {
+        match mode {
+            ProtocolMode::Boot => &self.boot,
+            ProtocolMode::Report => &self.nkro,
+        }
+    }
+}
+
+impl Default for KeyboardReports {
+    fn default() -> KeyboardReports {
+        KeyboardReports::new()
+    }
+}
+
+/// Adds a non modifier usage to the boot report the way keyberon's
+/// `KbHidReport` does: more than 6 keys fills every slot with
+/// ErrorRollOver
+fn boot_press(boot: &mut [u8; BOOT_REPORT_LEN], usage: u8) {
+    let keys = &mut boot[2..];
+    match usage {
+        0 => {}
+        USAGE_ERROR_ROLL_OVER..=USAGE_ERROR_UNDEFINED => keys.fill(usage),
+        _ if keys.contains(&usage) => {}
+        _ => match keys.iter_mut().find(|key| **key == 0) {
+            Some(key) => *key = usage,
+            None => keys.fill(USAGE_ERROR_ROLL_OVER),
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const A: u8 = 0x04;
+    const Z: u8 = 0x1d;
+    const LEFT_SHIFT: u8 = 0xe1;
+
+    const INTERFACE: u8 = 1;
+    const DESCRIPTOR: &[u8] = &[0x05, 0x01, 0x09, 0x06, 0xa1, 0x01, 0xc0];
+
+    /// A keyboard with A held down and an LED output report
+    struct MockKeyboard {
+        reports: KeyboardReports,
+        leds: u8,
+    }
+
+    impl HidDevice for MockKeyboard {
+        fn subclass(&self) -> Subclass {
+            Subclass::BootInterface
+        }
+
+        fn protocol(&self) -> Protocol {
+            Protocol::Keyboard
+        }
+
+        fn max_packet_size(&self) -> u16 {
+            NKRO_REPORT_LEN as u16
+        }
+
+        fn report_descriptor(&self) -> &[u8] {
+            DESCRIPTOR
+        }
+
+        fn set_report(&mut self, report_type: ReportType, report_id: u8, data: &[u8]) -> bool {
+            match (report_type, report_id, data) {
+                (ReportType::Output, 0, &[leds]) => {
+                    self.leds = leds;
+                    true
+                }
+                _ => false,
+            }
+        }
+
+        fn get_report(&mut self, mode: ProtocolMode, report_type: ReportType, _report_id: u8) -> Option<&[u8]> {
+            match report_type {
+                ReportType::Input => Some(self.reports.report(mode)),
+                _ => None,
+            }
+        }
+    }
+
+    fn interface() -> HidInterface<MockKeyboard> {
+        let mut reports = KeyboardReports::new();
+        reports.set_usages([A]);
+        HidInterface::new(MockKeyboard { reports, leds: 0 }, INTERFACE)
+    }
+
+    fn class(request: u8, value: u16) -> ControlRequest {
+        ControlRequest {
+            request_type: RequestType::Class,
+            recipient: Recipient::Interface,
+            request,
+            value,
+            index: INTERFACE as u16,
+        }
+    }
+
+    /// `wValue` for GET_REPORT and SET_REPORT
+    fn report_value(report_type: u8) -> u16 {
+        (report_type as u16) << 8
+    }
+
+    #[test]
+    fn protocol_defaults_to_report() {
+        let state = HidState::new();
+        assert_eq!(state.protocol_mode(), ProtocolMode::Report);
+        assert_eq!(state.control_in(REQ_GET_PROTOCOL), Some(ProtocolMode::Report as u8));
+    }
+
+    #[test]
+    fn set_protocol() {
+        let mut state = HidState::new();
+        assert!(state.control_out(REQ_SET_PROTOCOL, 0));
+        assert_eq!(state.protocol_mode(), ProtocolMode::Boot);
+        assert_eq!(state.control_in(REQ_GET_PROTOCOL), Some(0));
+
+        assert!(state.control_out(REQ_SET_PROTOCOL, 1));
+        assert_eq!(state.protocol_mode(), ProtocolMode::Report);
+        assert_eq!(state.control_in(REQ_GET_PROTOCOL), Some(1));
+    }
+
+    #[test]
+    fn set_idle() {
+        let mut state = HidState::new();
+        assert_eq!(state.control_in(REQ_GET_IDLE), Some(0));
+        assert_eq!(state.idle_ms(), 0);
+
+        // 500ms for report ID 0
+        assert!(state.control_out(REQ_SET_IDLE, 125 << 8));
+        assert_eq!(state.control_in(REQ_GET_IDLE), Some(125));
+        assert_eq!(state.idle_ms(), 500);
+
+        // The report ID doesn't matter
+        assert!(state.control_out(REQ_SET_IDLE, (10 << 8) | 3));
+        assert_eq!(state.control_in(REQ_GET_IDLE), Some(10));
+    }
+
+    #[test]
+    fn reset_restores_defaults() {
+        let mut state = HidState::new();
+        state.control_out(REQ_SET_PROTOCOL, 0);
+        state.control_out(REQ_SET_IDLE, 125 << 8);
+        state.reset();
+        assert_eq!(state, HidState::new());
+    }
+
+    #[test]
+    fn unknown_requests() {
+        let mut state = HidState::new();
+        assert!(!state.control_out(REQ_SET_REPORT, 0));
+        assert!(!state.control_out(0x42, 0));
+        assert_eq!(state.control_in(REQ_GET_REPORT), None);
+        assert_eq!(state, HidState::new());
+    }
+
+    #[test]
+    fn protocol_requests() {
+        let mut hid = interface();
+        assert_eq!(hid.control_in(&class(REQ_GET_PROTOCOL, 0)), Reply::Accept(&[1]));
+        assert_eq!(hid.control_out(&class(REQ_SET_PROTOCOL, 0), &[]), Reply::Accept(&[]));
+        assert_eq!(hid.state().protocol_mode(), ProtocolMode::Boot);
+        assert_eq!(hid.control_in(&class(REQ_GET_PROTOCOL, 0)), Reply::Accept(&[0]));
+        // The input report follows the protocol
+        assert_eq!(hid.input_report(), Some(&[0, 0, A, 0, 0, 0, 0, 0][..]));
+
+        hid.reset();
+        assert_eq!(hid.state().protocol_mode(), ProtocolMode::Report);
+        assert_eq!(hid.input_report().map(<[u8]>::len), Some(NKRO_REPORT_LEN));
+    }
+
+    #[test]
+    fn idle_requests() {
+        let mut hid = interface();
+        assert_eq!(hid.control_in(&class(REQ_GET_IDLE, 0)), Reply::Accept(&[0]));
+        assert_eq!(hid.control_out(&class(REQ_SET_IDLE, 125 << 8), &[]), Reply::Accept(&[]));
+        assert_eq!(hid.control_in(&class(REQ_GET_IDLE, 0)), Reply::Accept(&[125]));
+        assert_eq!(hid.state().idle_ms(), 500);
+    }
+
+    #[test]
+    fn report_requests() {
+        let mut hid = interface();
+        assert_eq!(hid.control_out(&class(REQ_SET_PROTOCOL, 0), &[]), Reply::Accept(&[]));
+        let boot = [0, 0, A, 0, 0, 0, 0, 0];
+        assert_eq!(hid.control_in(&class(REQ_GET_REPORT, report_value(1))), Reply::Accept(&boot));
+        assert_eq!(hid.control_in(&class(REQ_GET_REPORT, report_value(3))), Reply::Reject);
+
+        // Caps lock on
+        assert_eq!(hid.control_out(&class(REQ_SET_REPORT, report_value(2)), &[0x02]), Reply::Accept(&[]));
+        assert_eq!(hid.device().leds, 0x02);
+        assert_eq!(hid.control_out(&class(REQ_SET_REPORT, report_value(2)), &[1, 2]), Reply::Reject);
+        assert_eq!(hid.control_out(&class(REQ_SET_REPORT, report_value(3)), &[1]), Reply::Reject);
+        assert_eq!(hid.device().leds, 0x02);
+    }
+
+    #[test]
+    fn descriptor_requests() {
+        let mut hid = interface();
+        let get_descriptor = |descriptor_type: u8| ControlRequest {
+            request_type: RequestType::Standard,
+            request: REQ_GET_DESCRIPTOR,
+            value: (descriptor_type as u16) << 8,
+            ..class(0, 0)
+        };
+        assert_eq!(hid.control_in(&get_descriptor(DESC_TYPE_REPORT)), Reply::Accept(DESCRIPTOR));
+        let hid_descriptor = [0x11, 0x01, 0, 1, DESC_TYPE_REPORT, DESCRIPTOR.len() as u8, 0];
+        assert_eq!(hid.control_in(&get_descriptor(DESC_TYPE_HID)), Reply::Accept(&hid_descriptor));
+        // Strings and the like are the device's business
+        assert_eq!(hid.control_in(&get_descriptor(0x03)), Reply::Ignore);
+    }
+
+    #[test]
+    fn other_interfaces_and_recipients_are_ignored() {
+        let mut hid = interface();
+        let other_interface = ControlRequest {
+            index: INTERFACE as u16 + 1,
+            ..class(REQ_SET_PROTOCOL, 0)
+        };
+        let device = ControlRequest {
+            recipient: Recipient::Device,
+            ..class(REQ_SET_PROTOCOL, 0)
+        };
+        let endpoint = ControlRequest {
+            recipient: Recipient::Endpoint,
+            ..class(REQ_SET_PROTOCOL, 0)
+        };
+        let vendor = ControlRequest {
+            request_type: RequestType::Vendor,
+            ..class(REQ_SET_PROTOCOL, 0)
+        };
+        for req in [other_interface, device, endpoint, vendor] {
+            assert_eq!(hid.control_out(&req, &[]), Reply::Ignore);
+            let get = ControlRequest {
+                request: REQ_GET_PROTOCOL,
+                ..req
+            };
+            assert_eq!(hid.control_in(&get), Reply::Ignore);
+        }
+        assert_eq!(hid.state().protocol_mode(), ProtocolMode::Report);
+    }
+
+    #[test]
+    fn unknown_class_requests_are_rejected() {
+        let mut hid = interface();
+        assert_eq!(hid.control_out(&class(0x42, 0), &[]), Reply::Reject);
+        assert_eq!(hid.control_in(&class(0x42, 0)), Reply::Reject);
+        // SET requests don't go IN
+        assert_eq!(hid.control_in(&class(REQ_SET_IDLE, 0)), Reply::Reject);
+    }
+
+    #[test]
+    fn boot_and_nkro_reports() {
+        let mut reports = KeyboardReports::new();
+        assert!(reports.set_usages([LEFT_SHIFT, A, Z]));
+        assert_eq!(reports.report(ProtocolMode::Boot), [0x02, 0, A, Z, 0, 0, 0, 0]);
+
+        let nkro = reports.report(ProtocolMode::Report);
+        assert_eq!(nkro.len(), NKRO_REPORT_LEN);
+        assert_eq!(nkro[0], 0x02);
+        assert_eq!(nkro[1 + (A / 8) as usize], 1 << (A % 8));
+        assert_eq!(nkro[1 + (Z / 8) as usize], 1 << (Z % 8));
+        assert_eq!(nkro.iter().filter(|b| **b != 0).count(), 3);
+    }
+
+    #[test]
+    fn unchanged_keys_are_not_a_change() {
+        let mut reports = KeyboardReports::new();
+        assert!(!reports.set_usages([]));
+        assert!(reports.set_usages([A]));
+        assert!(!reports.set_usages([A]));
+        assert!(reports.set_usages([]));
+        assert_eq!(reports.report(ProtocolMode::Boot), [0; BOOT_REPORT_LEN]);
+        assert_eq!(reports.report(ProtocolMode::Report), [0; NKRO_REPORT_LEN]);
+    }
+
+    #[test]
+    fn boot_report_rolls_over_past_six_keys() {
+        let mut reports = KeyboardReports::new();
+        reports.set_usages([LEFT_SHIFT, A, A + 1, A + 2, A + 3, A + 4, A + 5, A + 6]);
+        let boot = reports.report(ProtocolMode::Boot);
+        assert_eq!(boot[0], 0x02);
+        assert_eq!(boot[2..], [USAGE_ERROR_ROLL_OVER; 6]);
+
+        // NKRO has every one of them
+        let nkro = reports.report(ProtocolMode::Report);
+        let keys = (0..=NKRO_MAX_USAGE).filter(|u| nkro[1 + (u / 8) as usize] & (1 << (u % 8)) != 0);
+        assert!(keys.eq(A..=A + 6));
+    }
+
+    #[test]
+    fn nkro_covers_the_keyboard_page() {
+        let mut reports = KeyboardReports::new();
+        // International and keypad keys past the old 0x77 limit
+        reports.set_usages([0x87, 0xb0, NKRO_MAX_USAGE]);
+        let nkro = reports.report(ProtocolMode::Report);
+        for usage in [0x87u8, 0xb0, NKRO_MAX_USAGE] {
+            assert_ne!(nkro[1 + (usage / 8) as usize] & (1 << (usage % 8)), 0);
+        }
+
+        // Errors and usages past the modifiers aren't keys
+        reports.set_usages([USAGE_ERROR_ROLL_OVER, 0xe8, 0xff]);
+        assert_eq!(reports.report(ProtocolMode::Report), [0; NKRO_REPORT_LEN]);
+    }
+}
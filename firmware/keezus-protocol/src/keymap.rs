@@ -0,0 +1,209 @@
+//! The keyboard's matrix and default keymap, and the staging copy of the
+//! keymap that `ReadKeymap` and `WriteKeymap` work on.
+//!
+//! The firmware builds its layout from `DEFAULT_KEYMAP`, and keezus-cli's
+//! fake board uses the same one, so it can't drift from what's on the board.
+
+use crate::{KeyAction, KeymapChunk};
+
+pub const LAYERS: usize = 2;
+pub const ROWS: usize = 4;
+pub const COLS: usize = 10;
+/// Entries are indexed `(layer * ROWS + row) * COLS + col`
+pub const KEYMAP_LEN: usize = LAYERS * ROWS * COLS;
+
+/// Shown on the display for each layer
+pub const LAYER_NAMES: [&str; LAYERS] = ["QWERTY", "Fn"];
+
+/// Indices of the firmware's custom actions, for `KeyAction::Custom`
+pub const CUSTOM_BOOTLOADER: u8 = 0;
+pub const CUSTOM_CYCLE_REPORT_RATE: u8 = 1;
+pub const CUSTOM_NEXT_SCREEN: u8 = 2;
+pub const CUSTOM_BRIGHTNESS_UP: u8 = 3;
+pub const CUSTOM_BRIGHTNESS_DOWN: u8 = 4;
+pub const CUSTOM_GAME_MODE: u8 = 5;
+pub const CUSTOM_ACTIONS: usize = 6;
+
+const fn k(usage: u8) -> KeyAction {
+    KeyAction::KeyCode(usage)
+}
+
+const fn c(index: u8) -> KeyAction {
+    KeyAction::Custom(index)
+}
+
+/// Placeholders where a 2u key leaves a hole in the matrix, and keys the Fn
+/// layer leaves alone
+const T: KeyAction = KeyAction::Trans;
+/// Held for the Fn layer
+const FN: KeyAction = KeyAction::Layer(1);
+const SCRN: KeyAction = c(CUSTOM_NEXT_SCREEN);
+const BRI_UP: KeyAction = c(CUSTOM_BRIGHTNESS_UP);
+const BRI_DN: KeyAction = c(CUSTOM_BRIGHTNESS_DOWN);
+const GAME: KeyAction = c(CUSTOM_GAME_MODE);
+const RATE: KeyAction = c(CUSTOM_CYCLE_REPORT_RATE);
+
+// HID usages of the keys below
+const A: u8 = 0x04;
+const B: u8 = 0x05;
+const C: u8 = 0x06;
+const D: u8 = 0x07;
+const E: u8 = 0x08;
+const F: u8 = 0x09;
+const G: u8 = 0x0a;
+const H: u8 = 0x0b;
+const I: u8 = 0x0c;
+const J: u8 = 0x0d;
+const K: u8 = 0x0e;
+const L: u8 = 0x0f;
+const M: u8 = 0x10;
+const N: u8 = 0x11;
+const O: u8 = 0x12;
+const P: u8 = 0x13;
+const Q: u8 = 0x14;
+const R: u8 = 0x15;
+const S: u8 = 0x16;
+const T_: u8 = 0x17;
+const U: u8 = 0x18;
+const V: u8 = 0x19;
+const W: u8 = 0x1a;
+const X: u8 = 0x1b;
+const Y: u8 = 0x1c;
+const Z: u8 = 0x1d;
+const SPACE: u8 = 0x2c;
+const SCOLON: u8 = 0x33;
+const COMMA: u8 = 0x36;
+const DOT: u8 = 0x37;
+const SLASH: u8 = 0x38;
+const RCTRL: u8 = 0xe4;
+const LALT: u8 = 0xe2;
+const LGUI: u8 = 0xe3;
+const RALT: u8 = 0xe6;
+
+#[rustfmt::skip]
+pub const DEFAULT_KEYMAP: [KeyAction; KEYMAP_LEN] = [
+    // QWERTY
+    k(Q),    k(W),    k(E),    k(R),    k(T_),    k(Y),    k(U),    k(I),     k(O),     k(P),
+    k(A),    k(S),    k(D),    k(F),    k(G),     k(H),    k(J),    k(K),     k(L),     k(SCOLON),
+    k(Z),    k(X),    k(C),    k(V),    k(B),     k(N),    k(M),    k(COMMA), k(DOT),   k(SLASH),
+    k(LGUI), k(LALT), T,       T,       k(SPACE), T,       T,       k(RALT),  k(RCTRL), FN,
+    // Fn
+    T,       T,       T,       RATE,    T,        T,       T,       T,        T,        T,
+    T,       T,       T,       T,       GAME,     T,       T,       T,        T,        T,
+    T,       T,       T,       T,       T,        SCRN,    T,       BRI_DN,   BRI_UP,   T,
+    T,       T,       T,       T,       T,        T,       T,       T,        T,        T,
+];
+
+/// Where an entry sits, `(layer, row, col)`
+pub fn position(index: usize) -> (usize, usize, usize) {
+    (index / (ROWS * COLS), index / COLS % ROWS, index % COLS)
+}
+
+/// The keymap as host tools see it. Writes land here and are only applied
+/// once saved and the board has reset.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Staging {
+    entries: [KeyAction; KEYMAP_LEN],
+}
+
+impl Staging {
+    pub const fn new(entries: [KeyAction; KEYMAP_LEN]) -> Staging {
+        Staging { entries }
+    }
+
+    pub fn entries(&self) -> &[KeyAction; KEYMAP_LEN] {
+        &self.entries
+    }
+
+    /// Up to `count` entries from `offset` on, `None` if `offset` is past
+    /// the end
+    pub fn read(&self, offset: u16, count: u8) -> Option<KeymapChunk> {
+        let start = offset as usize;
+        if start >= KEYMAP_LEN {
+            return None;
+        }
+        let end = (start + count as usize).min(KEYMAP_LEN);
+        Some(KeymapChunk::new(offset, &self.entries[start..end]))
+    }
+
+    /// `None`, changing nothing, if the chunk runs past the end
+    pub fn write(&mut self, chunk: &KeymapChunk) -> Option<()> {
+        let start = chunk.offset as usize;
+        let end = start + chunk.count as usize;
+        self.entries.get_mut(start..end)?.copy_from_slice(chunk.actions());
+        Some(())
+    }
+
+    /// Back to `DEFAULT_KEYMAP`
+    pub fn reset(&mut self) {
+        self.entries = DEFAULT_KEYMAP;
+    }
+}
+
+impl Default for Staging {
+    fn default() -> Staging {
+        Staging::new(DEFAULT_KEYMAP)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::KEYMAP_CHUNK;
+
+    #[test]
+    fn default_keymap_fits_the_board() {
+        for (index, action) in DEFAULT_KEYMAP.iter().enumerate() {
+            let (layer, _, _) = position(index);
+            match *action {
+                KeyAction::Layer(to) | KeyAction::DefaultLayer(to) => assert!((to as usize) < LAYERS),
+                KeyAction::Custom(custom) => assert!((custom as usize) < CUSTOM_ACTIONS),
+                // Nothing under a transparent key on the base layer
+                KeyAction::Trans if layer == 0 => {}
+                KeyAction::Other => panic!("entry {} can't be sent to host tools", index),
+                _ => {}
+            }
+        }
+    }
+
+    #[test]
+    fn custom_actions_are_bound() {
+        // Apart from the bootloader, which a stray press shouldn't get to
+        for custom in CUSTOM_CYCLE_REPORT_RATE..CUSTOM_ACTIONS as u8 {
+            assert!(DEFAULT_KEYMAP.contains(&KeyAction::Custom(custom)), "C({}) isn't bound", custom);
+        }
+    }
+
+    #[test]
+    fn positions() {
+        assert_eq!(position(0), (0, 0, 0));
+        assert_eq!(position(COLS + 3), (0, 1, 3));
+        assert_eq!(position(KEYMAP_LEN - 1), (LAYERS - 1, ROWS - 1, COLS - 1));
+    }
+
+    #[test]
+    fn staging_reads_and_writes() {
+        let mut staging = Staging::default();
+        let chunk = staging.read(0, KEYMAP_CHUNK as u8).unwrap();
+        assert_eq!(chunk.actions(), &DEFAULT_KEYMAP[..KEYMAP_CHUNK]);
+        // The last chunk stops at the end
+        let last = staging.read(KEYMAP_LEN as u16 - 2, KEYMAP_CHUNK as u8).unwrap();
+        assert_eq!(last.actions(), &DEFAULT_KEYMAP[KEYMAP_LEN - 2..]);
+        assert_eq!(staging.read(KEYMAP_LEN as u16, 1), None);
+
+        let write = KeymapChunk::new(3, &[KeyAction::NoOp, KeyAction::Custom(CUSTOM_GAME_MODE)]);
+        assert_eq!(staging.write(&write), Some(()));
+        assert_eq!(staging.entries()[3..5], [KeyAction::NoOp, KeyAction::Custom(CUSTOM_GAME_MODE)]);
+
+        staging.reset();
+        assert_eq!(staging, Staging::default());
+    }
+
+    #[test]
+    fn staging_rejects_writes_past_the_end() {
+        let mut staging = Staging::default();
+        let write = KeymapChunk::new(KEYMAP_LEN as u16 - 1, &[KeyAction::NoOp; 2]);
+        assert_eq!(staging.write(&write), None);
+        assert_eq!(staging, Staging::default());
+    }
+}
@@ -0,0 +1,856 @@
+//! The raw HID protocol spoken between the keezus firmware and host tools
+//! such as `keezus-cli`.
+//!
+//! Every packet is a single `REPORT_LEN` byte HID report. Requests from the
+//! host start with a command byte followed by the payload. Responses echo the
+//! command byte, then a status byte, then the payload. Events the firmware
+//! sends on its own (e.g. live key events) use command bytes with the top bit
+//! set, so they can't be confused with a response.
+//!
+//! Multi byte values are little endian.
+
+#![no_std]
+
+mod action;
+mod canvas;
+pub mod hid;
+pub mod keymap;
+mod stats;
+mod text;
+mod time;
+
+pub use action::KeyAction;
+pub use canvas::{
+    rgb565, CanvasText, PixelRun, Rect, CANVAS_FONT_HEIGHT, CANVAS_FONT_WIDTH, CANVAS_HEIGHT, CANVAS_TEXT_LEN,
+    CANVAS_WIDTH, PIXEL_RUN_LEN,
+};
+pub use stats::{CountChunk, LatencySummary, StatsSummary, COUNT_CHUNK};
+pub use text::{ShortText, SHORT_TEXT_LEN};
+pub use time::DateTime;
+
+/// Size of the input and output reports of the raw HID interface
+pub const REPORT_LEN: usize = 32;
+
+/// Vendor usage page/usage of the raw HID interface, the same pair QMK uses
+/// so existing raw HID tooling can find it
+pub const USAGE_PAGE: u16 = 0xff60;
+pub const USAGE: u16 = 0x61;
+
+/// Bumped whenever the packet layout changes incompatibly
+pub const PROTOCOL_VERSION: u8 = 1;
+
+/// Keymap entries that fit in one packet
+pub const KEYMAP_CHUNK: usize = 13;
+
+/// Values of the `PollIntervalMs` setting
+pub const POLL_INTERVALS_MS: [u8; 4] = [1, 2, 4, 8];
+
+/// Highest value of the `Brightness` setting
+pub const BRIGHTNESS_LEVELS: u8 = 8;
+
+/// Values of the `Screensaver` setting: 0 takes each screensaver in turn,
+/// the ones after it pick one
+pub const SCREENSAVERS: u8 = 5;
+
+/// Values of the `Orientation` setting: portrait, landscape, then the same
+/// two turned upside down
+pub const ORIENTATIONS: u8 = 4;
+
+const REQUEST_PAYLOAD: usize = 1;
+const RESPONSE_PAYLOAD: usize = 2;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Error {
+    /// Packet is shorter than its command requires
+    Truncated,
+    UnknownCommand(u8),
+    InvalidArgument,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u8)]
+pub enum Command {
+    GetVersion = 0x01,
+    GetKeymapInfo = 0x02,
+    ReadKeymap = 0x03,
+    WriteKeymap = 0x04,
+    SaveKeymap = 0x05,
+    ResetKeymap = 0x06,
+    GetSetting = 0x07,
+    SetSetting = 0x08,
+    SaveSettings = 0x09,
+    Bootloader = 0x0a,
+    StreamEvents = 0x0b,
+    CanvasShow = 0x0c,
+    CanvasFill = 0x0d,
+    CanvasBlit = 0x0e,
+    CanvasPixels = 0x0f,
+    CanvasText = 0x10,
+    SetTime = 0x11,
+    GetTime = 0x12,
+    GetStats = 0x13,
+    ReadKeyCounts = 0x14,
+    ReadDayCounts = 0x15,
+    ResetStats = 0x16,
+    SetScreensaverText = 0x17,
+    GetScreensaverText = 0x18,
+    Notify = 0x19,
+    GetLatency = 0x1a,
+    /// Unsolicited, sent for every matrix event while streaming is enabled
+    KeyEvent = 0x80,
+}
+
+impl Command {
+    pub fn from_u8(value: u8) -> Result<Command, Error> {
+        Ok(match value {
+            0x01 => Command::GetVersion,
+            0x02 => Command::GetKeymapInfo,
+            0x03 => Command::ReadKeymap,
+            0x04 => Command::WriteKeymap,
+            0x05 => Command::SaveKeymap,
+            0x06 => Command::ResetKeymap,
+            0x07 => Command::GetSetting,
+            0x08 => Command::SetSetting,
+            0x09 => Command::SaveSettings,
+            0x0a => Command::Bootloader,
+            0x0b => Command::StreamEvents,
+            0x0c => Command::CanvasShow,
+            0x0d => Command::CanvasFill,
+            0x0e => Command::CanvasBlit,
+            0x0f => Command::CanvasPixels,
+            0x10 => Command::CanvasText,
+            0x11 => Command::SetTime,
+            0x12 => Command::GetTime,
+            0x13 => Command::GetStats,
+            0x14 => Command::ReadKeyCounts,
+            0x15 => Command::ReadDayCounts,
+            0x16 => Command::ResetStats,
+            0x17 => Command::SetScreensaverText,
+            0x18 => Command::GetScreensaverText,
+            0x19 => Command::Notify,
+            0x1a => Command::GetLatency,
+            0x80 => Command::KeyEvent,
+            _ => return Err(Error::UnknownCommand(value)),
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u8)]
+pub enum Status {
+    Ok = 0x00,
+    UnknownCommand = 0x01,
+    InvalidArgument = 0x02,
+    Failed = 0x03,
+}
+
+impl Status {
+    pub fn from_u8(value: u8) -> Status {
+        match value {
+            0x00 => Status::Ok,
+            0x01 => Status::UnknownCommand,
+            0x02 => Status::InvalidArgument,
+            _ => Status::Failed,
+        }
+    }
+}
+
+impl From<Error> for Status {
+    fn from(error: Error) -> Status {
+        match error {
+            Error::UnknownCommand(_) => Status::UnknownCommand,
+            Error::Truncated | Error::InvalidArgument => Status::InvalidArgument,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u8)]
+pub enum SettingId {
+    /// USB polling interval and matrix scan period, applied after a reset
+    PollIntervalMs = 0x01,
+    /// Display backlight level, from 1 to `BRIGHTNESS_LEVELS`
+    Brightness = 0x02,
+    /// Minutes without a keypress before the display dims and then goes to
+    /// sleep, 0 to never
+    DisplayTimeoutMin = 0x03,
+    /// Which way up the display is, below `ORIENTATIONS`
+    Orientation = 0x04,
+    /// Where the visible area starts in the panel's memory, which differs
+    /// between panel variants
+    PanelOffsetX = 0x05,
+    PanelOffsetY = 0x06,
+    /// Minutes without a keypress before the status screen gives way to a
+    /// screensaver, 0 to never
+    ScreensaverMin = 0x07,
+    /// Which screensaver, below `SCREENSAVERS`
+    Screensaver = 0x08,
+}
+
+impl SettingId {
+    pub const ALL: [SettingId; 8] = [
+        SettingId::PollIntervalMs,
+        SettingId::Brightness,
+        SettingId::DisplayTimeoutMin,
+        SettingId::Orientation,
+        SettingId::PanelOffsetX,
+        SettingId::PanelOffsetY,
+        SettingId::ScreensaverMin,
+        SettingId::Screensaver,
+    ];
+
+    pub fn from_u8(value: u8) -> Result<SettingId, Error> {
+        SettingId::ALL
+            .iter()
+            .copied()
+            .find(|id| *id as u8 == value)
+            .ok_or(Error::InvalidArgument)
+    }
+
+    /// `value` as the setting stores it, `None` if it's out of range
+    pub fn check(self, value: u16) -> Option<u8> {
+        let value = u8::try_from(value).ok()?;
+        let valid = match self {
+            SettingId::PollIntervalMs => POLL_INTERVALS_MS.contains(&value),
+            SettingId::Brightness => (1..=BRIGHTNESS_LEVELS).contains(&value),
+            SettingId::Orientation => value < ORIENTATIONS,
+            SettingId::Screensaver => value < SCREENSAVERS,
+            SettingId::DisplayTimeoutMin
+            | SettingId::PanelOffsetX
+            | SettingId::PanelOffsetY
+            | SettingId::ScreensaverMin => true,
+        };
+        valid.then_some(value)
+    }
+}
+
+/// A run of consecutive keymap entries. Entries are indexed layer by layer,
+/// row by row, `index = (layer * rows + row) * cols + col`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct KeymapChunk {
+    pub offset: u16,
+    pub count: u8,
+    pub actions: [KeyAction; KEYMAP_CHUNK],
+}
+
+impl KeymapChunk {
+    pub fn new(offset: u16, actions: &[KeyAction]) -> KeymapChunk {
+        let count = actions.len().min(KEYMAP_CHUNK);
+        let mut chunk = KeymapChunk {
+            offset,
+            count: count as u8,
+            actions: [KeyAction::Other; KEYMAP_CHUNK],
+        };
+        chunk.actions[..count].copy_from_slice(&actions[..count]);
+        chunk
+    }
+
+    pub fn actions(&self) -> &[KeyAction] {
+        &self.actions[..self.count as usize]
+    }
+
+    fn write(&self, w: &mut Writer) {
+        w.u16(self.offset);
+        w.u8(self.count);
+        for action in self.actions() {
+            w.u16(action.to_u16());
+        }
+    }
+
+    fn read(r: &mut Reader) -> Result<KeymapChunk, Error> {
+        let offset = r.u16()?;
+        let count = r.u8()? as usize;
+        if count > KEYMAP_CHUNK {
+            return Err(Error::InvalidArgument);
+        }
+        let mut actions = [KeyAction::Other; KEYMAP_CHUNK];
+        for action in actions.iter_mut().take(count) {
+            *action = KeyAction::from_u16(r.u16()?);
+        }
+        Ok(KeymapChunk {
+            offset,
+            count: count as u8,
+            actions,
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Request {
+    GetVersion,
+    GetKeymapInfo,
+    ReadKeymap { offset: u16, count: u8 },
+    /// Stages keymap entries, they only end up in flash with `SaveKeymap`
+    WriteKeymap(KeymapChunk),
+    /// Saves the staged keymap, it takes effect after a reset
+    SaveKeymap,
+    /// Drops any saved keymap in favour of the one compiled in
+    ResetKeymap,
+    GetSetting(SettingId),
+    SetSetting(SettingId, u16),
+    SaveSettings,
+    Bootloader,
+    StreamEvents(bool),
+    /// Shows the host canvas instead of the firmware's own screens, or
+    /// hides it again. The canvas keeps its contents while hidden.
+    CanvasShow(bool),
+    CanvasFill(Rect, u16),
+    /// Opens a window on the canvas for the `CanvasPixels` that follow
+    CanvasBlit(Rect),
+    CanvasPixels(PixelRun),
+    CanvasText(CanvasText),
+    /// Sets the board's clock, which keeps going until the board resets
+    SetTime(DateTime),
+    /// Fails if the clock hasn't been set since the board powered up
+    GetTime,
+    /// Totals and words per minute, and how many of the counts below
+    /// there are
+    GetStats,
+    ReadKeyCounts { offset: u8, count: u8 },
+    ReadDayCounts { offset: u8, count: u8 },
+    /// Clears the counts and saves that
+    ResetStats,
+    /// What the scrolling text screensaver shows, saved with
+    /// `SaveSettings`. Empty goes back to the default.
+    SetScreensaverText(ShortText),
+    GetScreensaverText,
+    /// Pops the text up over the display for a moment
+    Notify(ShortText),
+    /// Matrix to report latency
+    GetLatency,
+}
+
+impl Request {
+    pub fn command(&self) -> Command {
+        match self {
+            Request::GetVersion => Command::GetVersion,
+            Request::GetKeymapInfo => Command::GetKeymapInfo,
+            Request::ReadKeymap { .. } => Command::ReadKeymap,
+            Request::WriteKeymap(_) => Command::WriteKeymap,
+            Request::SaveKeymap => Command::SaveKeymap,
+            Request::ResetKeymap => Command::ResetKeymap,
+            Request::GetSetting(_) => Command::GetSetting,
+            Request::SetSetting(..) => Command::SetSetting,
+            Request::SaveSettings => Command::SaveSettings,
+            Request::Bootloader => Command::Bootloader,
+            Request::StreamEvents(_) => Command::StreamEvents,
+            Request::CanvasShow(_) => Command::CanvasShow,
+            Request::CanvasFill(..) => Command::CanvasFill,
+            Request::CanvasBlit(_) => Command::CanvasBlit,
+            Request::CanvasPixels(_) => Command::CanvasPixels,
+            Request::CanvasText(_) => Command::CanvasText,
+            Request::SetTime(_) => Command::SetTime,
+            Request::GetTime => Command::GetTime,
+            Request::GetStats => Command::GetStats,
+            Request::ReadKeyCounts { .. } => Command::ReadKeyCounts,
+            Request::ReadDayCounts { .. } => Command::ReadDayCounts,
+            Request::ResetStats => Command::ResetStats,
+            Request::SetScreensaverText(_) => Command::SetScreensaverText,
+            Request::GetScreensaverText => Command::GetScreensaverText,
+            Request::Notify(_) => Command::Notify,
+            Request::GetLatency => Command::GetLatency,
+        }
+    }
+
+    pub fn encode(&self, buf: &mut [u8; REPORT_LEN]) {
+        *buf = [0; REPORT_LEN];
+        buf[0] = self.command() as u8;
+        let mut w = Writer::new(buf, REQUEST_PAYLOAD);
+        match *self {
+            Request::ReadKeymap { offset, count } => {
+                w.u16(offset);
+                w.u8(count);
+            }
+            Request::WriteKeymap(ref chunk) => chunk.write(&mut w),
+            Request::GetSetting(id) => w.u8(id as u8),
+            Request::SetSetting(id, value) => {
+                w.u8(id as u8);
+                w.u16(value);
+            }
+            Request::StreamEvents(enable) => w.u8(enable as u8),
+            Request::CanvasShow(show) => w.u8(show as u8),
+            Request::CanvasFill(ref rect, color) => {
+                rect.write(&mut w);
+                w.u16(color);
+            }
+            Request::CanvasBlit(ref rect) => rect.write(&mut w),
+            Request::CanvasPixels(ref run) => run.write(&mut w),
+            Request::CanvasText(ref text) => text.write(&mut w),
+            Request::SetTime(ref time) => time.write(&mut w),
+            Request::SetScreensaverText(ref text) | Request::Notify(ref text) => text.write(&mut w),
+            Request::ReadKeyCounts { offset, count } | Request::ReadDayCounts { offset, count } => {
+                w.u8(offset);
+                w.u8(count);
+            }
+            Request::GetVersion
+            | Request::GetKeymapInfo
+            | Request::SaveKeymap
+            | Request::ResetKeymap
+            | Request::SaveSettings
+            | Request::Bootloader
+            | Request::GetTime
+            | Request::GetStats
+            | Request::ResetStats
+            | Request::GetScreensaverText
+            | Request::GetLatency => {}
+        }
+    }
+
+    pub fn decode(buf: &[u8]) -> Result<Request, Error> {
+        let command = Command::from_u8(*buf.first().ok_or(Error::Truncated)?)?;
+        let mut r = Reader::new(buf, REQUEST_PAYLOAD);
+        Ok(match command {
+            Command::GetVersion => Request::GetVersion,
+            Command::GetKeymapInfo => Request::GetKeymapInfo,
+            Command::ReadKeymap => Request::ReadKeymap {
+                offset: r.u16()?,
+                count: r.u8()?,
+            },
+            Command::WriteKeymap => Request::WriteKeymap(KeymapChunk::read(&mut r)?),
+            Command::SaveKeymap => Request::SaveKeymap,
+            Command::ResetKeymap => Request::ResetKeymap,
+            Command::GetSetting => Request::GetSetting(SettingId::from_u8(r.u8()?)?),
+            Command::SetSetting => Request::SetSetting(SettingId::from_u8(r.u8()?)?, r.u16()?),
+            Command::SaveSettings => Request::SaveSettings,
+            Command::Bootloader => Request::Bootloader,
+            Command::StreamEvents => Request::StreamEvents(r.u8()? != 0),
+            Command::CanvasShow => Request::CanvasShow(r.u8()? != 0),
+            Command::CanvasFill => Request::CanvasFill(Rect::read(&mut r)?, r.u16()?),
+            Command::CanvasBlit => Request::CanvasBlit(Rect::read(&mut r)?),
+            Command::CanvasPixels => Request::CanvasPixels(PixelRun::read(&mut r)?),
+            Command::CanvasText => Request::CanvasText(CanvasText::read(&mut r)?),
+            Command::SetTime => Request::SetTime(DateTime::read(&mut r)?),
+            Command::GetTime => Request::GetTime,
+            Command::GetStats => Request::GetStats,
+            Command::ReadKeyCounts => Request::ReadKeyCounts {
+                offset: r.u8()?,
+                count: r.u8()?,
+            },
+            Command::ReadDayCounts => Request::ReadDayCounts {
+                offset: r.u8()?,
+                count: r.u8()?,
+            },
+            Command::ResetStats => Request::ResetStats,
+            Command::SetScreensaverText => Request::SetScreensaverText(ShortText::read(&mut r)?),
+            Command::GetScreensaverText => Request::GetScreensaverText,
+            Command::Notify => Request::Notify(ShortText::read(&mut r)?),
+            Command::GetLatency => Request::GetLatency,
+            Command::KeyEvent => return Err(Error::UnknownCommand(command as u8)),
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Response {
+    Version {
+        protocol: u8,
+        major: u8,
+        minor: u8,
+        patch: u8,
+    },
+    KeymapInfo {
+        layers: u8,
+        rows: u8,
+        cols: u8,
+    },
+    Keymap(KeymapChunk),
+    Setting(SettingId, u16),
+    Time(DateTime),
+    Stats(StatsSummary),
+    KeyCounts(CountChunk),
+    DayCounts(CountChunk),
+    ScreensaverText(ShortText),
+    Latency(LatencySummary),
+    /// Success for requests that have nothing to return
+    Ack(Command),
+    Error(Command, Status),
+    KeyEvent {
+        row: u8,
+        col: u8,
+        pressed: bool,
+    },
+}
+
+impl Response {
+    pub fn command(&self) -> Command {
+        match self {
+            Response::Version { .. } => Command::GetVersion,
+            Response::KeymapInfo { .. } => Command::GetKeymapInfo,
+            Response::Keymap(_) => Command::ReadKeymap,
+            Response::Setting(..) => Command::GetSetting,
+            Response::Time(_) => Command::GetTime,
+            Response::Stats(_) => Command::GetStats,
+            Response::KeyCounts(_) => Command::ReadKeyCounts,
+            Response::DayCounts(_) => Command::ReadDayCounts,
+            Response::ScreensaverText(_) => Command::GetScreensaverText,
+            Response::Latency(_) => Command::GetLatency,
+            Response::Ack(command) | Response::Error(command, _) => *command,
+            Response::KeyEvent { .. } => Command::KeyEvent,
+        }
+    }
+
+    /// True for packets the firmware sends without being asked
+    pub fn is_event(&self) -> bool {
+        self.command() as u8 & 0x80 != 0
+    }
+
+    pub fn encode(&self, buf: &mut [u8; REPORT_LEN]) {
+        *buf = [0; REPORT_LEN];
+        buf[0] = self.command() as u8;
+        buf[1] = match self {
+            Response::Error(_, status) => *status as u8,
+            _ => Status::Ok as u8,
+        };
+        let mut w = Writer::new(buf, RESPONSE_PAYLOAD);
+        match *self {
+            Response::Version {
+                protocol,
+                major,
+                minor,
+                patch,
+            } => {
+                w.u8(protocol);
+                w.u8(major);
+                w.u8(minor);
+                w.u8(patch);
+            }
+            Response::KeymapInfo { layers, rows, cols } => {
+                w.u8(layers);
+                w.u8(rows);
+                w.u8(cols);
+            }
+            Response::Keymap(ref chunk) => chunk.write(&mut w),
+            Response::Setting(id, value) => {
+                w.u8(id as u8);
+                w.u16(value);
+            }
+            Response::Time(ref time) => time.write(&mut w),
+            Response::Stats(ref summary) => summary.write(&mut w),
+            Response::KeyCounts(ref chunk) | Response::DayCounts(ref chunk) => chunk.write(&mut w),
+            Response::ScreensaverText(ref text) => text.write(&mut w),
+            Response::Latency(ref latency) => latency.write(&mut w),
+            Response::KeyEvent { row, col, pressed } => {
+                w.u8(row);
+                w.u8(col);
+                w.u8(pressed as u8);
+            }
+            Response::Ack(_) | Response::Error(..) => {}
+        }
+    }
+
+    pub fn decode(buf: &[u8]) -> Result<Response, Error> {
+        let command = Command::from_u8(*buf.first().ok_or(Error::Truncated)?)?;
+        let status = Status::from_u8(*buf.get(1).ok_or(Error::Truncated)?);
+        if status != Status::Ok {
+            return Ok(Response::Error(command, status));
+        }
+        let mut r = Reader::new(buf, RESPONSE_PAYLOAD);
+        Ok(match command {
+            Command::GetVersion => Response::Version {
+                protocol: r.u8()?,
+                major: r.u8()?,
+                minor: r.u8()?,
+                patch: r.u8()?,
+            },
+            Command::GetKeymapInfo => Response::KeymapInfo {
+                layers: r.u8()?,
+                rows: r.u8()?,
+                cols: r.u8()?,
+            },
+            Command::ReadKeymap => Response::Keymap(KeymapChunk::read(&mut r)?),
+            Command::GetSetting => Response::Setting(SettingId::from_u8(r.u8()?)?, r.u16()?),
+            Command::GetTime => Response::Time(DateTime::read(&mut r)?),
+            Command::GetStats => Response::Stats(StatsSummary::read(&mut r)?),
+            Command::ReadKeyCounts => Response::KeyCounts(CountChunk::read(&mut r)?),
+            Command::ReadDayCounts => Response::DayCounts(CountChunk::read(&mut r)?),
+            Command::GetScreensaverText => Response::ScreensaverText(ShortText::read(&mut r)?),
+            Command::GetLatency => Response::Latency(LatencySummary::read(&mut r)?),
+            Command::KeyEvent => Response::KeyEvent {
+                row: r.u8()?,
+                col: r.u8()?,
+                pressed: r.u8()? != 0,
+            },
+            Command::WriteKeymap
+            | Command::SaveKeymap
+            | Command::ResetKeymap
+            | Command::SetSetting
+            | Command::SaveSettings
+            | Command::Bootloader
+            | Command::StreamEvents
+            | Command::CanvasShow
+            | Command::CanvasFill
+            | Command::CanvasBlit
+            | Command::CanvasPixels
+            | Command::CanvasText
+            | Command::SetTime
+            | Command::ResetStats
+            | Command::SetScreensaverText
+            | Command::Notify => Response::Ack(command),
+        })
+    }
+}
+
+pub(crate) struct Writer<'a> {
+    buf: &'a mut [u8],
+    pos: usize,
+}
+
+impl<'a> Writer<'a> {
+    fn new(buf: &'a mut [u8], pos: usize) -> Writer<'a> {
+        Writer { buf, pos }
+    }
+
+    pub(crate) fn u8(&mut self, value: u8) {
+        self.buf[self.pos] = value;
+        self.pos += 1;
+    }
+
+    pub(crate) fn u16(&mut self, value: u16) {
+        self.buf[self.pos..self.pos + 2].copy_from_slice(&value.to_le_bytes());
+        self.pos += 2;
+    }
+
+    pub(crate) fn u32(&mut self, value: u32) {
+        self.buf[self.pos..self.pos + 4].copy_from_slice(&value.to_le_bytes());
+        self.pos += 4;
+    }
+}
+
+pub(crate) struct Reader<'a> {
+    buf: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn new(buf: &'a [u8], pos: usize) -> Reader<'a> {
+        Reader { buf, pos }
+    }
+
+    pub(crate) fn u8(&mut self) -> Result<u8, Error> {
+        let value = *self.buf.get(self.pos).ok_or(Error::Truncated)?;
+        self.pos += 1;
+        Ok(value)
+    }
+
+    pub(crate) fn u16(&mut self) -> Result<u16, Error> {
+        Ok(u16::from_le_bytes([self.u8()?, self.u8()?]))
+    }
+
+    pub(crate) fn u32(&mut self) -> Result<u32, Error> {
+        Ok(u32::from_le_bytes([self.u8()?, self.u8()?, self.u8()?, self.u8()?]))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn request_round_trip(request: Request) {
+        let mut buf = [0u8; REPORT_LEN];
+        request.encode(&mut buf);
+        assert_eq!(buf[0], request.command() as u8);
+        assert_eq!(Request::decode(&buf), Ok(request));
+    }
+
+    fn response_round_trip(response: Response) {
+        let mut buf = [0u8; REPORT_LEN];
+        response.encode(&mut buf);
+        assert_eq!(buf[0], response.command() as u8);
+        assert_eq!(Response::decode(&buf), Ok(response));
+    }
+
+    #[test]
+    fn requests_round_trip() {
+        let actions = [
+            KeyAction::NoOp,
+            KeyAction::Trans,
+            KeyAction::KeyCode(0x04),
+            KeyAction::Layer(1),
+            KeyAction::DefaultLayer(2),
+            KeyAction::Custom(3),
+            KeyAction::Other,
+        ];
+        let rect = Rect {
+            x: 1,
+            y: 2,
+            width: 30,
+            height: 40,
+        };
+        let text = ShortText::new("Build passed");
+        let requests = [
+            Request::GetVersion,
+            Request::GetKeymapInfo,
+            Request::ReadKeymap { offset: 300, count: 13 },
+            Request::WriteKeymap(KeymapChunk::new(26, &actions)),
+            Request::SaveKeymap,
+            Request::ResetKeymap,
+            Request::GetSetting(SettingId::Screensaver),
+            Request::SetSetting(SettingId::PollIntervalMs, 4),
+            Request::SaveSettings,
+            Request::Bootloader,
+            Request::StreamEvents(true),
+            Request::CanvasShow(false),
+            Request::CanvasFill(rect, rgb565(255, 0, 0)),
+            Request::CanvasBlit(rect),
+            Request::CanvasPixels(PixelRun::new(&[0xf800, 0x07e0, 0x001f])),
+            Request::CanvasText(CanvasText::new(4, 8, 0xffff, 0, "hello")),
+            Request::SetTime(DateTime::from_unix(1_700_000_000)),
+            Request::GetTime,
+            Request::GetStats,
+            Request::ReadKeyCounts { offset: 6, count: 6 },
+            Request::ReadDayCounts { offset: 0, count: 3 },
+            Request::ResetStats,
+            Request::SetScreensaverText(text),
+            Request::GetScreensaverText,
+            Request::Notify(text),
+            Request::GetLatency,
+        ];
+        for request in requests {
+            request_round_trip(request);
+        }
+    }
+
+    #[test]
+    fn responses_round_trip() {
+        let responses = [
+            Response::Version {
+                protocol: PROTOCOL_VERSION,
+                major: 0,
+                minor: 1,
+                patch: 2,
+            },
+            Response::KeymapInfo {
+                layers: 4,
+                rows: 4,
+                cols: 10,
+            },
+            Response::Keymap(KeymapChunk::new(13, &[KeyAction::KeyCode(0x2c); KEYMAP_CHUNK])),
+            Response::Setting(SettingId::Brightness, BRIGHTNESS_LEVELS as u16),
+            Response::Time(DateTime::from_unix(0)),
+            Response::Stats(StatsSummary {
+                total: 123_456,
+                wpm: 87,
+                keys: 40,
+                days: 7,
+                today: Some(19_700),
+            }),
+            Response::Stats(StatsSummary {
+                total: 0,
+                wpm: 0,
+                keys: 40,
+                days: 7,
+                today: None,
+            }),
+            Response::KeyCounts(CountChunk::new(0, &[1, 2, 3, 4, 5, 6])),
+            Response::DayCounts(CountChunk::new(6, &[42])),
+            Response::ScreensaverText(ShortText::EMPTY),
+            Response::Latency(LatencySummary {
+                samples: 1000,
+                min_us: 5_000,
+                average_us: 6_500,
+                max_us: 12_000,
+            }),
+            Response::Ack(Command::SaveSettings),
+            Response::Ack(Command::Notify),
+            Response::Error(Command::SetSetting, Status::InvalidArgument),
+            Response::Error(Command::GetTime, Status::Failed),
+            Response::KeyEvent {
+                row: 3,
+                col: 9,
+                pressed: true,
+            },
+        ];
+        for response in responses {
+            response_round_trip(response);
+        }
+    }
+
+    #[test]
+    fn truncated() {
+        assert_eq!(Request::decode(&[]), Err(Error::Truncated));
+        assert_eq!(Response::decode(&[]), Err(Error::Truncated));
+        // No status byte
+        assert_eq!(Response::decode(&[Command::GetVersion as u8]), Err(Error::Truncated));
+
+        let mut buf = [0u8; REPORT_LEN];
+        Request::ReadKeymap { offset: 1, count: 2 }.encode(&mut buf);
+        assert_eq!(Request::decode(&buf[..3]), Err(Error::Truncated));
+        assert!(Request::decode(&buf[..4]).is_ok());
+
+        Response::Stats(StatsSummary {
+            total: 1,
+            wpm: 2,
+            keys: 3,
+            days: 4,
+            today: None,
+        })
+        .encode(&mut buf);
+        assert_eq!(Response::decode(&buf[..10]), Err(Error::Truncated));
+    }
+
+    #[test]
+    fn unknown_command() {
+        let mut buf = [0u8; REPORT_LEN];
+        buf[0] = 0x7f;
+        assert_eq!(Request::decode(&buf), Err(Error::UnknownCommand(0x7f)));
+        assert_eq!(Response::decode(&buf), Err(Error::UnknownCommand(0x7f)));
+        assert_eq!(Status::from(Error::UnknownCommand(0x7f)), Status::UnknownCommand);
+
+        // Events only ever go from the board to the host
+        buf[0] = Command::KeyEvent as u8;
+        assert_eq!(Request::decode(&buf), Err(Error::UnknownCommand(Command::KeyEvent as u8)));
+    }
+
+    #[test]
+    fn invalid_arguments() {
+        let mut buf = [0u8; REPORT_LEN];
+        buf[0] = Command::GetSetting as u8;
+        buf[1] = 0xee;
+        assert_eq!(Request::decode(&buf), Err(Error::InvalidArgument));
+
+        // More entries than fit in a packet
+        Request::WriteKeymap(KeymapChunk::new(0, &[])).encode(&mut buf);
+        buf[3] = KEYMAP_CHUNK as u8 + 1;
+        assert_eq!(Request::decode(&buf), Err(Error::InvalidArgument));
+    }
+
+    #[test]
+    fn setting_ranges() {
+        assert_eq!(SettingId::PollIntervalMs.check(4), Some(4));
+        assert_eq!(SettingId::PollIntervalMs.check(3), None);
+        assert_eq!(SettingId::Brightness.check(0), None);
+        assert_eq!(SettingId::Brightness.check(BRIGHTNESS_LEVELS as u16), Some(BRIGHTNESS_LEVELS));
+        assert_eq!(SettingId::Orientation.check(ORIENTATIONS as u16), None);
+        assert_eq!(SettingId::Screensaver.check(SCREENSAVERS as u16 - 1), Some(SCREENSAVERS - 1));
+        assert_eq!(SettingId::ScreensaverMin.check(0), Some(0));
+        // Every setting is a byte on the board
+        assert_eq!(SettingId::PanelOffsetX.check(255), Some(255));
+        assert_eq!(SettingId::PanelOffsetY.check(256), None);
+    }
+
+    #[test]
+    fn count_chunks() {
+        let counts = [1, 2, 3, 4, 5];
+        assert_eq!(CountChunk::from_counts(&counts, 1, 2).unwrap().counts(), &[2, 3]);
+        // The last chunk stops at the end
+        assert_eq!(CountChunk::from_counts(&counts, 3, 9).unwrap().counts(), &[4, 5]);
+        assert_eq!(CountChunk::from_counts(&counts, 5, 1), None);
+    }
+
+    #[test]
+    fn error_status_skips_the_payload() {
+        let mut buf = [0u8; REPORT_LEN];
+        Response::Error(Command::ReadKeymap, Status::InvalidArgument).encode(&mut buf);
+        assert_eq!(buf[1], Status::InvalidArgument as u8);
+        assert_eq!(
+            Response::decode(&buf[..2]),
+            Ok(Response::Error(Command::ReadKeymap, Status::InvalidArgument))
+        );
+    }
+
+    #[test]
+    fn events() {
+        assert!(Response::KeyEvent { row: 0, col: 0, pressed: false }.is_event());
+        assert!(!Response::Ack(Command::Notify).is_event());
+    }
+}
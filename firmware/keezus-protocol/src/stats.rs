@@ -0,0 +1,139 @@
+//! Payloads for reading the board's typing statistics. The per key and per
+//! day counts don't fit in one packet, so they're read a run at a time like
+//! the keymap.
+
+use crate::{Error, Reader, Writer};
+
+/// Counts that fit in one `KeyCounts` or `DayCounts` packet
+pub const COUNT_CHUNK: usize = 6;
+
+/// What `GetStats` answers with
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct StatsSummary {
+    /// Presses since the counters were last reset
+    pub total: u32,
+    /// Words per minute over the last minute
+    pub wpm: u16,
+    /// How many per key counts there are, keys are numbered
+    /// `row * cols + col`
+    pub keys: u8,
+    /// How many per day counts there are, today first
+    pub days: u8,
+    /// Which day today is in days since 1970, `None` until the clock has
+    /// been set
+    pub today: Option<u32>,
+}
+
+impl StatsSummary {
+    pub(crate) fn write(&self, w: &mut Writer) {
+        w.u32(self.total);
+        w.u16(self.wpm);
+        w.u8(self.keys);
+        w.u8(self.days);
+        w.u8(self.today.is_some() as u8);
+        w.u32(self.today.unwrap_or(0));
+    }
+
+    pub(crate) fn read(r: &mut Reader) -> Result<StatsSummary, Error> {
+        let (total, wpm, keys, days) = (r.u32()?, r.u16()?, r.u8()?, r.u8()?);
+        let known = r.u8()? != 0;
+        let today = r.u32()?;
+        Ok(StatsSummary {
+            total,
+            wpm,
+            keys,
+            days,
+            today: known.then_some(today),
+        })
+    }
+}
+
+/// What `GetLatency` answers with: how long key changes take to go from
+/// the matrix to a HID report, debouncing included, since the board reset
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LatencySummary {
+    /// Changes measured, the times are all 0 until there's one
+    pub samples: u32,
+    pub min_us: u32,
+    pub average_us: u32,
+    pub max_us: u32,
+}
+
+impl LatencySummary {
+    pub(crate) fn write(&self, w: &mut Writer) {
+        w.u32(self.samples);
+        w.u32(self.min_us);
+        w.u32(self.average_us);
+        w.u32(self.max_us);
+    }
+
+    pub(crate) fn read(r: &mut Reader) -> Result<LatencySummary, Error> {
+        Ok(LatencySummary {
+            samples: r.u32()?,
+            min_us: r.u32()?,
+            average_us: r.u32()?,
+            max_us: r.u32()?,
+        })
+    }
+}
+
+/// A run of consecutive per key or per day counts
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CountChunk {
+    pub offset: u8,
+    pub count: u8,
+    pub counts: [u32; COUNT_CHUNK],
+}
+
+impl CountChunk {
+    pub fn new(offset: u8, counts: &[u32]) -> CountChunk {
+        let count = counts.len().min(COUNT_CHUNK);
+        let mut chunk = CountChunk {
+            offset,
+            count: count as u8,
+            counts: [0; COUNT_CHUNK],
+        };
+        chunk.counts[..count].copy_from_slice(&counts[..count]);
+        chunk
+    }
+
+    /// Up to `count` of `counts` from `offset` on, `None` if `offset` is
+    /// past the end
+    pub fn from_counts(counts: &[u32], offset: u8, count: u8) -> Option<CountChunk> {
+        let start = offset as usize;
+        if start >= counts.len() {
+            return None;
+        }
+        let end = counts.len().min(start + count as usize);
+        Some(CountChunk::new(offset, &counts[start..end]))
+    }
+
+    pub fn counts(&self) -> &[u32] {
+        &self.counts[..self.count as usize]
+    }
+
+    pub(crate) fn write(&self, w: &mut Writer) {
+        w.u8(self.offset);
+        w.u8(self.count);
+        for &count in self.counts() {
+            w.u32(count);
+        }
+    }
+
+    pub(crate) fn read(r: &mut Reader) -> Result<CountChunk, Error> {
+        let offset = r.u8()?;
+        let count = r.u8()? as usize;
+        if count > COUNT_CHUNK {
+            return Err(Error::InvalidArgument);
+        }
+        let mut counts = [0; COUNT_CHUNK];
+        for value in counts.iter_mut().take(count) {
+            *value = r.u32()?;
+        }
+        Ok(CountChunk {
+            offset,
+            count: count as u8,
+            counts,
+        })
+    }
+}
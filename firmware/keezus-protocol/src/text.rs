@@ -0,0 +1,61 @@
+//! A short piece of text that fits in one packet with room to spare, for
+//! things like the screensaver's message.
+
+use crate::{Error, Reader, Writer};
+
+/// Bytes of UTF-8 in a `ShortText`
+pub const SHORT_TEXT_LEN: usize = 28;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ShortText {
+    len: u8,
+    bytes: [u8; SHORT_TEXT_LEN],
+}
+
+impl ShortText {
+    pub const EMPTY: ShortText = ShortText {
+        len: 0,
+        bytes: [0; SHORT_TEXT_LEN],
+    };
+
+    /// Text that doesn't fit is cut off at a character boundary
+    pub fn new(text: &str) -> ShortText {
+        let mut len = text.len().min(SHORT_TEXT_LEN);
+        while !text.is_char_boundary(len) {
+            len -= 1;
+        }
+        let mut bytes = [0; SHORT_TEXT_LEN];
+        bytes[..len].copy_from_slice(&text.as_bytes()[..len]);
+        ShortText { len: len as u8, bytes }
+    }
+
+    pub fn as_str(&self) -> &str {
+        core::str::from_utf8(&self.bytes[..self.len as usize]).unwrap_or("")
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub(crate) fn write(&self, w: &mut Writer) {
+        w.u8(self.len);
+        for &byte in &self.bytes[..self.len as usize] {
+            w.u8(byte);
+        }
+    }
+
+    pub(crate) fn read(r: &mut Reader) -> Result<ShortText, Error> {
+        let len = r.u8()? as usize;
+        if len > SHORT_TEXT_LEN {
+            return Err(Error::InvalidArgument);
+        }
+        let mut bytes = [0; SHORT_TEXT_LEN];
+        for byte in bytes.iter_mut().take(len) {
+            *byte = r.u8()?;
+        }
+        if core::str::from_utf8(&bytes[..len]).is_err() {
+            return Err(Error::InvalidArgument);
+        }
+        Ok(ShortText { len: len as u8, bytes })
+    }
+}
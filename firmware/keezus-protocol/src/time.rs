@@ -0,0 +1,95 @@
+//! Wall clock time, as the host sets it on the board's RTC. There's no
+//! notion of time zones, the host sends its local time.
+
+use crate::{Error, Reader, Writer};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DateTime {
+    pub year: u16,
+    /// 1 to 12
+    pub month: u8,
+    /// 1 to 31
+    pub day: u8,
+    /// 0 is Sunday, like the RP2040's RTC
+    pub weekday: u8,
+    pub hour: u8,
+    pub minute: u8,
+    pub second: u8,
+}
+
+impl DateTime {
+    /// Seconds since 1970-01-01 00:00:00, whatever zone that's in
+    pub fn from_unix(seconds: i64) -> DateTime {
+        let (days, second_of_day) = (seconds.div_euclid(86_400), seconds.rem_euclid(86_400));
+        // Howard Hinnant's civil_from_days
+        let z = days + 719_468;
+        let era = z.div_euclid(146_097);
+        let day_of_era = z.rem_euclid(146_097);
+        let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+        let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+        let shifted_month = (5 * day_of_year + 2) / 153;
+        let day = day_of_year - (153 * shifted_month + 2) / 5 + 1;
+        let month = if shifted_month < 10 { shifted_month + 3 } else { shifted_month - 9 };
+        let year = year_of_era + era * 400 + (month <= 2) as i64;
+        DateTime {
+            year: year as u16,
+            month: month as u8,
+            day: day as u8,
+            // The epoch was a Thursday
+            weekday: (days + 4).rem_euclid(7) as u8,
+            hour: (second_of_day / 3600) as u8,
+            minute: (second_of_day / 60 % 60) as u8,
+            second: (second_of_day % 60) as u8,
+        }
+    }
+
+    /// The inverse of `from_unix`
+    pub fn to_unix(&self) -> i64 {
+        // Howard Hinnant's days_from_civil
+        let (month, day) = (self.month as i64, self.day as i64);
+        let year = self.year as i64 - (month <= 2) as i64;
+        let era = year.div_euclid(400);
+        let year_of_era = year.rem_euclid(400);
+        let day_of_year = (153 * (if month > 2 { month - 3 } else { month + 9 }) + 2) / 5 + day - 1;
+        let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+        let days = era * 146_097 + day_of_era - 719_468;
+        days * 86_400 + self.hour as i64 * 3600 + self.minute as i64 * 60 + self.second as i64
+    }
+
+    /// Whether every field is in range. The RTC only takes years up to 4095.
+    pub fn is_valid(&self) -> bool {
+        self.year <= 4095
+            && (1..=12).contains(&self.month)
+            && (1..=31).contains(&self.day)
+            && self.weekday < 7
+            && self.hour < 24
+            && self.minute < 60
+            && self.second < 60
+    }
+
+    pub(crate) fn write(&self, w: &mut Writer) {
+        w.u16(self.year);
+        w.u8(self.month);
+        w.u8(self.day);
+        w.u8(self.weekday);
+        w.u8(self.hour);
+        w.u8(self.minute);
+        w.u8(self.second);
+    }
+
+    pub(crate) fn read(r: &mut Reader) -> Result<DateTime, Error> {
+        let time = DateTime {
+            year: r.u16()?,
+            month: r.u8()?,
+            day: r.u8()?,
+            weekday: r.u8()?,
+            hour: r.u8()?,
+            minute: r.u8()?,
+            second: r.u8()?,
+        };
+        if !time.is_valid() {
+            return Err(Error::InvalidArgument);
+        }
+        Ok(time)
+    }
+}
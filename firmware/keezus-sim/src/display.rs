@@ -0,0 +1,117 @@
+//! An in-memory stand-in for the board's display.
+
+use std::convert::Infallible;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+
+use anyhow::{Context, Result};
+use embedded_graphics::pixelcolor::{Rgb565, Rgb888};
+use embedded_graphics::prelude::*;
+
+pub struct SimDisplay {
+    width: u32,
+    height: u32,
+    pixels: Vec<Rgb565>,
+}
+
+impl SimDisplay {
+    pub fn new(width: u32, height: u32) -> SimDisplay {
+        SimDisplay {
+            width,
+            height,
+            pixels: vec![Rgb565::BLACK; (width * height) as usize],
+        }
+    }
+
+    pub fn pixel(&self, x: u32, y: u32) -> Rgb565 {
+        self.pixels[(y * self.width + x) as usize]
+    }
+
+    /// The display as 8 bit RGB, row by row
+    pub fn to_rgb8(&self) -> Vec<u8> {
+        self.pixels
+            .iter()
+            .flat_map(|&color| {
+                let rgb = to_rgb888(color);
+                [rgb.r(), rgb.g(), rgb.b()]
+            })
+            .collect()
+    }
+
+    /// Writes a PNG, each display pixel `scale` pixels square
+    pub fn save_png(&self, path: &Path, scale: u32) -> Result<()> {
+        let file = File::create(path).with_context(|| format!("couldn't create {}", path.display()))?;
+        let (width, height) = (self.width * scale, self.height * scale);
+        let mut encoder = png::Encoder::new(BufWriter::new(file), width, height);
+        encoder.set_color(png::ColorType::Rgb);
+        encoder.set_depth(png::BitDepth::Eight);
+        let mut writer = encoder.write_header()?;
+
+        let rgb = self.to_rgb8();
+        let mut data = Vec::with_capacity((width * height * 3) as usize);
+        for y in 0..height {
+            let row = (y / scale * self.width) as usize * 3;
+            for x in 0..width {
+                let at = row + (x / scale) as usize * 3;
+                data.extend_from_slice(&rgb[at..at + 3]);
+            }
+        }
+        writer.write_image_data(&data)?;
+        Ok(())
+    }
+
+    /// Draws the display on a truecolor terminal, two pixels to a character
+    /// cell using the upper half block
+    pub fn print_ansi(&self, out: &mut impl Write) -> Result<()> {
+        for y in (0..self.height).step_by(2) {
+            for x in 0..self.width {
+                let top = to_rgb888(self.pixel(x, y));
+                let bottom = if y + 1 < self.height {
+                    to_rgb888(self.pixel(x, y + 1))
+                } else {
+                    Rgb888::BLACK
+                };
+                write!(
+                    out,
+                    "\x1b[38;2;{};{};{}m\x1b[48;2;{};{};{}m\u{2580}",
+                    top.r(),
+                    top.g(),
+                    top.b(),
+                    bottom.r(),
+                    bottom.g(),
+                    bottom.b()
+                )?;
+            }
+            writeln!(out, "\x1b[0m")?;
+        }
+        Ok(())
+    }
+}
+
+fn to_rgb888(color: Rgb565) -> Rgb888 {
+    color.into()
+}
+
+impl OriginDimensions for SimDisplay {
+    fn size(&self) -> Size {
+        Size::new(self.width, self.height)
+    }
+}
+
+impl DrawTarget for SimDisplay {
+    type Color = Rgb565;
+    type Error = Infallible;
+
+    fn draw_iter<I>(&mut self, pixels: I) -> Result<(), Infallible>
+    where
+        I: IntoIterator<Item = Pixel<Rgb565>>,
+    {
+        for Pixel(point, color) in pixels {
+            if point.x >= 0 && point.y >= 0 && (point.x as u32) < self.width && (point.y as u32) < self.height {
+                self.pixels[(point.y as u32 * self.width + point.x as u32) as usize] = color;
+            }
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,68 @@
+//! A stand-in for the keyberon layout the firmware runs, just enough of one
+//! for `keymap::DEFAULT_KEYMAP`: momentary and default layers, transparent
+//! keys, and each key doing what it did when it went down.
+
+use keezus_protocol::keymap::{COLS, DEFAULT_KEYMAP, LAYERS, ROWS};
+use keezus_protocol::KeyAction;
+
+pub struct Layout {
+    /// What each key on the matrix is holding down, `row * COLS + col`
+    held: [Option<KeyAction>; ROWS * COLS],
+    /// The layer with no layer key held, the `layer` command sets it too
+    pub default_layer: usize,
+}
+
+impl Layout {
+    pub fn new() -> Layout {
+        Layout {
+            held: [None; ROWS * COLS],
+            default_layer: 0,
+        }
+    }
+
+    /// The last layer key held down wins, like in keyberon
+    pub fn current_layer(&self) -> usize {
+        self.held
+            .iter()
+            .rev()
+            .find_map(|action| match action {
+                Some(KeyAction::Layer(layer)) => Some(*layer as usize),
+                _ => None,
+            })
+            .unwrap_or(self.default_layer)
+            .min(LAYERS - 1)
+    }
+
+    /// The keymap entry under a key on the current layer, as
+    /// `KeyRouter::key` wants it
+    pub fn entry(&self, row: u8, col: u8) -> KeyAction {
+        DEFAULT_KEYMAP[(self.current_layer() * ROWS + row as usize) * COLS + col as usize]
+    }
+
+    /// Returns what the key does, for a press what it starts doing and for a
+    /// release what it stops doing
+    pub fn event(&mut self, row: u8, col: u8, pressed: bool) -> Option<KeyAction> {
+        let key = row as usize * COLS + col as usize;
+        if !pressed {
+            return self.held[key].take();
+        }
+        // Transparent keys do what they do on the default layer
+        let action = match self.entry(row, col) {
+            KeyAction::Trans => DEFAULT_KEYMAP[(self.default_layer * ROWS + row as usize) * COLS + col as usize],
+            action => action,
+        };
+        if let KeyAction::DefaultLayer(layer) = action {
+            self.default_layer = layer as usize;
+        }
+        self.held[key] = Some(action);
+        Some(action)
+    }
+
+    /// The HID usages held down, for the host report
+    pub fn usages(&self) -> impl Iterator<Item = u8> + '_ {
+        self.held.iter().filter_map(|action| match action {
+            Some(KeyAction::KeyCode(usage)) => Some(*usage),
+            _ => None,
+        })
+    }
+}
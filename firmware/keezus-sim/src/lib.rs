@@ -0,0 +1,12 @@
+//! The simulator itself, shared by the `keezus-sim` binary and the
+//! snapshot test in `tests/`.
+
+pub mod display;
+mod layout;
+pub mod script;
+pub mod sim;
+pub mod snapshots;
+
+/// The panel's size in portrait
+pub const WIDTH: u32 = 128;
+pub const HEIGHT: u32 = 160;
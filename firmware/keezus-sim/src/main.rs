@@ -0,0 +1,153 @@
+//! Runs the keezus screens on the host, drawing into memory instead of the
+//! board's display. Key events come from a script, or typed in one command
+//! at a time, and frames go out as PNGs or to the terminal.
+
+use std::fs;
+use std::io::{self, BufRead, Write};
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result};
+use clap::{Parser, Subcommand};
+
+use keezus_sim::sim::Simulator;
+use keezus_sim::{script, snapshots, HEIGHT, WIDTH};
+
+#[derive(Parser)]
+#[command(version, about)]
+struct Args {
+    #[command(subcommand)]
+    command: Cmd,
+}
+
+#[derive(Subcommand)]
+enum Cmd {
+    /// Run a script of key events, or read commands from stdin one at a
+    /// time when no script is given
+    Run {
+        script: Option<PathBuf>,
+        /// Save the last frame as a PNG
+        #[arg(short, long)]
+        out: Option<PathBuf>,
+        /// Screen to start on
+        #[arg(long, default_value = "status")]
+        screen: String,
+        /// Lay the display out 160x128 instead of 128x160
+        #[arg(long)]
+        landscape: bool,
+        /// Size of a display pixel in saved PNGs
+        #[arg(long, default_value_t = 1)]
+        scale: u32,
+        /// Draw frames on the terminal, which needs truecolor support
+        #[arg(long)]
+        terminal: bool,
+    },
+    #[command(subcommand)]
+    Snapshot(SnapshotCmd),
+}
+
+/// Golden images of the screens, checked in under `keezus-sim/snapshots`
+#[derive(Subcommand)]
+enum SnapshotCmd {
+    /// Draw every screen and compare it with its snapshot
+    Check {
+        /// Only the snapshots whose names start with these
+        names: Vec<String>,
+        /// Where the snapshots are kept
+        #[arg(long)]
+        dir: Option<PathBuf>,
+    },
+    /// Save what the screens draw now as their snapshots
+    Update {
+        names: Vec<String>,
+        #[arg(long)]
+        dir: Option<PathBuf>,
+    },
+}
+
+struct Output {
+    scale: u32,
+    terminal: bool,
+}
+
+impl Output {
+    fn frame(&self, sim: &Simulator) -> Result<()> {
+        if self.terminal {
+            let mut out = io::stdout().lock();
+            sim.display.print_ansi(&mut out)?;
+            out.flush()?;
+        }
+        Ok(())
+    }
+
+    /// Runs a command, saving a PNG if that's what it asks for
+    fn run(&self, sim: &mut Simulator, command: script::Command) -> Result<()> {
+        if let Some(path) = sim.run(command) {
+            sim.display.save_png(&path, self.scale)?;
+        }
+        Ok(())
+    }
+}
+
+fn main() -> Result<()> {
+    match Args::parse().command {
+        Cmd::Run {
+            script,
+            out,
+            screen,
+            landscape,
+            scale,
+            terminal,
+        } => run(script.as_deref(), out.as_deref(), &screen, landscape, scale, terminal),
+        Cmd::Snapshot(SnapshotCmd::Check { names, dir }) => {
+            snapshots::run(&dir.unwrap_or_else(snapshots::default_dir), &names, false)
+        }
+        Cmd::Snapshot(SnapshotCmd::Update { names, dir }) => {
+            snapshots::run(&dir.unwrap_or_else(snapshots::default_dir), &names, true)
+        }
+    }
+}
+
+fn run(
+    script: Option<&Path>,
+    out: Option<&Path>,
+    screen: &str,
+    landscape: bool,
+    scale: u32,
+    terminal: bool,
+) -> Result<()> {
+    let (width, height) = if landscape { (HEIGHT, WIDTH) } else { (WIDTH, HEIGHT) };
+    let mut sim = Simulator::new(width, height);
+    sim.screens().show(script::screen(screen)?);
+    sim.tick();
+    let output = Output {
+        scale: scale.max(1),
+        terminal,
+    };
+
+    match script {
+        Some(path) => {
+            let text = fs::read_to_string(path).with_context(|| format!("couldn't read {}", path.display()))?;
+            for command in script::parse(&text)? {
+                output.run(&mut sim, command)?;
+            }
+            output.frame(&sim)?;
+        }
+        None => {
+            output.frame(&sim)?;
+            for line in io::stdin().lock().lines() {
+                // A typo shouldn't end the session
+                match script::parse_line(&line?) {
+                    Ok(Some(command)) => output.run(&mut sim, command)?,
+                    Ok(None) => continue,
+                    Err(err) => eprintln!("{:#}", err),
+                }
+                output.frame(&sim)?;
+            }
+        }
+    }
+
+    if let Some(path) = out {
+        sim.display.save_png(path, output.scale)?;
+    }
+    Ok(())
+}
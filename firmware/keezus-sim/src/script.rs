@@ -0,0 +1,224 @@
+//! The simulator's input: one command per line, `#` starts a comment.
+//!
+//! ```text
+//! press 0 3        # key down at row 0, column 3
+//! release 0 3
+//! tap 1 0          # press, a tick, release
+//! mods ctrl shift  # or none
+//! caps on
+//! layer 0
+//! next             # the NextScreen key
+//! game             # the GameMode key, keys go to Snake until it's used again
+//! bootloader press # the Bootloader key, or release or tap
+//! screen keymap
+//! wait 500         # let 500ms go by
+//! tick 10          # or 10 display ticks
+//! time 2024-01-31 13:45:00  # set the clock, as keezus-cli would
+//! pet 10 80 50 asleep       # the pet's food, fun and energy, asleep or awake
+//! count 1 0 250    # add 250 presses of a key to today's typing stats
+//! screensaver rain 2        # which screensaver (or each), after how many minutes
+//! screensaver-text HELLO    # the text one's text, none for the default
+//! idle             # start the screensaver now
+//! notify Build passed       # pop a message up, as keezus-cli notify would
+//! png out.png      # save what's on the display
+//! ```
+
+use std::path::PathBuf;
+
+use anyhow::{anyhow, bail, Context, Result};
+use keezus_ui::pet::STAT_MAX;
+use keezus_ui::screens::{Saver, ShortText, MOD_LALT, MOD_LCTRL, MOD_LGUI, MOD_LSHIFT};
+use keezus_ui::{DateTime, ScreenId, NUM_COLS, NUM_ROWS};
+
+pub enum Command {
+    Press { row: u8, col: u8 },
+    Release { row: u8, col: u8 },
+    Tap { row: u8, col: u8 },
+    Mods(u8),
+    CapsLock(bool),
+    Layer(usize),
+    NextScreen,
+    GameMode,
+    /// The Bootloader key going down or up
+    Bootloader { pressed: bool },
+    BootloaderTap,
+    Screen(ScreenId),
+    Wait { ms: u64 },
+    Tick(u32),
+    Time(DateTime),
+    Pet { food: u8, fun: u8, energy: u8, asleep: bool },
+    Count { row: u8, col: u8, presses: u32 },
+    /// `which` and `after_min` as in `ScreensaverConfig`
+    Screensaver { which: u8, after_min: Option<u8> },
+    ScreensaverText(ShortText),
+    Idle,
+    Notify(ShortText),
+    Png(PathBuf),
+}
+
+/// Parses a whole script, errors point at the line they're on
+pub fn parse(text: &str) -> Result<Vec<Command>> {
+    let mut commands = Vec::new();
+    for (number, line) in text.lines().enumerate() {
+        if let Some(command) = parse_line(line).with_context(|| format!("line {}: {:?}", number + 1, line.trim()))? {
+            commands.push(command);
+        }
+    }
+    Ok(commands)
+}
+
+/// Parses one line, `None` if there's nothing on it
+pub fn parse_line(line: &str) -> Result<Option<Command>> {
+    let line = line.split('#').next().unwrap_or("");
+    let mut words = line.split_whitespace();
+    let Some(name) = words.next() else {
+        return Ok(None);
+    };
+    let args: Vec<&str> = words.collect();
+
+    let command = match (name, args.as_slice()) {
+        ("press", [row, col]) => {
+            let (row, col) = key(row, col)?;
+            Command::Press { row, col }
+        }
+        ("release", [row, col]) => {
+            let (row, col) = key(row, col)?;
+            Command::Release { row, col }
+        }
+        ("tap", [row, col]) => {
+            let (row, col) = key(row, col)?;
+            Command::Tap { row, col }
+        }
+        ("mods", names) if !names.is_empty() => Command::Mods(mods(names)?),
+        ("caps", ["on"]) => Command::CapsLock(true),
+        ("caps", ["off"]) => Command::CapsLock(false),
+        ("layer", [layer]) => Command::Layer(layer.parse().context("layer isn't a number")?),
+        ("next", []) => Command::NextScreen,
+        ("game", []) => Command::GameMode,
+        ("bootloader", ["press"]) => Command::Bootloader { pressed: true },
+        ("bootloader", ["release"]) => Command::Bootloader { pressed: false },
+        ("bootloader", ["tap"]) => Command::BootloaderTap,
+        ("screen", [id]) => Command::Screen(screen(id)?),
+        ("wait", [ms]) => Command::Wait {
+            ms: ms.parse().context("wait takes milliseconds")?,
+        },
+        ("tick", [ticks]) => Command::Tick(ticks.parse().context("tick takes a number of ticks")?),
+        ("time", [date, time]) => Command::Time(date_time(date, time)?),
+        ("pet", [food, fun, energy, state @ ("awake" | "asleep")]) => Command::Pet {
+            food: stat(food)?,
+            fun: stat(fun)?,
+            energy: stat(energy)?,
+            asleep: *state == "asleep",
+        },
+        ("count", [row, col, presses]) => {
+            let (row, col) = key(row, col)?;
+            Command::Count {
+                row,
+                col,
+                presses: presses.parse().context("count takes a number of presses")?,
+            }
+        }
+        ("screensaver", [name]) => Command::Screensaver {
+            which: saver(name)?,
+            after_min: None,
+        },
+        ("screensaver", [name, minutes]) => Command::Screensaver {
+            which: saver(name)?,
+            after_min: Some(minutes.parse().context("screensaver takes a number of minutes")?),
+        },
+        ("screensaver-text", words) => Command::ScreensaverText(ShortText::new(&words.join(" "))),
+        ("idle", []) => Command::Idle,
+        ("notify", words) if !words.is_empty() => Command::Notify(ShortText::new(&words.join(" "))),
+        ("png", [file]) => Command::Png(PathBuf::from(file)),
+        (
+            "press" | "release" | "tap" | "mods" | "caps" | "layer" | "next" | "game" | "bootloader" | "screen"
+            | "wait" | "tick" | "time" | "pet" | "count" | "screensaver" | "idle" | "notify" | "png",
+            _,
+        ) => bail!("wrong arguments for {}", name),
+        _ => bail!("unknown command {:?}", name),
+    };
+    Ok(Some(command))
+}
+
+fn key(row: &str, col: &str) -> Result<(u8, u8)> {
+    let row: u8 = row.parse().context("row isn't a number")?;
+    let col: u8 = col.parse().context("column isn't a number")?;
+    if row as usize >= NUM_ROWS || col as usize >= NUM_COLS {
+        bail!(
+            "no key at row {} column {}, the matrix is {}x{}",
+            row,
+            col,
+            NUM_ROWS,
+            NUM_COLS
+        );
+    }
+    Ok((row, col))
+}
+
+fn mods(names: &[&str]) -> Result<u8> {
+    names.iter().try_fold(0, |mods, name| {
+        Ok(mods
+            | match *name {
+                "none" => 0,
+                "ctrl" => MOD_LCTRL,
+                "shift" => MOD_LSHIFT,
+                "alt" => MOD_LALT,
+                "gui" => MOD_LGUI,
+                _ => bail!("unknown modifier {:?}, expected ctrl, shift, alt, gui or none", name),
+            })
+    })
+}
+
+fn stat(value: &str) -> Result<u8> {
+    match value.parse() {
+        Ok(value) if value <= STAT_MAX => Ok(value),
+        _ => bail!("pet stats go from 0 to {}, not {:?}", STAT_MAX, value),
+    }
+}
+
+/// "2024-01-31" "13:45:00", with the weekday worked out
+fn date_time(date: &str, time: &str) -> Result<DateTime> {
+    let numbers = |text: &str, separator: char| -> Option<Vec<u8>> {
+        text.split(separator).map(|n| n.parse().ok()).collect()
+    };
+    let (year, date) = date.split_once('-').context("time takes a date like 2024-01-31")?;
+    let (Some(&[month, day]), Some(&[hour, minute, second])) =
+        (numbers(date, '-').as_deref(), numbers(time, ':').as_deref())
+    else {
+        bail!("time takes a date and time like 2024-01-31 13:45:00");
+    };
+    let parsed = DateTime {
+        year: year.parse().context("year isn't a number")?,
+        month,
+        day,
+        weekday: 0,
+        hour,
+        minute,
+        second,
+    };
+    if !parsed.is_valid() {
+        bail!("{} {} isn't a time the board can keep", date, time);
+    }
+    Ok(DateTime::from_unix(parsed.to_unix()))
+}
+
+/// "each" or one of the screensavers, numbered like the setting
+fn saver(name: &str) -> Result<u8> {
+    if name == "each" {
+        return Ok(0);
+    }
+    match Saver::ALL.iter().position(|saver| saver.name() == name) {
+        Some(i) => Ok(i as u8 + 1),
+        None => {
+            let names: Vec<&str> = Saver::ALL.iter().map(|saver| saver.name()).collect();
+            bail!("unknown screensaver {:?}, expected each, {}", name, names.join(", "))
+        }
+    }
+}
+
+pub fn screen(name: &str) -> Result<ScreenId> {
+    ScreenId::from_name(name).ok_or_else(|| {
+        let names: Vec<&str> = ScreenId::ALL.iter().map(|id| id.name()).collect();
+        anyhow!("unknown screen {:?}, expected one of {}", name, names.join(", "))
+    })
+}
@@ -0,0 +1,223 @@
+//! The keyboard side of the simulator: turns script commands into the
+//! `UiEvent`s core 0 would send, and ticks the screens like core 1 does.
+
+use std::path::PathBuf;
+
+use keezus_protocol::keymap::{self, DEFAULT_KEYMAP, LAYER_NAMES};
+use keezus_protocol::KeyAction;
+use keezus_ui::bootloader::{BootloaderConfirm, Prompt};
+use keezus_ui::keys::KeyRouter;
+use keezus_ui::legend::{self, KeymapLegends};
+use keezus_ui::pet::{Pet, TICK_US};
+use keezus_ui::screens::Notification;
+use keezus_ui::stats::Stats;
+use keezus_ui::{DateTime, Screens, UiEvent, UiState, DISPLAY_UPDATE_TIME_US, NUM_COLS};
+
+use crate::display::SimDisplay;
+use crate::layout::Layout;
+use crate::script::Command;
+
+static LEGENDS: KeymapLegends = legend::keymap(&DEFAULT_KEYMAP);
+/// Usage of Left Control, the other modifiers follow it in `mods` bit order
+const LCTRL: u8 = 0xe0;
+
+pub struct Simulator {
+    screens: Screens,
+    state: UiState,
+    /// Between the matrix and the screens, like core 0
+    layout: Layout,
+    router: KeyRouter,
+    /// Modifiers held down by `mods` commands, on top of the matrix
+    set_mods: u8,
+    caps_lock: bool,
+    /// When the clock was set, and to what, in seconds since 1970
+    clock: Option<(u64, i64)>,
+    /// Kept here like core 0 does, the screens get a copy after each tick
+    pet: Pet,
+    next_pet_tick_us: u64,
+    /// Counted here too, the screens get a copy each tick like core 1 does
+    stats: Stats,
+    bootloader: BootloaderConfirm,
+    /// The Bootloader key was confirmed, the board would have reset with
+    /// the display frozen on its last frame
+    rebooted: bool,
+    pub display: SimDisplay,
+}
+
+impl Simulator {
+    pub fn new(width: u32, height: u32) -> Simulator {
+        Simulator {
+            screens: Screens::new(&LEGENDS),
+            state: UiState::new(LAYER_NAMES[0], 2),
+            layout: Layout::new(),
+            router: KeyRouter::new(),
+            set_mods: 0,
+            caps_lock: false,
+            clock: None,
+            pet: Pet::new(),
+            next_pet_tick_us: TICK_US,
+            stats: Stats::new(),
+            bootloader: BootloaderConfirm::new(),
+            rebooted: false,
+            display: SimDisplay::new(width, height),
+        }
+    }
+
+    pub fn screens(&mut self) -> &mut Screens {
+        &mut self.screens
+    }
+
+    /// One display tick, `DISPLAY_UPDATE_TIME_US` after the last
+    pub fn tick(&mut self) {
+        if self.rebooted {
+            return;
+        }
+        self.state.now_us += DISPLAY_UPDATE_TIME_US;
+        match self.bootloader.update(self.state.now_us) {
+            Some(Prompt::Reboot) => {
+                self.rebooted = true;
+                return;
+            }
+            Some(prompt) => self.send(UiEvent::Notify(Notification::Bootloader(prompt))),
+            None => (),
+        }
+        let unix = self
+            .clock
+            .map(|(set_at_us, time)| time + ((self.state.now_us - set_at_us) / 1_000_000) as i64);
+        self.state.time = unix.map(DateTime::from_unix);
+        self.stats.tick(self.state.now_us);
+        if let Some(unix) = unix {
+            self.stats.set_day(unix.div_euclid(86_400) as u32);
+        }
+        self.state.stats = self.stats;
+        if self.state.now_us >= self.next_pet_tick_us {
+            self.next_pet_tick_us += TICK_US;
+            self.pet.tick();
+            self.send(UiEvent::Pet(self.pet));
+        }
+        let mods = (0..8).filter(|bit| self.set_mods & 1 << bit != 0).map(|bit| LCTRL + bit);
+        let usages = self.layout.usages().chain(mods);
+        let state = &mut self.state;
+        self.router
+            .layout_ticked(usages, self.layout.current_layer(), self.caps_lock, |event| state.apply(event));
+        // Drawing into memory can't fail
+        let _ = self.screens.tick(&self.state, &mut self.display);
+    }
+
+    /// Ticks until at least `ms` have gone by
+    pub fn wait(&mut self, ms: u64) {
+        let until = self.state.now_us + ms * 1000;
+        while self.state.now_us < until && !self.rebooted {
+            self.tick();
+        }
+    }
+
+    /// Runs a command, handing back the file name for `png`, which is left
+    /// to the caller
+    pub fn run(&mut self, command: Command) -> Option<PathBuf> {
+        match command {
+            Command::Press { row, col } => self.key(row, col, true),
+            Command::Release { row, col } => self.key(row, col, false),
+            Command::Tap { row, col } => {
+                self.key(row, col, true);
+                self.tick();
+                self.key(row, col, false);
+            }
+            Command::Mods(mods) => self.set_mods = mods,
+            // As the host would set the LED
+            Command::CapsLock(on) => self.caps_lock = on,
+            Command::Layer(layer) => self.layout.default_layer = layer,
+            Command::NextScreen => self.custom(keymap::CUSTOM_NEXT_SCREEN),
+            Command::GameMode if self.router.game_mode() => {
+                let state = &mut self.state;
+                self.router.leave_game(|event| state.apply(event));
+                self.save_high_score();
+            }
+            Command::GameMode => self.custom(keymap::CUSTOM_GAME_MODE),
+            Command::Bootloader { pressed: true } => self.bootloader.press(self.state.now_us),
+            Command::Bootloader { pressed: false } => self.bootloader.release(self.state.now_us),
+            Command::BootloaderTap => {
+                self.bootloader.press(self.state.now_us);
+                self.tick();
+                self.bootloader.release(self.state.now_us);
+            }
+            Command::Screen(id) => self.screens.show(id),
+            Command::Wait { ms } => self.wait(ms),
+            // Counting the tick every command ends with
+            Command::Tick(ticks) => {
+                for _ in 1..ticks {
+                    self.tick();
+                }
+            }
+            Command::Pet { food, fun, energy, asleep } => {
+                self.pet.food = food;
+                self.pet.fun = fun;
+                self.pet.energy = energy;
+                self.pet.asleep = asleep;
+                self.send(UiEvent::Pet(self.pet));
+            }
+            // Counted as if typed earlier today, so they don't show in
+            // the words per minute
+            Command::Count { row, col, presses } => {
+                self.stats.keys[row as usize * NUM_COLS + col as usize] += presses;
+                self.stats.days[0] += presses;
+                self.stats.total += presses;
+            }
+            Command::Time(time) => self.clock = Some((self.state.now_us, time.to_unix())),
+            Command::Screensaver { which, after_min } => {
+                self.state.screensaver.which = which;
+                if let Some(minutes) = after_min {
+                    self.state.screensaver.after_min = minutes;
+                }
+            }
+            Command::ScreensaverText(text) => self.state.screensaver.text = text,
+            Command::Idle => self.screens.start_screensaver(),
+            Command::Notify(text) => self.send(UiEvent::Notify(Notification::Text(text))),
+            Command::Png(path) => return Some(path),
+        }
+        self.tick();
+        None
+    }
+
+    fn key(&mut self, row: u8, col: u8, pressed: bool) {
+        let action = self.layout.entry(row, col);
+        let state = &mut self.state;
+        let route = self.router.key(action, row, col, pressed, |event| state.apply(event));
+        if route.left_game {
+            self.save_high_score();
+        }
+        if route.layout {
+            match self.layout.event(row, col, pressed) {
+                Some(KeyAction::Custom(custom)) if pressed => self.custom(custom),
+                _ => (),
+            }
+        }
+        if route.count {
+            self.stats.key_pressed(row, col);
+            if self.pet.key_pressed() {
+                self.send(UiEvent::Pet(self.pet));
+            }
+        }
+    }
+
+    /// A custom action going down, for the ones that reach the display
+    fn custom(&mut self, custom: u8) {
+        match custom {
+            keymap::CUSTOM_NEXT_SCREEN => self.send(UiEvent::NextScreen),
+            keymap::CUSTOM_GAME_MODE => {
+                let state = &mut self.state;
+                self.router.enter_game(|event| state.apply(event));
+            }
+            _ => (),
+        }
+    }
+
+    /// On the way out of the game, like game.rs in the firmware does
+    fn save_high_score(&mut self) {
+        self.state.high_score = self.state.high_score.max(self.screens.high_score());
+    }
+
+    fn send(&mut self, event: UiEvent) {
+        self.state.apply(event);
+    }
+}
@@ -0,0 +1,237 @@
+//! Golden images of every screen. Each case runs a script on a fresh
+//! simulator and compares the display with a PNG checked in under
+//! `snapshots/`, so a change to how a screen draws shows up as a failed
+//! `keezus-sim snapshot check` rather than on the board.
+
+use std::fs::{self, File};
+use std::path::{Path, PathBuf};
+
+use anyhow::{bail, Context, Result};
+use embedded_graphics::geometry::OriginDimensions;
+
+use crate::display::SimDisplay;
+use crate::script;
+use crate::sim::Simulator;
+use crate::{HEIGHT, WIDTH};
+
+struct Case {
+    name: &'static str,
+    landscape: bool,
+    script: &'static str,
+}
+
+const fn portrait(name: &'static str, script: &'static str) -> Case {
+    Case {
+        name,
+        landscape: false,
+        script,
+    }
+}
+
+const fn landscape(name: &'static str, script: &'static str) -> Case {
+    Case {
+        name,
+        landscape: true,
+        script,
+    }
+}
+
+const CASES: &[Case] = &[
+    portrait("status-idle", "screen status"),
+    portrait(
+        "status-typing",
+        // Waiting out the Caps Lock notification, which leaves nothing behind
+        "screen status\npress 3 0\ntap 1 5\nrelease 3 0\ntap 0 7\nmods shift\ncaps on\nwait 2000",
+    ),
+    portrait("notify-caps", "screen status\ncaps on"),
+    // Holding Fn
+    portrait("notify-layer", "screen keymap\npress 3 9\nwait 500"),
+    // Most of the way through fading out
+    portrait("notify-fading", "screen clock\nnotify Settings saved\nwait 1400"),
+    portrait("notify-long", "screen stats\nnotify Build passed in 3m 12s"),
+    // A new one replaces the last, which doesn't leave its edges behind
+    portrait("notify-replaced", "screen status\nnotify Keymap uploaded to flash\nwait 300\nnotify Hi"),
+    portrait("notify-screensaver", "screensaver stars\nidle\ntick 50\nnotify Build passed"),
+    portrait("layer", "screen layer"),
+    portrait("keymap-held", "screen keymap\npress 1 2\npress 3 4"),
+    portrait("keymap-fn", "screen keymap\nlayer 1"),
+    portrait("clock-start", "screen clock"),
+    portrait("clock-1h", "screen clock\nwait 3723000"),
+    portrait("clock-time", "screen clock\ntime 2024-02-29 23:59:58\nwait 2000"),
+    portrait("pet-start", "screen pet"),
+    // A keypress wakes it, and a minute of typing cheers it up
+    portrait(
+        "pet-played",
+        "screen pet\npet 40 75 60 asleep\ntap 0 0\ntap 0 1\ntap 0 2\ntap 0 3\ntap 0 4\ntap 0 5\ntap 0 6\ntap 0 7\ntap 0 8\ntap 0 9\nwait 60000",
+    ),
+    portrait("pet-hungry", "screen pet\npet 10 60 80 awake"),
+    portrait("pet-sad-blink", "screen pet\npet 60 20 80 awake\nwait 600"),
+    portrait("pet-asleep", "screen pet\npet 60 40 10 asleep\ntap 1 1"),
+    portrait("stats-empty", "screen stats"),
+    // A few days of typing, with a gap, then a quick burst for the wpm
+    portrait(
+        "stats-typed",
+        "time 2024-03-01 10:00:00\ncount 0 2 900\ncount 1 0 700\ntime 2024-03-02 10:00:00\ncount 0 2 300\n\
+         time 2024-03-04 10:00:00\ncount 0 2 1200\ncount 1 0 800\ntime 2024-03-05 10:00:00\ncount 0 2 600\n\
+         time 2024-03-06 10:00:00\nscreen stats\ncount 0 2 420\ncount 1 0 330\ncount 0 4 310\ncount 0 8 300\n\
+         count 1 5 240\ncount 3 4 500\ncount 2 6 90\ntap 0 1\ntap 0 2\ntap 1 2\ntap 0 9\ntap 1 8\ntap 2 4\n\
+         tap 0 1\ntap 0 2\ntap 1 2\ntap 0 9\ntap 1 8\ntap 2 4",
+    ),
+    portrait("saver-text-1", "screensaver text\nidle"),
+    portrait("saver-text-40", "screensaver text\nidle\ntick 40"),
+    // The scroll wraps after 160 + 13 lines, either side of it
+    portrait("saver-text-172", "screensaver text\nidle\ntick 172"),
+    portrait("saver-text-173", "screensaver text\nidle\ntick 173"),
+    portrait("saver-text-200", "screensaver text\nidle\ntick 200"),
+    portrait("saver-text-custom", "screensaver-text hello from keezus\nscreensaver text\nidle\ntick 60"),
+    portrait("saver-ferris", "screensaver ferris\nidle\ntick 90"),
+    portrait("saver-stars", "screensaver stars\nidle\ntick 120"),
+    portrait("saver-rain", "screensaver rain\nidle\ntick 300"),
+    // Any key goes back to status, and still types
+    portrait("saver-wake", "screen status\nscreensaver rain\nidle\ntick 100\ntap 0 1"),
+    // Nothing pressed for a minute on the status screen
+    portrait("saver-timeout", "screen status\nscreensaver stars 1\nwait 60100"),
+    portrait("bootloader-held", "screen status\nbootloader press\nwait 1200"),
+    portrait("bootloader-tapped", "screen keymap\nbootloader tap\nwait 200"),
+    // Let go halfway through the countdown
+    portrait("bootloader-cancelled", "screen status\nbootloader press\nwait 1500\nbootloader release\nwait 100"),
+    // Too slow with the second press, and the popup is gone without a trace
+    portrait("bootloader-too-slow", "screen status\nbootloader tap\nwait 1600\nbootloader tap\nwait 3000"),
+    // The board resets into the bootloader, leaving the last frame up
+    portrait("bootloader-confirmed", "screen status\nbootloader tap\nwait 300\nbootloader press\nwait 1000"),
+    portrait("image", "screen image"),
+    portrait("sprite-1", "screen sprite"),
+    portrait("sprite-100", "screen sprite\ntick 100"),
+    portrait("next-from-sprite", "screen sprite\nnext"),
+    // Fn+N on the keymap rather than the `next` command
+    portrait("next-fn", "screen status\npress 3 9\ntap 2 5\nrelease 3 9\nwait 2000"),
+    // and Fn+N with the screensaver up, stopping it on the way
+    portrait("next-fn-from-saver", "screensaver stars\nidle\ntick 50\npress 3 9\ntap 2 5\nrelease 3 9\nwait 2000"),
+    portrait("game-start", "game"),
+    // Up three rows, then left to the first food
+    portrait("game-ate", "game\ntap 0 1\nwait 450\ntap 1 0\nwait 1250"),
+    portrait("game-over", "game\ntap 1 2\nwait 1500"),
+    portrait("game-best", "game\ntap 0 1\nwait 450\ntap 1 0\nwait 1500\ngame\ngame"),
+    // What was typed in the game doesn't show up once it's left
+    portrait("game-exit", "screen status\ngame\ntap 0 1\ntap 1 2\ngame"),
+    // In and out with Fn+G, Fn still reaches the layout during the game
+    portrait(
+        "game-fn",
+        "screen status\npress 3 9\ntap 1 4\nrelease 3 9\ntap 0 1\npress 3 9\ntap 1 4\nrelease 3 9\ntap 0 4\nwait 2000",
+    ),
+    landscape("landscape-status", "screen status\ntap 0 0"),
+    landscape("landscape-keymap", "screen keymap\npress 0 9"),
+    landscape("landscape-saver-text", "screensaver text\nidle\ntick 150"),
+    landscape("landscape-saver-ferris", "screensaver ferris\nidle\ntick 400"),
+    landscape("landscape-saver-rain", "screensaver rain\nidle\ntick 300"),
+    landscape("landscape-pet", "screen pet"),
+    landscape("landscape-notify", "screen keymap\nnotify Layer: NAV"),
+    landscape("landscape-stats", "count 1 3 120\ncount 0 2 80\nscreen stats\ntap 1 3\ntap 1 3"),
+    landscape("landscape-game", "game\ntap 1 1\nwait 500"),
+];
+
+pub fn default_dir() -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR")).join("snapshots")
+}
+
+/// Runs the cases whose names start with one of `filters` (all of them
+/// if there are none). Checking fails if any image differs, a failed case
+/// leaves what it drew next to the snapshot as `<name>.actual.png`.
+pub fn run(dir: &Path, filters: &[String], update: bool) -> Result<()> {
+    let cases: Vec<&Case> = CASES
+        .iter()
+        .filter(|case| filters.is_empty() || filters.iter().any(|f| case.name.starts_with(f.as_str())))
+        .collect();
+    if cases.is_empty() {
+        bail!("no snapshot matches {}", filters.join(", "));
+    }
+    fs::create_dir_all(dir).with_context(|| format!("couldn't create {}", dir.display()))?;
+
+    let mut failed = 0;
+    for case in &cases {
+        let display = draw(case)?;
+        let path = dir.join(format!("{}.png", case.name));
+        let actual = dir.join(format!("{}.actual.png", case.name));
+        if update {
+            display.save_png(&path, 1)?;
+            let _ = fs::remove_file(&actual);
+            println!("updated {}", case.name);
+            continue;
+        }
+
+        match compare(&display, &path)? {
+            None => {
+                let _ = fs::remove_file(&actual);
+                println!("ok      {}", case.name);
+            }
+            Some(problem) => {
+                display.save_png(&actual, 1)?;
+                println!("FAILED  {}: {}", case.name, problem);
+                failed += 1;
+            }
+        }
+    }
+
+    if failed > 0 {
+        bail!(
+            "{} of {} snapshots differ, see the .actual.png files in {}, and run `snapshot update` if the change is intended",
+            failed,
+            cases.len(),
+            dir.display()
+        );
+    }
+    Ok(())
+}
+
+fn draw(case: &Case) -> Result<SimDisplay> {
+    let (width, height) = if case.landscape {
+        (HEIGHT, WIDTH)
+    } else {
+        (WIDTH, HEIGHT)
+    };
+    let mut sim = Simulator::new(width, height);
+    for command in script::parse(case.script).with_context(|| format!("snapshot {}", case.name))? {
+        if sim.run(command).is_some() {
+            bail!("snapshot {} saves its own PNG", case.name);
+        }
+    }
+    Ok(sim.display)
+}
+
+/// `None` if the display matches the snapshot, otherwise what's wrong
+fn compare(display: &SimDisplay, path: &Path) -> Result<Option<String>> {
+    let file = match File::open(path) {
+        Ok(file) => file,
+        Err(_) => return Ok(Some("no snapshot yet".into())),
+    };
+    let mut decoder = png::Decoder::new(file);
+    decoder.set_transformations(png::Transformations::normalize_to_color8());
+    let mut reader = decoder
+        .read_info()
+        .with_context(|| format!("{} isn't a PNG file", path.display()))?;
+    let mut buf = vec![0; reader.output_buffer_size()];
+    let info = reader.next_frame(&mut buf)?;
+
+    let size = display.size();
+    if (info.width, info.height) != (size.width, size.height) {
+        return Ok(Some(format!(
+            "the snapshot is {}x{}, the display {}x{}",
+            info.width, info.height, size.width, size.height
+        )));
+    }
+    if info.color_type != png::ColorType::Rgb {
+        bail!("{} isn't an RGB image", path.display());
+    }
+
+    let expected = &buf[..info.buffer_size()];
+    let actual = display.to_rgb8();
+    let differing = expected
+        .chunks_exact(3)
+        .zip(actual.chunks_exact(3))
+        .filter(|(e, a)| e != a)
+        .count();
+    Ok(match differing {
+        0 => None,
+        n => Some(format!("{} pixels differ", n)),
+    })
+}
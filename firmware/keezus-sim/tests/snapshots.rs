@@ -0,0 +1,11 @@
+//! Every snapshot case, checked as `snapshot check` does, so `cargo test`
+//! catches screens that changed without their snapshots being updated.
+
+use keezus_sim::snapshots;
+
+#[test]
+fn snapshots_match() {
+    if let Err(err) = snapshots::run(&snapshots::default_dir(), &[], false) {
+        panic!("{:#}", err);
+    }
+}
@@ -0,0 +1,345 @@
+//! Turns the images and animations in `assets/` into RLE compressed
+//! RGB565, see `src/assets.rs`.
+
+use std::{
+    fs::{self, File},
+    io::Write,
+    path::{Path, PathBuf},
+};
+
+/// Images bigger than this are scaled down to fit the display
+const DISPLAY_WIDTH: u32 = 128;
+const DISPLAY_HEIGHT: u32 = 160;
+/// How long each frame of a sprite sheet is shown for by default
+const DEFAULT_FRAME_TIME_US: u32 = 100_000;
+
+/// 8 bit RGB pixels, row by row
+struct Rgb888Image {
+    width: u32,
+    height: u32,
+    pixels: Vec<[u8; 3]>,
+}
+
+/// Converts every PNG, BMP and GIF in `assets/` and writes a constant for
+/// each, named after the file (`ferris.png` -> `FERRIS`). Single images
+/// become an `Asset`, GIFs and sprite sheets an `Animation`.
+pub fn write_assets(out: &Path) {
+    println!("cargo:rerun-if-changed=assets");
+
+    let mut paths: Vec<PathBuf> = fs::read_dir("assets")
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| matches!(extension(path).as_str(), "png" | "bmp" | "gif"))
+        .collect();
+    paths.sort();
+
+    let mut f = File::create(out.join("assets.rs")).unwrap();
+    for path in paths {
+        println!("cargo:rerun-if-changed={}", path.display());
+        let file_name = path.file_name().unwrap().to_string_lossy().into_owned();
+        let stem = path.file_stem().unwrap().to_string_lossy().into_owned();
+        let fail = |e: String| -> ! { panic!("{}: {}", path.display(), e) };
+
+        if extension(&path) == "gif" {
+            let (frames, frame_time_us) = load_gif(&path).unwrap_or_else(|e| fail(e));
+            write_animation(&mut f, out, &stem, &file_name, frames, frame_time_us);
+            continue;
+        }
+
+        let image = match extension(&path).as_str() {
+            "png" => load_png(&path),
+            _ => load_bmp(&path),
+        }
+        .unwrap_or_else(|e| fail(e));
+
+        match sprite_sheet(&stem) {
+            Some((stem, frame_width, frame_height)) => {
+                let frames = split_sheet(&image, frame_width, frame_height).unwrap_or_else(|e| fail(e));
+                write_animation(&mut f, out, stem, &file_name, frames, DEFAULT_FRAME_TIME_US);
+            }
+            None => {
+                let image = fit_display(image, &path);
+                writeln!(f, "/// {}x{}, from `assets/{}`", image.width, image.height, file_name).unwrap();
+                writeln!(f, "pub const {}: Asset = {};", const_name(&stem), write_rle(out, &stem, &image)).unwrap();
+            }
+        }
+    }
+}
+
+/// Writes out the compressed pixels and returns the expression for the
+/// `Asset` pointing at them
+fn write_rle(out: &Path, blob: &str, image: &Rgb888Image) -> String {
+    let blob = format!("{}.rle", blob);
+    File::create(out.join(&blob)).unwrap().write_all(&encode_rle(image)).unwrap();
+    format!(
+        "Asset::new({}, {}, include_bytes!(concat!(env!(\"OUT_DIR\"), \"/{}\")))",
+        image.width, image.height, blob
+    )
+}
+
+/// Frames go in a static array of their own so the `Animation` can point
+/// at them
+fn write_animation(f: &mut File, out: &Path, stem: &str, file_name: &str, frames: Vec<Rgb888Image>, frame_time_us: u32) {
+    let name = const_name(stem);
+    let (width, height) = (frames[0].width, frames[0].height);
+    writeln!(f, "static {}_FRAMES: [Asset; {}] = [", name, frames.len()).unwrap();
+    for (i, frame) in frames.iter().enumerate() {
+        writeln!(f, "    {},", write_rle(out, &format!("{}.{}", stem, i), frame)).unwrap();
+    }
+    writeln!(f, "];").unwrap();
+    writeln!(f, "/// {} frames of {}x{}, from `assets/{}`", frames.len(), width, height, file_name).unwrap();
+    writeln!(
+        f,
+        "pub static {}: Animation = Animation::new({}, {}, {}, &{}_FRAMES);",
+        name, width, height, frame_time_us, name
+    )
+    .unwrap();
+}
+
+/// Sprite sheets have the frame size at the end of their name, e.g.
+/// `walk.32x24.png` is cut into 32x24 frames
+fn sprite_sheet(stem: &str) -> Option<(&str, u32, u32)> {
+    let (name, size) = stem.rsplit_once('.')?;
+    let (width, height) = size.split_once('x')?;
+    Some((name, width.parse().ok()?, height.parse().ok()?))
+}
+
+/// Cuts a sprite sheet into frames, left to right then top to bottom
+fn split_sheet(sheet: &Rgb888Image, width: u32, height: u32) -> Result<Vec<Rgb888Image>, String> {
+    if width == 0 || height == 0 || width > DISPLAY_WIDTH || height > DISPLAY_HEIGHT {
+        return Err(format!("frames must be between 1x1 and {}x{}", DISPLAY_WIDTH, DISPLAY_HEIGHT));
+    }
+    let (columns, rows) = (sheet.width / width, sheet.height / height);
+    if columns * width != sheet.width || rows * height != sheet.height {
+        return Err(format!("{}x{} isn't a whole number of {}x{} frames", sheet.width, sheet.height, width, height));
+    }
+    let mut frames = Vec::new();
+    for top in (0..rows).map(|row| row * height) {
+        for left in (0..columns).map(|column| column * width) {
+            let pixels = (top..top + height)
+                .flat_map(|y| (left..left + width).map(move |x| (x, y)))
+                .map(|(x, y)| sheet.pixels[(y * sheet.width + x) as usize])
+                .collect();
+            frames.push(Rgb888Image { width, height, pixels });
+        }
+    }
+    Ok(frames)
+}
+
+/// Every frame of a GIF as a full image, and how long the first one is
+/// shown for
+fn load_gif(path: &Path) -> Result<(Vec<Rgb888Image>, u32), String> {
+    let mut options = gif::DecodeOptions::new();
+    options.set_color_output(gif::ColorOutput::RGBA);
+    let mut decoder = options.read_info(File::open(path).map_err(|e| e.to_string())?).map_err(|e| e.to_string())?;
+    let (width, height) = (decoder.width() as usize, decoder.height() as usize);
+
+    // Frames only cover what changed, so draw each onto the last
+    let mut canvas = vec![[0u8; 4]; width * height];
+    let mut frames = Vec::new();
+    let mut frame_time_us = None;
+    while let Some(frame) = decoder.read_next_frame().map_err(|e| e.to_string())? {
+        let previous = canvas.clone();
+        let (left, top) = (frame.left as usize, frame.top as usize);
+        let area = (top..top + frame.height as usize)
+            .flat_map(|y| (left..left + frame.width as usize).map(move |x| (x, y)))
+            .filter(|&(x, y)| x < width && y < height);
+        for ((x, y), px) in area.clone().zip(frame.buffer.chunks(4)) {
+            if px[3] != 0 {
+                canvas[y * width + x] = [px[0], px[1], px[2], px[3]];
+            }
+        }
+
+        let pixels = canvas
+            .iter()
+            .map(|px| [0, 1, 2].map(|c| (px[c] as u32 * px[3] as u32 / 255) as u8))
+            .collect();
+        let image = Rgb888Image {
+            width: width as u32,
+            height: height as u32,
+            pixels,
+        };
+        frames.push(fit_display(image, path));
+        // Delays are in hundredths of a second, and browsers treat 0 as 10
+        let delay = match frame.delay {
+            0 => 10,
+            delay => delay as u32,
+        };
+        frame_time_us.get_or_insert(delay * 10_000);
+
+        match frame.dispose {
+            gif::DisposalMethod::Background => area.for_each(|(x, y)| canvas[y * width + x] = [0; 4]),
+            gif::DisposalMethod::Previous => canvas = previous,
+            _ => {}
+        }
+    }
+    match frame_time_us {
+        Some(frame_time_us) => Ok((frames, frame_time_us)),
+        None => Err("no frames".into()),
+    }
+}
+
+fn extension(path: &Path) -> String {
+    path.extension().map(|e| e.to_string_lossy().to_lowercase()).unwrap_or_default()
+}
+
+/// Upper snake case, with a prefix if the file name starts with a digit
+fn const_name(stem: &str) -> String {
+    let name: String = stem
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c.to_ascii_uppercase() } else { '_' })
+        .collect();
+    if name.starts_with(|c: char| c.is_ascii_digit()) {
+        format!("IMAGE_{}", name)
+    } else {
+        name
+    }
+}
+
+fn load_png(path: &Path) -> Result<Rgb888Image, String> {
+    let mut decoder = png::Decoder::new(File::open(path).map_err(|e| e.to_string())?);
+    decoder.set_transformations(png::Transformations::EXPAND | png::Transformations::STRIP_16);
+    let mut reader = decoder.read_info().map_err(|e| e.to_string())?;
+    let mut buf = vec![0; reader.output_buffer_size()];
+    let info = reader.next_frame(&mut buf).map_err(|e| e.to_string())?;
+
+    let channels = match info.color_type {
+        png::ColorType::Grayscale => 1,
+        png::ColorType::GrayscaleAlpha => 2,
+        png::ColorType::Rgb => 3,
+        png::ColorType::Rgba => 4,
+        other => return Err(format!("unsupported color type {:?}", other)),
+    };
+    let mut pixels = Vec::with_capacity((info.width * info.height) as usize);
+    for row in buf.chunks(info.line_size).take(info.height as usize) {
+        for px in row.chunks(channels).take(info.width as usize) {
+            // There is nothing behind the display, transparency goes black
+            let (rgb, alpha) = match channels {
+                1 => ([px[0]; 3], 255),
+                2 => ([px[0]; 3], px[1]),
+                3 => ([px[0], px[1], px[2]], 255),
+                _ => ([px[0], px[1], px[2]], px[3]),
+            };
+            pixels.push(rgb.map(|c| (c as u32 * alpha as u32 / 255) as u8));
+        }
+    }
+    Ok(Rgb888Image {
+        width: info.width,
+        height: info.height,
+        pixels,
+    })
+}
+
+/// Uncompressed 24/32 bit and bitfield 16/32 bit BMPs, which covers what
+/// image editors write for RGB565
+fn load_bmp(path: &Path) -> Result<Rgb888Image, String> {
+    let data = fs::read(path).map_err(|e| e.to_string())?;
+    let u16_at = |at: usize| data.get(at..at + 2).map(|b| u16::from_le_bytes([b[0], b[1]]) as u32);
+    let u32_at = |at: usize| data.get(at..at + 4).map(|b| u32::from_le_bytes([b[0], b[1], b[2], b[3]]));
+    let truncated = || "truncated header".to_string();
+
+    if data.get(..2) != Some(b"BM") {
+        return Err("not a BMP file".into());
+    }
+    let offset = u32_at(10).ok_or_else(truncated)? as usize;
+    let header_size = u32_at(14).ok_or_else(truncated)?;
+    let width = u32_at(18).ok_or_else(truncated)? as i32;
+    let height = u32_at(22).ok_or_else(truncated)? as i32;
+    let bpp = u16_at(28).ok_or_else(truncated)?;
+    let compression = u32_at(30).ok_or_else(truncated)?;
+
+    let masks = match (compression, bpp) {
+        (0, 24) | (0, 32) => [0xff_0000, 0x00_ff00, 0x00_00ff],
+        // The masks follow the 40 byte header, or are part of a V4/V5 one
+        (3, 16) | (3, 32) if header_size >= 40 => [
+            u32_at(54).ok_or_else(truncated)?,
+            u32_at(58).ok_or_else(truncated)?,
+            u32_at(62).ok_or_else(truncated)?,
+        ],
+        _ => return Err(format!("unsupported {} bpp BMP with compression {}", bpp, compression)),
+    };
+    if width <= 0 || height == 0 {
+        return Err("bad dimensions".into());
+    }
+
+    let (width, bottom_up) = (width as u32, height > 0);
+    let height = height.unsigned_abs();
+    let bytes_per_pixel = bpp as usize / 8;
+    let stride = (width as usize * bytes_per_pixel + 3) & !3;
+    let mut pixels = Vec::with_capacity((width * height) as usize);
+    for y in 0..height as usize {
+        let row = if bottom_up { height as usize - 1 - y } else { y };
+        let start = offset + row * stride;
+        let row = data
+            .get(start..start + width as usize * bytes_per_pixel)
+            .ok_or("truncated pixel data")?;
+        for px in row.chunks(bytes_per_pixel) {
+            let value = px.iter().rev().fold(0u32, |acc, &b| acc << 8 | b as u32);
+            pixels.push(masks.map(|mask| scale_channel(value, mask)));
+        }
+    }
+    Ok(Rgb888Image { width, height, pixels })
+}
+
+/// Pulls the channel under `mask` out of `value`, scaled to 8 bits
+fn scale_channel(value: u32, mask: u32) -> u8 {
+    if mask == 0 {
+        return 0;
+    }
+    let shift = mask.trailing_zeros();
+    (((value & mask) >> shift) * 255 / (mask >> shift)) as u8
+}
+
+/// Box filters the image down if it is bigger than the display, keeping
+/// its aspect ratio
+fn fit_display(image: Rgb888Image, path: &Path) -> Rgb888Image {
+    if image.width <= DISPLAY_WIDTH && image.height <= DISPLAY_HEIGHT {
+        return image;
+    }
+    let scale = f64::max(
+        image.width as f64 / DISPLAY_WIDTH as f64,
+        image.height as f64 / DISPLAY_HEIGHT as f64,
+    );
+    let width = ((image.width as f64 / scale) as u32).clamp(1, DISPLAY_WIDTH);
+    let height = ((image.height as f64 / scale) as u32).clamp(1, DISPLAY_HEIGHT);
+    println!(
+        "cargo:warning={} is {}x{}, scaled down to {}x{}",
+        path.display(),
+        image.width,
+        image.height,
+        width,
+        height
+    );
+
+    let mut pixels = Vec::with_capacity((width * height) as usize);
+    for y in 0..height {
+        let (y0, y1) = (y * image.height / height, ((y + 1) * image.height / height).max(y * image.height / height + 1));
+        for x in 0..width {
+            let (x0, x1) = (x * image.width / width, ((x + 1) * image.width / width).max(x * image.width / width + 1));
+            let mut sum = [0u32; 3];
+            for sy in y0..y1 {
+                for sx in x0..x1 {
+                    let px = image.pixels[(sy * image.width + sx) as usize];
+                    for (total, channel) in sum.iter_mut().zip(px) {
+                        *total += channel as u32;
+                    }
+                }
+            }
+            let count = (y1 - y0) * (x1 - x0);
+            pixels.push(sum.map(|total| (total / count) as u8));
+        }
+    }
+    Rgb888Image { width, height, pixels }
+}
+
+fn rgb565([r, g, b]: [u8; 3]) -> u16 {
+    (r as u16 >> 3) << 11 | (g as u16 >> 2) << 5 | b as u16 >> 3
+}
+
+/// See `src/rle.rs` for the packets
+fn encode_rle(image: &Rgb888Image) -> Vec<u8> {
+    let pixels: Vec<u16> = image.pixels.iter().copied().map(rgb565).collect();
+    let mut out = Vec::new();
+    crate::rle::encode(&pixels, |byte| out.push(byte));
+    out
+}
@@ -0,0 +1,362 @@
+//! Builds the glyph tables in `src/font.rs` from the BDF fonts listed in
+//! `fonts/fonts.txt`, keeping only the characters the firmware can
+//! actually end up drawing.
+
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fs::{self, File},
+    io::Write,
+    path::{Path, PathBuf},
+};
+
+/// A glyph's pixels, positioned relative to the pen on the baseline
+pub struct Glyph {
+    /// Left edge, from the pen position
+    pub x: i32,
+    /// Top edge, from the baseline (so usually negative)
+    pub y: i32,
+    pub width: u32,
+    pub height: u32,
+    /// How far the pen moves on
+    pub advance: i32,
+    /// Row by row, `width * height` of them
+    pub pixels: Vec<bool>,
+}
+
+pub struct SourceFont {
+    pub ascent: i32,
+    pub descent: i32,
+    pub glyphs: BTreeMap<char, Glyph>,
+}
+
+struct FontSpec {
+    name: String,
+    sources: Vec<Source>,
+    chars: BTreeSet<char>,
+}
+
+struct Source {
+    file: String,
+    proportional: bool,
+}
+
+/// Writes `fonts.rs` and a bitmap blob for every font in the manifest
+pub fn write_fonts(out: &Path) {
+    println!("cargo:rerun-if-changed=fonts");
+    let manifest = fs::read_to_string("fonts/fonts.txt").unwrap();
+    let specs = parse_manifest(&manifest).unwrap_or_else(|e| panic!("fonts/fonts.txt: {}", e));
+
+    // Whatever the screens draw is in their source, anything else has to
+    // be asked for with `chars`
+    let mut used: BTreeSet<char> = (' '..='~').collect();
+    used.insert(char::REPLACEMENT_CHARACTER);
+    for path in rust_sources(Path::new("src")) {
+        println!("cargo:rerun-if-changed={}", path.display());
+        used.extend(fs::read_to_string(&path).unwrap().chars().filter(|c| !c.is_ascii()));
+    }
+
+    let mut f = File::create(out.join("fonts.rs")).unwrap();
+    for spec in specs {
+        let wanted: BTreeSet<char> = used.union(&spec.chars).copied().collect();
+        let sources: Vec<SourceFont> = spec
+            .sources
+            .iter()
+            .map(|source| {
+                let path = Path::new("fonts").join(&source.file);
+                println!("cargo:rerun-if-changed={}", path.display());
+                load(&path, source, &wanted).unwrap_or_else(|e| panic!("{}: {}", path.display(), e))
+            })
+            .collect();
+        for c in &spec.chars {
+            if !sources.iter().any(|font| font.glyphs.contains_key(c)) {
+                println!("cargo:warning=font {} has no glyph for {:?} (U+{:04X})", spec.name, c, *c as u32);
+            }
+        }
+        write_font(&mut f, out, &spec, &sources, &wanted);
+    }
+}
+
+fn parse_manifest(text: &str) -> Result<Vec<FontSpec>, String> {
+    let mut specs: Vec<FontSpec> = Vec::new();
+    for (number, line) in text.lines().enumerate() {
+        let fail = |e: &str| format!("line {}: {}", number + 1, e);
+        let line = line.split('#').next().unwrap_or("").trim();
+        if line.is_empty() {
+            continue;
+        }
+        let (keyword, rest) = line.split_once(char::is_whitespace).unwrap_or((line, ""));
+        if keyword == "font" {
+            specs.push(FontSpec {
+                name: rest.trim().to_string(),
+                sources: Vec::new(),
+                chars: BTreeSet::new(),
+            });
+            continue;
+        }
+        let spec = specs.last_mut().ok_or_else(|| fail("expected `font NAME` first"))?;
+        match keyword {
+            "source" => {
+                let mut words = rest.split_whitespace();
+                let file = words.next().ok_or_else(|| fail("source needs a file"))?.to_string();
+                let proportional = match words.next() {
+                    None => false,
+                    Some("proportional") => true,
+                    Some(word) => return Err(fail(&format!("unknown source option {:?}", word))),
+                };
+                spec.sources.push(Source { file, proportional });
+            }
+            "chars" => {
+                for word in rest.split_whitespace() {
+                    spec.chars.extend(parse_chars(word).map_err(|e| fail(&e))?);
+                }
+            }
+            _ => return Err(fail(&format!("unknown keyword {:?}", keyword))),
+        }
+    }
+    Ok(specs)
+}
+
+/// `U+3041-U+3096`, `U+30FC` or just the characters themselves
+fn parse_chars(word: &str) -> Result<Vec<char>, String> {
+    let code = |text: &str| -> Result<char, String> {
+        u32::from_str_radix(text.trim_start_matches("U+"), 16)
+            .ok()
+            .and_then(char::from_u32)
+            .ok_or_else(|| format!("bad code point {:?}", text))
+    };
+    if !word.starts_with("U+") {
+        return Ok(word.chars().collect());
+    }
+    match word.split_once('-') {
+        Some((first, last)) => Ok((code(first)?..=code(last)?).collect()),
+        None => Ok(vec![code(word)?]),
+    }
+}
+
+fn rust_sources(dir: &Path) -> Vec<PathBuf> {
+    let mut paths = Vec::new();
+    for entry in fs::read_dir(dir).unwrap() {
+        let path = entry.unwrap().path();
+        if path.is_dir() {
+            paths.extend(rust_sources(&path));
+        } else if path.extension().is_some_and(|e| e == "rs") {
+            paths.push(path);
+        }
+    }
+    paths
+}
+
+fn load(path: &Path, source: &Source, wanted: &BTreeSet<char>) -> Result<SourceFont, String> {
+    if path.extension().is_none_or(|e| !e.eq_ignore_ascii_case("bdf")) {
+        return Err("expected a .bdf font".into());
+    }
+    let mut font = load_bdf(path, wanted)?;
+    for glyph in font.glyphs.values_mut() {
+        crop(glyph);
+        if source.proportional {
+            make_proportional(glyph);
+        }
+    }
+    Ok(font)
+}
+
+/// Keeps only the `wanted` glyphs, so a complete CJK font costs nothing
+/// beyond the few characters the screens use. Fonts encoded in JIS X 0208,
+/// like misc-fixed's `12x13ja.bdf`, have their kana and punctuation mapped
+/// to Unicode, see `jis_to_unicode`.
+fn load_bdf(path: &Path, wanted: &BTreeSet<char>) -> Result<SourceFont, String> {
+    // Some fonts have Latin-1 in their comments
+    let text: String = fs::read(path).map_err(|e| e.to_string())?.iter().map(|&b| b as char).collect();
+    let mut font = SourceFont {
+        ascent: 0,
+        descent: 0,
+        glyphs: BTreeMap::new(),
+    };
+    let mut lines = text.lines();
+    let numbers = |rest: &str| -> Vec<i32> { rest.split_whitespace().filter_map(|n| n.parse().ok()).collect() };
+
+    let mut jis = false;
+    let mut encoding = None;
+    let mut glyphs = 0;
+    let mut advance = 0;
+    let mut bbx = [0; 4];
+    while let Some(line) = lines.next() {
+        let (keyword, rest) = line.split_once(' ').unwrap_or((line, ""));
+        match keyword {
+            "FONTBOUNDINGBOX" => {
+                let n = numbers(rest);
+                if n.len() == 4 && font.ascent == 0 {
+                    font.ascent = n[1] + n[3];
+                    font.descent = -n[3];
+                }
+            }
+            "FONT_ASCENT" => font.ascent = numbers(rest).first().copied().unwrap_or(0),
+            "FONT_DESCENT" => font.descent = numbers(rest).first().copied().unwrap_or(0),
+            "CHARSET_REGISTRY" => jis = rest.trim_matches('"').starts_with("JISX0208"),
+            "STARTCHAR" => {
+                encoding = None;
+                advance = 0;
+                bbx = [0; 4];
+            }
+            "ENCODING" => {
+                let code = numbers(rest).first().and_then(|&n| u32::try_from(n).ok());
+                encoding = if jis { code.and_then(jis_to_unicode) } else { code.and_then(char::from_u32) };
+            }
+            "DWIDTH" => advance = numbers(rest).first().copied().unwrap_or(0),
+            "BBX" => {
+                let n = numbers(rest);
+                if n.len() != 4 {
+                    return Err(format!("bad BBX {:?}", line));
+                }
+                bbx.copy_from_slice(&n);
+            }
+            "BITMAP" => {
+                glyphs += 1;
+                let [width, height, x, y] = bbx;
+                let (width, height) = (width.max(0) as u32, height.max(0) as u32);
+                let mut pixels = Vec::with_capacity((width * height) as usize);
+                for _ in 0..height {
+                    let row = lines.next().ok_or("bitmap cut short")?.trim();
+                    let bytes: Vec<u8> = (0..row.len() / 2)
+                        .map(|i| u8::from_str_radix(&row[i * 2..i * 2 + 2], 16))
+                        .collect::<Result<_, _>>()
+                        .map_err(|_| format!("bad bitmap row {:?}", row))?;
+                    pixels.extend((0..width as usize).map(|x| bytes.get(x / 8).is_some_and(|b| b & (0x80 >> (x % 8)) != 0)));
+                }
+                // Code points below 0 are unencoded glyphs, no use to us
+                if let Some(c) = encoding.filter(|c| wanted.contains(c)) {
+                    font.glyphs.insert(
+                        c,
+                        Glyph {
+                            x,
+                            y: -(y + height as i32),
+                            width,
+                            height,
+                            advance,
+                            pixels,
+                        },
+                    );
+                }
+            }
+            _ => {}
+        }
+    }
+    if glyphs == 0 {
+        return Err("no glyphs".into());
+    }
+    Ok(font)
+}
+
+/// The first 59 symbols of JIS X 0208 row 1, from 0x2121 on
+const JIS_ROW_1: &str = "\u{3000}、。，．・：；？！゛゜´｀¨＾￣＿ヽヾゝゞ〃仝々〆〇ー―‐／＼〜‖｜…‥‘’“”（）〔〕［］｛｝〈〉《》「」『』【】";
+
+/// Kana and the common punctuation, which is all the screens need from a
+/// Japanese font. The kanji are left out rather than carry a 7000 entry
+/// table around.
+fn jis_to_unicode(code: u32) -> Option<char> {
+    let c = match code {
+        0x2121..=0x215b => return JIS_ROW_1.chars().nth((code - 0x2121) as usize),
+        // Hiragana and katakana are in Unicode order
+        0x2421..=0x2473 => 0x3041 + code - 0x2421,
+        0x2521..=0x2576 => 0x30a1 + code - 0x2521,
+        _ => return None,
+    };
+    char::from_u32(c)
+}
+
+/// Drops empty rows and columns around the ink
+fn crop(glyph: &mut Glyph) {
+    let (width, height) = (glyph.width as usize, glyph.height as usize);
+    let ink = |x: usize, y: usize| glyph.pixels[y * width + x];
+    let rows: Vec<usize> = (0..height).filter(|&y| (0..width).any(|x| ink(x, y))).collect();
+    let cols: Vec<usize> = (0..width).filter(|&x| (0..height).any(|y| ink(x, y))).collect();
+    let (Some(&top), Some(&bottom), Some(&left), Some(&right)) = (rows.first(), rows.last(), cols.first(), cols.last())
+    else {
+        glyph.width = 0;
+        glyph.height = 0;
+        glyph.pixels.clear();
+        return;
+    };
+    let pixels = (top..=bottom).flat_map(|y| (left..=right).map(move |x| (x, y))).map(|(x, y)| ink(x, y)).collect();
+    glyph.pixels = pixels;
+    glyph.x += left as i32;
+    glyph.y += top as i32;
+    glyph.width = (right - left + 1) as u32;
+    glyph.height = (bottom - top + 1) as u32;
+}
+
+/// Turns a fixed width glyph into a proportional one: as wide as its ink
+/// plus a pixel of spacing, blank ones half their old width
+fn make_proportional(glyph: &mut Glyph) {
+    if glyph.width == 0 {
+        glyph.advance = (glyph.advance + 1) / 2;
+    } else {
+        glyph.x = 0;
+        glyph.advance = glyph.width as i32 + 1;
+    }
+}
+
+fn write_font(f: &mut File, out: &Path, spec: &FontSpec, sources: &[SourceFont], wanted: &BTreeSet<char>) {
+    let ascent = sources.iter().map(|s| s.ascent).max().unwrap_or(0);
+    let descent = sources.iter().map(|s| s.descent).max().unwrap_or(0);
+
+    let mut bits = BitWriter::default();
+    let mut glyphs = Vec::new();
+    for &c in wanted {
+        let Some(glyph) = sources.iter().find_map(|s| s.glyphs.get(&c)) else {
+            continue;
+        };
+        let offset = bits.len;
+        for &pixel in &glyph.pixels {
+            bits.push(pixel);
+        }
+        glyphs.push(format!(
+            "Glyph::new('\\u{{{:x}}}', {}, {}, {}, {}, {}, {})",
+            c as u32, offset, glyph.width, glyph.height, glyph.x, glyph.y, glyph.advance
+        ));
+    }
+
+    let blob = format!("font_{}.bin", spec.name.to_lowercase());
+    File::create(out.join(&blob)).unwrap().write_all(&bits.bytes).unwrap();
+    let files: Vec<String> = spec.sources.iter().map(|s| format!("`{}`", s.file)).collect();
+
+    writeln!(f, "static {}_GLYPHS: [Glyph; {}] = [", spec.name, glyphs.len()).unwrap();
+    for glyph in &glyphs {
+        writeln!(f, "    {},", glyph).unwrap();
+    }
+    writeln!(f, "];").unwrap();
+    writeln!(
+        f,
+        "/// {} glyphs from {}, {} pixels above the baseline and {} below",
+        glyphs.len(),
+        files.join(", "),
+        ascent,
+        descent
+    )
+    .unwrap();
+    writeln!(
+        f,
+        "pub static {}: Font = Font::new({}, {}, &{}_GLYPHS, include_bytes!(concat!(env!(\"OUT_DIR\"), \"/{}\")));",
+        spec.name, ascent, descent, spec.name, blob
+    )
+    .unwrap();
+}
+
+/// Packs glyph pixels one bit each, first pixel in the top bit
+#[derive(Default)]
+struct BitWriter {
+    bytes: Vec<u8>,
+    len: usize,
+}
+
+impl BitWriter {
+    fn push(&mut self, bit: bool) {
+        if self.len / 8 == self.bytes.len() {
+            self.bytes.push(0);
+        }
+        if bit {
+            self.bytes[self.len / 8] |= 0x80 >> (self.len % 8);
+        }
+        self.len += 1;
+    }
+}
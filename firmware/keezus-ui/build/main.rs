@@ -0,0 +1,15 @@
+//! Generates the parts of keezus-ui that come from files: images and
+//! animations from `assets/` and glyph tables from `fonts/`.
+
+mod assets;
+mod fonts;
+#[path = "../src/rle.rs"]
+mod rle;
+
+use std::{env, path::PathBuf};
+
+fn main() {
+    let out = &PathBuf::from(env::var_os("OUT_DIR").unwrap());
+    assets::write_assets(out);
+    fonts::write_fonts(out);
+}
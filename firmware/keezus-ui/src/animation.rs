@@ -0,0 +1,86 @@
+//! Plays an `Animation` from assets.rs, one step per display tick.
+//!
+//! Frame times are counted in ticks of `DISPLAY_UPDATE_TIME_US`, the same
+//! clock everything else on the display moves with, rounded to at least
+//! one tick per frame.
+
+use crate::assets::{Animation, Asset};
+use crate::DISPLAY_UPDATE_TIME_US;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Playback {
+    /// First to last, then back to the first
+    Loop,
+    /// First to last and back again
+    PingPong,
+}
+
+pub struct Player {
+    animation: &'static Animation,
+    playback: Playback,
+    ticks_per_frame: u32,
+    ticks: u32,
+    frame: usize,
+    /// Which way ping-pong playback is going
+    forward: bool,
+}
+
+impl Player {
+    /// Plays at the frame rate the animation came with
+    pub fn new(animation: &'static Animation, playback: Playback) -> Player {
+        Player {
+            animation,
+            playback,
+            ticks_per_frame: ticks(animation.frame_time_us as u64),
+            ticks: 0,
+            frame: 0,
+            forward: true,
+        }
+    }
+
+    /// Overrides the animation's own frame rate
+    pub fn with_frame_rate(mut self, frames_per_second: u32) -> Player {
+        self.ticks_per_frame = ticks(1_000_000 / frames_per_second.max(1) as u64);
+        self
+    }
+
+    /// Back to the first frame
+    pub fn restart(&mut self) {
+        self.ticks = 0;
+        self.frame = 0;
+        self.forward = true;
+    }
+
+    /// Call once per display tick, returns whether the frame changed
+    pub fn tick(&mut self) -> bool {
+        let count = self.animation.frames().len();
+        self.ticks += 1;
+        if self.ticks < self.ticks_per_frame || count < 2 {
+            return false;
+        }
+        self.ticks = 0;
+
+        self.frame = match self.playback {
+            Playback::Loop => (self.frame + 1) % count,
+            Playback::PingPong => {
+                if (self.forward && self.frame == count - 1) || (!self.forward && self.frame == 0) {
+                    self.forward = !self.forward;
+                }
+                if self.forward {
+                    self.frame + 1
+                } else {
+                    self.frame - 1
+                }
+            }
+        };
+        true
+    }
+
+    pub fn frame(&self) -> &'static Asset {
+        &self.animation.frames()[self.frame]
+    }
+}
+
+fn ticks(frame_time_us: u64) -> u32 {
+    ((frame_time_us + DISPLAY_UPDATE_TIME_US / 2) / DISPLAY_UPDATE_TIME_US).max(1) as u32
+}
@@ -0,0 +1,194 @@
+//! Images from `assets/`, converted to RLE compressed RGB565 by build.rs.
+//!
+//! Drop a PNG or BMP into `assets/` and it shows up here as a constant
+//! named after the file, e.g. `assets/ferris.png` becomes `FERRIS`. Images
+//! bigger than the display are scaled down to fit. They are decoded on the
+//! fly while drawing, nothing is unpacked into RAM.
+//!
+//! GIFs and sprite sheets become an `Animation` instead. A sprite sheet is
+//! a PNG or BMP with the frame size in its name, e.g. `walk.32x24.png`,
+//! and is cut into frames left to right, then top to bottom. See
+//! animation.rs for playing them.
+
+use embedded_graphics::pixelcolor::raw::RawU16;
+use embedded_graphics::pixelcolor::Rgb565;
+use embedded_graphics::prelude::*;
+use embedded_graphics::primitives::Rectangle;
+
+use crate::rle;
+
+mod generated {
+    use super::{Animation, Asset};
+    include!(concat!(env!("OUT_DIR"), "/assets.rs"));
+}
+pub use generated::*;
+
+#[derive(Clone, Copy)]
+pub struct Asset {
+    width: u32,
+    height: u32,
+    data: &'static [u8],
+}
+
+impl Asset {
+    pub const fn new(width: u32, height: u32, data: &'static [u8]) -> Asset {
+        Asset { width, height, data }
+    }
+
+    /// Every pixel, row by row
+    pub fn pixels(&self) -> Pixels {
+        Pixels {
+            data: self.data,
+            left: 0,
+            repeat: None,
+        }
+    }
+}
+
+impl OriginDimensions for Asset {
+    fn size(&self) -> Size {
+        Size::new(self.width, self.height)
+    }
+}
+
+impl ImageDrawable for Asset {
+    type Color = Rgb565;
+
+    fn draw<D>(&self, target: &mut D) -> Result<(), D::Error>
+    where
+        D: DrawTarget<Color = Rgb565>,
+    {
+        target.fill_contiguous(&self.bounding_box(), self.pixels())
+    }
+
+    fn draw_sub_image<D>(&self, target: &mut D, area: &Rectangle) -> Result<(), D::Error>
+    where
+        D: DrawTarget<Color = Rgb565>,
+    {
+        // Still has to decode everything up to the end of the area
+        let width = self.width as usize;
+        let pixels = self
+            .pixels()
+            .enumerate()
+            .take(area.bottom_right().map_or(0, |end| (end.y as usize + 1) * width))
+            .map(|(i, color)| Pixel(Point::new((i % width) as i32, (i / width) as i32), color))
+            .filter(|Pixel(point, _)| area.contains(*point))
+            .map(|Pixel(point, color)| Pixel(point - area.top_left, color));
+        target.draw_iter(pixels)
+    }
+}
+
+/// Frames of the same size, shown one after the other
+pub struct Animation {
+    width: u32,
+    height: u32,
+    /// How long each frame is shown for, unless the player is told otherwise
+    pub frame_time_us: u32,
+    frames: &'static [Asset],
+}
+
+impl Animation {
+    pub const fn new(width: u32, height: u32, frame_time_us: u32, frames: &'static [Asset]) -> Animation {
+        Animation {
+            width,
+            height,
+            frame_time_us,
+            frames,
+        }
+    }
+
+    pub fn frames(&self) -> &'static [Asset] {
+        self.frames
+    }
+}
+
+impl OriginDimensions for Animation {
+    fn size(&self) -> Size {
+        Size::new(self.width, self.height)
+    }
+}
+
+/// Decodes the packets build.rs writes, see rle.rs
+pub struct Pixels {
+    data: &'static [u8],
+    /// Pixels left in the current packet
+    left: u8,
+    repeat: Option<Rgb565>,
+}
+
+impl Pixels {
+    fn read(&mut self) -> Option<Rgb565> {
+        let (pixel, rest) = (self.data.get(..2)?, &self.data[2..]);
+        self.data = rest;
+        Some(RawU16::new(u16::from_le_bytes([pixel[0], pixel[1]])).into())
+    }
+}
+
+impl Iterator for Pixels {
+    type Item = Rgb565;
+
+    fn next(&mut self) -> Option<Rgb565> {
+        if self.left == 0 {
+            let (&header, rest) = self.data.split_first()?;
+            self.data = rest;
+            self.left = (header & 0x7f) + 1;
+            self.repeat = match header & rle::REPEAT {
+                0 => None,
+                _ => Some(self.read()?),
+            };
+        }
+        self.left -= 1;
+        match self.repeat {
+            Some(color) => Some(color),
+            None => self.read(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate std;
+
+    use std::vec::Vec;
+
+    use super::*;
+
+    fn round_trip(pixels: &[u16]) -> Vec<u8> {
+        let mut encoded = Vec::new();
+        rle::encode(pixels, |byte| encoded.push(byte));
+        let asset = Asset::new(pixels.len() as u32, 1, encoded.clone().leak());
+        let decoded: Vec<u16> = asset.pixels().map(|color| RawU16::from(color).into_inner()).collect();
+        assert_eq!(decoded, pixels);
+        encoded
+    }
+
+    #[test]
+    fn runs_and_literals_round_trip() {
+        let mut pixels = Vec::new();
+        // Longer than a packet, a run of two, a run of three
+        pixels.extend([0x07e0; 300]);
+        pixels.extend([0xf800, 0xf800, 0x001f, 0x001f, 0x001f]);
+        // Longer than a packet with nothing repeated, then one on its own
+        pixels.extend((0..200).map(|i| i * 0x0101));
+        pixels.push(0xffff);
+        round_trip(&pixels);
+    }
+
+    #[test]
+    fn runs_are_packed() {
+        // A whole packet in three bytes, and two alike aren't worth a
+        // packet of their own
+        let packed = [rle::REPEAT | 127, 0x34, 0x12, 1, 0x34, 0x12, 0x34, 0x12];
+        assert_eq!(round_trip(&[0x1234; 130]), packed);
+        assert_eq!(round_trip(&[1, 1, 2]), [2, 1, 0, 1, 0, 2, 0]);
+        assert_eq!(round_trip(&[]), []);
+    }
+
+    #[test]
+    fn built_assets_decode_to_their_size() {
+        for asset in [FERRIS].iter().chain(FERRIS_HOP.frames()) {
+            let size = asset.size();
+            assert_eq!(asset.pixels().count(), (size.width * size.height) as usize);
+        }
+    }
+}
@@ -0,0 +1,215 @@
+//! Confirmation for the `Bootloader` key, so a stray press doesn't drop
+//! the board into the USB bootloader. Holding the key counts down to the
+//! reset, a tap asks for a second press, and letting go early or waiting
+//! too long calls it off.
+//!
+//! `BootloaderConfirm` only sees presses, releases and the time it's
+//! given: the firmware feeds it the key's custom action events and
+//! resets when it says to, `keezus-sim` does the same without the reset.
+
+use core::fmt;
+
+/// Holding the key this long resets into the bootloader
+pub const HOLD_US: u64 = 3_000_000;
+/// Let go sooner than this and it's a tap, which wants a second press
+pub const TAP_US: u64 = 300_000;
+/// Time after a tap for the second press, as long as the notification
+/// asking for it stays up
+pub const WINDOW_US: u64 = crate::screens::NOTIFICATION_US;
+
+/// What to tell whoever pressed the key
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Prompt {
+    /// Held, the reset comes in this many seconds unless it's let go
+    Countdown(u8),
+    /// Tapped, another press within `WINDOW_US` confirms
+    PressAgain,
+    Cancelled,
+    /// Confirmed, reset now
+    Reboot,
+}
+
+impl fmt::Display for Prompt {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            // "Bootloader in 3…", then "3…2…" and "3…2…1"
+            Prompt::Countdown(left) => {
+                f.write_str("Bootloader in ")?;
+                for second in (left.max(1)..=HOLD_US.div_ceil(1_000_000) as u8).rev() {
+                    write!(f, "{}", second)?;
+                    if second > 1 {
+                        f.write_str("…")?;
+                    }
+                }
+                Ok(())
+            }
+            Prompt::PressAgain => f.write_str("Press again for bootloader"),
+            Prompt::Cancelled => f.write_str("Bootloader cancelled"),
+            Prompt::Reboot => f.write_str("Rebooting to bootloader"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum State {
+    Idle,
+    Held { since_us: u64 },
+    Tapped { at_us: u64 },
+    Confirmed,
+    Cancelled,
+}
+
+pub struct BootloaderConfirm {
+    state: State,
+    /// The prompt `update` last handed out
+    shown: Option<Prompt>,
+}
+
+impl BootloaderConfirm {
+    pub const fn new() -> BootloaderConfirm {
+        BootloaderConfirm {
+            state: State::Idle,
+            shown: None,
+        }
+    }
+
+    /// The key went down
+    pub fn press(&mut self, now_us: u64) {
+        self.state = match self.state {
+            State::Tapped { at_us } if now_us < at_us + WINDOW_US => State::Confirmed,
+            State::Idle | State::Tapped { .. } | State::Cancelled => State::Held { since_us: now_us },
+            state => state,
+        };
+    }
+
+    /// The key came back up
+    pub fn release(&mut self, now_us: u64) {
+        if let State::Held { since_us } = self.state {
+            self.state = if now_us < since_us + TAP_US {
+                State::Tapped { at_us: now_us }
+            } else {
+                State::Cancelled
+            };
+        }
+    }
+
+    /// Moves the countdown along, call after every press and release and
+    /// regularly in between. Returns the prompt when it changes,
+    /// `Prompt::Reboot` meaning it's time to reset.
+    pub fn update(&mut self, now_us: u64) -> Option<Prompt> {
+        let prompt = match self.state {
+            State::Idle => None,
+            State::Held { since_us } if now_us >= since_us + HOLD_US => {
+                self.state = State::Idle;
+                Some(Prompt::Reboot)
+            }
+            State::Held { since_us } => {
+                let left_us = since_us + HOLD_US - now_us;
+                Some(Prompt::Countdown(left_us.div_ceil(1_000_000) as u8))
+            }
+            State::Tapped { at_us } if now_us >= at_us + WINDOW_US => {
+                self.state = State::Idle;
+                Some(Prompt::Cancelled)
+            }
+            State::Tapped { .. } => Some(Prompt::PressAgain),
+            State::Confirmed => {
+                self.state = State::Idle;
+                Some(Prompt::Reboot)
+            }
+            State::Cancelled => {
+                self.state = State::Idle;
+                Some(Prompt::Cancelled)
+            }
+        };
+        let changed = prompt != self.shown;
+        self.shown = prompt;
+        prompt.filter(|_| changed)
+    }
+}
+
+impl Default for BootloaderConfirm {
+    fn default() -> BootloaderConfirm {
+        BootloaderConfirm::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate std;
+
+    use std::string::ToString;
+
+    use super::*;
+
+    #[test]
+    fn holding_counts_down_to_the_reset() {
+        let mut confirm = BootloaderConfirm::new();
+        assert_eq!(confirm.update(0), None);
+        confirm.press(0);
+        assert_eq!(confirm.update(0), Some(Prompt::Countdown(3)));
+        // Only handed out when it changes
+        assert_eq!(confirm.update(999_999), None);
+        assert_eq!(confirm.update(1_000_000), Some(Prompt::Countdown(2)));
+        assert_eq!(confirm.update(2_000_000), Some(Prompt::Countdown(1)));
+        assert_eq!(confirm.update(HOLD_US - 1), None);
+        assert_eq!(confirm.update(HOLD_US), Some(Prompt::Reboot));
+        assert_eq!(confirm.update(HOLD_US + 1_000), None);
+    }
+
+    #[test]
+    fn letting_go_early_cancels() {
+        let mut confirm = BootloaderConfirm::new();
+        confirm.press(0);
+        confirm.update(0);
+        confirm.release(TAP_US);
+        assert_eq!(confirm.update(TAP_US), Some(Prompt::Cancelled));
+        assert_eq!(confirm.update(HOLD_US), None);
+        assert_eq!(confirm.update(HOLD_US * 2), None);
+    }
+
+    #[test]
+    fn a_second_press_confirms_a_tap() {
+        let mut confirm = BootloaderConfirm::new();
+        confirm.press(0);
+        confirm.release(TAP_US - 1);
+        assert_eq!(confirm.update(TAP_US - 1), Some(Prompt::PressAgain));
+        assert_eq!(confirm.update(WINDOW_US / 2), None);
+        confirm.press(TAP_US - 1 + WINDOW_US - 1);
+        assert_eq!(confirm.update(TAP_US + WINDOW_US), Some(Prompt::Reboot));
+    }
+
+    #[test]
+    fn a_late_second_press_starts_over() {
+        let mut confirm = BootloaderConfirm::new();
+        confirm.press(0);
+        confirm.release(100_000);
+        confirm.update(100_000);
+        // Not updated in between, so the tap hasn't timed out yet
+        let late = 100_000 + WINDOW_US;
+        confirm.press(late);
+        assert_eq!(confirm.update(late), Some(Prompt::Countdown(3)));
+        confirm.release(late + 100_000);
+        assert_eq!(confirm.update(late + 100_000), Some(Prompt::PressAgain));
+    }
+
+    #[test]
+    fn an_unanswered_tap_times_out() {
+        let mut confirm = BootloaderConfirm::new();
+        confirm.press(0);
+        confirm.release(100_000);
+        assert_eq!(confirm.update(100_000), Some(Prompt::PressAgain));
+        assert_eq!(confirm.update(100_000 + WINDOW_US), Some(Prompt::Cancelled));
+        assert_eq!(confirm.update(100_000 + WINDOW_US * 2), None);
+        // And a press after that is a fresh start, not a confirmation
+        confirm.press(200_000 + WINDOW_US * 2);
+        assert_eq!(confirm.update(200_000 + WINDOW_US * 2), Some(Prompt::Countdown(3)));
+    }
+
+    #[test]
+    fn prompts() {
+        assert_eq!(Prompt::Countdown(3).to_string(), "Bootloader in 3…");
+        assert_eq!(Prompt::Countdown(2).to_string(), "Bootloader in 3…2…");
+        assert_eq!(Prompt::Countdown(1).to_string(), "Bootloader in 3…2…1");
+        assert_eq!(Prompt::PressAgain.to_string(), "Press again for bootloader");
+    }
+}
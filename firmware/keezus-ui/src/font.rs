@@ -0,0 +1,471 @@
+//! Proportional bitmap fonts, built from `fonts/` by build.rs.
+//!
+//! Each font in `fonts/fonts.txt` becomes a constant here (`REGULAR`,
+//! `BOLD`) holding only the glyphs that can be drawn: printable ASCII,
+//! every character that appears in this crate's source, and whatever the
+//! manifest asks for. A font can be made of several BDF files,
+//! so a Latin font and a Japanese one together cover strings like
+//! "! システム". Characters that didn't make it in are drawn as U+FFFD.
+//!
+//! `FontStyle` is an `embedded_graphics` text renderer, so it works with
+//! `Text` like the mono fonts do. `TextBox` adds word wrapping and
+//! alignment within a rectangle.
+
+use embedded_graphics::prelude::*;
+use embedded_graphics::primitives::Rectangle;
+use embedded_graphics::text::renderer::{TextMetrics, TextRenderer};
+use embedded_graphics::text::{Alignment, Baseline};
+
+mod generated {
+    use super::{Font, Glyph};
+    include!(concat!(env!("OUT_DIR"), "/fonts.rs"));
+}
+pub use generated::*;
+
+#[derive(Clone, Copy)]
+pub struct Glyph {
+    c: char,
+    /// Bit offset of the first pixel in the font's bitmap
+    offset: u32,
+    width: u8,
+    height: u8,
+    /// Top left of the pixels from the pen position on the baseline
+    x: i8,
+    y: i8,
+    advance: u8,
+}
+
+impl Glyph {
+    pub const fn new(c: char, offset: u32, width: u8, height: u8, x: i8, y: i8, advance: u8) -> Glyph {
+        Glyph {
+            c,
+            offset,
+            width,
+            height,
+            x,
+            y,
+            advance,
+        }
+    }
+}
+
+pub struct Font {
+    ascent: u32,
+    descent: u32,
+    /// Sorted by character
+    glyphs: &'static [Glyph],
+    /// One bit per pixel, each glyph's row by row, first pixel in the top bit
+    bitmap: &'static [u8],
+}
+
+impl Font {
+    pub const fn new(ascent: u32, descent: u32, glyphs: &'static [Glyph], bitmap: &'static [u8]) -> Font {
+        Font {
+            ascent,
+            descent,
+            glyphs,
+            bitmap,
+        }
+    }
+
+    pub fn line_height(&self) -> u32 {
+        self.ascent + self.descent
+    }
+
+    /// The glyph for `c`, or the replacement character if the font doesn't
+    /// have one
+    pub fn glyph(&self, c: char) -> &Glyph {
+        self.find(c)
+            .or_else(|| self.find(char::REPLACEMENT_CHARACTER))
+            .or_else(|| self.find('?'))
+            .unwrap_or(&self.glyphs[0])
+    }
+
+    fn find(&self, c: char) -> Option<&Glyph> {
+        self.glyphs.binary_search_by_key(&c, |g| g.c).ok().map(|i| &self.glyphs[i])
+    }
+
+    /// How far a line of text moves the pen
+    pub fn width(&self, text: &str) -> u32 {
+        text.chars().map(|c| self.glyph(c).advance as u32).sum()
+    }
+
+    fn pixel(&self, glyph: &Glyph, x: u32, y: u32) -> bool {
+        let bit = (glyph.offset + y * glyph.width as u32 + x) as usize;
+        self.bitmap.get(bit / 8).is_some_and(|byte| byte & (0x80 >> (bit % 8)) != 0)
+    }
+
+    /// Splits `text` into lines at most `max_width` pixels wide, see `Lines`
+    pub fn lines<'a>(&'static self, text: &'a str, max_width: u32) -> Lines<'a> {
+        Lines {
+            font: self,
+            rest: text,
+            max_width,
+        }
+    }
+}
+
+/// A font and colours, for drawing with `embedded_graphics::text::Text`
+#[derive(Clone, Copy)]
+pub struct FontStyle<C> {
+    pub font: &'static Font,
+    pub text_color: C,
+    /// Fills each character's cell behind the text when set
+    pub background_color: Option<C>,
+}
+
+impl<C: PixelColor> FontStyle<C> {
+    pub const fn new(font: &'static Font, text_color: C) -> FontStyle<C> {
+        FontStyle {
+            font,
+            text_color,
+            background_color: None,
+        }
+    }
+
+    pub const fn with_background(self, background_color: C) -> FontStyle<C> {
+        FontStyle {
+            background_color: Some(background_color),
+            ..self
+        }
+    }
+
+    /// The top of the line for a position with the given baseline
+    fn line_top(&self, position: Point, baseline: Baseline) -> i32 {
+        let height = self.font.line_height() as i32;
+        position.y
+            - match baseline {
+                Baseline::Top => 0,
+                Baseline::Bottom => height - 1,
+                Baseline::Middle => (height - 1) / 2,
+                // The bottom row of capital letters, like the mono fonts
+                Baseline::Alphabetic => self.font.ascent as i32 - 1,
+            }
+    }
+}
+
+impl<C: PixelColor> TextRenderer for FontStyle<C> {
+    type Color = C;
+
+    fn draw_string<D>(&self, text: &str, position: Point, baseline: Baseline, target: &mut D) -> Result<Point, D::Error>
+    where
+        D: DrawTarget<Color = C>,
+    {
+        let top = self.line_top(position, baseline);
+        let baseline_y = top + self.font.ascent as i32;
+        let mut x = position.x;
+        for c in text.chars() {
+            let glyph = self.font.glyph(c);
+            if let Some(background) = self.background_color {
+                let cell = Rectangle::new(Point::new(x, top), Size::new(glyph.advance as u32, self.font.line_height()));
+                target.fill_solid(&cell, background)?;
+            }
+            let origin = Point::new(x + glyph.x as i32, baseline_y + glyph.y as i32);
+            let pixels = (0..glyph.height as u32)
+                .flat_map(|y| (0..glyph.width as u32).map(move |x| (x, y)))
+                .filter(|&(x, y)| self.font.pixel(glyph, x, y))
+                .map(|(x, y)| Pixel(origin + Point::new(x as i32, y as i32), self.text_color));
+            target.draw_iter(pixels)?;
+            x += glyph.advance as i32;
+        }
+        Ok(Point::new(x, position.y))
+    }
+
+    fn draw_whitespace<D>(&self, width: u32, position: Point, baseline: Baseline, target: &mut D) -> Result<Point, D::Error>
+    where
+        D: DrawTarget<Color = C>,
+    {
+        if let Some(background) = self.background_color {
+            let top = self.line_top(position, baseline);
+            let area = Rectangle::new(Point::new(position.x, top), Size::new(width, self.font.line_height()));
+            target.fill_solid(&area, background)?;
+        }
+        Ok(position + Point::new(width as i32, 0))
+    }
+
+    fn measure_string(&self, text: &str, position: Point, baseline: Baseline) -> TextMetrics {
+        let width = self.font.width(text);
+        let top_left = Point::new(position.x, self.line_top(position, baseline));
+        TextMetrics {
+            bounding_box: Rectangle::new(top_left, Size::new(width, self.font.line_height())),
+            next_position: position + Point::new(width as i32, 0),
+        }
+    }
+
+    fn line_height(&self) -> u32 {
+        self.font.line_height()
+    }
+}
+
+/// Splits text into lines that fit a width. Lines break at spaces and
+/// newlines, and between full-width characters, which Japanese doesn't
+/// put spaces between. Closing brackets and punctuation stay with the
+/// character before them. A word too long for a line of its own is broken
+/// wherever it runs out of room.
+pub struct Lines<'a> {
+    font: &'static Font,
+    rest: &'a str,
+    max_width: u32,
+}
+
+impl<'a> Iterator for Lines<'a> {
+    type Item = &'a str;
+
+    fn next(&mut self) -> Option<&'a str> {
+        if self.rest.is_empty() {
+            return None;
+        }
+        let text = self.rest;
+        let mut width = 0;
+        // Where the line would end and the next start if broken here
+        let mut last_break: Option<(usize, usize)> = None;
+        let mut previous: Option<char> = None;
+
+        for (i, c) in text.char_indices() {
+            if c == '\n' {
+                self.rest = &text[i + 1..];
+                return Some(text[..i].trim_end_matches(' '));
+            }
+            let advance = self.font.glyph(c).advance as u32;
+            if c == ' ' {
+                if i > 0 {
+                    last_break = Some((i, i + 1));
+                }
+                width += advance;
+                previous = Some(c);
+                continue;
+            }
+            if previous.is_some_and(|p| p != ' ' && can_break_between(p, c)) {
+                last_break = Some((i, i));
+            }
+            if width + advance > self.max_width && i > 0 {
+                let (end, next) = last_break.unwrap_or((i, i));
+                self.rest = text[next..].trim_start_matches(' ');
+                return Some(text[..end].trim_end_matches(' '));
+            }
+            width += advance;
+            previous = Some(c);
+        }
+        self.rest = "";
+        Some(text.trim_end_matches(' '))
+    }
+}
+
+fn is_wide(c: char) -> bool {
+    matches!(c as u32, 0x2e80..=0x9fff | 0xac00..=0xd7a3 | 0xf900..=0xfaff | 0xff00..=0xff60)
+}
+
+fn can_break_between(before: char, after: char) -> bool {
+    const NO_BREAK_BEFORE: &str = "、。，．」』）】〉》ー・ッャュョァィゥェォっゃゅょぁぃぅぇぉ！？!?,.:;)]}";
+    const NO_BREAK_AFTER: &str = "「『（【〈《([{";
+    (is_wide(before) || is_wide(after)) && !NO_BREAK_BEFORE.contains(after) && !NO_BREAK_AFTER.contains(before)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum VerticalAlignment {
+    Top,
+    Middle,
+    Bottom,
+}
+
+/// Text wrapped to fit a rectangle and aligned within it. Lines that don't
+/// fit below the rectangle are cut off.
+pub struct TextBox<'a, C> {
+    pub text: &'a str,
+    pub bounds: Rectangle,
+    pub style: FontStyle<C>,
+    pub alignment: Alignment,
+    pub vertical_alignment: VerticalAlignment,
+}
+
+impl<'a, C: PixelColor> TextBox<'a, C> {
+    /// Top left aligned
+    pub fn new(text: &'a str, bounds: Rectangle, style: FontStyle<C>) -> TextBox<'a, C> {
+        TextBox {
+            text,
+            bounds,
+            style,
+            alignment: Alignment::Left,
+            vertical_alignment: VerticalAlignment::Top,
+        }
+    }
+
+    pub fn with_alignment(self, alignment: Alignment, vertical_alignment: VerticalAlignment) -> TextBox<'a, C> {
+        TextBox {
+            alignment,
+            vertical_alignment,
+            ..self
+        }
+    }
+
+    fn lines(&self) -> Lines<'a> {
+        self.style.font.lines(self.text, self.bounds.size.width)
+    }
+}
+
+impl<C: PixelColor> Drawable for TextBox<'_, C> {
+    type Color = C;
+    type Output = ();
+
+    fn draw<D>(&self, target: &mut D) -> Result<(), D::Error>
+    where
+        D: DrawTarget<Color = C>,
+    {
+        let line_height = self.style.font.line_height() as i32;
+        let text_height = self.lines().count() as i32 * line_height;
+        let free = self.bounds.size.height as i32 - text_height;
+        let mut y = self.bounds.top_left.y
+            + match self.vertical_alignment {
+                VerticalAlignment::Top => 0,
+                VerticalAlignment::Middle => free / 2,
+                VerticalAlignment::Bottom => free,
+            };
+
+        let mut target = target.clipped(&self.bounds);
+        for line in self.lines() {
+            let free = self.bounds.size.width as i32 - self.style.font.width(line) as i32;
+            let x = self.bounds.top_left.x
+                + match self.alignment {
+                    Alignment::Left => 0,
+                    Alignment::Center => free / 2,
+                    Alignment::Right => free,
+                };
+            self.style.draw_string(line, Point::new(x, y), Baseline::Top, &mut target)?;
+            y += line_height;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate std;
+
+    use core::convert::Infallible;
+    use std::vec::Vec;
+
+    use embedded_graphics::pixelcolor::BinaryColor;
+
+    use super::*;
+
+    fn lines(text: &str, max_width: u32) -> Vec<&str> {
+        REGULAR.lines(text, max_width).collect()
+    }
+
+    #[test]
+    fn breaks_at_a_space() {
+        let max = REGULAR.width("hello world") - 1;
+        assert_eq!(lines("hello world", max), ["hello", "world"]);
+        assert_eq!(lines("hello world", max + 1), ["hello world"]);
+        // Spaces at the break go with neither line
+        assert_eq!(lines("hello   world", REGULAR.width("hello ")), ["hello", "world"]);
+    }
+
+    #[test]
+    fn breaks_a_word_longer_than_the_line() {
+        let max = REGULAR.width("abcd");
+        let wrapped = lines("go abcdefghij", max);
+        assert_eq!(wrapped[0], "go");
+        assert_eq!(wrapped[1], "abcd");
+        assert!(wrapped.iter().all(|line| REGULAR.width(line) <= max));
+        assert_eq!(wrapped[1..].concat(), "abcdefghij");
+    }
+
+    #[test]
+    fn breaks_at_newlines() {
+        assert_eq!(lines("ab\ncd", 100), ["ab", "cd"]);
+        assert_eq!(lines("ab \n\ncd", 100), ["ab", "", "cd"]);
+        assert_eq!(lines("", 100), Vec::<&str>::new());
+    }
+
+    #[test]
+    fn breaks_between_full_width_characters() {
+        let max = REGULAR.width("シス");
+        assert_eq!(lines("システム", max), ["シス", "テム"]);
+    }
+
+    #[test]
+    fn keeps_closing_punctuation_with_the_character_before() {
+        // 。 doesn't start a line, so ム goes down with it
+        assert_eq!(lines("システム。", REGULAR.width("システム")), ["システ", "ム。"]);
+        assert_eq!(lines("ニパーム", REGULAR.width("ニパ")), ["ニ", "パー", "ム"]);
+    }
+
+    #[test]
+    fn keeps_opening_brackets_with_the_character_after() {
+        assert_eq!(lines("ニ「クシ」", REGULAR.width("ニ「")), ["ニ", "「ク", "シ」"]);
+    }
+
+    /// Where a `TextBox` put its ink
+    struct Ink(Option<Rectangle>);
+
+    impl OriginDimensions for Ink {
+        fn size(&self) -> Size {
+            Size::new(200, 200)
+        }
+    }
+
+    impl DrawTarget for Ink {
+        type Color = BinaryColor;
+        type Error = Infallible;
+
+        fn draw_iter<I>(&mut self, pixels: I) -> Result<(), Infallible>
+        where
+            I: IntoIterator<Item = Pixel<BinaryColor>>,
+        {
+            for Pixel(point, _) in pixels {
+                let pixel = Rectangle::new(point, Size::new(1, 1));
+                self.0 = Some(match self.0 {
+                    Some(ink) => Rectangle::with_corners(
+                        ink.top_left.component_min(point),
+                        ink.bottom_right().unwrap().component_max(point),
+                    ),
+                    None => pixel,
+                });
+            }
+            Ok(())
+        }
+    }
+
+    const BOUNDS: Rectangle = Rectangle::new(Point::new(10, 20), Size::new(100, 40));
+
+    fn ink(text: &str, alignment: Alignment, vertical_alignment: VerticalAlignment) -> Rectangle {
+        let mut target = Ink(None);
+        TextBox::new(text, BOUNDS, FontStyle::new(&REGULAR, BinaryColor::On))
+            .with_alignment(alignment, vertical_alignment)
+            .draw(&mut target)
+            .unwrap();
+        target.0.unwrap()
+    }
+
+    #[test]
+    fn aligns_lines_across_the_box() {
+        let left = ink("Hi", Alignment::Left, VerticalAlignment::Top);
+        assert_eq!(left.top_left.x, BOUNDS.top_left.x);
+        let free = BOUNDS.size.width as i32 - REGULAR.width("Hi") as i32;
+        let center = ink("Hi", Alignment::Center, VerticalAlignment::Top);
+        assert_eq!(center.top_left.x - left.top_left.x, free / 2);
+        let right = ink("Hi", Alignment::Right, VerticalAlignment::Top);
+        assert_eq!(right.top_left.x - left.top_left.x, free);
+        assert_eq!((center.size, right.size), (left.size, left.size));
+    }
+
+    #[test]
+    fn aligns_text_down_the_box() {
+        let top = ink("Hi\nthere", Alignment::Left, VerticalAlignment::Top);
+        assert!(top.top_left.y >= BOUNDS.top_left.y);
+        assert!(top.top_left.y < BOUNDS.top_left.y + REGULAR.ascent as i32);
+        let free = BOUNDS.size.height as i32 - 2 * REGULAR.line_height() as i32;
+        let middle = ink("Hi\nthere", Alignment::Left, VerticalAlignment::Middle);
+        assert_eq!(middle.top_left.y - top.top_left.y, free / 2);
+        let bottom = ink("Hi\nthere", Alignment::Left, VerticalAlignment::Bottom);
+        assert_eq!(bottom.top_left.y - top.top_left.y, free);
+    }
+
+    #[test]
+    fn cuts_off_lines_below_the_box() {
+        let text = "one\ntwo\nthree\nfour\nfive";
+        let drawn = ink(text, Alignment::Left, VerticalAlignment::Top);
+        assert!(drawn.bottom_right().unwrap().y <= BOUNDS.bottom_right().unwrap().y);
+    }
+}
@@ -0,0 +1,348 @@
+//! An off-screen copy of the display that screens draw into. Only pixels
+//! that actually change mark their tile dirty, and just the dirty tiles are
+//! sent to the panel, neighbouring ones merged into one window. On the
+//! board the firmware's lcd_dma.rs does the sending, `flush` is the
+//! blocking equivalent.
+//!
+//! The size follows the display's orientation, 128x160 in portrait and
+//! 160x128 in landscape, see `resize`.
+
+use core::convert::Infallible;
+
+use embedded_graphics::pixelcolor::raw::RawU16;
+use embedded_graphics::pixelcolor::Rgb565;
+use embedded_graphics::prelude::*;
+use embedded_graphics::primitives::Rectangle;
+
+/// The panel in portrait, its native orientation
+pub const WIDTH: usize = 128;
+pub const HEIGHT: usize = 160;
+pub const TILE_SIZE: usize = 16;
+/// Tiles in either orientation, one bit each in `dirty`
+const TILES: usize = (WIDTH / TILE_SIZE) * (HEIGHT / TILE_SIZE);
+/// Column and row address plus memory write commands sent before each
+/// window's pixels
+pub const WINDOW_OVERHEAD_BYTES: u32 = 3 + 2 * 4;
+
+/// Something a rectangle of pixels can be written to, in rows from the top
+/// left corner. Coordinates are inclusive.
+pub trait PixelSink {
+    type Error;
+
+    fn set_pixels<P>(&mut self, sx: u16, sy: u16, ex: u16, ey: u16, colors: P) -> Result<(), Self::Error>
+    where
+        P: IntoIterator<Item = u16>;
+}
+
+/// Blanks the whole panel, `width` x `height` in its current orientation
+pub fn clear_panel<S: PixelSink>(sink: &mut S, width: usize, height: usize) -> Result<(), S::Error> {
+    let (ex, ey) = (width as u16 - 1, height as u16 - 1);
+    sink.set_pixels(0, 0, ex, ey, core::iter::repeat_n(0, width * height))
+}
+
+/// The most pixels a single window can cover, a full row of tiles in
+/// landscape
+pub const MAX_WINDOW_PIXELS: usize = HEIGHT * TILE_SIZE;
+
+/// A run of dirty tiles, in pixels
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Window {
+    pub x: usize,
+    pub y: usize,
+    pub width: usize,
+    pub height: usize,
+}
+
+impl Window {
+    pub fn pixel_count(&self) -> usize {
+        self.width * self.height
+    }
+
+    /// Sets the panel's address window to this one, ready for pixel data
+    pub fn select<S: PixelSink>(&self, sink: &mut S) -> Result<(), S::Error> {
+        let (ex, ey) = (self.x + self.width - 1, self.y + self.height - 1);
+        sink.set_pixels(self.x as u16, self.y as u16, ex as u16, ey as u16, core::iter::empty())
+    }
+}
+
+/// What one `flush` sent to the panel
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct FlushStats {
+    pub windows: u32,
+    /// Including the commands that set up each window
+    pub bytes: u32,
+}
+
+impl FlushStats {
+    pub fn add(&mut self, window: Window) {
+        self.windows += 1;
+        self.bytes += WINDOW_OVERHEAD_BYTES + window.pixel_count() as u32 * 2;
+    }
+}
+
+pub struct FrameBuffer {
+    pixels: [u16; WIDTH * HEIGHT],
+    width: usize,
+    height: usize,
+    tile_cols: usize,
+    /// One bit per tile, row by row
+    dirty: u128,
+}
+
+impl Default for FrameBuffer {
+    fn default() -> FrameBuffer {
+        FrameBuffer::new()
+    }
+}
+
+impl FrameBuffer {
+    /// In portrait, all black like the display once it's been cleared
+    pub const fn new() -> FrameBuffer {
+        FrameBuffer {
+            pixels: [0; WIDTH * HEIGHT],
+            width: WIDTH,
+            height: HEIGHT,
+            tile_cols: WIDTH / TILE_SIZE,
+            dirty: 0,
+        }
+    }
+
+    /// Marks everything dirty, for when something else has drawn on the
+    /// display
+    pub fn invalidate(&mut self) {
+        self.dirty = (1 << TILES) - 1;
+    }
+
+    /// Switches between portrait (`WIDTH` x `HEIGHT`) and landscape
+    /// (`HEIGHT` x `WIDTH`). What was drawn before is left scrambled, so
+    /// everything is marked dirty and should be redrawn.
+    pub fn resize(&mut self, width: usize, height: usize) {
+        assert!((width, height) == (WIDTH, HEIGHT) || (width, height) == (HEIGHT, WIDTH));
+        self.width = width;
+        self.height = height;
+        self.tile_cols = width / TILE_SIZE;
+        self.invalidate();
+    }
+
+    fn set(&mut self, x: usize, y: usize, color: u16) {
+        let pixel = &mut self.pixels[y * self.width + x];
+        if *pixel != color {
+            *pixel = color;
+            self.dirty |= 1 << (y / TILE_SIZE * self.tile_cols + x / TILE_SIZE);
+        }
+    }
+
+    pub fn is_clean(&self) -> bool {
+        self.dirty == 0
+    }
+
+    /// Finds the topmost run of dirty tiles and marks it clean, the caller
+    /// is expected to send it
+    pub fn take_dirty_window(&mut self) -> Option<Window> {
+        if self.dirty == 0 {
+            return None;
+        }
+        let first = self.dirty.trailing_zeros() as usize;
+        let (tile_row, first_col) = (first / self.tile_cols, first % self.tile_cols);
+        let mut col = first_col;
+        while col < self.tile_cols && self.is_dirty(tile_row, col) {
+            self.dirty &= !(1 << (tile_row * self.tile_cols + col));
+            col += 1;
+        }
+        Some(Window {
+            x: first_col * TILE_SIZE,
+            y: tile_row * TILE_SIZE,
+            width: (col - first_col) * TILE_SIZE,
+            height: TILE_SIZE,
+        })
+    }
+
+    /// The pixels of `window`, row by row
+    pub fn window_pixels(&self, window: Window) -> impl Iterator<Item = u16> + '_ {
+        let width = self.width;
+        (window.y..window.y + window.height)
+            .flat_map(move |y| self.pixels[y * width + window.x..y * width + window.x + window.width].iter().copied())
+    }
+
+    /// Sends all the dirty tiles to `sink` straight away, without DMA
+    pub fn flush<S: PixelSink>(&mut self, sink: &mut S) -> Result<FlushStats, S::Error> {
+        let mut stats = FlushStats::default();
+        while let Some(window) = self.take_dirty_window() {
+            let (ex, ey) = (window.x + window.width - 1, window.y + window.height - 1);
+            sink.set_pixels(window.x as u16, window.y as u16, ex as u16, ey as u16, self.window_pixels(window))?;
+            stats.add(window);
+        }
+        Ok(stats)
+    }
+
+    fn is_dirty(&self, tile_row: usize, tile_col: usize) -> bool {
+        self.dirty & (1 << (tile_row * self.tile_cols + tile_col)) != 0
+    }
+}
+
+impl OriginDimensions for FrameBuffer {
+    fn size(&self) -> Size {
+        Size::new(self.width as u32, self.height as u32)
+    }
+}
+
+impl DrawTarget for FrameBuffer {
+    type Color = Rgb565;
+    type Error = Infallible;
+
+    fn draw_iter<I>(&mut self, pixels: I) -> Result<(), Self::Error>
+    where
+        I: IntoIterator<Item = Pixel<Self::Color>>,
+    {
+        for Pixel(point, color) in pixels {
+            if point.x >= 0 && point.y >= 0 && (point.x as usize) < self.width && (point.y as usize) < self.height {
+                self.set(point.x as usize, point.y as usize, RawU16::from(color).into_inner());
+            }
+        }
+        Ok(())
+    }
+
+    fn fill_solid(&mut self, area: &Rectangle, color: Self::Color) -> Result<(), Self::Error> {
+        let area = area.intersection(&self.bounding_box());
+        let color = RawU16::from(color).into_inner();
+        if let Some(bottom_right) = area.bottom_right() {
+            for y in area.top_left.y..=bottom_right.y {
+                for x in area.top_left.x..=bottom_right.x {
+                    self.set(x as usize, y as usize, color);
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use keezus_protocol::keymap::DEFAULT_KEYMAP;
+
+    use super::*;
+    use crate::legend::{self, KeymapLegends};
+    use crate::{ScreenId, Screens, UiEvent, UiState, DISPLAY_UPDATE_TIME_US};
+
+    static LEGENDS: KeymapLegends = legend::keymap(&DEFAULT_KEYMAP);
+
+    /// Every window in full, with its address commands
+    const FULL_FRAME_BYTES: u32 = (HEIGHT / TILE_SIZE) as u32 * WINDOW_OVERHEAD_BYTES + (WIDTH * HEIGHT * 2) as u32;
+
+    /// Counts what goes over SPI to the panel
+    #[derive(Default)]
+    struct Spi {
+        bytes: u32,
+    }
+
+    impl PixelSink for Spi {
+        type Error = Infallible;
+
+        fn set_pixels<P>(&mut self, _sx: u16, _sy: u16, _ex: u16, _ey: u16, colors: P) -> Result<(), Infallible>
+        where
+            P: IntoIterator<Item = u16>,
+        {
+            self.bytes += WINDOW_OVERHEAD_BYTES + colors.into_iter().count() as u32 * 2;
+            Ok(())
+        }
+    }
+
+    struct Board {
+        screens: Screens,
+        state: UiState,
+        framebuffer: FrameBuffer,
+    }
+
+    impl Board {
+        /// On the status screen, with its first frame sent
+        fn new() -> Board {
+            let mut board = Board {
+                screens: Screens::new(&LEGENDS),
+                state: UiState::new("QWERTY", 1),
+                framebuffer: FrameBuffer::new(),
+            };
+            board.screens.show(ScreenId::Status);
+            board.frame();
+            board
+        }
+
+        /// Draws a frame and sends it, returning what was sent
+        fn frame(&mut self) -> FlushStats {
+            self.state.now_us += DISPLAY_UPDATE_TIME_US;
+            self.screens.tick(&self.state, &mut self.framebuffer).unwrap();
+            let mut spi = Spi::default();
+            let stats = self.framebuffer.flush(&mut spi).unwrap();
+            assert_eq!(stats.bytes, spi.bytes);
+            stats
+        }
+    }
+
+    fn tiles(x: usize, y: usize, cols: usize) -> Rectangle {
+        let size = Size::new((cols * TILE_SIZE) as u32, TILE_SIZE as u32);
+        Rectangle::new(Point::new((x * TILE_SIZE) as i32, (y * TILE_SIZE) as i32), size)
+    }
+
+    #[test]
+    fn idle_frames_send_nothing() {
+        let mut board = Board::new();
+        for _ in 0..10 {
+            assert_eq!(board.frame(), FlushStats::default());
+        }
+    }
+
+    #[test]
+    fn a_key_sends_a_few_tiles() {
+        let mut board = Board::new();
+        board.state.apply(UiEvent::Key { row: 0, col: 0, pressed: true });
+        board.state.apply(UiEvent::KeyPressed("Q"));
+        // Just the tile the Q lands in under the last keys
+        let tile_bytes = WINDOW_OVERHEAD_BYTES + (TILE_SIZE * TILE_SIZE * 2) as u32;
+        assert_eq!(board.frame(), FlushStats { windows: 1, bytes: tile_bytes });
+        assert_eq!(board.frame(), FlushStats::default());
+    }
+
+    #[test]
+    fn full_redraw_sends_every_tile_once() {
+        let mut board = Board::new();
+        board.framebuffer.invalidate();
+        let stats = board.frame();
+        assert_eq!(stats, FlushStats { windows: (HEIGHT / TILE_SIZE) as u32, bytes: FULL_FRAME_BYTES });
+        assert_eq!(board.frame(), FlushStats::default());
+    }
+
+    #[test]
+    fn dirty_tiles_next_to_each_other_go_in_one_window() {
+        let mut framebuffer = FrameBuffer::new();
+        framebuffer.fill_solid(&tiles(1, 2, 3), Rgb565::WHITE).unwrap();
+        framebuffer.fill_solid(&tiles(6, 2, 1), Rgb565::WHITE).unwrap();
+        // Changing one pixel dirties its tile
+        Pixel(Point::new(0, (4 * TILE_SIZE) as i32), Rgb565::RED).draw(&mut framebuffer).unwrap();
+        // Drawing what's there already doesn't
+        framebuffer.fill_solid(&tiles(0, 7, 8), Rgb565::BLACK).unwrap();
+
+        let window = |x, y, cols: usize| Window {
+            x: x * TILE_SIZE,
+            y: y * TILE_SIZE,
+            width: cols * TILE_SIZE,
+            height: TILE_SIZE,
+        };
+        assert_eq!(framebuffer.take_dirty_window(), Some(window(1, 2, 3)));
+        assert_eq!(framebuffer.take_dirty_window(), Some(window(6, 2, 1)));
+        let last = framebuffer.take_dirty_window().unwrap();
+        assert_eq!(last, window(0, 4, 1));
+        assert_eq!(framebuffer.window_pixels(last).next(), Some(RawU16::from(Rgb565::RED).into_inner()));
+        assert_eq!(framebuffer.take_dirty_window(), None);
+        assert!(framebuffer.is_clean());
+    }
+
+    #[test]
+    fn landscape_tiles() {
+        let mut framebuffer = FrameBuffer::new();
+        framebuffer.resize(HEIGHT, WIDTH);
+        let mut spi = Spi::default();
+        let stats = framebuffer.flush(&mut spi).unwrap();
+        // A row of tiles is longer in landscape, and there are fewer rows
+        assert_eq!(stats.windows, (WIDTH / TILE_SIZE) as u32);
+        assert_eq!(stats.bytes, (WIDTH / TILE_SIZE) as u32 * WINDOW_OVERHEAD_BYTES + (WIDTH * HEIGHT * 2) as u32);
+    }
+}
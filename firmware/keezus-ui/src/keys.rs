@@ -0,0 +1,225 @@
+//! How core 0 routes keys: which presses reach the layout, which count
+//! towards the typing statistics and the pet, and what the screens are told
+//! about them. The firmware and keezus-sim both go through `KeyRouter`, the
+//! firmware with keyberon behind it and the sim with its own stand-in
+//! layout, so a key does the same on the simulated board as on the real one.
+//!
+//! In game mode presses go to the game on the display instead of the
+//! layout, so nothing is typed on the host. Releases still reach the layout,
+//! so keys held down as the game started don't get stuck, and so do presses
+//! of layer keys, so the game mode key can be reached on whichever layer
+//! it's on. Pressing it again leaves the game.
+
+use heapless::Vec;
+use keezus_protocol::keymap::{CUSTOM_GAME_MODE, LAYER_NAMES};
+use keezus_protocol::KeyAction;
+
+use crate::screens::Notification;
+use crate::{legend, UiEvent, NUM_COLS, NUM_ROWS};
+
+/// Usages the layout can hold down at once that are looked at, one per
+/// key on the matrix
+const MAX_HELD: usize = NUM_ROWS * NUM_COLS;
+
+/// Where a key event goes after the screens have heard about it
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Route {
+    /// On to the layout
+    pub layout: bool,
+    /// A press that counts as typing, for the statistics and the pet
+    pub count: bool,
+    /// On to host tools streaming key events
+    pub host: bool,
+    /// The press left game mode, so the high score wants saving
+    pub left_game: bool,
+}
+
+pub struct KeyRouter {
+    game_mode: bool,
+    /// What the screens were last told
+    layer: usize,
+    mods: u8,
+    caps_lock: bool,
+    /// Usages the layout held down last time, to spot new presses
+    held: Vec<u8, MAX_HELD>,
+}
+
+impl Default for KeyRouter {
+    fn default() -> KeyRouter {
+        KeyRouter::new()
+    }
+}
+
+impl KeyRouter {
+    pub const fn new() -> KeyRouter {
+        KeyRouter {
+            game_mode: false,
+            layer: 0,
+            mods: 0,
+            caps_lock: false,
+            held: Vec::new(),
+        }
+    }
+
+    pub fn game_mode(&self) -> bool {
+        self.game_mode
+    }
+
+    pub fn enter_game(&mut self, mut send: impl FnMut(UiEvent)) {
+        self.game_mode = true;
+        send(UiEvent::GameMode(true));
+    }
+
+    pub fn leave_game(&mut self, mut send: impl FnMut(UiEvent)) {
+        self.game_mode = false;
+        send(UiEvent::GameMode(false));
+    }
+
+    /// A switch on the matrix, after debouncing. `action` is the keymap
+    /// entry under it on the layer the layout is on.
+    pub fn key(&mut self, action: KeyAction, row: u8, col: u8, pressed: bool, mut send: impl FnMut(UiEvent)) -> Route {
+        let in_game = self.game_mode;
+        let route = if in_game && pressed {
+            let left_game = action == KeyAction::Custom(CUSTOM_GAME_MODE);
+            if left_game {
+                self.leave_game(&mut send);
+            }
+            Route {
+                layout: matches!(action, KeyAction::Layer(_)),
+                count: false,
+                host: false,
+                left_game,
+            }
+        } else {
+            Route {
+                layout: true,
+                count: pressed,
+                host: !in_game,
+                left_game: false,
+            }
+        };
+        send(UiEvent::Key { row, col, pressed });
+        route
+    }
+
+    /// After every tick of the layout, with the usages it holds down and
+    /// the layer it's on. Tells the screens about whatever changed, and
+    /// the legends of keys that just went down.
+    pub fn layout_ticked<I>(&mut self, usages: I, layer: usize, caps_lock: bool, mut send: impl FnMut(UiEvent))
+    where
+        I: IntoIterator<Item = u8>,
+    {
+        let held: Vec<u8, MAX_HELD> = usages.into_iter().take(MAX_HELD).collect();
+        let mods = held.iter().filter_map(|&usage| legend::modifier_bit(usage)).fold(0, |m, bit| m | bit);
+        if layer != self.layer {
+            let name = LAYER_NAMES.get(layer).copied().unwrap_or("?");
+            send(UiEvent::Layer(layer, name));
+            send(UiEvent::Notify(Notification::Layer(name)));
+        }
+        if mods != self.mods {
+            send(UiEvent::Mods(mods));
+        }
+        if caps_lock != self.caps_lock {
+            send(UiEvent::CapsLock(caps_lock));
+            send(UiEvent::Notify(Notification::CapsLock(caps_lock)));
+        }
+        for &usage in held.iter().filter(|usage| !self.held.contains(usage)) {
+            if legend::modifier_bit(usage).is_none() {
+                send(UiEvent::KeyPressed(legend::usage(usage)));
+            }
+        }
+        (self.layer, self.mods, self.caps_lock, self.held) = (layer, mods, caps_lock, held);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate std;
+
+    use std::vec::Vec;
+
+    use super::*;
+    use crate::UiState;
+
+    const GAME: KeyAction = KeyAction::Custom(CUSTOM_GAME_MODE);
+    const Q: KeyAction = KeyAction::KeyCode(0x14);
+
+    fn key(router: &mut KeyRouter, action: KeyAction, pressed: bool) -> (Route, Vec<UiEvent>) {
+        let mut events = Vec::new();
+        let route = router.key(action, 1, 4, pressed, |event| events.push(event));
+        (route, events)
+    }
+
+    fn state(events: &[UiEvent]) -> UiState {
+        let mut state = UiState::new(LAYER_NAMES[0], 1);
+        for &event in events {
+            state.apply(event);
+        }
+        state
+    }
+
+    #[test]
+    fn typing() {
+        let mut router = KeyRouter::new();
+        let (route, events) = key(&mut router, Q, true);
+        assert_eq!(route, Route { layout: true, count: true, host: true, left_game: false });
+        assert_eq!(state(&events).pressed, 1 << (NUM_COLS + 4));
+        // Releases aren't counted
+        let (route, _) = key(&mut router, Q, false);
+        assert!(route.layout && !route.count && route.host);
+    }
+
+    #[test]
+    fn game_mode_keeps_presses_from_the_layout() {
+        let mut router = KeyRouter::new();
+        router.enter_game(|_| {});
+        let (route, events) = key(&mut router, Q, true);
+        assert_eq!(route, Route { layout: false, count: false, host: false, left_game: false });
+        // The game still sees the key
+        assert_eq!(state(&events).pressed, 1 << (NUM_COLS + 4));
+        let (route, _) = key(&mut router, Q, false);
+        assert!(route.layout && !route.host);
+        // Layer keys reach the layout, so the game mode key can be found
+        let (route, _) = key(&mut router, KeyAction::Layer(1), true);
+        assert!(route.layout && !route.count);
+    }
+
+    #[test]
+    fn game_mode_key_leaves_the_game() {
+        let mut router = KeyRouter::new();
+        router.enter_game(|_| {});
+        let (route, events) = key(&mut router, GAME, true);
+        assert!(route.left_game && !route.layout);
+        assert!(!router.game_mode());
+        assert!(!state(&[&[UiEvent::GameMode(true)], &events[..]].concat()).game_mode);
+        // and only once
+        let (route, _) = key(&mut router, GAME, false);
+        assert!(!route.left_game && route.layout);
+    }
+
+    #[test]
+    fn layout_changes_reach_the_screens() {
+        let mut router = KeyRouter::new();
+        let mut events = Vec::new();
+        // Left Shift and Q
+        router.layout_ticked([0xe1, 0x14], 1, true, |event| events.push(event));
+        let shown = state(&events);
+        assert_eq!((shown.layer, shown.layer_name), (1, LAYER_NAMES[1]));
+        assert_eq!(shown.mods, crate::screens::MOD_LSHIFT);
+        assert!(shown.caps_lock);
+        assert_eq!(shown.last_keys[..2], ["Q", ""]);
+        // A notification each for the layer and caps lock
+        assert_eq!(shown.notifications, 2);
+
+        // Nothing new, nothing sent
+        events.clear();
+        router.layout_ticked([0xe1, 0x14], 1, true, |event| events.push(event));
+        assert!(events.is_empty());
+
+        // Shift let go and W joins Q
+        router.layout_ticked([0x14, 0x1a], 1, true, |event| events.push(event));
+        let shown = state(&events);
+        assert_eq!(shown.last_keys[..2], ["W", ""]);
+        assert_eq!(shown.mods, 0);
+    }
+}
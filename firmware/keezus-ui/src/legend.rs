@@ -0,0 +1,178 @@
+//! Short labels for keys, for drawing them on the display. They're worked
+//! out from `keezus_protocol::KeyAction`s, so the firmware and keezus-sim
+//! label the same keymap the same way.
+
+use keezus_protocol::keymap::{self, KEYMAP_LEN, LAYERS};
+use keezus_protocol::KeyAction;
+
+use crate::screens::LayerLegends;
+use crate::{NUM_COLS, NUM_ROWS};
+
+const LETTERS: [&str; 26] = [
+    "A", "B", "C", "D", "E", "F", "G", "H", "I", "J", "K", "L", "M", "N", "O", "P", "Q", "R", "S", "T", "U", "V",
+    "W", "X", "Y", "Z",
+];
+const DIGITS: [&str; 10] = ["1", "2", "3", "4", "5", "6", "7", "8", "9", "0"];
+const SHIFTED_DIGITS: [&str; 10] = ["!", "@", "#", "$", "%", "^", "&", "*", "(", ")"];
+/// Enter to Slash
+const PUNCTUATION: [&str; 17] = [
+    "Ent", "Esc", "Bksp", "Tab", "Spc", "-", "=", "[", "]", "\\", "#", ";", "'", "`", ",", ".", "/",
+];
+const FUNCTION: [&str; 12] = ["F1", "F2", "F3", "F4", "F5", "F6", "F7", "F8", "F9", "F10", "F11", "F12"];
+/// Print Screen to Up
+const NAVIGATION: [&str; 13] = [
+    "PrSc", "ScLk", "Pause", "Ins", "Home", "PgUp", "Del", "End", "PgDn", "Right", "Left", "Down", "Up",
+];
+/// Num Lock to the keypad's Dot
+const KEYPAD: [&str; 17] = [
+    "NumLk", "/", "*", "-", "+", "Ent", "1", "2", "3", "4", "5", "6", "7", "8", "9", "0", ".",
+];
+/// Left Control to Right GUI
+const MODIFIERS: [&str; 8] = ["Ctrl", "Shift", "Alt", "Gui", "Ctrl", "Shift", "Alt", "Gui"];
+/// keyberon's media keys, which follow the modifiers
+const MEDIA: [&str; 8] = ["Play", "Stop", "Prev", "Next", "Eject", "Vol+", "Vol-", "Mute"];
+
+const LAYER_LEGENDS: [&str; 8] = ["L0", "L1", "L2", "L3", "L4", "L5", "L6", "L7"];
+const DEFAULT_LAYER_LEGENDS: [&str; 8] = ["D0", "D1", "D2", "D3", "D4", "D5", "D6", "D7"];
+
+const LCTRL: u8 = 0xe0;
+const RGUI: u8 = 0xe7;
+
+/// A legend for every key on every layer, indexed like the keymap
+pub type KeymapLegends = [LayerLegends; LAYERS];
+
+/// Legends for a whole keymap, laid out like `keymap::DEFAULT_KEYMAP`.
+/// Transparent keys show whatever the base layer does.
+pub const fn keymap(entries: &[KeyAction; KEYMAP_LEN]) -> KeymapLegends {
+    let mut legends = [[[""; NUM_COLS]; NUM_ROWS]; LAYERS];
+    let mut index = 0;
+    while index < KEYMAP_LEN {
+        let (layer, row, col) = (index / (NUM_ROWS * NUM_COLS), index / NUM_COLS % NUM_ROWS, index % NUM_COLS);
+        legends[layer][row][col] = match entries[index] {
+            KeyAction::Trans if layer > 0 => action(entries[row * NUM_COLS + col]),
+            entry => action(entry),
+        };
+        index += 1;
+    }
+    legends
+}
+
+pub const fn action(action: KeyAction) -> &'static str {
+    match action {
+        KeyAction::NoOp | KeyAction::Trans => "",
+        KeyAction::KeyCode(usage) => self::usage(usage),
+        KeyAction::Layer(layer) if (layer as usize) < LAYER_LEGENDS.len() => LAYER_LEGENDS[layer as usize],
+        KeyAction::Layer(_) => "L?",
+        KeyAction::DefaultLayer(layer) if (layer as usize) < DEFAULT_LAYER_LEGENDS.len() => {
+            DEFAULT_LAYER_LEGENDS[layer as usize]
+        }
+        KeyAction::DefaultLayer(_) => "D?",
+        KeyAction::Custom(custom) => self::custom(custom),
+        KeyAction::Other => "?",
+    }
+}
+
+/// For the `keymap::CUSTOM_*` actions
+pub const fn custom(custom: u8) -> &'static str {
+    match custom {
+        keymap::CUSTOM_BOOTLOADER => "Boot",
+        keymap::CUSTOM_CYCLE_REPORT_RATE => "Rate",
+        keymap::CUSTOM_NEXT_SCREEN => "Scrn",
+        keymap::CUSTOM_BRIGHTNESS_UP => "Bri+",
+        keymap::CUSTOM_BRIGHTNESS_DOWN => "Bri-",
+        keymap::CUSTOM_GAME_MODE => "Game",
+        _ => "?",
+    }
+}
+
+/// For a HID keyboard usage, at most five characters, `?` for keys
+/// without one
+pub const fn usage(usage: u8) -> &'static str {
+    let index = usage as usize;
+    match usage {
+        0x00 => "",
+        0x04..=0x1d => LETTERS[index - 0x04],
+        0x1e..=0x27 => DIGITS[index - 0x1e],
+        0x28..=0x38 => PUNCTUATION[index - 0x28],
+        0x39 => "Caps",
+        0x3a..=0x45 => FUNCTION[index - 0x3a],
+        0x46..=0x52 => NAVIGATION[index - 0x46],
+        0x53..=0x63 => KEYPAD[index - 0x53],
+        0x64 => "\\",
+        0x65 => "App",
+        0x7f => "Mute",
+        0x80 => "Vol+",
+        0x81 => "Vol-",
+        LCTRL..=RGUI => MODIFIERS[index - LCTRL as usize],
+        0xe8..=0xef => MEDIA[index - 0xe8],
+        _ => "?",
+    }
+}
+
+/// What the key types with Shift held, for the US layout
+pub const fn shifted(usage: u8) -> Option<&'static str> {
+    Some(match usage {
+        0x1e..=0x27 => SHIFTED_DIGITS[usage as usize - 0x1e],
+        0x2d => "_",
+        0x2e => "+",
+        0x2f => "{",
+        0x30 => "}",
+        0x31 => "|",
+        0x33 => ":",
+        0x34 => "\"",
+        0x35 => "~",
+        0x36 => "<",
+        0x37 => ">",
+        0x38 => "?",
+        _ => return None,
+    })
+}
+
+/// The bit a modifier has in `UiState::mods`, which follows the modifier
+/// byte of a boot keyboard report
+pub const fn modifier_bit(usage: u8) -> Option<u8> {
+    match usage {
+        LCTRL..=RGUI => Some(1 << (usage - LCTRL)),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use keezus_protocol::keymap::DEFAULT_KEYMAP;
+
+    #[test]
+    fn usages() {
+        assert_eq!(usage(0x04), "A");
+        assert_eq!(usage(0x1d), "Z");
+        assert_eq!(usage(0x27), "0");
+        assert_eq!(usage(0x2c), "Spc");
+        assert_eq!(usage(0x38), "/");
+        assert_eq!(usage(0x45), "F12");
+        assert_eq!(usage(0x52), "Up");
+        assert_eq!(usage(0x63), ".");
+        assert_eq!(usage(0xe6), "Alt");
+        assert_eq!(usage(0x02), "?");
+        assert_eq!((shifted(0x1e), shifted(0x38), shifted(0x04)), (Some("!"), Some("?"), None));
+    }
+
+    #[test]
+    fn modifier_bits() {
+        assert_eq!(modifier_bit(0xe0), Some(crate::screens::MOD_LCTRL));
+        assert_eq!(modifier_bit(0xe6), Some(crate::screens::MOD_RALT));
+        assert_eq!(modifier_bit(0xe7), Some(crate::screens::MOD_RGUI));
+        assert_eq!(modifier_bit(0x04), None);
+        assert_eq!(modifier_bit(0xe8), None);
+    }
+
+    #[test]
+    fn default_keymap() {
+        let legends = keymap(&DEFAULT_KEYMAP);
+        assert_eq!(legends[0][0], ["Q", "W", "E", "R", "T", "Y", "U", "I", "O", "P"]);
+        assert_eq!(legends[0][3], ["Gui", "Alt", "", "", "Spc", "", "", "Alt", "Ctrl", "L1"]);
+        // The Fn layer shows the base layer through its transparent keys
+        assert_eq!(legends[1][2], ["Z", "X", "C", "V", "B", "Scrn", "M", "Bri-", "Bri+", "/"]);
+        assert_eq!(legends[1][3], legends[0][3]);
+    }
+}
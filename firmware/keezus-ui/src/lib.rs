@@ -0,0 +1,32 @@
+//! Everything the keezus display shows, drawn through
+//! `embedded_graphics::DrawTarget<Color = Rgb565>` without knowing what's
+//! behind it. The firmware draws into a `framebuffer::FrameBuffer` and sends
+//! its dirty tiles on to the panel, `keezus-sim` draws into memory on the
+//! host.
+//!
+//! Nothing in here touches hardware, screens learn about the keyboard from
+//! the `UiEvent`s the firmware sends and time from `UiState::now_us`.
+
+#![no_std]
+
+pub mod animation;
+pub mod assets;
+pub mod bootloader;
+pub mod font;
+pub mod framebuffer;
+pub mod keys;
+pub mod legend;
+pub mod pet;
+pub mod rle;
+pub mod screens;
+pub mod snake;
+pub mod stats;
+
+pub use screens::{DateTime, Screen, ScreenId, Screens, UiEvent, UiState};
+
+/// The keyboard's matrix, which `UiState::pressed` and the keymap screen
+/// are laid out for
+pub use keezus_protocol::keymap::{COLS as NUM_COLS, ROWS as NUM_ROWS};
+
+/// Time between display ticks, the clock animations count in
+pub const DISPLAY_UPDATE_TIME_US: u64 = 1700;
@@ -0,0 +1,383 @@
+//! A virtual pet that lives on typing. Keypresses feed it and keep it
+//! amused, typing tires it out, and it naps when the keyboard goes quiet.
+//!
+//! `Pet` is just the simulation: the firmware counts keypresses into it,
+//! calls `tick` every `TICK_US` and saves it to flash now and then, and
+//! `keezus-sim` does the same on the host. Nothing in here looks at the
+//! clock or anything else outside the pet, so the same presses and ticks
+//! always end up with the same pet.
+
+/// Simulated time between `Pet::tick`s
+pub const TICK_US: u64 = 60_000_000;
+
+/// Stats go from 0 to this
+pub const STAT_MAX: u8 = 100;
+
+/// Keypresses that make up one point of food
+const PRESSES_PER_FOOD: u32 = 20;
+/// Keypresses that use up one point of energy
+const PRESSES_PER_ENERGY: u32 = 100;
+/// Keypresses in a tick that count as playing with it
+const PLAY_PRESSES: u32 = 10;
+/// Ticks without a keypress before it dozes off
+const NAP_AFTER_TICKS: u16 = 15;
+/// Energy it has to have back before a keypress wakes it
+const WAKE_ENERGY: u8 = 20;
+
+/// What the pet looks like, see `Pet::mood`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Mood {
+    Happy,
+    Okay,
+    Hungry,
+    Sad,
+    Sleeping,
+}
+
+impl Mood {
+    pub fn name(self) -> &'static str {
+        match self {
+            Mood::Happy => "happy",
+            Mood::Okay => "okay",
+            Mood::Hungry => "hungry",
+            Mood::Sad => "sad",
+            Mood::Sleeping => "asleep",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Pet {
+    pub food: u8,
+    pub fun: u8,
+    pub energy: u8,
+    pub asleep: bool,
+    /// Ticks since it hatched
+    pub age: u32,
+    /// Ticks since the last keypress
+    idle_ticks: u16,
+    /// Keypresses since the last tick
+    presses: u32,
+    /// Keypresses not yet turned into food or tiredness
+    food_presses: u32,
+    energy_presses: u32,
+}
+
+impl Default for Pet {
+    fn default() -> Self {
+        Pet::new()
+    }
+}
+
+/// Bytes `Pet::to_bytes` fills in
+pub const SAVED_LEN: usize = 14;
+
+impl Pet {
+    /// A freshly hatched pet, fed and rested but waiting to be played with
+    pub const fn new() -> Pet {
+        Pet {
+            food: STAT_MAX,
+            fun: STAT_MAX / 2,
+            energy: STAT_MAX,
+            asleep: false,
+            age: 0,
+            idle_ticks: 0,
+            presses: 0,
+            food_presses: 0,
+            energy_presses: 0,
+        }
+    }
+
+    /// Returns whether that woke it up, which is worth showing straight
+    /// away rather than on the next tick
+    pub fn key_pressed(&mut self) -> bool {
+        self.presses = self.presses.saturating_add(1);
+        let wakes = self.asleep && self.energy >= WAKE_ENERGY;
+        if wakes {
+            self.asleep = false;
+        }
+        wakes
+    }
+
+    /// Moves the pet on by `TICK_US`, going by the keypresses since the
+    /// last tick
+    pub fn tick(&mut self) {
+        let presses = core::mem::take(&mut self.presses);
+        self.age = self.age.wrapping_add(1);
+
+        self.food_presses += presses;
+        self.food = add(self.food, self.food_presses / PRESSES_PER_FOOD);
+        self.food_presses %= PRESSES_PER_FOOD;
+        // Sleeping, it only gets hungry every other tick
+        if !self.asleep || self.age & 1 == 0 {
+            self.food = self.food.saturating_sub(1);
+        }
+
+        self.energy_presses += presses;
+        self.energy = self.energy.saturating_sub((self.energy_presses / PRESSES_PER_ENERGY).min(u8::MAX as u32) as u8);
+        self.energy_presses %= PRESSES_PER_ENERGY;
+
+        if presses >= PLAY_PRESSES {
+            self.fun = add(self.fun, 2);
+        } else if presses == 0 {
+            self.fun = self.fun.saturating_sub(1);
+        }
+        if self.food == 0 {
+            self.fun = self.fun.saturating_sub(2);
+        }
+
+        self.idle_ticks = if presses == 0 { self.idle_ticks.saturating_add(1) } else { 0 };
+        if self.asleep {
+            self.energy = add(self.energy, 2);
+        } else if self.energy == 0 || self.idle_ticks >= NAP_AFTER_TICKS {
+            self.asleep = true;
+        }
+    }
+
+    pub fn mood(&self) -> Mood {
+        if self.asleep {
+            Mood::Sleeping
+        } else if self.food < 20 {
+            Mood::Hungry
+        } else if self.fun < 30 {
+            Mood::Sad
+        } else if self.fun >= 70 && self.food >= 50 {
+            Mood::Happy
+        } else {
+            Mood::Okay
+        }
+    }
+
+    /// Everything but the keypresses since the last tick, for saving
+    pub fn to_bytes(&self) -> [u8; SAVED_LEN] {
+        let mut bytes = [0; SAVED_LEN];
+        bytes[0] = self.food;
+        bytes[1] = self.fun;
+        bytes[2] = self.energy;
+        bytes[3] = self.asleep as u8;
+        bytes[4..8].copy_from_slice(&self.age.to_le_bytes());
+        bytes[8..10].copy_from_slice(&self.idle_ticks.to_le_bytes());
+        bytes[10..12].copy_from_slice(&(self.food_presses as u16).to_le_bytes());
+        bytes[12..14].copy_from_slice(&(self.energy_presses as u16).to_le_bytes());
+        bytes
+    }
+
+    /// The inverse of `to_bytes`, `None` if anything is out of range
+    pub fn from_bytes(bytes: &[u8]) -> Option<Pet> {
+        let bytes: &[u8; SAVED_LEN] = bytes.get(..SAVED_LEN)?.try_into().ok()?;
+        let u16_at = |i: usize| u16::from_le_bytes([bytes[i], bytes[i + 1]]);
+        let pet = Pet {
+            food: bytes[0],
+            fun: bytes[1],
+            energy: bytes[2],
+            asleep: bytes[3] != 0,
+            age: u32::from_le_bytes([bytes[4], bytes[5], bytes[6], bytes[7]]),
+            idle_ticks: u16_at(8),
+            presses: 0,
+            food_presses: u16_at(10) as u32,
+            energy_presses: u16_at(12) as u32,
+        };
+        let valid = pet.food <= STAT_MAX
+            && pet.fun <= STAT_MAX
+            && pet.energy <= STAT_MAX
+            && bytes[3] <= 1
+            && pet.food_presses < PRESSES_PER_FOOD
+            && pet.energy_presses < PRESSES_PER_ENERGY;
+        valid.then_some(pet)
+    }
+}
+
+fn add(stat: u8, amount: u32) -> u8 {
+    (stat as u32 + amount).min(STAT_MAX as u32) as u8
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn press(pet: &mut Pet, times: u32) {
+        for _ in 0..times {
+            pet.key_pressed();
+        }
+    }
+
+    #[test]
+    fn gets_hungry_and_sad_without_presses() {
+        let mut pet = Pet::new();
+        pet.tick();
+        assert_eq!((pet.food, pet.fun, pet.energy), (STAT_MAX - 1, STAT_MAX / 2 - 1, STAT_MAX));
+
+        pet.fun = 31;
+        pet.tick();
+        assert_eq!(pet.mood(), Mood::Okay);
+        pet.tick();
+        assert_eq!(pet.mood(), Mood::Sad);
+
+        // Starving makes it miserable twice as fast
+        pet.food = 1;
+        pet.fun = 20;
+        pet.tick();
+        assert_eq!((pet.food, pet.fun), (0, 17));
+        assert_eq!(pet.mood(), Mood::Hungry);
+    }
+
+    #[test]
+    fn starves_in_its_sleep() {
+        let mut pet = Pet::new();
+        for _ in 0..1000 {
+            pet.tick();
+        }
+        assert_eq!((pet.food, pet.fun, pet.energy), (0, 0, STAT_MAX));
+        assert_eq!(pet.age, 1000);
+        assert_eq!(pet.mood(), Mood::Sleeping);
+    }
+
+    #[test]
+    fn eats_more_slowly_asleep() {
+        let mut pet = Pet::new();
+        pet.asleep = true;
+        for _ in 0..10 {
+            pet.tick();
+        }
+        assert_eq!(pet.food, STAT_MAX - 5);
+    }
+
+    #[test]
+    fn naps_when_left_alone() {
+        let mut pet = Pet::new();
+        for _ in 1..NAP_AFTER_TICKS {
+            pet.tick();
+        }
+        assert!(!pet.asleep);
+        // A press starts the count again
+        press(&mut pet, 1);
+        pet.tick();
+        for _ in 1..NAP_AFTER_TICKS {
+            pet.tick();
+        }
+        assert!(!pet.asleep);
+        pet.tick();
+        assert!(pet.asleep);
+        assert_eq!(pet.mood(), Mood::Sleeping);
+    }
+
+    #[test]
+    fn naps_when_worn_out() {
+        let mut pet = Pet::new();
+        pet.energy = 1;
+        press(&mut pet, PRESSES_PER_ENERGY);
+        pet.tick();
+        assert_eq!(pet.energy, 0);
+        assert!(pet.asleep);
+    }
+
+    #[test]
+    fn only_wakes_once_rested() {
+        let mut pet = Pet::new();
+        pet.asleep = true;
+        pet.energy = WAKE_ENERGY - 1;
+        assert!(!pet.key_pressed());
+        assert!(pet.asleep);
+
+        // Still asleep, so the tick rests it
+        pet.tick();
+        assert_eq!(pet.energy, WAKE_ENERGY + 1);
+        assert!(pet.key_pressed());
+        assert!(!pet.asleep);
+        // Already awake
+        assert!(!pet.key_pressed());
+    }
+
+    #[test]
+    fn presses_feed_it() {
+        let mut pet = Pet::new();
+        pet.food = 50;
+        press(&mut pet, PRESSES_PER_FOOD - 1);
+        pet.tick();
+        assert_eq!(pet.food, 49);
+        // The leftover presses count towards the next point
+        press(&mut pet, 1);
+        pet.tick();
+        assert_eq!(pet.food, 49);
+        press(&mut pet, PRESSES_PER_FOOD * 2 + 5);
+        pet.tick();
+        assert_eq!(pet.food, 50);
+        assert_eq!(pet.food_presses, 5);
+
+        pet.food = STAT_MAX;
+        press(&mut pet, PRESSES_PER_FOOD * 10);
+        pet.tick();
+        assert_eq!(pet.food, STAT_MAX - 1);
+    }
+
+    #[test]
+    fn presses_tire_it() {
+        let mut pet = Pet::new();
+        press(&mut pet, PRESSES_PER_ENERGY - 1);
+        pet.tick();
+        assert_eq!(pet.energy, STAT_MAX);
+        press(&mut pet, 1);
+        pet.tick();
+        assert_eq!(pet.energy, STAT_MAX - 1);
+        press(&mut pet, PRESSES_PER_ENERGY * 2 + 50);
+        pet.tick();
+        assert_eq!(pet.energy, STAT_MAX - 3);
+        assert_eq!(pet.energy_presses, 50);
+    }
+
+    #[test]
+    fn playing_cheers_it_up() {
+        let mut pet = Pet::new();
+        press(&mut pet, PLAY_PRESSES - 1);
+        pet.tick();
+        assert_eq!(pet.fun, STAT_MAX / 2);
+        press(&mut pet, PLAY_PRESSES);
+        pet.tick();
+        assert_eq!(pet.fun, STAT_MAX / 2 + 2);
+
+        pet.fun = 70;
+        pet.food = 50;
+        assert_eq!(pet.mood(), Mood::Happy);
+    }
+
+    #[test]
+    fn saves_and_loads() {
+        let mut pet = Pet::new();
+        press(&mut pet, 123);
+        for _ in 0..NAP_AFTER_TICKS + 3 {
+            pet.tick();
+        }
+        assert!(pet.asleep);
+        let bytes = pet.to_bytes();
+        assert_eq!(Pet::from_bytes(&bytes), Some(pet));
+        // Anything after the saved bytes is ignored
+        assert_eq!(Pet::from_bytes(&[&bytes[..], &[0xff; 4]].concat()), Some(pet));
+
+        // Presses since the last tick aren't saved
+        press(&mut pet, 3);
+        assert_eq!(Pet::from_bytes(&pet.to_bytes()).map(|p| p.presses), Some(0));
+    }
+
+    #[test]
+    fn rejects_bad_saves() {
+        let bytes = Pet::new().to_bytes();
+        assert_eq!(Pet::from_bytes(&bytes[..SAVED_LEN - 1]), None);
+        // Erased flash
+        assert_eq!(Pet::from_bytes(&[0xff; SAVED_LEN]), None);
+
+        let with = |index: usize, value: u8| {
+            let mut bad = bytes;
+            bad[index] = value;
+            Pet::from_bytes(&bad)
+        };
+        for index in 0..3 {
+            assert!(with(index, STAT_MAX).is_some());
+            assert_eq!(with(index, STAT_MAX + 1), None);
+        }
+        assert_eq!(with(3, 2), None);
+        assert!(with(10, PRESSES_PER_FOOD as u8 - 1).is_some());
+        assert_eq!(with(10, PRESSES_PER_FOOD as u8), None);
+        assert!(with(12, PRESSES_PER_ENERGY as u8 - 1).is_some());
+        assert_eq!(with(12, PRESSES_PER_ENERGY as u8), None);
+    }
+}
@@ -0,0 +1,40 @@
+//! The RLE packets images are kept in: a header byte, then either one pixel
+//! repeated `(header & 0x7f) + 1` times if the top bit is set, or
+//! `header + 1` pixels as they are. Pixels are little endian RGB565.
+//!
+//! build.rs encodes `assets/` with this, including the file as it is, so it
+//! only uses `core`. `assets::Pixels` decodes on the fly.
+
+/// Most pixels a single packet covers
+pub const MAX_PACKET: usize = 128;
+/// Set in the header of a packet repeating one pixel
+pub const REPEAT: u8 = 0x80;
+
+/// Hands the encoded bytes to `out`, in order
+pub fn encode(pixels: &[u16], mut out: impl FnMut(u8)) {
+    let (mut literal_start, mut i) = (0, 0);
+    while i < pixels.len() {
+        let run = pixels[i..].iter().take(MAX_PACKET).take_while(|&&p| p == pixels[i]).count();
+        // A run of two costs the same as two literal pixels, but breaks up
+        // the literal packet around it
+        if run >= 3 {
+            literal(&pixels[literal_start..i], &mut out);
+            out(REPEAT | (run as u8 - 1));
+            pixels[i].to_le_bytes().into_iter().for_each(&mut out);
+            i += run;
+            literal_start = i;
+        } else {
+            i += 1;
+        }
+    }
+    literal(&pixels[literal_start..], &mut out);
+}
+
+fn literal(pixels: &[u16], out: &mut impl FnMut(u8)) {
+    for chunk in pixels.chunks(MAX_PACKET) {
+        out(chunk.len() as u8 - 1);
+        for pixel in chunk {
+            pixel.to_le_bytes().into_iter().for_each(&mut *out);
+        }
+    }
+}
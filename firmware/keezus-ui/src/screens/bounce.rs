@@ -0,0 +1,87 @@
+//! The bouncing ferris screensaver: ferris drifts diagonally and turns
+//! around at the edges of the display.
+
+use embedded_graphics::image::Image;
+use embedded_graphics::pixelcolor::Rgb565;
+use embedded_graphics::prelude::*;
+use embedded_graphics::primitives::Rectangle;
+
+use super::{Screen, UiState};
+use crate::assets::{self, Asset};
+
+/// Ticks per pixel moved
+const TICKS_PER_STEP: u32 = 2;
+
+pub struct BounceScreen {
+    image: Asset,
+    /// Top left, `None` until the first draw puts ferris in the middle
+    at: Option<Point>,
+    velocity: Point,
+    ticks: u32,
+    steps: u32,
+    /// Where ferris was last drawn, to be cleared
+    drawn: Option<Rectangle>,
+}
+
+impl BounceScreen {
+    pub fn new() -> BounceScreen {
+        BounceScreen {
+            image: assets::FERRIS,
+            at: None,
+            velocity: Point::new(1, 1),
+            ticks: 0,
+            steps: 0,
+            drawn: None,
+        }
+    }
+
+    /// Back to the middle, heading off a different way each time
+    pub fn restart(&mut self, seed: u32) {
+        self.at = None;
+        self.velocity = Point::new(if seed & 1 == 0 { 1 } else { -1 }, if seed & 2 == 0 { 1 } else { -1 });
+        self.ticks = 0;
+        self.steps = 0;
+    }
+}
+
+impl Screen for BounceScreen {
+    fn update(&mut self, _state: &UiState) {
+        // Moved in `draw`, which knows where the edges are
+        self.ticks += 1;
+        if self.ticks >= TICKS_PER_STEP {
+            self.ticks = 0;
+            self.steps += 1;
+        }
+    }
+
+    fn draw<D>(&mut self, target: &mut D, full: bool) -> Result<(), D::Error>
+    where
+        D: DrawTarget<Color = Rgb565>,
+    {
+        let area = target.bounding_box();
+        let size = self.image.bounding_box().size;
+        let max = Point::new((area.size.width - size.width) as i32, (area.size.height - size.height) as i32);
+        let mut at = *self.at.get_or_insert(max / 2);
+        if self.steps == 0 && !full {
+            return Ok(());
+        }
+        for _ in 0..self.steps {
+            at += self.velocity;
+            if at.x <= 0 || at.x >= max.x {
+                self.velocity.x = -self.velocity.x;
+            }
+            if at.y <= 0 || at.y >= max.y {
+                self.velocity.y = -self.velocity.y;
+            }
+            at = at.component_max(Point::zero()).component_min(max);
+        }
+        self.steps = 0;
+        self.at = Some(at);
+
+        if let Some(drawn) = self.drawn.filter(|_| !full) {
+            target.fill_solid(&drawn, Rgb565::BLACK)?;
+        }
+        self.drawn = Some(Rectangle::new(at, size));
+        Image::new(&self.image, at).draw(target)
+    }
+}
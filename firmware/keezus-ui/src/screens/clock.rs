@@ -0,0 +1,104 @@
+//! The time of day once the host has set the board's clock, otherwise time
+//! since the board was powered up.
+
+use core::fmt::Write;
+
+use embedded_graphics::mono_font::ascii::{FONT_10X20, FONT_6X10};
+use embedded_graphics::mono_font::{MonoTextStyle, MonoTextStyleBuilder};
+use embedded_graphics::pixelcolor::Rgb565;
+use embedded_graphics::prelude::*;
+use embedded_graphics::text::{Alignment, Text};
+
+use super::{DateTime, Screen, UiState};
+
+const WEEKDAYS: [&str; 7] = ["Sunday", "Monday", "Tuesday", "Wednesday", "Thursday", "Friday", "Saturday"];
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Shown {
+    Uptime(u64),
+    Time(DateTime),
+}
+
+pub struct ClockScreen {
+    shown: Shown,
+    changed: bool,
+    /// Whether the labels around the time need redrawing, because it went
+    /// from uptime to the time of day or the day changed
+    relabel: bool,
+}
+
+impl ClockScreen {
+    pub fn new() -> ClockScreen {
+        ClockScreen {
+            shown: Shown::Uptime(0),
+            changed: true,
+            relabel: false,
+        }
+    }
+}
+
+impl Screen for ClockScreen {
+    fn update(&mut self, state: &UiState) {
+        let shown = match state.time {
+            Some(time) => Shown::Time(time),
+            None => Shown::Uptime(state.now_us / 1_000_000),
+        };
+        if shown != self.shown {
+            self.relabel |= match (shown, self.shown) {
+                (Shown::Time(new), Shown::Time(old)) => new.day != old.day,
+                (Shown::Uptime(_), Shown::Uptime(_)) => false,
+                _ => true,
+            };
+            self.shown = shown;
+            self.changed = true;
+        }
+    }
+
+    fn draw<D>(&mut self, target: &mut D, full: bool) -> Result<(), D::Error>
+    where
+        D: DrawTarget<Color = Rgb565>,
+    {
+        if !full && !self.changed {
+            return Ok(());
+        }
+        let center = target.bounding_box().center();
+        let label = MonoTextStyleBuilder::new()
+            .font(&FONT_6X10)
+            .text_color(Rgb565::WHITE)
+            .background_color(Rgb565::BLACK)
+            .build();
+        if full || self.relabel {
+            if !full {
+                target.clear(Rgb565::BLACK)?;
+            }
+            let mut date: heapless::String<16> = heapless::String::new();
+            let weekday = match self.shown {
+                Shown::Uptime(_) => "uptime",
+                Shown::Time(time) => {
+                    let _ = write!(date, "{:04}-{:02}-{:02}", time.year, time.month, time.day);
+                    WEEKDAYS.get(time.weekday as usize).copied().unwrap_or("")
+                }
+            };
+            Text::with_alignment(weekday, center - Point::new(0, 20), label, Alignment::Center).draw(target)?;
+            let style = MonoTextStyle::new(&FONT_6X10, Rgb565::CSS_LIGHT_GRAY);
+            Text::with_alignment(&date, center + Point::new(0, 24), style, Alignment::Center).draw(target)?;
+        }
+        self.changed = false;
+        self.relabel = false;
+
+        let style = MonoTextStyleBuilder::new()
+            .font(&FONT_10X20)
+            .text_color(Rgb565::GREEN)
+            .background_color(Rgb565::BLACK)
+            .build();
+        let (hours, minutes, seconds) = match self.shown {
+            Shown::Uptime(seconds) => ((seconds / 3600) % 100, seconds / 60 % 60, seconds % 60),
+            Shown::Time(time) => (time.hour as u64, time.minute as u64, time.second as u64),
+        };
+        let mut time: heapless::String<16> = heapless::String::new();
+        let _ = write!(time, "{:02}:{:02}:{:02}", hours, minutes, seconds);
+        Text::with_alignment(&time, center + Point::new(0, 6), style, Alignment::Center).draw(target)?;
+
+        Ok(())
+    }
+}
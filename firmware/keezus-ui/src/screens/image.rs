@@ -0,0 +1,33 @@
+//! Ferris, sitting in the middle of the screen.
+
+use embedded_graphics::image::Image;
+use embedded_graphics::pixelcolor::Rgb565;
+use embedded_graphics::prelude::*;
+
+use super::{Screen, UiState};
+use crate::assets::{self, Asset};
+
+pub struct ImageScreen {
+    image: Asset,
+}
+
+impl ImageScreen {
+    pub fn new() -> ImageScreen {
+        ImageScreen { image: assets::FERRIS }
+    }
+}
+
+impl Screen for ImageScreen {
+    fn update(&mut self, _state: &UiState) {}
+
+    fn draw<D>(&mut self, target: &mut D, full: bool) -> Result<(), D::Error>
+    where
+        D: DrawTarget<Color = Rgb565>,
+    {
+        if !full {
+            return Ok(());
+        }
+        let at = target.bounding_box().center() - self.image.bounding_box().size / 2;
+        Image::new(&self.image, at).draw(target)
+    }
+}
@@ -0,0 +1,163 @@
+//! The active layer's keymap, with keys lighting up as they're pressed.
+//!
+//! A 10 column row doesn't leave room for legends across 128 pixels, so in
+//! portrait the left half of the board is drawn above the right half. In
+//! landscape the whole row fits.
+
+use embedded_graphics::mono_font::ascii::{FONT_5X8, FONT_6X10};
+use embedded_graphics::mono_font::MonoTextStyleBuilder;
+use embedded_graphics::pixelcolor::{Rgb565, WebColors};
+use embedded_graphics::prelude::*;
+use embedded_graphics::primitives::{PrimitiveStyleBuilder, Rectangle};
+use embedded_graphics::text::{Alignment, Baseline, Text, TextStyleBuilder};
+
+use super::{Screen, UiState};
+use crate::{NUM_COLS, NUM_ROWS};
+
+/// A legend for every key on one layer, row by row
+pub type LayerLegends = [[&'static str; NUM_COLS]; NUM_ROWS];
+
+const HALF_COLS: usize = NUM_COLS / 2;
+const GRID_Y: i32 = 14;
+/// Space between the two halves
+const HALF_GAP: i32 = 4;
+/// Narrowest a cell can be with the whole row side by side, room for a
+/// couple of characters
+const MIN_CELL_WIDTH: i32 = 16;
+const MAX_CELL_HEIGHT: i32 = 24;
+
+/// Where the keys go on a screen of a given size
+struct Grid {
+    /// Columns side by side, the whole row or half of it
+    cols: usize,
+    margin: i32,
+    cell_width: i32,
+    cell_height: i32,
+}
+
+impl Grid {
+    fn new(size: Size) -> Grid {
+        let (width, height) = (size.width as i32, size.height as i32 - GRID_Y);
+        let split = width / (NUM_COLS as i32) < MIN_CELL_WIDTH;
+        let (cols, rows, gaps) = if split {
+            (HALF_COLS, NUM_ROWS * 2, HALF_GAP)
+        } else {
+            (NUM_COLS, NUM_ROWS, 0)
+        };
+        let cell_width = width / cols as i32;
+        Grid {
+            cols,
+            margin: (width - cell_width * cols as i32) / 2,
+            cell_width,
+            cell_height: ((height - gaps) / rows as i32).min(MAX_CELL_HEIGHT),
+        }
+    }
+
+    fn cell(&self, row: usize, col: usize) -> Rectangle {
+        let (block, col_in_block) = (col / self.cols, col % self.cols);
+        let y = GRID_Y + block as i32 * (NUM_ROWS as i32 * self.cell_height + HALF_GAP) + row as i32 * self.cell_height;
+        Rectangle::new(
+            Point::new(self.margin + col_in_block as i32 * self.cell_width, y),
+            Size::new(self.cell_width as u32 - 1, self.cell_height as u32 - 1),
+        )
+    }
+}
+
+pub struct KeymapScreen {
+    /// One entry per layer
+    legends: &'static [LayerLegends],
+    layer: usize,
+    layer_name: &'static str,
+    pressed: u64,
+    drawn_layer: Option<usize>,
+    drawn_pressed: u64,
+}
+
+impl KeymapScreen {
+    pub fn new(legends: &'static [LayerLegends]) -> KeymapScreen {
+        KeymapScreen {
+            legends,
+            layer: 0,
+            layer_name: "",
+            pressed: 0,
+            drawn_layer: None,
+            drawn_pressed: 0,
+        }
+    }
+
+    fn draw_key<D>(&self, target: &mut D, row: usize, col: usize, pressed: bool) -> Result<(), D::Error>
+    where
+        D: DrawTarget<Color = Rgb565>,
+    {
+        let grid = Grid::new(target.bounding_box().size);
+        let cell = grid.cell(row, col);
+
+        let (fill, text) = if pressed {
+            (Rgb565::YELLOW, Rgb565::BLACK)
+        } else {
+            (Rgb565::BLACK, Rgb565::WHITE)
+        };
+        let style = PrimitiveStyleBuilder::new()
+            .fill_color(fill)
+            .stroke_color(Rgb565::CSS_DIM_GRAY)
+            .stroke_width(1)
+            .build();
+        cell.into_styled(style).draw(target)?;
+
+        let legend = self.legends[self.layer][row][col];
+        // Cut down to what fits in the cell
+        let fits = ((grid.cell_width - 2) / 5) as usize;
+        let legend = legend.get(..fits.min(legend.len())).unwrap_or(legend);
+        let character = MonoTextStyleBuilder::new().font(&FONT_5X8).text_color(text).build();
+        let centered = TextStyleBuilder::new()
+            .alignment(Alignment::Center)
+            .baseline(Baseline::Middle)
+            .build();
+        Text::with_text_style(legend, cell.center(), character, centered).draw(target)?;
+        Ok(())
+    }
+}
+
+impl Screen for KeymapScreen {
+    fn update(&mut self, state: &UiState) {
+        // Layers past what the legend table knows about can't be shown
+        if state.layer < self.legends.len() {
+            self.layer = state.layer;
+            self.layer_name = state.layer_name;
+        }
+        self.pressed = state.pressed;
+    }
+
+    fn draw<D>(&mut self, target: &mut D, full: bool) -> Result<(), D::Error>
+    where
+        D: DrawTarget<Color = Rgb565>,
+    {
+        let redraw_all = full || self.drawn_layer != Some(self.layer);
+        if redraw_all {
+            let title = MonoTextStyleBuilder::new()
+                .font(&FONT_6X10)
+                .text_color(Rgb565::CYAN)
+                .background_color(Rgb565::BLACK)
+                .build();
+            let width = target.bounding_box().size.width;
+            Rectangle::new(Point::zero(), Size::new(width, GRID_Y as u32))
+                .into_styled(PrimitiveStyleBuilder::new().fill_color(Rgb565::BLACK).build())
+                .draw(target)?;
+            Text::with_baseline(self.layer_name, Point::new(2, 2), title, Baseline::Top).draw(target)?;
+        }
+
+        let changed = self.pressed ^ self.drawn_pressed;
+        for row in 0..NUM_ROWS {
+            for col in 0..NUM_COLS {
+                let bit = 1 << (row * NUM_COLS + col);
+                if redraw_all || changed & bit != 0 {
+                    self.draw_key(target, row, col, self.pressed & bit != 0)?;
+                }
+            }
+        }
+
+        self.drawn_layer = Some(self.layer);
+        self.drawn_pressed = self.pressed;
+        Ok(())
+    }
+}
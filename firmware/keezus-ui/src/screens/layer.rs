@@ -0,0 +1,73 @@
+//! The active layer, big enough to read at a glance.
+
+use core::fmt::Write;
+
+use embedded_graphics::mono_font::ascii::{FONT_10X20, FONT_6X10};
+use embedded_graphics::mono_font::MonoTextStyleBuilder;
+use embedded_graphics::pixelcolor::Rgb565;
+use embedded_graphics::prelude::*;
+use embedded_graphics::primitives::{PrimitiveStyle, Rectangle};
+use embedded_graphics::text::{Alignment, Text};
+
+use super::{Screen, UiState};
+
+pub struct LayerScreen {
+    layer: usize,
+    name: &'static str,
+    changed: bool,
+}
+
+impl LayerScreen {
+    pub fn new() -> LayerScreen {
+        LayerScreen {
+            layer: 0,
+            name: "",
+            changed: true,
+        }
+    }
+}
+
+impl Screen for LayerScreen {
+    fn update(&mut self, state: &UiState) {
+        if state.layer != self.layer || state.layer_name != self.name {
+            self.layer = state.layer;
+            self.name = state.layer_name;
+            self.changed = true;
+        }
+    }
+
+    fn draw<D>(&mut self, target: &mut D, full: bool) -> Result<(), D::Error>
+    where
+        D: DrawTarget<Color = Rgb565>,
+    {
+        if !full && !self.changed {
+            return Ok(());
+        }
+        self.changed = false;
+
+        let area = target.bounding_box();
+        let center = area.center();
+        let big = MonoTextStyleBuilder::new()
+            .font(&FONT_10X20)
+            .text_color(Rgb565::CYAN)
+            .background_color(Rgb565::BLACK)
+            .build();
+        let small = MonoTextStyleBuilder::new()
+            .font(&FONT_6X10)
+            .text_color(Rgb565::WHITE)
+            .background_color(Rgb565::BLACK)
+            .build();
+
+        // Names differ in length, clear the band they go in first
+        Rectangle::new(Point::new(0, center.y - 20), Size::new(area.size.width, 40))
+            .into_styled(PrimitiveStyle::with_fill(Rgb565::BLACK))
+            .draw(target)?;
+
+        let mut number: heapless::String<16> = heapless::String::new();
+        let _ = write!(number, "layer {}", self.layer);
+        Text::with_alignment(&number, center - Point::new(0, 8), small, Alignment::Center).draw(target)?;
+        Text::with_alignment(self.name, center + Point::new(0, 14), big, Alignment::Center).draw(target)?;
+
+        Ok(())
+    }
+}
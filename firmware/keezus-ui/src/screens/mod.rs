@@ -0,0 +1,514 @@
+//! What the display shows when the host canvas isn't up: a handful of
+//! screens, stepped through with the `NextScreen` key, and screensavers
+//! that take over from the status screen when the keyboard is left alone.
+//! Notifications pop up over all of them (see overlay.rs).
+//!
+//! Screens only draw through `DrawTarget` and take everything they need to
+//! know about the keyboard from a `UiState`, so none of them touch the
+//! hardware directly.
+
+mod bounce;
+mod clock;
+mod image;
+mod keymap;
+mod layer;
+mod overlay;
+mod pet;
+mod rain;
+mod screensaver;
+mod scroller;
+mod snake;
+mod sprite;
+mod starfield;
+mod stats;
+mod status;
+
+use embedded_graphics::pixelcolor::Rgb565;
+use embedded_graphics::prelude::*;
+
+use bounce::BounceScreen;
+use clock::ClockScreen;
+use image::ImageScreen;
+use keymap::KeymapScreen;
+use layer::LayerScreen;
+use overlay::Overlay;
+use pet::PetScreen;
+use rain::RainScreen;
+use scroller::ScrollerScreen;
+use snake::SnakeScreen;
+use sprite::SpriteScreen;
+use starfield::StarfieldScreen;
+use stats::StatsScreen;
+use status::StatusScreen;
+
+use crate::pet::Pet;
+use crate::stats::Stats;
+
+pub use keezus_protocol::{DateTime, ShortText};
+pub use keymap::LayerLegends;
+pub use overlay::{Notification, SHOW_US as NOTIFICATION_US};
+pub use screensaver::{Saver, ScreensaverConfig, DEFAULT_AFTER_MIN};
+pub use scroller::DEFAULT_TEXT as DEFAULT_SCREENSAVER_TEXT;
+
+/// How many of the most recent key presses `UiState` remembers
+pub const LAST_KEYS: usize = 6;
+
+/// Bits of `UiState::mods`, the same as the modifier byte of a boot
+/// keyboard report
+pub const MOD_LCTRL: u8 = 1 << 0;
+pub const MOD_LSHIFT: u8 = 1 << 1;
+pub const MOD_LALT: u8 = 1 << 2;
+pub const MOD_LGUI: u8 = 1 << 3;
+pub const MOD_RCTRL: u8 = 1 << 4;
+pub const MOD_RSHIFT: u8 = 1 << 5;
+pub const MOD_RALT: u8 = 1 << 6;
+pub const MOD_RGUI: u8 = 1 << 7;
+
+/// What the rest of the firmware tells the screens. Core 1 keeps its own
+/// copy, built up from the `UiEvent`s core 0 sends it.
+#[derive(Clone, Copy)]
+pub struct UiState {
+    pub now_us: u64,
+    /// Wall clock time, once the host has set it
+    pub time: Option<DateTime>,
+    pub layer: usize,
+    pub layer_name: &'static str,
+    pub mods: u8,
+    pub caps_lock: bool,
+    /// One bit per key held down on the matrix, `row * NUM_COLS + col`
+    pub pressed: u64,
+    /// Legends of the last keys pressed, most recent first
+    pub last_keys: [&'static str; LAST_KEYS],
+    pub poll_interval_ms: u8,
+    /// Bumped on every `NextScreen` press
+    pub screen_switches: u8,
+    pub pet: Pet,
+    /// Typing statistics, which the firmware copies over every frame
+    /// rather than sending as events
+    pub stats: Stats,
+    /// Keys go to the game on the display instead of the host
+    pub game_mode: bool,
+    /// Best game score, as saved in flash
+    pub high_score: u16,
+    pub screensaver: ScreensaverConfig,
+    /// The latest notification, and a count of them so the same one twice
+    /// shows twice
+    pub notification: Option<Notification>,
+    pub notifications: u8,
+}
+
+impl UiState {
+    pub const fn new(layer_name: &'static str, poll_interval_ms: u8) -> UiState {
+        UiState {
+            now_us: 0,
+            time: None,
+            layer: 0,
+            layer_name,
+            mods: 0,
+            caps_lock: false,
+            pressed: 0,
+            last_keys: [""; LAST_KEYS],
+            poll_interval_ms,
+            screen_switches: 0,
+            pet: Pet::new(),
+            stats: Stats::new(),
+            game_mode: false,
+            high_score: 0,
+            screensaver: ScreensaverConfig::new(),
+            notification: None,
+            notifications: 0,
+        }
+    }
+
+    pub fn push_key(&mut self, legend: &'static str) {
+        self.last_keys.copy_within(..LAST_KEYS - 1, 1);
+        self.last_keys[0] = legend;
+    }
+
+    pub fn apply(&mut self, event: UiEvent) {
+        match event {
+            UiEvent::Key { row, col, pressed } => {
+                let bit = 1 << (row as usize * crate::NUM_COLS + col as usize);
+                if pressed {
+                    self.pressed |= bit;
+                } else {
+                    self.pressed &= !bit;
+                }
+            }
+            UiEvent::KeyPressed(legend) => self.push_key(legend),
+            UiEvent::Layer(layer, name) => {
+                self.layer = layer;
+                self.layer_name = name;
+            }
+            UiEvent::Mods(mods) => self.mods = mods,
+            UiEvent::CapsLock(on) => self.caps_lock = on,
+            UiEvent::NextScreen => self.screen_switches = self.screen_switches.wrapping_add(1),
+            UiEvent::Pet(pet) => self.pet = pet,
+            UiEvent::GameMode(on) => self.game_mode = on,
+            UiEvent::Notify(notification) => {
+                self.notification = Some(notification);
+                self.notifications = self.notifications.wrapping_add(1);
+            }
+        }
+    }
+}
+
+/// Changes to the keyboard's state, sent from core 0 to the display
+#[derive(Clone, Copy)]
+pub enum UiEvent {
+    /// A switch on the matrix, after debouncing
+    Key { row: u8, col: u8, pressed: bool },
+    /// A key (not a modifier) went down, with its legend
+    KeyPressed(&'static str),
+    Layer(usize, &'static str),
+    Mods(u8),
+    CapsLock(bool),
+    NextScreen,
+    /// The pet after one of its ticks
+    Pet(Pet),
+    /// Game mode was switched on or off
+    GameMode(bool),
+    /// Pops up over the screen for a moment
+    Notify(Notification),
+}
+
+pub trait Screen {
+    /// Called once per display tick while the screen is shown
+    fn update(&mut self, state: &UiState);
+
+    /// Draws whatever changed since the last call, or everything if `full`
+    /// (the display has just been cleared)
+    fn draw<D>(&mut self, target: &mut D, full: bool) -> Result<(), D::Error>
+    where
+        D: DrawTarget<Color = Rgb565>;
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ScreenId {
+    Status,
+    Layer,
+    Keymap,
+    Clock,
+    Pet,
+    Stats,
+    Image,
+    Sprite,
+}
+
+impl ScreenId {
+    /// In the order `NextScreen` steps through them
+    pub const ALL: [ScreenId; 8] = [
+        ScreenId::Status,
+        ScreenId::Layer,
+        ScreenId::Keymap,
+        ScreenId::Clock,
+        ScreenId::Pet,
+        ScreenId::Stats,
+        ScreenId::Image,
+        ScreenId::Sprite,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            ScreenId::Status => "status",
+            ScreenId::Layer => "layer",
+            ScreenId::Keymap => "keymap",
+            ScreenId::Clock => "clock",
+            ScreenId::Pet => "pet",
+            ScreenId::Stats => "stats",
+            ScreenId::Image => "image",
+            ScreenId::Sprite => "sprite",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<ScreenId> {
+        ScreenId::ALL.iter().copied().find(|id| id.name() == name)
+    }
+
+    fn next(self) -> ScreenId {
+        match self {
+            ScreenId::Status => ScreenId::Layer,
+            ScreenId::Layer => ScreenId::Keymap,
+            ScreenId::Keymap => ScreenId::Clock,
+            ScreenId::Clock => ScreenId::Pet,
+            ScreenId::Pet => ScreenId::Stats,
+            ScreenId::Stats => ScreenId::Image,
+            ScreenId::Image => ScreenId::Sprite,
+            ScreenId::Sprite => ScreenId::Status,
+        }
+    }
+}
+
+pub struct Screens {
+    current: ScreenId,
+    switches_seen: u8,
+    full_redraw: bool,
+    status: StatusScreen,
+    layer: LayerScreen,
+    keymap: KeymapScreen,
+    clock: ClockScreen,
+    pet: PetScreen,
+    stats: StatsScreen,
+    image: ImageScreen,
+    sprite: SpriteScreen,
+    /// Takes over the display in game mode, outside of `ScreenId::ALL`
+    snake: SnakeScreen,
+    in_game: bool,
+    /// Showing in place of the status screen
+    saver: Option<Saver>,
+    savers_shown: usize,
+    /// Start one on the next tick whatever the time
+    saver_wanted: bool,
+    /// Keys held as of the last tick, any change counts as activity
+    pressed: u64,
+    last_activity_us: u64,
+    scroller: ScrollerScreen,
+    bounce: BounceScreen,
+    starfield: StarfieldScreen,
+    rain: RainScreen,
+    overlay: Overlay,
+    notifications_seen: u8,
+}
+
+impl Screens {
+    /// `legends` has one entry per layer, for the keymap and stats screens
+    pub fn new(legends: &'static [LayerLegends]) -> Screens {
+        Screens {
+            current: ScreenId::Status,
+            switches_seen: 0,
+            full_redraw: true,
+            status: StatusScreen::new(),
+            layer: LayerScreen::new(),
+            keymap: KeymapScreen::new(legends),
+            clock: ClockScreen::new(),
+            pet: PetScreen::new(),
+            stats: StatsScreen::new(legends),
+            image: ImageScreen::new(),
+            sprite: SpriteScreen::new(),
+            snake: SnakeScreen::new(),
+            in_game: false,
+            saver: None,
+            savers_shown: 0,
+            saver_wanted: false,
+            pressed: 0,
+            last_activity_us: 0,
+            scroller: ScrollerScreen::new(),
+            bounce: BounceScreen::new(),
+            starfield: StarfieldScreen::new(),
+            rain: RainScreen::new(),
+            overlay: Overlay::new(),
+            notifications_seen: 0,
+        }
+    }
+
+    pub fn current(&self) -> ScreenId {
+        self.current
+    }
+
+    /// Jumps straight to a screen, redrawn on the next tick
+    pub fn show(&mut self, id: ScreenId) {
+        self.current = id;
+        self.saver = None;
+        self.full_redraw = true;
+    }
+
+    /// The screensaver showing, if any
+    pub fn screensaver(&self) -> Option<Saver> {
+        self.saver
+    }
+
+    /// Starts the screensaver on the next tick as if the keyboard had been
+    /// left alone on the status screen
+    pub fn start_screensaver(&mut self) {
+        self.current = ScreenId::Status;
+        self.saver_wanted = true;
+    }
+
+    /// Clears and redraws the current screen on the next tick, for when
+    /// something else has been drawing on the display
+    pub fn redraw(&mut self) {
+        self.full_redraw = true;
+    }
+
+    /// The best game score seen on the display, which may beat the one in
+    /// `UiState::high_score` and want saving
+    pub fn high_score(&self) -> u16 {
+        self.snake.best()
+    }
+
+    /// Switches screens if asked to, then updates and draws the current one
+    /// with any notification over it
+    pub fn tick<D>(&mut self, state: &UiState, target: &mut D) -> Result<(), D::Error>
+    where
+        D: DrawTarget<Color = Rgb565>,
+    {
+        // The screensaver goes first, as the key that stops it may be the one
+        // switching screens and stopping it goes back to status
+        self.update_screensaver(state);
+        while self.switches_seen != state.screen_switches {
+            self.switches_seen = self.switches_seen.wrapping_add(1);
+            self.current = self.current.next();
+            self.saver = None;
+            self.full_redraw = true;
+        }
+        if self.in_game != state.game_mode {
+            self.in_game = state.game_mode;
+            self.snake.restart();
+            self.full_redraw = true;
+        }
+        if self.notifications_seen != state.notifications {
+            self.notifications_seen = state.notifications;
+            if let Some(notification) = &state.notification {
+                self.overlay.show(notification, state.now_us);
+            }
+        }
+        if self.overlay.update(state.now_us, target.bounding_box().size) {
+            self.full_redraw = true;
+        }
+
+        let full = self.full_redraw;
+        if full {
+            target.clear(Rgb565::BLACK)?;
+            self.full_redraw = false;
+        }
+        self.draw_screen(state, target, full)?;
+        self.overlay.draw(target)
+    }
+}
+
+impl Screens {
+    fn draw_screen<D>(&mut self, state: &UiState, target: &mut D, full: bool) -> Result<(), D::Error>
+    where
+        D: DrawTarget<Color = Rgb565>,
+    {
+        if self.in_game {
+            return run(&mut self.snake, state, target, full);
+        }
+        match self.current {
+            ScreenId::Status => match self.saver {
+                Some(Saver::Text) => run(&mut self.scroller, state, target, full),
+                Some(Saver::Ferris) => run(&mut self.bounce, state, target, full),
+                Some(Saver::Starfield) => run(&mut self.starfield, state, target, full),
+                Some(Saver::Rain) => run(&mut self.rain, state, target, full),
+                None => run(&mut self.status, state, target, full),
+            },
+            ScreenId::Layer => run(&mut self.layer, state, target, full),
+            ScreenId::Keymap => run(&mut self.keymap, state, target, full),
+            ScreenId::Clock => run(&mut self.clock, state, target, full),
+            ScreenId::Pet => run(&mut self.pet, state, target, full),
+            ScreenId::Stats => run(&mut self.stats, state, target, full),
+            ScreenId::Image => run(&mut self.image, state, target, full),
+            ScreenId::Sprite => run(&mut self.sprite, state, target, full),
+        }
+    }
+
+    /// Starts the screensaver once the status screen has been left alone
+    /// for long enough, and stops it on any key
+    fn update_screensaver(&mut self, state: &UiState) {
+        if state.pressed != self.pressed {
+            self.pressed = state.pressed;
+            self.last_activity_us = state.now_us;
+            self.saver_wanted = false;
+            if self.saver.take().is_some() {
+                self.current = ScreenId::Status;
+                self.full_redraw = true;
+            }
+            return;
+        }
+        if self.saver.is_some() || self.in_game || self.current != ScreenId::Status {
+            return;
+        }
+        let after_us = state.screensaver.after_min as u64 * 60_000_000;
+        let idle = after_us != 0 && state.now_us >= self.last_activity_us + after_us;
+        if !(idle || core::mem::take(&mut self.saver_wanted)) {
+            return;
+        }
+
+        let saver = state.screensaver.pick(self.savers_shown);
+        self.savers_shown = self.savers_shown.wrapping_add(1);
+        let seed = state.now_us as u32;
+        match saver {
+            Saver::Text => self.scroller.restart(),
+            Saver::Ferris => self.bounce.restart(seed),
+            Saver::Starfield => self.starfield.restart(seed),
+            Saver::Rain => self.rain.restart(seed),
+        }
+        self.saver = Some(saver);
+        self.full_redraw = true;
+    }
+}
+
+fn run<S, D>(screen: &mut S, state: &UiState, target: &mut D, full: bool) -> Result<(), D::Error>
+where
+    S: Screen,
+    D: DrawTarget<Color = Rgb565>,
+{
+    screen.update(state);
+    screen.draw(target, full)
+}
+
+#[cfg(test)]
+mod tests {
+    use core::convert::Infallible;
+
+    use super::*;
+
+    /// Takes whatever the screens draw and throws it away
+    struct Blank;
+
+    impl OriginDimensions for Blank {
+        fn size(&self) -> Size {
+            Size::new(135, 240)
+        }
+    }
+
+    impl DrawTarget for Blank {
+        type Color = Rgb565;
+        type Error = Infallible;
+
+        fn draw_iter<I>(&mut self, _pixels: I) -> Result<(), Infallible>
+        where
+            I: IntoIterator<Item = Pixel<Rgb565>>,
+        {
+            Ok(())
+        }
+    }
+
+    fn saver_showing() -> (Screens, UiState) {
+        let mut screens = Screens::new(&[]);
+        let state = UiState::new("QWERTY", 1);
+        screens.start_screensaver();
+        screens.tick(&state, &mut Blank).unwrap();
+        assert!(screens.screensaver().is_some());
+        (screens, state)
+    }
+
+    #[test]
+    fn next_screen_stops_the_screensaver() {
+        let (mut screens, mut state) = saver_showing();
+        // The key and the switch it makes arrive on the same tick
+        state.apply(UiEvent::Key { row: 2, col: 5, pressed: true });
+        state.apply(UiEvent::NextScreen);
+        screens.tick(&state, &mut Blank).unwrap();
+        assert_eq!(screens.screensaver(), None);
+        assert_eq!(screens.current(), ScreenId::Status.next());
+    }
+
+    #[test]
+    fn next_screen_without_a_key_stops_the_screensaver() {
+        let (mut screens, mut state) = saver_showing();
+        state.apply(UiEvent::NextScreen);
+        screens.tick(&state, &mut Blank).unwrap();
+        assert_eq!(screens.screensaver(), None);
+        assert_eq!(screens.current(), ScreenId::Status.next());
+    }
+
+    #[test]
+    fn any_other_key_goes_back_to_status() {
+        let (mut screens, mut state) = saver_showing();
+        state.apply(UiEvent::Key { row: 0, col: 1, pressed: true });
+        screens.tick(&state, &mut Blank).unwrap();
+        assert_eq!(screens.screensaver(), None);
+        assert_eq!(screens.current(), ScreenId::Status);
+    }
+}
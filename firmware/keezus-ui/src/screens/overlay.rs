@@ -0,0 +1,160 @@
+//! Notifications popped up over whatever screen is showing, like
+//! "Layer: NAV" or "Settings saved". One shows at a time, a new one takes
+//! the place of the last, and each fades out after a moment.
+//!
+//! `Screens` draws the popup after the screen under it on every tick, so
+//! whatever the screen changes underneath is painted over again before it
+//! reaches the display. Once the popup goes, or moves to make room for a
+//! longer one, the screen is redrawn in full to get rid of it.
+
+use core::fmt::{self, Write};
+
+use embedded_graphics::pixelcolor::Rgb565;
+use embedded_graphics::prelude::*;
+use embedded_graphics::primitives::{PrimitiveStyleBuilder, Rectangle, RoundedRectangle};
+use embedded_graphics::text::Alignment;
+use keezus_protocol::BRIGHTNESS_LEVELS;
+
+use super::ShortText;
+use crate::bootloader::Prompt;
+use crate::font::{FontStyle, TextBox, VerticalAlignment, BOLD};
+
+/// How long a notification stays up, fade included
+pub const SHOW_US: u64 = 1_500_000;
+/// The last part of it, fading into the background
+const FADE_US: u64 = 400_000;
+/// Between the text and the edge of the popup
+const PADDING: u32 = 5;
+/// Between the popup and the edge of the display
+const MARGIN: u32 = 6;
+const RADIUS: u32 = 4;
+/// Fits the longest notification text
+const TEXT_LEN: usize = 48;
+
+const BACKGROUND: Rgb565 = Rgb565::new(2, 5, 8);
+const BORDER: Rgb565 = Rgb565::new(10, 36, 28);
+const TEXT: Rgb565 = Rgb565::WHITE;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Notification {
+    /// The active layer changed, with its name
+    Layer(&'static str),
+    CapsLock(bool),
+    /// The backlight level, out of `BRIGHTNESS_LEVELS`
+    Brightness(u8),
+    SettingsSaved,
+    KeymapSaved,
+    /// The USB polling interval changed to this many ms, and the board is
+    /// about to reset to apply it
+    ReportRate(u8),
+    /// Anything the host wants to say
+    Text(ShortText),
+    /// Where the `Bootloader` key's confirmation has got to
+    Bootloader(Prompt),
+}
+
+impl fmt::Display for Notification {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Notification::Layer(name) => write!(f, "Layer: {}", name),
+            Notification::CapsLock(on) => write!(f, "Caps Lock {}", if *on { "ON" } else { "OFF" }),
+            Notification::Brightness(level) => write!(f, "Brightness {}/{}", level, BRIGHTNESS_LEVELS),
+            Notification::SettingsSaved => f.write_str("Settings saved"),
+            Notification::KeymapSaved => f.write_str("Keymap saved"),
+            Notification::ReportRate(ms) => write!(f, "Polling every {} ms, restarting", ms),
+            Notification::Text(text) => f.write_str(text.as_str()),
+            Notification::Bootloader(prompt) => write!(f, "{}", prompt),
+        }
+    }
+}
+
+pub struct Overlay {
+    text: heapless::String<TEXT_LEN>,
+    shown_at_us: u64,
+    now_us: u64,
+    showing: bool,
+    /// Where the popup was last drawn
+    drawn: Option<Rectangle>,
+}
+
+impl Overlay {
+    pub fn new() -> Overlay {
+        Overlay {
+            text: heapless::String::new(),
+            shown_at_us: 0,
+            now_us: 0,
+            showing: false,
+            drawn: None,
+        }
+    }
+
+    pub fn show(&mut self, notification: &Notification, now_us: u64) {
+        self.text.clear();
+        // Too long for the popup anyway if it doesn't fit
+        write!(self.text, "{}", notification).ok();
+        self.shown_at_us = now_us;
+        self.showing = true;
+    }
+
+    /// Moves the fade along, call every tick before the screen underneath
+    /// draws. Returns true if the popup has gone or moved since it was last
+    /// drawn, and the screen needs drawing in full to cover up where it was.
+    pub fn update(&mut self, now_us: u64, display: Size) -> bool {
+        self.now_us = now_us;
+        if now_us >= self.shown_at_us + SHOW_US {
+            self.showing = false;
+        }
+        let area = self.area(display);
+        match self.drawn {
+            Some(drawn) if area != Some(drawn) => {
+                self.drawn = None;
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// The popup's place in the middle of the display, sized to its text
+    fn area(&self, display: Size) -> Option<Rectangle> {
+        if !self.showing {
+            return None;
+        }
+        let max_width = display.width.saturating_sub(2 * MARGIN);
+        let width = (BOLD.width(&self.text) + 2 * PADDING).min(max_width);
+        let lines = BOLD.lines(&self.text, width.saturating_sub(2 * PADDING)).count() as u32;
+        let height = (lines * BOLD.line_height() + 2 * PADDING).min(display.height.saturating_sub(2 * MARGIN));
+        let top_left = Point::new((display.width - width) as i32 / 2, (display.height - height) as i32 / 2);
+        Some(Rectangle::new(top_left, Size::new(width, height)))
+    }
+
+    /// Draws the popup over the screen, if there is one
+    pub fn draw<D>(&mut self, target: &mut D) -> Result<(), D::Error>
+    where
+        D: DrawTarget<Color = Rgb565>,
+    {
+        let area = match self.area(target.bounding_box().size) {
+            Some(area) => area,
+            None => return Ok(()),
+        };
+        let left_us = (self.shown_at_us + SHOW_US).saturating_sub(self.now_us);
+        let fade = (FADE_US.saturating_sub(left_us) * 255 / FADE_US) as u8;
+        let style = PrimitiveStyleBuilder::new()
+            .fill_color(BACKGROUND)
+            .stroke_color(blend(BORDER, BACKGROUND, fade))
+            .stroke_width(1)
+            .build();
+        RoundedRectangle::with_equal_corners(area, Size::new(RADIUS, RADIUS)).into_styled(style).draw(target)?;
+        let style = FontStyle::new(&BOLD, blend(TEXT, BACKGROUND, fade));
+        TextBox::new(&self.text, area.offset(-(PADDING as i32)), style)
+            .with_alignment(Alignment::Center, VerticalAlignment::Middle)
+            .draw(target)?;
+        self.drawn = Some(area);
+        Ok(())
+    }
+}
+
+/// `from` moved `amount` 255ths of the way to `to`
+fn blend(from: Rgb565, to: Rgb565, amount: u8) -> Rgb565 {
+    let mix = |a: u8, b: u8| ((a as u32 * (255 - amount as u32) + b as u32 * amount as u32) / 255) as u8;
+    Rgb565::new(mix(from.r(), to.r()), mix(from.g(), to.g()), mix(from.b(), to.b()))
+}
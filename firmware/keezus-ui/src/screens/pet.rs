@@ -0,0 +1,133 @@
+//! The virtual pet from `pet.rs`: its sprite for how it's feeling, how old
+//! it is and a bar for each of its stats.
+
+use core::fmt::Write;
+
+use embedded_graphics::image::Image;
+use embedded_graphics::mono_font::ascii::FONT_6X10;
+use embedded_graphics::mono_font::{MonoTextStyle, MonoTextStyleBuilder};
+use embedded_graphics::pixelcolor::{Rgb565, WebColors};
+use embedded_graphics::prelude::*;
+use embedded_graphics::primitives::{PrimitiveStyle, Rectangle};
+use embedded_graphics::text::{Alignment, Baseline, Text, TextStyleBuilder};
+
+use super::{Screen, UiState};
+use crate::assets;
+use crate::pet::{Mood, Pet, STAT_MAX, TICK_US};
+
+/// How long each of a mood's two frames is shown for
+const FRAME_TIME_US: u64 = 600_000;
+const MARGIN: i32 = 8;
+const BAR_HEIGHT: u32 = 6;
+/// Label above each bar plus the bar itself
+const STAT_HEIGHT: i32 = 20;
+/// The line under the sprite
+const CAPTION_HEIGHT: i32 = 12;
+
+pub struct PetScreen {
+    pet: Pet,
+    frame: usize,
+    /// What's on the display
+    drawn_pet: Option<Pet>,
+    drawn_frame: Option<usize>,
+}
+
+impl PetScreen {
+    pub fn new() -> PetScreen {
+        PetScreen {
+            pet: Pet::new(),
+            frame: 0,
+            drawn_pet: None,
+            drawn_frame: None,
+        }
+    }
+}
+
+/// The first of the mood's frames in `assets::PET`
+fn first_frame(mood: Mood) -> usize {
+    match mood {
+        Mood::Happy => 0,
+        Mood::Okay => 2,
+        Mood::Hungry => 4,
+        Mood::Sad => 6,
+        Mood::Sleeping => 8,
+    }
+}
+
+impl Screen for PetScreen {
+    fn update(&mut self, state: &UiState) {
+        self.pet = state.pet;
+        self.frame = first_frame(self.pet.mood()) + (state.now_us / FRAME_TIME_US % 2) as usize;
+    }
+
+    fn draw<D>(&mut self, target: &mut D, full: bool) -> Result<(), D::Error>
+    where
+        D: DrawTarget<Color = Rgb565>,
+    {
+        if full {
+            self.drawn_pet = None;
+            self.drawn_frame = None;
+        }
+        // The stats go along the bottom and the sprite and its caption are
+        // centred in what's left, which only just fits in landscape
+        let area = target.bounding_box();
+        let width = area.size.width as i32;
+        let stats_top = area.size.height as i32 - MARGIN / 2 - STAT_HEIGHT * 3;
+        let frame_height = assets::PET.frames()[0].bounding_box().size.height as i32;
+        let sprite_top = (stats_top - frame_height - CAPTION_HEIGHT) / 2;
+        let sprite_center = Point::new(width / 2, sprite_top + frame_height / 2);
+
+        if self.drawn_frame != Some(self.frame) {
+            let frame = &assets::PET.frames()[self.frame];
+            Image::new(frame, sprite_center - frame.bounding_box().size / 2).draw(target)?;
+            self.drawn_frame = Some(self.frame);
+        }
+        if self.drawn_pet == Some(self.pet) {
+            return Ok(());
+        }
+        self.drawn_pet = Some(self.pet);
+
+        let text = MonoTextStyleBuilder::new()
+            .font(&FONT_6X10)
+            .text_color(Rgb565::WHITE)
+            .background_color(Rgb565::BLACK)
+            .build();
+        let centered = TextStyleBuilder::new().alignment(Alignment::Center).baseline(Baseline::Top).build();
+
+        // Cleared first as the line changes length
+        let caption_y = sprite_top + frame_height;
+        Rectangle::new(Point::new(0, caption_y), Size::new(width as u32, 10))
+            .into_styled(PrimitiveStyle::with_fill(Rgb565::BLACK))
+            .draw(target)?;
+        let mut caption: heapless::String<24> = heapless::String::new();
+        let minutes = self.pet.age as u64 * TICK_US / 60_000_000;
+        let (days, hours) = (minutes / (24 * 60), minutes / 60 % 24);
+        if days > 0 {
+            let _ = write!(caption, "{}d {}h, {}", days, hours, self.pet.mood().name());
+        } else {
+            let _ = write!(caption, "{}h {}m, {}", hours, minutes % 60, self.pet.mood().name());
+        }
+        Text::with_text_style(&caption, Point::new(width / 2, caption_y), text, centered).draw(target)?;
+
+        let stats = [
+            ("food", self.pet.food, Rgb565::CSS_ORANGE),
+            ("fun", self.pet.fun, Rgb565::CSS_HOT_PINK),
+            ("energy", self.pet.energy, Rgb565::CSS_DEEP_SKY_BLUE),
+        ];
+        let bar_width = (width - 2 * MARGIN) as u32;
+        let label = MonoTextStyle::new(&FONT_6X10, Rgb565::CSS_LIGHT_GRAY);
+        for (i, (name, value, color)) in stats.into_iter().enumerate() {
+            let y = stats_top + i as i32 * STAT_HEIGHT;
+            if full {
+                Text::with_baseline(name, Point::new(MARGIN, y), label, Baseline::Top).draw(target)?;
+            }
+            let filled = bar_width * value as u32 / STAT_MAX as u32;
+            let bar = Rectangle::new(Point::new(MARGIN, y + 12), Size::new(bar_width, BAR_HEIGHT));
+            bar.into_styled(PrimitiveStyle::with_fill(Rgb565::CSS_DIM_GRAY)).draw(target)?;
+            Rectangle::new(bar.top_left, Size::new(filled, BAR_HEIGHT))
+                .into_styled(PrimitiveStyle::with_fill(color))
+                .draw(target)?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,176 @@
+//! The matrix rain screensaver: columns of half-width katakana and digits
+//! falling at their own speeds, white at the head and fading out at the
+//! tail.
+
+use embedded_graphics::mono_font::jis_x0201::FONT_6X13;
+use embedded_graphics::mono_font::{MonoFont, MonoTextStyleBuilder};
+use embedded_graphics::pixelcolor::Rgb565;
+use embedded_graphics::prelude::*;
+use embedded_graphics::text::{Baseline, Text};
+
+use super::{Screen, UiState};
+
+const FONT: &MonoFont = &FONT_6X13;
+/// Enough for the display either way up
+const MAX_COLUMNS: usize = 32;
+const MAX_ROWS: usize = 16;
+/// Half-width katakana, U+FF66 to U+FF9D, then the digits
+const KATAKANA: u32 = 0xff66;
+const KATAKANA_COUNT: u32 = 56;
+const SYMBOLS: u32 = KATAKANA_COUNT + 10;
+/// Ticks a drop takes to fall one row, from fastest to slowest
+const MIN_TICKS_PER_ROW: u32 = 6;
+const MAX_TICKS_PER_ROW: u32 = 20;
+const MIN_TRAIL: i32 = 4;
+const MAX_TRAIL: i32 = 14;
+
+const HEAD: Rgb565 = Rgb565::new(24, 63, 24);
+const TRAIL: Rgb565 = Rgb565::new(0, 50, 4);
+const FADING: Rgb565 = Rgb565::new(0, 22, 2);
+
+#[derive(Debug, Clone, Copy)]
+struct Drop {
+    /// Row of the head, negative while it waits to fall in from the top
+    head: i32,
+    trail: i32,
+    ticks_per_row: u32,
+    ticks: u32,
+}
+
+pub struct RainScreen {
+    drops: [Drop; MAX_COLUMNS],
+    /// Which symbol is in each cell, an index into the katakana then
+    /// digits
+    cells: [[u8; MAX_ROWS]; MAX_COLUMNS],
+    /// Ticks since the last draw
+    ticks: u32,
+    /// Columns and rows, known on the first draw
+    grid: Option<(usize, usize)>,
+    rng: u32,
+}
+
+impl RainScreen {
+    pub fn new() -> RainScreen {
+        RainScreen {
+            drops: [Drop {
+                head: 0,
+                trail: 0,
+                ticks_per_row: 1,
+                ticks: 0,
+            }; MAX_COLUMNS],
+            cells: [[0; MAX_ROWS]; MAX_COLUMNS],
+            ticks: 0,
+            grid: None,
+            rng: 1,
+        }
+    }
+
+    /// Starts again from an empty screen
+    pub fn restart(&mut self, seed: u32) {
+        // xorshift gets stuck on 0
+        self.rng = seed | 1;
+        self.ticks = 0;
+        self.grid = None;
+    }
+
+    /// A new drop somewhere above the top
+    fn new_drop(&mut self, rows: usize) -> Drop {
+        let speed_range = MAX_TICKS_PER_ROW - MIN_TICKS_PER_ROW + 1;
+        let trail_range = (MAX_TRAIL - MIN_TRAIL + 1) as u32;
+        Drop {
+            head: -1 - (self.next_random() % rows as u32) as i32,
+            trail: MIN_TRAIL + (self.next_random() % trail_range) as i32,
+            ticks_per_row: MIN_TICKS_PER_ROW + self.next_random() % speed_range,
+            ticks: 0,
+        }
+    }
+
+    /// xorshift32
+    fn next_random(&mut self) -> u32 {
+        self.rng ^= self.rng << 13;
+        self.rng ^= self.rng >> 17;
+        self.rng ^= self.rng << 5;
+        self.rng
+    }
+}
+
+fn draw_cell<D>(target: &mut D, column: usize, row: i32, symbol: u8, color: Rgb565) -> Result<(), D::Error>
+where
+    D: DrawTarget<Color = Rgb565>,
+{
+    let symbol = symbol as u32;
+    let c = match symbol.checked_sub(KATAKANA_COUNT) {
+        Some(digit) => char::from_digit(digit, 10),
+        None => char::from_u32(KATAKANA + symbol),
+    };
+    let mut buf = [0; 4];
+    let text = c.unwrap_or(' ').encode_utf8(&mut buf);
+    let style = MonoTextStyleBuilder::new().font(FONT).text_color(color).background_color(Rgb565::BLACK).build();
+    let at = Point::new(
+        column as i32 * FONT.character_size.width as i32,
+        row * FONT.character_size.height as i32,
+    );
+    Text::with_baseline(text, at, style, Baseline::Top).draw(target)?;
+    Ok(())
+}
+
+impl Screen for RainScreen {
+    fn update(&mut self, _state: &UiState) {
+        // The drops fall in `draw`, which knows how many rows there are
+        self.ticks += 1;
+    }
+
+    fn draw<D>(&mut self, target: &mut D, full: bool) -> Result<(), D::Error>
+    where
+        D: DrawTarget<Color = Rgb565>,
+    {
+        let size = target.bounding_box().size;
+        let grid = (
+            (size.width / FONT.character_size.width) as usize,
+            (size.height / FONT.character_size.height) as usize,
+        );
+        let (columns, rows) = (grid.0.min(MAX_COLUMNS), grid.1.min(MAX_ROWS));
+        if full || self.grid != Some(grid) {
+            if !full {
+                target.clear(Rgb565::BLACK)?;
+            }
+            self.grid = Some(grid);
+            for column in 0..columns {
+                self.drops[column] = self.new_drop(rows);
+            }
+        }
+        let ticks = core::mem::take(&mut self.ticks);
+
+        for column in 0..columns {
+            let mut drop = self.drops[column];
+            drop.ticks += ticks;
+            while drop.ticks >= drop.ticks_per_row {
+                drop.ticks -= drop.ticks_per_row;
+                drop.head += 1;
+                let visible = |row: i32| (0..rows as i32).contains(&row);
+                if visible(drop.head - 1) {
+                    let symbol = self.cells[column][drop.head as usize - 1];
+                    draw_cell(target, column, drop.head - 1, symbol, TRAIL)?;
+                }
+                if visible(drop.head) {
+                    let symbol = (self.next_random() % SYMBOLS) as u8;
+                    self.cells[column][drop.head as usize] = symbol;
+                    draw_cell(target, column, drop.head, symbol, HEAD)?;
+                }
+                let fading = drop.head - drop.trail + 1;
+                if visible(fading) {
+                    draw_cell(target, column, fading, self.cells[column][fading as usize], FADING)?;
+                }
+                let tail = drop.head - drop.trail;
+                if visible(tail) {
+                    draw_cell(target, column, tail, 0, Rgb565::BLACK)?;
+                }
+                if tail >= rows as i32 {
+                    drop = self.new_drop(rows);
+                }
+            }
+            self.drops[column] = drop;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,72 @@
+//! Which screensaver takes over from the status screen, and when. The
+//! screensavers themselves are screens like any other, `Screens` switches
+//! to one once the keyboard has been left alone long enough and back to
+//! status on the next keypress.
+
+use super::ShortText;
+
+/// Minutes on the status screen without a keypress before the
+/// screensaver starts, unless the settings say otherwise
+pub const DEFAULT_AFTER_MIN: u8 = 3;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Saver {
+    /// Scrolling text, "SYSTEM PANIC" unless the host has set some
+    Text,
+    /// Ferris bouncing around the display
+    Ferris,
+    Starfield,
+    /// Falling katakana
+    Rain,
+}
+
+impl Saver {
+    /// In the order they're taken in turn, and numbered from 1 in the
+    /// `Screensaver` setting
+    pub const ALL: [Saver; 4] = [Saver::Text, Saver::Ferris, Saver::Starfield, Saver::Rain];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Saver::Text => "text",
+            Saver::Ferris => "ferris",
+            Saver::Starfield => "stars",
+            Saver::Rain => "rain",
+        }
+    }
+}
+
+/// The screensaver settings, which the firmware copies over every frame
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ScreensaverConfig {
+    /// Minutes without a keypress, 0 for never
+    pub after_min: u8,
+    /// 0 takes each in turn, otherwise one of `Saver::ALL` counting from 1
+    pub which: u8,
+    /// For `Saver::Text`, empty for the default
+    pub text: ShortText,
+}
+
+impl ScreensaverConfig {
+    pub const fn new() -> ScreensaverConfig {
+        ScreensaverConfig {
+            after_min: DEFAULT_AFTER_MIN,
+            which: 0,
+            text: ShortText::EMPTY,
+        }
+    }
+
+    /// The screensaver to show after `shown` have been, for taking them in
+    /// turn
+    pub fn pick(&self, shown: usize) -> Saver {
+        match self.which as usize {
+            0 => Saver::ALL[shown % Saver::ALL.len()],
+            which => Saver::ALL[(which - 1).min(Saver::ALL.len() - 1)],
+        }
+    }
+}
+
+impl Default for ScreensaverConfig {
+    fn default() -> ScreensaverConfig {
+        ScreensaverConfig::new()
+    }
+}
@@ -0,0 +1,104 @@
+//! The scrolling text screensaver, once the "SYSTEM PANIC" screen the
+//! board has shown since its first firmware. Each line is the text split
+//! in two, the left halves scrolling up and the right halves down.
+//!
+//! The text comes from `ScreensaverConfig::text`. The default one keeps
+//! its Japanese first line, others have their own in red.
+
+use embedded_graphics::pixelcolor::Rgb565;
+use embedded_graphics::prelude::*;
+use embedded_graphics::text::Text;
+
+use super::{Screen, ShortText, UiState};
+use crate::font::{FontStyle, BOLD};
+
+/// Shown when the text hasn't been set
+pub const DEFAULT_TEXT: &str = "! SYSTEM PANIC !";
+/// The default text's first line
+const JAPANESE: (&str, &str) = ("! システム", "パニック!");
+
+/// Extra scroll distance so lines are fully off screen before wrapping
+const FONT_BUFFER: i32 = 13;
+const NUM_LINES: i32 = 8;
+/// Room to the right of the right column
+const RIGHT_MARGIN: i32 = 2;
+
+pub struct ScrollerScreen {
+    y: i32,
+    text: ShortText,
+    /// The text changed, what's on the display needs clearing
+    changed: bool,
+}
+
+impl ScrollerScreen {
+    pub fn new() -> ScrollerScreen {
+        ScrollerScreen {
+            y: 0,
+            text: ShortText::EMPTY,
+            changed: false,
+        }
+    }
+
+    /// Back to the top, for when the screensaver starts
+    pub fn restart(&mut self) {
+        self.y = 0;
+    }
+}
+
+/// Splits `text` at the space nearest its middle, with the space dropped
+fn halves(text: &str) -> (&str, &str) {
+    let middle = text.len() / 2;
+    let split = text
+        .char_indices()
+        .filter(|&(_, c)| c == ' ')
+        .min_by_key(|&(at, _)| at.abs_diff(middle))
+        .map(|(at, _)| at);
+    match split {
+        Some(at) => (&text[..at], &text[at + 1..]),
+        None => (text, ""),
+    }
+}
+
+impl Screen for ScrollerScreen {
+    fn update(&mut self, state: &UiState) {
+        // Wrapped in `draw`, which knows the screen height
+        self.y += 1;
+        if state.screensaver.text != self.text {
+            self.text = state.screensaver.text;
+            self.changed = true;
+        }
+    }
+
+    fn draw<D>(&mut self, target: &mut D, _full: bool) -> Result<(), D::Error>
+    where
+        D: DrawTarget<Color = Rgb565>,
+    {
+        if self.changed {
+            self.changed = false;
+            target.clear(Rgb565::BLACK)?;
+        }
+        let text = match self.text.as_str() {
+            "" => DEFAULT_TEXT,
+            text => text,
+        };
+        let line = halves(text);
+        let first = if text == DEFAULT_TEXT { JAPANESE } else { line };
+
+        let size = target.bounding_box().size;
+        let total_height = size.height as i32 + FONT_BUFFER;
+        let right_width = BOLD.width(first.1).max(BOLD.width(line.1)) as i32;
+        let right_x = size.width as i32 - right_width - RIGHT_MARGIN;
+        self.y %= total_height;
+
+        let white = FontStyle::new(&BOLD, Rgb565::WHITE).with_background(Rgb565::BLACK);
+        let red = FontStyle::new(&BOLD, Rgb565::RED).with_background(Rgb565::BLACK);
+
+        for i in 0..NUM_LINES {
+            let y = (self.y + total_height / NUM_LINES * i) % total_height;
+            let (style, (left, right)) = if i == 0 { (red, first) } else { (white, line) };
+            Text::new(left, Point::new(0, total_height - y), style).draw(target)?;
+            Text::new(right, Point::new(right_x, y), style).draw(target)?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,298 @@
+//! Snake in game mode. WASD or IJKL steer, space pauses, and once the
+//! snake has crashed any key starts a new game.
+//!
+//! Keys come from `UiState::pressed` rather than the layout, which doesn't
+//! see presses while game mode is on.
+
+use core::fmt::Write;
+
+use embedded_graphics::mono_font::ascii::FONT_6X10;
+use embedded_graphics::mono_font::MonoTextStyleBuilder;
+use embedded_graphics::pixelcolor::{Rgb565, WebColors};
+use embedded_graphics::prelude::*;
+use embedded_graphics::primitives::{PrimitiveStyle, Rectangle};
+use embedded_graphics::text::{Alignment, Baseline, Text, TextStyleBuilder};
+
+use super::{Screen, UiState};
+use crate::font::{FontStyle, TextBox, VerticalAlignment, BOLD};
+use crate::snake::{Cell, Direction, Snake, Step};
+use crate::NUM_COLS;
+
+const CELL: i32 = 8;
+/// Space above the board for the score
+const HEADER: i32 = 14;
+/// Time between steps, shorter the longer the snake gets
+const STEP_TIME_US: u64 = 180_000;
+const FASTEST_STEP_TIME_US: u64 = 70_000;
+const SPEED_UP_PER_FOOD_US: u64 = 6_000;
+/// Keys pressed this soon after crashing don't start a new game, they
+/// were meant for the last one
+const GAME_OVER_GRACE_US: u64 = 700_000;
+
+const BODY: Rgb565 = Rgb565::CSS_LIME_GREEN;
+const HEAD: Rgb565 = Rgb565::CSS_PALE_GREEN;
+const FOOD: Rgb565 = Rgb565::CSS_TOMATO;
+const WALL: Rgb565 = Rgb565::CSS_DIM_GRAY;
+
+/// Matrix positions of the keys that steer
+const DIRECTIONS: [((usize, usize), Direction); 8] = [
+    ((0, 1), Direction::Up),
+    ((1, 0), Direction::Left),
+    ((1, 1), Direction::Down),
+    ((1, 2), Direction::Right),
+    ((0, 7), Direction::Up),
+    ((1, 6), Direction::Left),
+    ((1, 7), Direction::Down),
+    ((1, 8), Direction::Right),
+];
+const PAUSE: (usize, usize) = (3, 4);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Play {
+    /// Waiting for a direction to set off in
+    Ready,
+    Playing,
+    Paused,
+    /// Crashed at the given time
+    Over(u64),
+}
+
+pub struct SnakeScreen {
+    /// Made on the first draw, once the size of the display is known
+    snake: Option<Snake>,
+    play: Play,
+    next_step_us: u64,
+    /// Keys down as of the last update, to tell new presses apart
+    pressed: u64,
+    best: u16,
+    /// What the last step changed, for drawing
+    step: Option<Step>,
+    /// The whole board needs drawing again, e.g. to get rid of a message
+    redraw: bool,
+    seed: u32,
+}
+
+impl SnakeScreen {
+    pub fn new() -> SnakeScreen {
+        SnakeScreen {
+            snake: None,
+            play: Play::Ready,
+            next_step_us: 0,
+            pressed: 0,
+            best: 0,
+            step: None,
+            redraw: true,
+            seed: 0,
+        }
+    }
+
+    /// Throws away any game in progress, for when game mode is entered
+    pub fn restart(&mut self) {
+        self.snake = None;
+        self.play = Play::Ready;
+        self.step = None;
+        self.redraw = true;
+    }
+
+    /// The best score seen, including any the screen was told about
+    pub fn best(&self) -> u16 {
+        self.best
+    }
+
+    fn new_game(&mut self) {
+        if let Some(snake) = &self.snake {
+            self.snake = Some(Snake::new(snake.cols(), snake.rows(), self.seed));
+        }
+        self.play = Play::Ready;
+        self.step = None;
+        self.redraw = true;
+    }
+}
+
+fn step_time_us(score: u16) -> u64 {
+    STEP_TIME_US.saturating_sub(score as u64 * SPEED_UP_PER_FOOD_US).max(FASTEST_STEP_TIME_US)
+}
+
+impl Screen for SnakeScreen {
+    fn update(&mut self, state: &UiState) {
+        self.best = self.best.max(state.high_score);
+        self.seed = state.now_us as u32;
+        let presses = state.pressed & !self.pressed;
+        self.pressed = state.pressed;
+        if self.snake.is_none() {
+            return;
+        }
+
+        for bit in (0..64).filter(|bit| presses & (1 << bit) != 0) {
+            let key = (bit / NUM_COLS, bit % NUM_COLS);
+            let direction = DIRECTIONS.iter().find(|(at, _)| *at == key).map(|&(_, direction)| direction);
+            match self.play {
+                Play::Over(at) if state.now_us >= at + GAME_OVER_GRACE_US => self.new_game(),
+                Play::Over(_) => {}
+                Play::Playing if key == PAUSE => {
+                    self.play = Play::Paused;
+                    self.redraw = true;
+                }
+                Play::Paused if key == PAUSE => {
+                    self.play = Play::Playing;
+                    self.next_step_us = state.now_us;
+                    self.redraw = true;
+                }
+                Play::Ready | Play::Paused if direction.is_some() => {
+                    self.play = Play::Playing;
+                    self.next_step_us = state.now_us;
+                    self.redraw = true;
+                }
+                _ => {}
+            }
+            if let (Some(direction), Some(snake)) = (direction, self.snake.as_mut()) {
+                if self.play == Play::Playing {
+                    snake.turn(direction);
+                }
+            }
+        }
+
+        let snake = self.snake.as_mut().unwrap();
+        if self.play != Play::Playing || state.now_us < self.next_step_us || self.step.is_some() {
+            return;
+        }
+        self.next_step_us = state.now_us + step_time_us(snake.score());
+        let step = snake.step();
+        if step == Step::Died {
+            self.play = Play::Over(state.now_us);
+            self.best = self.best.max(snake.score());
+        }
+        self.step = Some(step);
+    }
+
+    fn draw<D>(&mut self, target: &mut D, full: bool) -> Result<(), D::Error>
+    where
+        D: DrawTarget<Color = Rgb565>,
+    {
+        let size = target.bounding_box().size;
+        let (cols, rows) = ((size.width as i32 - 2) / CELL, (size.height as i32 - HEADER - 2) / CELL);
+        if self.snake.is_none() {
+            self.snake = Some(Snake::new(cols as u8, rows as u8, self.seed));
+            self.redraw = true;
+        }
+        let snake = self.snake.as_ref().unwrap();
+        let board = Board::new(size, snake);
+
+        if full || self.redraw {
+            self.redraw = false;
+            self.step = None;
+            target.clear(Rgb565::BLACK)?;
+            board.outline().into_styled(PrimitiveStyle::with_stroke(WALL, 1)).draw(target)?;
+            for (i, cell) in snake.body().enumerate() {
+                board.fill(target, cell, if i == 0 { HEAD } else { BODY })?;
+            }
+            if let Some(food) = snake.food() {
+                board.fill(target, food, FOOD)?;
+            }
+            self.draw_header(target, snake.score())?;
+            return match self.play {
+                Play::Ready => message(target, &board, "SNAKE\nWASD or IJKL"),
+                Play::Paused => message(target, &board, "PAUSED"),
+                Play::Over(_) => message(target, &board, "GAME OVER\nany key for more"),
+                Play::Playing => Ok(()),
+            };
+        }
+
+        match self.step.take() {
+            Some(Step::Moved { head, tail }) => {
+                board.fill(target, tail, Rgb565::BLACK)?;
+                if let Some(neck) = snake.body().nth(1) {
+                    board.fill(target, neck, BODY)?;
+                }
+                board.fill(target, head, HEAD)?;
+            }
+            Some(Step::Ate { head, food }) => {
+                if let Some(neck) = snake.body().nth(1) {
+                    board.fill(target, neck, BODY)?;
+                }
+                board.fill(target, head, HEAD)?;
+                if let Some(food) = food {
+                    board.fill(target, food, FOOD)?;
+                }
+                self.draw_header(target, snake.score())?;
+            }
+            Some(Step::Died) => {
+                self.draw_header(target, snake.score())?;
+                message(target, &board, "GAME OVER\nany key for more")?;
+            }
+            None => {}
+        }
+        Ok(())
+    }
+}
+
+impl SnakeScreen {
+    fn draw_header<D>(&self, target: &mut D, score: u16) -> Result<(), D::Error>
+    where
+        D: DrawTarget<Color = Rgb565>,
+    {
+        let width = target.bounding_box().size.width;
+        Rectangle::new(Point::zero(), Size::new(width, HEADER as u32 - 2))
+            .into_styled(PrimitiveStyle::with_fill(Rgb565::BLACK))
+            .draw(target)?;
+        let style = MonoTextStyleBuilder::new().font(&FONT_6X10).text_color(Rgb565::WHITE).build();
+        let mut text: heapless::String<16> = heapless::String::new();
+        let _ = write!(text, "score {}", score);
+        Text::with_baseline(&text, Point::new(2, 1), style, Baseline::Top).draw(target)?;
+        text.clear();
+        let _ = write!(text, "best {}", self.best.max(score));
+        let right = TextStyleBuilder::new().alignment(Alignment::Right).baseline(Baseline::Top).build();
+        Text::with_text_style(&text, Point::new(width as i32 - 2, 1), style, right).draw(target)?;
+        Ok(())
+    }
+}
+
+/// Where the board's cells are on the display, centred under the header
+struct Board {
+    top_left: Point,
+    size: Size,
+}
+
+impl Board {
+    fn new(display: Size, snake: &Snake) -> Board {
+        let size = Size::new(snake.cols() as u32 * CELL as u32, snake.rows() as u32 * CELL as u32);
+        let free_height = display.height as i32 - HEADER;
+        Board {
+            top_left: Point::new(
+                (display.width - size.width) as i32 / 2,
+                HEADER + (free_height - size.height as i32) / 2,
+            ),
+            size,
+        }
+    }
+
+    fn outline(&self) -> Rectangle {
+        Rectangle::new(self.top_left - Point::new(1, 1), self.size + Size::new(2, 2))
+    }
+
+    /// Cells are drawn a pixel smaller so the snake's segments show
+    fn fill<D>(&self, target: &mut D, cell: Cell, color: Rgb565) -> Result<(), D::Error>
+    where
+        D: DrawTarget<Color = Rgb565>,
+    {
+        let at = self.top_left + Point::new(cell.x as i32 * CELL, cell.y as i32 * CELL);
+        target.fill_solid(&Rectangle::new(at, Size::new(CELL as u32 - 1, CELL as u32 - 1)), color)
+    }
+}
+
+/// A message in the top half of the board, clear of where the snake starts
+fn message<D>(target: &mut D, board: &Board, text: &str) -> Result<(), D::Error>
+where
+    D: DrawTarget<Color = Rgb565>,
+{
+    // On a band of its own so the snake doesn't show between the letters
+    let lines = BOLD.lines(text, board.size.width).count() as u32;
+    let height = lines * BOLD.line_height() + 6;
+    let top = board.top_left.y + (board.size.height / 2 - height) as i32 / 2;
+    let area = Rectangle::new(Point::new(board.top_left.x, top), Size::new(board.size.width, height));
+    target.fill_solid(&area, Rgb565::BLACK)?;
+    let style = FontStyle::new(&BOLD, Rgb565::WHITE);
+    TextBox::new(text, area, style)
+        .with_alignment(Alignment::Center, VerticalAlignment::Middle)
+        .draw(target)
+}
@@ -0,0 +1,42 @@
+//! Ferris hopping up and down, played from `assets/ferris_hop.gif`.
+
+use embedded_graphics::image::Image;
+use embedded_graphics::pixelcolor::Rgb565;
+use embedded_graphics::prelude::*;
+
+use super::{Screen, UiState};
+use crate::animation::{Playback, Player};
+use crate::assets;
+
+pub struct SpriteScreen {
+    player: Player,
+    changed: bool,
+}
+
+impl SpriteScreen {
+    pub fn new() -> SpriteScreen {
+        SpriteScreen {
+            player: Player::new(&assets::FERRIS_HOP, Playback::PingPong),
+            changed: true,
+        }
+    }
+}
+
+impl Screen for SpriteScreen {
+    fn update(&mut self, _state: &UiState) {
+        self.changed |= self.player.tick();
+    }
+
+    fn draw<D>(&mut self, target: &mut D, full: bool) -> Result<(), D::Error>
+    where
+        D: DrawTarget<Color = Rgb565>,
+    {
+        if !(full || self.changed) {
+            return Ok(());
+        }
+        self.changed = false;
+        let frame = self.player.frame();
+        let at = target.bounding_box().center() - frame.bounding_box().size / 2;
+        Image::new(frame, at).draw(target)
+    }
+}
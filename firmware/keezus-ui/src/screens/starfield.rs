@@ -0,0 +1,114 @@
+//! The starfield screensaver: flying forwards through stars that get
+//! bigger and brighter as they come closer.
+
+use embedded_graphics::pixelcolor::Rgb565;
+use embedded_graphics::prelude::*;
+use embedded_graphics::primitives::Rectangle;
+
+use super::{Screen, UiState};
+
+const STARS: usize = 48;
+/// Stars start this far away and are passed at 0
+const FAR: i32 = 1024;
+/// Distance covered per tick
+const SPEED: i32 = 3;
+/// How far stars can be from the line of flight, either way. They're
+/// close to the middle when far away and spread out as they come closer.
+const SPREAD: i32 = 64;
+/// Stars closer than this are drawn two pixels wide
+const NEAR: i32 = FAR / 4;
+
+#[derive(Debug, Clone, Copy)]
+struct Star {
+    x: i32,
+    y: i32,
+    z: i32,
+}
+
+pub struct StarfieldScreen {
+    stars: [Star; STARS],
+    /// Where each star was last drawn, to be cleared
+    drawn: [Option<Rectangle>; STARS],
+    rng: u32,
+}
+
+impl StarfieldScreen {
+    pub fn new() -> StarfieldScreen {
+        let mut screen = StarfieldScreen {
+            stars: [Star { x: 0, y: 0, z: 0 }; STARS],
+            drawn: [None; STARS],
+            rng: 1,
+        };
+        screen.restart(0);
+        screen
+    }
+
+    /// A new sky, with the stars at all distances so it doesn't start
+    /// empty
+    pub fn restart(&mut self, seed: u32) {
+        // xorshift gets stuck on 0
+        self.rng = seed | 1;
+        for i in 0..STARS {
+            let mut star = self.new_star();
+            star.z = (i as i32 + 1) * FAR / STARS as i32;
+            self.stars[i] = star;
+        }
+        self.drawn = [None; STARS];
+    }
+
+    fn new_star(&mut self) -> Star {
+        let range = 2 * SPREAD as u32 + 1;
+        Star {
+            x: (self.next_random() % range) as i32 - SPREAD,
+            y: (self.next_random() % range) as i32 - SPREAD,
+            z: FAR,
+        }
+    }
+
+    /// xorshift32
+    fn next_random(&mut self) -> u32 {
+        self.rng ^= self.rng << 13;
+        self.rng ^= self.rng >> 17;
+        self.rng ^= self.rng << 5;
+        self.rng
+    }
+}
+
+impl Screen for StarfieldScreen {
+    fn update(&mut self, _state: &UiState) {
+        for i in 0..STARS {
+            self.stars[i].z -= SPEED;
+            if self.stars[i].z <= 0 {
+                self.stars[i] = self.new_star();
+            }
+        }
+    }
+
+    fn draw<D>(&mut self, target: &mut D, full: bool) -> Result<(), D::Error>
+    where
+        D: DrawTarget<Color = Rgb565>,
+    {
+        let area = target.bounding_box();
+        let center = area.center();
+        let scale = area.size.width.max(area.size.height) as i32;
+        for i in 0..STARS {
+            if let Some(drawn) = self.drawn[i].take().filter(|_| !full) {
+                target.fill_solid(&drawn, Rgb565::BLACK)?;
+            }
+            let star = self.stars[i];
+            let at = center + Point::new(star.x * scale / star.z, star.y * scale / star.z);
+            if !area.contains(at) {
+                // Gone past the edge, it comes back from the distance
+                self.stars[i] = self.new_star();
+                continue;
+            }
+            let size = if star.z < NEAR { 2 } else { 1 };
+            let level = (31 - 28 * star.z / FAR) as u8;
+            let color = Rgb565::new(level, level * 2, level);
+            let dot = Rectangle::new(at, Size::new(size, size));
+            target.fill_solid(&dot, color)?;
+            self.drawn[i] = Some(dot);
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,153 @@
+//! Typing statistics from `stats.rs`: words per minute, today's and all
+//! time presses, a heatmap of the keys on the base layer and the last
+//! week, one bar per day.
+
+use core::fmt::Write;
+
+use embedded_graphics::mono_font::ascii::{FONT_10X20, FONT_5X8, FONT_6X10};
+use embedded_graphics::mono_font::{MonoTextStyle, MonoTextStyleBuilder};
+use embedded_graphics::pixelcolor::{Rgb565, WebColors};
+use embedded_graphics::prelude::*;
+use embedded_graphics::primitives::{PrimitiveStyle, Rectangle};
+use embedded_graphics::text::{Alignment, Baseline, Text, TextStyleBuilder};
+
+use super::{LayerLegends, Screen, UiState};
+use crate::stats::{Stats, DAYS};
+use crate::{NUM_COLS, NUM_ROWS};
+
+const MARGIN: i32 = 4;
+const HEATMAP_Y: i32 = 38;
+const MAX_CELL_HEIGHT: i32 = 14;
+/// Room under the bars for the days' initials
+const DAY_LABEL_HEIGHT: i32 = 10;
+const NOTHING: Rgb565 = Rgb565::new(3, 6, 3);
+/// Sunday first, like `DateTime::weekday`
+const WEEKDAYS: [&str; 7] = ["S", "M", "T", "W", "T", "F", "S"];
+
+pub struct StatsScreen {
+    /// For the base layer's legends on the heatmap
+    legends: &'static [LayerLegends],
+    stats: Stats,
+    drawn: Option<Stats>,
+}
+
+impl StatsScreen {
+    pub fn new(legends: &'static [LayerLegends]) -> StatsScreen {
+        StatsScreen {
+            legends,
+            stats: Stats::new(),
+            drawn: None,
+        }
+    }
+}
+
+/// Dark blue through red to yellow as `count` goes up to `max`
+fn heat(count: u32, max: u32) -> Rgb565 {
+    if count == 0 {
+        return NOTHING;
+    }
+    let t = (count as u64 * 62 / max.max(1) as u64) as u8;
+    if t < 31 {
+        Rgb565::new(t + 1, 0, 30 - t)
+    } else {
+        Rgb565::new(31, (t - 31) * 2 + 1, 0)
+    }
+}
+
+impl Screen for StatsScreen {
+    fn update(&mut self, state: &UiState) {
+        self.stats = state.stats;
+    }
+
+    fn draw<D>(&mut self, target: &mut D, full: bool) -> Result<(), D::Error>
+    where
+        D: DrawTarget<Color = Rgb565>,
+    {
+        if full {
+            self.drawn = None;
+        }
+        if self.drawn == Some(self.stats) {
+            return Ok(());
+        }
+        let stats = self.stats;
+        self.drawn = Some(stats);
+
+        let size = target.bounding_box().size;
+        let width = size.width as i32;
+        Rectangle::new(Point::zero(), Size::new(size.width, HEATMAP_Y as u32))
+            .into_styled(PrimitiveStyle::with_fill(Rgb565::BLACK))
+            .draw(target)?;
+
+        let label = MonoTextStyle::new(&FONT_6X10, Rgb565::CSS_LIGHT_GRAY);
+        let number = MonoTextStyle::new(&FONT_6X10, Rgb565::WHITE);
+        let right = TextStyleBuilder::new().alignment(Alignment::Right).baseline(Baseline::Top).build();
+        let mut text: heapless::String<16> = heapless::String::new();
+
+        let _ = write!(text, "{}", stats.wpm());
+        let big = MonoTextStyle::new(&FONT_10X20, Rgb565::CYAN);
+        let end = Text::with_baseline(&text, Point::new(MARGIN, 2), big, Baseline::Top).draw(target)?;
+        Text::with_baseline(" wpm", end + Point::new(0, 8), label, Baseline::Top).draw(target)?;
+
+        Text::with_text_style("today", Point::new(width - MARGIN, 2), label, right).draw(target)?;
+        text.clear();
+        let _ = write!(text, "{}", stats.days[0]);
+        Text::with_text_style(&text, Point::new(width - MARGIN, 13), number, right).draw(target)?;
+
+        let end = Text::with_baseline("total ", Point::new(MARGIN, 25), label, Baseline::Top).draw(target)?;
+        text.clear();
+        let _ = write!(text, "{}", stats.total);
+        Text::with_baseline(&text, end, number, Baseline::Top).draw(target)?;
+
+        // The heatmap, brightest for the most pressed key
+        let cell_width = (width - 2 * MARGIN) / NUM_COLS as i32;
+        let cell_height = cell_width.min(MAX_CELL_HEIGHT);
+        let left = (width - cell_width * NUM_COLS as i32) / 2;
+        let max = stats.keys.iter().copied().max().unwrap_or(0);
+        let centered = TextStyleBuilder::new().alignment(Alignment::Center).baseline(Baseline::Middle).build();
+        for row in 0..NUM_ROWS {
+            for col in 0..NUM_COLS {
+                let color = heat(stats.keys[row * NUM_COLS + col], max);
+                let cell = Rectangle::new(
+                    Point::new(left + col as i32 * cell_width, HEATMAP_Y + row as i32 * cell_height),
+                    Size::new(cell_width as u32 - 1, cell_height as u32 - 1),
+                );
+                cell.into_styled(PrimitiveStyle::with_fill(color)).draw(target)?;
+                // Only the ones that fit, so "Q" but not "Spc"
+                let legend = self.legends.first().map_or("", |legends| legends[row][col]);
+                if legend.chars().count() == 1 {
+                    let text_color = if color.g() > 40 { Rgb565::BLACK } else { Rgb565::WHITE };
+                    let style = MonoTextStyleBuilder::new().font(&FONT_5X8).text_color(text_color).build();
+                    Text::with_text_style(legend, cell.center(), style, centered).draw(target)?;
+                }
+            }
+        }
+
+        // A bar per day, oldest on the left
+        let chart_top = HEATMAP_Y + NUM_ROWS as i32 * cell_height + MARGIN;
+        let bars_bottom = size.height as i32 - MARGIN / 2 - DAY_LABEL_HEIGHT;
+        let bar_space = (width - 2 * MARGIN) / DAYS as i32;
+        let most = stats.days.iter().copied().max().unwrap_or(0).max(1);
+        let tallest = (bars_bottom - chart_top).max(1);
+        let chart = Rectangle::new(Point::new(0, chart_top), Size::new(size.width, size.height - chart_top as u32));
+        chart.into_styled(PrimitiveStyle::with_fill(Rgb565::BLACK)).draw(target)?;
+        let day_label = TextStyleBuilder::new().alignment(Alignment::Center).baseline(Baseline::Top).build();
+        for (age, &count) in stats.days.iter().enumerate() {
+            let x = MARGIN + (DAYS - 1 - age) as i32 * bar_space;
+            let height = (count as u64 * tallest as u64 / most as u64) as i32;
+            let color = if age == 0 { Rgb565::CYAN } else { Rgb565::CSS_STEEL_BLUE };
+            Rectangle::new(Point::new(x + 2, bars_bottom - height), Size::new(bar_space as u32 - 4, height as u32))
+                .into_styled(PrimitiveStyle::with_fill(color))
+                .draw(target)?;
+            Rectangle::new(Point::new(x, bars_bottom), Size::new(bar_space as u32, 1))
+                .into_styled(PrimitiveStyle::with_fill(Rgb565::CSS_DIM_GRAY))
+                .draw(target)?;
+            // Days are only named once the clock has been set
+            if let Some(today) = stats.today {
+                let weekday = (today as i64 - age as i64 + 4).rem_euclid(7) as usize;
+                let at = Point::new(x + bar_space / 2, bars_bottom + 2);
+                Text::with_text_style(WEEKDAYS[weekday], at, label, day_label).draw(target)?;
+            }
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,170 @@
+//! An overview of the keyboard's state: the active layer, held modifiers,
+//! Caps Lock and the last few keys pressed.
+
+use core::fmt::Write;
+
+use embedded_graphics::mono_font::ascii::{FONT_10X20, FONT_6X10, FONT_8X13_BOLD};
+use embedded_graphics::mono_font::{MonoTextStyle, MonoTextStyleBuilder};
+use embedded_graphics::pixelcolor::{Rgb565, WebColors};
+use embedded_graphics::prelude::*;
+use embedded_graphics::primitives::{PrimitiveStyle, Rectangle};
+use embedded_graphics::text::{Baseline, Text};
+
+use super::{
+    Screen, UiState, LAST_KEYS, MOD_LALT, MOD_LCTRL, MOD_LGUI, MOD_LSHIFT, MOD_RALT, MOD_RCTRL, MOD_RGUI,
+    MOD_RSHIFT,
+};
+
+const MODIFIERS: [(&str, u8); 4] = [
+    ("CTL", MOD_LCTRL | MOD_RCTRL),
+    ("SFT", MOD_LSHIFT | MOD_RSHIFT),
+    ("ALT", MOD_LALT | MOD_RALT),
+    ("GUI", MOD_LGUI | MOD_RGUI),
+];
+
+const MARGIN: i32 = 4;
+
+/// Where each section starts, spread out over the screen's height so the
+/// same layout works in portrait and landscape
+struct Rows {
+    layer: i32,
+    mods: i32,
+    last_keys: i32,
+    polling: i32,
+}
+
+impl Rows {
+    fn new(height: i32) -> Rows {
+        let at = |percent: i32| height * percent / 100;
+        Rows {
+            layer: at(15),
+            mods: at(39),
+            last_keys: at(62),
+            polling: at(87),
+        }
+    }
+}
+
+/// The parts of `UiState` this screen shows
+#[derive(Clone, Copy, PartialEq)]
+struct Shown {
+    layer_name: &'static str,
+    mods: u8,
+    caps_lock: bool,
+    last_keys: [&'static str; LAST_KEYS],
+    poll_interval_ms: u8,
+}
+
+pub struct StatusScreen {
+    current: Option<Shown>,
+    drawn: Option<Shown>,
+}
+
+impl StatusScreen {
+    pub fn new() -> StatusScreen {
+        StatusScreen {
+            current: None,
+            drawn: None,
+        }
+    }
+}
+
+impl Screen for StatusScreen {
+    fn update(&mut self, state: &UiState) {
+        self.current = Some(Shown {
+            layer_name: state.layer_name,
+            mods: state.mods,
+            caps_lock: state.caps_lock,
+            last_keys: state.last_keys,
+            poll_interval_ms: state.poll_interval_ms,
+        });
+    }
+
+    fn draw<D>(&mut self, target: &mut D, full: bool) -> Result<(), D::Error>
+    where
+        D: DrawTarget<Color = Rgb565>,
+    {
+        let shown = match self.current {
+            Some(shown) => shown,
+            None => return Ok(()),
+        };
+        let drawn = if full { None } else { self.drawn };
+        if drawn == Some(shown) {
+            return Ok(());
+        }
+        self.drawn = Some(shown);
+
+        let Size { width, height } = target.bounding_box().size;
+        let rows = Rows::new(height as i32);
+        let label = MonoTextStyle::new(&FONT_6X10, Rgb565::CSS_GRAY);
+        let body = MonoTextStyleBuilder::new()
+            .font(&FONT_6X10)
+            .text_color(Rgb565::WHITE)
+            .background_color(Rgb565::BLACK)
+            .build();
+
+        if drawn.is_none() {
+            let title = MonoTextStyle::new(&FONT_8X13_BOLD, Rgb565::YELLOW);
+            Text::with_baseline("keezus", Point::new(MARGIN, MARGIN), title, Baseline::Top).draw(target)?;
+            Text::with_baseline("layer", Point::new(MARGIN, rows.layer), label, Baseline::Top).draw(target)?;
+            Text::with_baseline("last keys", Point::new(MARGIN, rows.last_keys), label, Baseline::Top).draw(target)?;
+        }
+
+        if drawn.map(|d| d.layer_name) != Some(shown.layer_name) {
+            let area = Rectangle::new(Point::new(0, rows.layer + 10), Size::new(width, 20));
+            area.into_styled(PrimitiveStyle::with_fill(Rgb565::BLACK)).draw(target)?;
+            let name = MonoTextStyle::new(&FONT_10X20, Rgb565::CYAN);
+            Text::with_baseline(shown.layer_name, Point::new(MARGIN, rows.layer + 10), name, Baseline::Top)
+                .draw(target)?;
+        }
+
+        if drawn.map(|d| (d.mods, d.caps_lock)) != Some((shown.mods, shown.caps_lock)) {
+            let boxes = MODIFIERS
+                .iter()
+                .map(|&(name, bits)| (name, shown.mods & bits != 0))
+                .chain(core::iter::once(("CAPS", shown.caps_lock)));
+            let mut x = MARGIN;
+            for (name, active) in boxes {
+                x += draw_indicator(target, name, Point::new(x, rows.mods), active)? + 2;
+            }
+        }
+
+        if drawn.map(|d| d.last_keys) != Some(shown.last_keys) {
+            let mut line: heapless::String<64> = heapless::String::new();
+            for key in shown.last_keys.iter().filter(|key| !key.is_empty()) {
+                let _ = write!(line, "{} ", key);
+            }
+            // Padded so a shorter line overwrites a longer one
+            let columns = ((width as i32 - MARGIN) / 6) as usize;
+            let _ = write!(line, "{:1$}", "", columns.saturating_sub(line.len()));
+            let text = line.get(..columns.min(line.len())).unwrap_or(&line);
+            Text::with_baseline(text, Point::new(MARGIN, rows.last_keys + 12), body, Baseline::Top).draw(target)?;
+        }
+
+        if drawn.map(|d| d.poll_interval_ms) != Some(shown.poll_interval_ms) {
+            let mut line: heapless::String<32> = heapless::String::new();
+            let _ = write!(line, "polling {} ms ", shown.poll_interval_ms);
+            Text::with_baseline(&line, Point::new(MARGIN, rows.polling), body, Baseline::Top).draw(target)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Draws `name` in a box, filled in if `active`, and returns its width
+fn draw_indicator<D>(target: &mut D, name: &str, at: Point, active: bool) -> Result<i32, D::Error>
+where
+    D: DrawTarget<Color = Rgb565>,
+{
+    let width = name.len() as u32 * 6 + 4;
+    let (fill, text) = if active {
+        (Rgb565::GREEN, Rgb565::BLACK)
+    } else {
+        (Rgb565::BLACK, Rgb565::CSS_DIM_GRAY)
+    };
+    let style = PrimitiveStyle::with_fill(fill);
+    Rectangle::new(at, Size::new(width, 14)).into_styled(style).draw(target)?;
+    Text::with_baseline(name, at + Point::new(2, 2), MonoTextStyle::new(&FONT_6X10, text), Baseline::Top)
+        .draw(target)?;
+    Ok(width as i32)
+}
@@ -0,0 +1,226 @@
+//! Snake, the first of the games game mode runs.
+//!
+//! Like `pet.rs` this is only the game: the board size, a seed for where
+//! the food turns up, turns and steps go in, and what moved comes out, so
+//! `screens::snake` can draw just the cells that changed.
+
+use heapless::Deque;
+
+/// The biggest board, 20x18 cells fits the display either way round
+pub const MAX_CELLS: usize = 20 * 18;
+const OCCUPIED_WORDS: usize = MAX_CELLS.div_ceil(32);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Cell {
+    pub x: u8,
+    pub y: u8,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Direction {
+    Up,
+    Down,
+    Left,
+    Right,
+}
+
+impl Direction {
+    fn opposite(self) -> Direction {
+        match self {
+            Direction::Up => Direction::Down,
+            Direction::Down => Direction::Up,
+            Direction::Left => Direction::Right,
+            Direction::Right => Direction::Left,
+        }
+    }
+}
+
+/// What a `Snake::step` changed
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Step {
+    /// The head moved on and the end of the tail left `tail`
+    Moved { head: Cell, tail: Cell },
+    /// The head moved onto the food and the snake grew, the next food is at
+    /// `food`. `None` when the snake fills the whole board.
+    Ate { head: Cell, food: Option<Cell> },
+    /// Ran into a wall or itself
+    Died,
+}
+
+pub struct Snake {
+    cols: u8,
+    rows: u8,
+    /// Head first
+    body: Deque<Cell, MAX_CELLS>,
+    /// One bit per cell, set where the snake is
+    occupied: [u32; OCCUPIED_WORDS],
+    direction: Direction,
+    /// Where the next step goes, turns only take effect on a step
+    next_direction: Direction,
+    food: Option<Cell>,
+    rng: u32,
+    score: u16,
+    alive: bool,
+}
+
+impl Snake {
+    /// A three cell snake in the middle of a `cols` by `rows` board, heading
+    /// right. The board is cut down to `MAX_CELLS` if it's bigger.
+    pub fn new(cols: u8, rows: u8, seed: u32) -> Snake {
+        let cols = cols.clamp(4, 20);
+        let rows = rows.clamp(3, (MAX_CELLS / cols as usize).min(u8::MAX as usize) as u8);
+        let mut snake = Snake {
+            cols,
+            rows,
+            body: Deque::new(),
+            occupied: [0; OCCUPIED_WORDS],
+            direction: Direction::Right,
+            next_direction: Direction::Right,
+            food: None,
+            // xorshift gets stuck on 0
+            rng: seed | 1,
+            score: 0,
+            alive: true,
+        };
+        let (x, y) = (cols / 2, rows / 2);
+        for dx in 0..3 {
+            snake.grow_tail(Cell { x: x - dx, y });
+        }
+        snake.food = snake.place_food();
+        snake
+    }
+
+    pub fn cols(&self) -> u8 {
+        self.cols
+    }
+
+    pub fn rows(&self) -> u8 {
+        self.rows
+    }
+
+    /// Head first
+    pub fn body(&self) -> impl Iterator<Item = Cell> + '_ {
+        self.body.iter().copied()
+    }
+
+    pub fn food(&self) -> Option<Cell> {
+        self.food
+    }
+
+    /// Food eaten so far
+    pub fn score(&self) -> u16 {
+        self.score
+    }
+
+    pub fn alive(&self) -> bool {
+        self.alive
+    }
+
+    /// Heads that way from the next step on, unless it would turn the
+    /// snake back onto itself
+    pub fn turn(&mut self, direction: Direction) {
+        if direction != self.direction.opposite() {
+            self.next_direction = direction;
+        }
+    }
+
+    pub fn step(&mut self) -> Step {
+        if !self.alive {
+            return Step::Died;
+        }
+        self.direction = self.next_direction;
+        let Some(head) = self.body.front().and_then(|&head| self.next_cell(head)) else {
+            self.alive = false;
+            return Step::Died;
+        };
+
+        if Some(head) == self.food {
+            self.score = self.score.saturating_add(1);
+            self.grow_head(head);
+            self.food = self.place_food();
+            return Step::Ate { head, food: self.food };
+        }
+
+        // The tail moves out of the way in the same step, so following it
+        // around is fine
+        let tail = self.body.pop_back().unwrap();
+        self.set_occupied(tail, false);
+        if self.is_occupied(head) {
+            self.alive = false;
+            return Step::Died;
+        }
+        self.grow_head(head);
+        Step::Moved { head, tail }
+    }
+
+    fn next_cell(&self, Cell { x, y }: Cell) -> Option<Cell> {
+        let (x, y) = match self.direction {
+            Direction::Up => (x, y.checked_sub(1)?),
+            Direction::Down => (x, y + 1),
+            Direction::Left => (x.checked_sub(1)?, y),
+            Direction::Right => (x + 1, y),
+        };
+        (x < self.cols && y < self.rows).then_some(Cell { x, y })
+    }
+
+    fn grow_head(&mut self, cell: Cell) {
+        self.set_occupied(cell, true);
+        // Can't be full, there's at most one cell per board square
+        let _ = self.body.push_front(cell);
+    }
+
+    fn grow_tail(&mut self, cell: Cell) {
+        self.set_occupied(cell, true);
+        let _ = self.body.push_back(cell);
+    }
+
+    fn index(&self, cell: Cell) -> usize {
+        cell.y as usize * self.cols as usize + cell.x as usize
+    }
+
+    fn is_occupied(&self, cell: Cell) -> bool {
+        let i = self.index(cell);
+        self.occupied[i / 32] & (1 << (i % 32)) != 0
+    }
+
+    fn set_occupied(&mut self, cell: Cell, occupied: bool) {
+        let i = self.index(cell);
+        if occupied {
+            self.occupied[i / 32] |= 1 << (i % 32);
+        } else {
+            self.occupied[i / 32] &= !(1 << (i % 32));
+        }
+    }
+
+    /// A free cell picked at random, `None` if there aren't any
+    fn place_food(&mut self) -> Option<Cell> {
+        let cells = self.cols as usize * self.rows as usize;
+        let free = cells - self.body.len();
+        if free == 0 {
+            return None;
+        }
+        let mut skip = self.next_random() as usize % free;
+        for i in 0..cells {
+            let cell = Cell {
+                x: (i % self.cols as usize) as u8,
+                y: (i / self.cols as usize) as u8,
+            };
+            if self.is_occupied(cell) {
+                continue;
+            }
+            if skip == 0 {
+                return Some(cell);
+            }
+            skip -= 1;
+        }
+        None
+    }
+
+    /// xorshift32
+    fn next_random(&mut self) -> u32 {
+        self.rng ^= self.rng << 13;
+        self.rng ^= self.rng >> 17;
+        self.rng ^= self.rng << 5;
+        self.rng
+    }
+}
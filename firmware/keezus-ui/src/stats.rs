@@ -0,0 +1,298 @@
+//! Typing statistics: presses per key, per day and in total, and words per
+//! minute over the last minute.
+//!
+//! Like `pet.rs` this is only the counting, so the firmware, which keeps
+//! the counts in flash and hands them to the host, and `keezus-sim` count
+//! the same way. Days come from the wall clock once the host has set it;
+//! until then presses go to whichever day was current when it was last
+//! known.
+
+use crate::{NUM_COLS, NUM_ROWS};
+
+pub const KEYS: usize = NUM_ROWS * NUM_COLS;
+/// Days of history kept, today included
+pub const DAYS: usize = 7;
+/// Words per minute are counted the usual way, five keypresses to a word
+pub const PRESSES_PER_WORD: u32 = 5;
+
+/// Words per minute come from the presses in the last `WPM_BUCKETS` of
+/// these, a minute in all
+const WPM_BUCKET_US: u64 = 5_000_000;
+const WPM_BUCKETS: usize = 12;
+
+/// Bytes `to_bytes` produces
+pub const SAVED_LEN: usize = 4 + KEYS * 4 + DAYS * 4 + 4;
+/// How `today` is saved when the day isn't known
+const NO_DAY: u32 = u32::MAX;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Stats {
+    /// Every press since the counters were last reset
+    pub total: u32,
+    /// Presses of each key on the matrix, `row * NUM_COLS + col`
+    pub keys: [u32; KEYS],
+    /// Presses per day, today first
+    pub days: [u32; DAYS],
+    /// Which day `days[0]` is, in days since 1970
+    pub today: Option<u32>,
+    /// Presses in each bucket, the current one first
+    recent: [u16; WPM_BUCKETS],
+    /// When the current bucket ends
+    bucket_end_us: u64,
+}
+
+impl Default for Stats {
+    fn default() -> Stats {
+        Stats::new()
+    }
+}
+
+impl Stats {
+    pub const fn new() -> Stats {
+        Stats {
+            total: 0,
+            keys: [0; KEYS],
+            days: [0; DAYS],
+            today: None,
+            recent: [0; WPM_BUCKETS],
+            bucket_end_us: 0,
+        }
+    }
+
+    pub fn key_pressed(&mut self, row: u8, col: u8) {
+        let key = row as usize * NUM_COLS + col as usize;
+        if let Some(count) = self.keys.get_mut(key) {
+            *count = count.saturating_add(1);
+        }
+        self.total = self.total.saturating_add(1);
+        self.days[0] = self.days[0].saturating_add(1);
+        self.recent[0] = self.recent[0].saturating_add(1);
+    }
+
+    /// Moves the words per minute window along, call at least every few
+    /// seconds
+    pub fn tick(&mut self, now_us: u64) {
+        if now_us >= self.bucket_end_us + WPM_BUCKETS as u64 * WPM_BUCKET_US {
+            self.recent = [0; WPM_BUCKETS];
+            self.bucket_end_us = now_us + WPM_BUCKET_US;
+        }
+        while now_us >= self.bucket_end_us {
+            self.recent.copy_within(..WPM_BUCKETS - 1, 1);
+            self.recent[0] = 0;
+            self.bucket_end_us += WPM_BUCKET_US;
+        }
+    }
+
+    /// Over the last minute
+    pub fn wpm(&self) -> u16 {
+        let presses: u32 = self.recent.iter().map(|&count| count as u32).sum();
+        (presses / PRESSES_PER_WORD) as u16
+    }
+
+    /// Today's date from the wall clock, in days since 1970. Moves the
+    /// daily counts along if it's a later day than the last one seen.
+    /// Returns whether anything changed.
+    pub fn set_day(&mut self, day: u32) -> bool {
+        match self.today {
+            Some(today) if today == day => return false,
+            Some(today) if day > today => {
+                let passed = (day - today) as usize;
+                if passed >= DAYS {
+                    self.days = [0; DAYS];
+                } else {
+                    self.days.copy_within(..DAYS - passed, passed);
+                    self.days[..passed].fill(0);
+                }
+            }
+            // The first time the clock is set the presses so far were
+            // today's, and a clock set backwards keeps them as they are
+            _ => {}
+        }
+        self.today = Some(day);
+        true
+    }
+
+    /// Clears the counts, the day and the words per minute carry on
+    pub fn reset(&mut self) {
+        self.total = 0;
+        self.keys = [0; KEYS];
+        self.days = [0; DAYS];
+    }
+
+    /// The counts, for saving to flash. Words per minute aren't kept.
+    pub fn to_bytes(&self) -> [u8; SAVED_LEN] {
+        let mut bytes = [0; SAVED_LEN];
+        let values = core::iter::once(self.total)
+            .chain(self.keys.iter().copied())
+            .chain(self.days.iter().copied())
+            .chain(core::iter::once(self.today.unwrap_or(NO_DAY)));
+        for (chunk, value) in bytes.chunks_exact_mut(4).zip(values) {
+            chunk.copy_from_slice(&value.to_le_bytes());
+        }
+        bytes
+    }
+
+    /// `None` if `bytes` isn't what `to_bytes` made
+    pub fn from_bytes(bytes: &[u8]) -> Option<Stats> {
+        if bytes.len() != SAVED_LEN {
+            return None;
+        }
+        let mut values = bytes
+            .chunks_exact(4)
+            .map(|chunk| u32::from_le_bytes([chunk[0], chunk[1], chunk[2], chunk[3]]));
+        let mut stats = Stats::new();
+        stats.total = values.next()?;
+        for count in stats.keys.iter_mut().chain(stats.days.iter_mut()) {
+            *count = values.next()?;
+        }
+        stats.today = Some(values.next()?).filter(|&day| day != NO_DAY);
+        Some(stats)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SECOND_US: u64 = 1_000_000;
+
+    fn press(stats: &mut Stats, times: u32) {
+        for _ in 0..times {
+            stats.key_pressed(1, 2);
+        }
+    }
+
+    #[test]
+    fn counts_presses() {
+        let mut stats = Stats::new();
+        press(&mut stats, 3);
+        stats.key_pressed(3, 9);
+        assert_eq!((stats.total, stats.days[0]), (4, 4));
+        assert_eq!((stats.keys[NUM_COLS + 2], stats.keys[KEYS - 1]), (3, 1));
+        // Off the matrix only counts towards the totals
+        stats.key_pressed(NUM_ROWS as u8, 0);
+        assert_eq!(stats.total, 5);
+        assert_eq!(stats.keys.iter().sum::<u32>(), 4);
+    }
+
+    #[test]
+    fn wpm_covers_the_last_minute() {
+        let mut stats = Stats::new();
+        stats.tick(0);
+        press(&mut stats, 50);
+        assert_eq!(stats.wpm(), 10);
+
+        // Still there in the last bucket of the minute
+        stats.tick(55 * SECOND_US);
+        press(&mut stats, 5);
+        assert_eq!(stats.wpm(), 11);
+        // and gone a bucket later
+        stats.tick(60 * SECOND_US);
+        assert_eq!(stats.wpm(), 1);
+        stats.tick(115 * SECOND_US);
+        assert_eq!(stats.wpm(), 0);
+    }
+
+    #[test]
+    fn wpm_after_a_long_gap() {
+        let mut stats = Stats::new();
+        stats.tick(0);
+        press(&mut stats, 50);
+        // Longer than the window, so it starts over rather than shifting
+        // bucket by bucket
+        stats.tick(3600 * SECOND_US);
+        assert_eq!(stats.wpm(), 0);
+        press(&mut stats, 10);
+        stats.tick(3604 * SECOND_US);
+        assert_eq!(stats.wpm(), 2);
+        stats.tick(3665 * SECOND_US);
+        assert_eq!(stats.wpm(), 0);
+    }
+
+    #[test]
+    fn first_day_keeps_the_presses() {
+        let mut stats = Stats::new();
+        press(&mut stats, 3);
+        assert!(stats.set_day(19_000));
+        assert_eq!(stats.today, Some(19_000));
+        assert_eq!(stats.days[0], 3);
+        assert!(!stats.set_day(19_000));
+    }
+
+    #[test]
+    fn next_day_moves_the_counts_along() {
+        let mut stats = Stats::new();
+        stats.set_day(19_000);
+        press(&mut stats, 3);
+        assert!(stats.set_day(19_001));
+        press(&mut stats, 2);
+        assert_eq!(stats.days, [2, 3, 0, 0, 0, 0, 0]);
+        assert!(stats.set_day(19_003));
+        assert_eq!(stats.days, [0, 0, 2, 3, 0, 0, 0]);
+        assert_eq!(stats.total, 5);
+    }
+
+    #[test]
+    fn a_week_or_more_clears_the_days() {
+        let mut stats = Stats::new();
+        stats.set_day(19_000);
+        press(&mut stats, 3);
+        assert!(stats.set_day(19_000 + DAYS as u32));
+        assert_eq!(stats.days, [0; DAYS]);
+
+        press(&mut stats, 2);
+        assert!(stats.set_day(20_000));
+        assert_eq!(stats.days, [0; DAYS]);
+        assert_eq!(stats.total, 5);
+    }
+
+    #[test]
+    fn clock_set_backwards_keeps_the_counts() {
+        let mut stats = Stats::new();
+        stats.set_day(19_000);
+        press(&mut stats, 3);
+        stats.set_day(19_001);
+        press(&mut stats, 2);
+        assert!(stats.set_day(18_999));
+        assert_eq!(stats.today, Some(18_999));
+        assert_eq!(stats.days, [2, 3, 0, 0, 0, 0, 0]);
+        // and counts on from there
+        stats.set_day(19_000);
+        assert_eq!(stats.days, [0, 2, 3, 0, 0, 0, 0]);
+    }
+
+    #[test]
+    fn saved_counts_round_trip() {
+        let mut stats = Stats::new();
+        press(&mut stats, 3);
+        stats.key_pressed(3, 9);
+        stats.set_day(19_000);
+        stats.set_day(19_002);
+        press(&mut stats, 1);
+
+        let loaded = Stats::from_bytes(&stats.to_bytes()).unwrap();
+        assert_eq!((loaded.total, loaded.keys, loaded.days), (stats.total, stats.keys, stats.days));
+        assert_eq!(loaded.today, Some(19_002));
+        // Words per minute aren't saved
+        assert_eq!(loaded.wpm(), 0);
+    }
+
+    #[test]
+    fn saved_without_a_day() {
+        let mut stats = Stats::new();
+        press(&mut stats, 7);
+        let loaded = Stats::from_bytes(&stats.to_bytes()).unwrap();
+        assert_eq!(loaded.today, None);
+        assert_eq!((loaded.total, loaded.days[0]), (7, 7));
+    }
+
+    #[test]
+    fn wrong_length_is_rejected() {
+        let bytes = Stats::new().to_bytes();
+        assert_eq!(Stats::from_bytes(&bytes[..SAVED_LEN - 4]), None);
+        assert_eq!(Stats::from_bytes(&[]), None);
+        let mut longer = [0u8; SAVED_LEN + 4];
+        longer[..SAVED_LEN].copy_from_slice(&bytes);
+        assert_eq!(Stats::from_bytes(&longer), None);
+    }
+}
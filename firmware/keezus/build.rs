@@ -7,8 +7,19 @@
 //! Cargo re-run the build script whenever `memory.x` is changed,
 //! updating `memory.x` ensures a rebuild of the application with the
 //! new memory settings.
+//!
+//! It also generates the USB VID/PID constants, see `src/usb.rs`.
 
-use std::{env, fs::File, io::Write, path::PathBuf};
+use std::{
+    env,
+    fs::File,
+    io::Write,
+    path::{Path, PathBuf},
+};
+
+// https://github.com/obdev/v-usb/blob/7a28fdc685952412dad2b8842429127bc1cf9fa7/usbdrv/USB-IDs-for-free.txt#L128
+const DEFAULT_USB_VID: u16 = 0x16c0;
+const DEFAULT_USB_PID: u16 = 0x27db;
 
 fn main() {
     // Put `memory.x` in our output directory and ensure it's
@@ -22,4 +33,38 @@ fn main() {
     // here, we ensure the build script is only re-run when
     // `memory.x` is changed.
     println!("cargo:rerun-if-changed=memory.x");
-}
\ No newline at end of file
+
+    write_usb_ids(out);
+}
+
+/// Reads an optional hex (`0x1234`) or decimal u16 override from the environment
+fn env_u16(name: &str, default: u16) -> u16 {
+    println!("cargo:rerun-if-env-changed={}", name);
+    match env::var(name) {
+        Ok(value) => {
+            let value = value.trim();
+            let parsed = match value.strip_prefix("0x").or_else(|| value.strip_prefix("0X")) {
+                Some(hex) => u16::from_str_radix(hex, 16),
+                None => value.parse(),
+            };
+            parsed.unwrap_or_else(|_| panic!("{} must be a 16 bit number, got {:?}", name, value))
+        }
+        Err(_) => default,
+    }
+}
+
+fn write_usb_ids(out: &Path) {
+    let vid = env_u16("KEEZUS_USB_VID", DEFAULT_USB_VID);
+    let pid = env_u16("KEEZUS_USB_PID", DEFAULT_USB_PID);
+
+    // bcdDevice is the crate version, e.g. 0.1.0 -> 0x0010
+    let major: u16 = env::var("CARGO_PKG_VERSION_MAJOR").unwrap().parse().unwrap();
+    let minor: u16 = env::var("CARGO_PKG_VERSION_MINOR").unwrap().parse().unwrap();
+    let patch: u16 = env::var("CARGO_PKG_VERSION_PATCH").unwrap().parse().unwrap();
+    let release = (major.min(0xff) << 8) | (minor.min(0xf) << 4) | patch.min(0xf);
+
+    let mut f = File::create(out.join("usb_ids.rs")).unwrap();
+    writeln!(f, "pub const USB_VID: u16 = {:#06x};", vid).unwrap();
+    writeln!(f, "pub const USB_PID: u16 = {:#06x};", pid).unwrap();
+    writeln!(f, "pub const USB_DEVICE_RELEASE: u16 = {:#06x};", release).unwrap();
+}
@@ -0,0 +1,116 @@
+//! The display backlight, and putting the panel to sleep when the keyboard
+//! has been left alone.
+//!
+//! `lcd_led` (GPIO15) is PWM slice 7's channel B. Core 1 owns the channel
+//! along with the rest of the display, while the brightness and timeout
+//! settings are changed on core 0 and handed over through atomics.
+//!
+//! After `display_timeout_min` minutes without a key event the backlight
+//! dims, a minute later it goes off and the panel is sent SLPIN. The next
+//! key event wakes it up again.
+
+use core::sync::atomic::{AtomicU8, Ordering};
+
+use embedded_hal::PwmPin;
+use keezus_protocol::BRIGHTNESS_LEVELS;
+use rp2040_hal::pwm::{Channel, FreeRunning, Pwm7, B};
+
+use crate::settings::Settings;
+
+pub type BacklightPwm = Channel<Pwm7, FreeRunning, B>;
+
+/// Duty cycle per brightness level, roughly even steps to the eye
+const DUTY: [u16; BRIGHTNESS_LEVELS as usize] = [0x0400, 0x0a00, 0x1400, 0x2400, 0x3c00, 0x6000, 0xa000, 0xffff];
+const DIM_DUTY: u16 = DUTY[0];
+/// How long the backlight stays dimmed before the panel goes to sleep
+const DIM_TIME_US: u64 = 60_000_000;
+/// The ST7735 wants this long after SLPIN/SLPOUT before the next command
+const SLEEP_SETTLE_US: u64 = 120_000;
+
+static BRIGHTNESS: AtomicU8 = AtomicU8::new(BRIGHTNESS_LEVELS);
+static TIMEOUT_MIN: AtomicU8 = AtomicU8::new(0);
+
+/// Passes the display settings on to core 1, call whenever they change
+pub fn configure(settings: &Settings) {
+    BRIGHTNESS.store(settings.brightness, Ordering::Relaxed);
+    TIMEOUT_MIN.store(settings.display_timeout_min, Ordering::Relaxed);
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Power {
+    On,
+    Dimmed,
+    /// SLPIN sent at the given time
+    Asleep(u64),
+    /// SLPOUT sent at the given time, not ready to draw on yet
+    Waking(u64),
+}
+
+pub struct Backlight {
+    pwm: BacklightPwm,
+    power: Power,
+    last_activity_us: u64,
+}
+
+impl Backlight {
+    /// Starts off dark, the first `poll` turns it on
+    pub fn new(mut pwm: BacklightPwm) -> Backlight {
+        pwm.set_duty(0);
+        pwm.enable();
+        Backlight {
+            pwm,
+            power: Power::On,
+            last_activity_us: 0,
+        }
+    }
+
+    /// A key was pressed or released
+    pub fn activity(&mut self, now: u64) {
+        self.last_activity_us = now;
+    }
+
+    /// Dims, sleeps or wakes the panel as needed. Returns whether it can be
+    /// drawn on. Sends panel commands, so only call it between DMA
+    /// transfers.
+    pub fn poll(&mut self, now: u64) -> bool {
+        let level = BRIGHTNESS.load(Ordering::Relaxed).clamp(1, BRIGHTNESS_LEVELS);
+        let duty = DUTY[level as usize - 1];
+        let idle_us = now.saturating_sub(self.last_activity_us);
+        let (dim, sleep) = match TIMEOUT_MIN.load(Ordering::Relaxed) {
+            0 => (false, false),
+            minutes => {
+                let timeout_us = minutes as u64 * 60_000_000;
+                (idle_us >= timeout_us, idle_us >= timeout_us + DIM_TIME_US)
+            }
+        };
+
+        match self.power {
+            Power::On | Power::Dimmed if sleep => {
+                self.pwm.set_duty(0);
+                crate::lcd_dma::send_command(crate::st7735::SLPIN);
+                self.power = Power::Asleep(now);
+                false
+            }
+            Power::On | Power::Dimmed => {
+                self.power = if dim { Power::Dimmed } else { Power::On };
+                self.pwm.set_duty(if dim { duty.min(DIM_DUTY) } else { duty });
+                true
+            }
+            Power::Asleep(since) => {
+                if !sleep && now - since >= SLEEP_SETTLE_US {
+                    crate::lcd_dma::send_command(crate::st7735::SLPOUT);
+                    self.power = Power::Waking(now);
+                }
+                false
+            }
+            Power::Waking(since) => {
+                if now - since < SLEEP_SETTLE_US {
+                    return false;
+                }
+                self.power = Power::On;
+                self.pwm.set_duty(duty);
+                true
+            }
+        }
+    }
+}
@@ -0,0 +1,184 @@
+//! A framebuffer host tools draw into over raw HID (see host.rs), shown in
+//! place of the firmware's own screen while the host has it enabled.
+//!
+//! Drawing only touches RAM. The renderer on core 1 copies changed bands
+//! of rows out to the display a few at a time, so it never holds the
+//! canvas for long. Both cores get at it through `with`, which takes a
+//! hardware spinlock.
+
+use core::convert::Infallible;
+
+use embedded_graphics::mono_font::ascii::FONT_6X10;
+use embedded_graphics::mono_font::MonoTextStyleBuilder;
+use embedded_graphics::pixelcolor::raw::RawU16;
+use embedded_graphics::pixelcolor::Rgb565;
+use embedded_graphics::prelude::*;
+use embedded_graphics::text::{Baseline, Text};
+use keezus_protocol::{CanvasText, Rect, CANVAS_HEIGHT, CANVAS_WIDTH};
+
+pub const WIDTH: usize = CANVAS_WIDTH as usize;
+pub const HEIGHT: usize = CANVAS_HEIGHT as usize;
+/// Rows copied to the display in one go
+pub const BAND_HEIGHT: usize = 8;
+const BANDS: usize = HEIGHT / BAND_HEIGHT;
+
+pub struct Canvas {
+    pixels: [u16; WIDTH * HEIGHT],
+    visible: bool,
+    /// Area the pixels of the current blit go to, and how many have arrived
+    window: Rect,
+    cursor: usize,
+    /// One bit per band that changed since it was last copied out
+    dirty: u32,
+}
+
+static mut CANVAS: Canvas = Canvas::new();
+
+type CanvasLock = rp2040_hal::sio::Spinlock1;
+
+/// Runs `f` on the canvas. Only one context per core may use this, so a
+/// core can't end up spinning on a lock it already holds.
+pub fn with<R>(f: impl FnOnce(&mut Canvas) -> R) -> R {
+    let _lock = CanvasLock::claim();
+    f(unsafe { &mut CANVAS })
+}
+
+impl Canvas {
+    const fn new() -> Canvas {
+        Canvas {
+            pixels: [0; WIDTH * HEIGHT],
+            visible: false,
+            window: Rect {
+                x: 0,
+                y: 0,
+                width: 0,
+                height: 0,
+            },
+            cursor: 0,
+            dirty: 0,
+        }
+    }
+
+    pub fn visible(&self) -> bool {
+        self.visible
+    }
+
+    /// Sends all of it to the display again
+    pub fn invalidate(&mut self) {
+        self.mark_dirty(0, HEIGHT);
+    }
+
+    /// Showing the canvas redraws all of it, hiding it leaves the contents
+    /// alone for next time
+    pub fn show(&mut self, visible: bool) {
+        if visible && !self.visible {
+            self.mark_dirty(0, HEIGHT);
+        }
+        self.visible = visible;
+    }
+
+    pub fn fill(&mut self, rect: &Rect, color: u16) -> Option<()> {
+        let (x, y, width, height) = clip(rect)?;
+        for row in y..y + height {
+            self.pixels[row * WIDTH + x..row * WIDTH + x + width].fill(color);
+        }
+        self.mark_dirty(y, height);
+        Some(())
+    }
+
+    pub fn blit(&mut self, rect: &Rect) -> Option<()> {
+        clip(rect)?;
+        self.window = *rect;
+        self.cursor = 0;
+        Some(())
+    }
+
+    /// Pixels past the end of the window are dropped, as are the ones that
+    /// fall outside the screen
+    pub fn push_pixels(&mut self, pixels: &[u16]) {
+        let window = self.window;
+        let len = window.width as usize * window.height as usize;
+        for &pixel in pixels {
+            if self.cursor >= len {
+                break;
+            }
+            let x = window.x as usize + self.cursor % window.width as usize;
+            let y = window.y as usize + self.cursor / window.width as usize;
+            if x < WIDTH && y < HEIGHT {
+                self.pixels[y * WIDTH + x] = pixel;
+                self.mark_dirty(y, 1);
+            }
+            self.cursor += 1;
+        }
+    }
+
+    pub fn text(&mut self, text: &CanvasText) {
+        // Host tools lay text out assuming CANVAS_FONT_WIDTH/HEIGHT
+        let style = MonoTextStyleBuilder::new()
+            .font(&FONT_6X10)
+            .text_color(RawU16::new(text.color).into())
+            .background_color(RawU16::new(text.background).into())
+            .build();
+        let position = Point::new(text.x as i32, text.y as i32);
+        let _ = Text::with_baseline(text.text(), position, style, Baseline::Top).draw(self);
+    }
+
+    fn mark_dirty(&mut self, y: usize, height: usize) {
+        if height == 0 {
+            return;
+        }
+        let first = y / BAND_HEIGHT;
+        let last = ((y + height - 1) / BAND_HEIGHT).min(BANDS - 1);
+        for band in first..=last {
+            self.dirty |= 1 << band;
+        }
+    }
+
+    /// Copies the topmost changed band into `buf` and returns its first row
+    pub fn take_dirty_band(&mut self, buf: &mut [u16; WIDTH * BAND_HEIGHT]) -> Option<usize> {
+        if self.dirty == 0 {
+            return None;
+        }
+        let band = self.dirty.trailing_zeros() as usize;
+        self.dirty &= !(1 << band);
+        let start = band * BAND_HEIGHT * WIDTH;
+        buf.copy_from_slice(&self.pixels[start..start + buf.len()]);
+        Some(band * BAND_HEIGHT)
+    }
+}
+
+/// The part of `rect` that's on screen, `None` if that's nothing
+fn clip(rect: &Rect) -> Option<(usize, usize, usize, usize)> {
+    let (x, y) = (rect.x as usize, rect.y as usize);
+    if x >= WIDTH || y >= HEIGHT || rect.width == 0 || rect.height == 0 {
+        return None;
+    }
+    let width = (rect.width as usize).min(WIDTH - x);
+    let height = (rect.height as usize).min(HEIGHT - y);
+    Some((x, y, width, height))
+}
+
+impl OriginDimensions for Canvas {
+    fn size(&self) -> Size {
+        Size::new(WIDTH as u32, HEIGHT as u32)
+    }
+}
+
+impl DrawTarget for Canvas {
+    type Color = Rgb565;
+    type Error = Infallible;
+
+    fn draw_iter<I>(&mut self, pixels: I) -> Result<(), Self::Error>
+    where
+        I: IntoIterator<Item = Pixel<Self::Color>>,
+    {
+        for Pixel(point, color) in pixels {
+            if point.x >= 0 && point.y >= 0 && (point.x as usize) < WIDTH && (point.y as usize) < HEIGHT {
+                let (x, y) = (point.x as usize, point.y as usize);
+                self.pixels[y * WIDTH + x] = RawU16::from(color).into_inner();
+                self.mark_dirty(y, 1);
+            }
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,262 @@
+//! Raw access to the Pico's QSPI flash chip.
+//!
+//! Everything in here runs with XIP turned off, so the functions that
+//! actually talk to the flash live in RAM and only touch registers and
+//! ROM function pointers that were looked up beforehand. Interrupts must
+//! be disabled while any of these run.
+
+// ROM function table lookups, see section 2.8.3 of the RP2040 datasheet
+const ROM_FUNC_TABLE: *const u16 = 0x0000_0014 as *const u16;
+const ROM_TABLE_LOOKUP: *const u16 = 0x0000_0018 as *const u16;
+
+// XIP SSI and QSPI chip select registers
+const SSI_SR: *const u32 = 0x1800_0028 as *const u32;
+const SSI_DR0: *mut u32 = 0x1800_0060 as *mut u32;
+const QSPI_SS_CTRL: *mut u32 = 0x4001_800c as *mut u32;
+
+const SSI_SR_TFNF: u32 = 1 << 1;
+const SSI_SR_RFNE: u32 = 1 << 3;
+const SS_OUTOVER_MASK: u32 = 0b11 << 8;
+const SS_OUTOVER_LOW: u32 = 0b10 << 8;
+const SS_OUTOVER_HIGH: u32 = 0b11 << 8;
+
+/// The SSI FIFOs are 16 entries deep, leave some headroom
+const SSI_FIFO_DEPTH: usize = 14;
+
+const XIP_BASE: u32 = 0x1000_0000;
+pub const FLASH_SIZE: u32 = 2048 * 1024;
+pub const SECTOR_SIZE: u32 = 4096;
+pub const PAGE_SIZE: usize = 256;
+
+/// Start of the area at the end of flash that is kept out of the program
+/// image for persistent storage. Must match the FLASH length in `memory.x`
+pub const STORAGE_OFFSET: u32 = FLASH_SIZE - 64 * 1024;
+
+/// Sectors within the storage area
+pub const SETTINGS_SECTOR: u32 = STORAGE_OFFSET;
+pub const KEYMAP_SECTOR: u32 = STORAGE_OFFSET + SECTOR_SIZE;
+pub const PET_SECTOR: u32 = STORAGE_OFFSET + 2 * SECTOR_SIZE;
+pub const GAME_SECTOR: u32 = STORAGE_OFFSET + 3 * SECTOR_SIZE;
+pub const STATS_SECTOR: u32 = STORAGE_OFFSET + 4 * SECTOR_SIZE;
+
+const CMD_SECTOR_ERASE: u8 = 0x20;
+const CMD_READ_UNIQUE_ID: u8 = 0x4b;
+const UNIQUE_ID_DUMMY_BYTES: usize = 4;
+pub const UNIQUE_ID_LEN: usize = 8;
+
+/// Pointers to the ROM flash routines. These have to be resolved while XIP
+/// is still running as the lookup helpers live in flash.
+#[derive(Clone, Copy)]
+struct RomFns {
+    connect_internal_flash: extern "C" fn(),
+    flash_exit_xip: extern "C" fn(),
+    flash_range_erase: extern "C" fn(u32, usize, u32, u8),
+    flash_range_program: extern "C" fn(u32, *const u8, usize),
+    flash_flush_cache: extern "C" fn(),
+}
+
+unsafe fn rom_fn(tag: &[u8; 2]) -> usize {
+    let lookup: extern "C" fn(*const u16, u32) -> usize =
+        core::mem::transmute(ROM_TABLE_LOOKUP.read() as usize);
+    let code = u16::from_le_bytes(*tag) as u32;
+    lookup(ROM_FUNC_TABLE.read() as *const u16, code)
+}
+
+impl RomFns {
+    unsafe fn lookup() -> RomFns {
+        RomFns {
+            connect_internal_flash: core::mem::transmute(rom_fn(b"IF")),
+            flash_exit_xip: core::mem::transmute(rom_fn(b"EX")),
+            flash_range_erase: core::mem::transmute(rom_fn(b"RE")),
+            flash_range_program: core::mem::transmute(rom_fn(b"RP")),
+            flash_flush_cache: core::mem::transmute(rom_fn(b"FC")),
+        }
+    }
+}
+
+/// Reads the 64 bit unique ID burnt into the flash chip. The RP2040 itself
+/// has no serial number, so this is what the pico-sdk uses as the board ID.
+///
+/// Must be called with interrupts disabled (e.g. from RTIC's `init`).
+pub fn unique_id() -> [u8; UNIQUE_ID_LEN] {
+    let mut buf = [0u8; 1 + UNIQUE_ID_DUMMY_BYTES + UNIQUE_ID_LEN];
+    buf[0] = CMD_READ_UNIQUE_ID;
+
+    unsafe {
+        let rom = RomFns::lookup();
+        let boot2 = Boot2Copy::new();
+        flash_do_cmd(&rom, &boot2, buf.as_mut_ptr(), buf.len());
+    }
+
+    let mut id = [0u8; UNIQUE_ID_LEN];
+    id.copy_from_slice(&buf[1 + UNIQUE_ID_DUMMY_BYTES..]);
+    id
+}
+
+/// Checksum for the records kept in the storage area
+pub fn fletcher16(data: &[u8]) -> u16 {
+    let (mut a, mut b) = (0u16, 0u16);
+    for &byte in data {
+        a = (a + byte as u16) % 255;
+        b = (b + a) % 255;
+    }
+    (b << 8) | a
+}
+
+/// magic, version, payload length, 16 bit checksum
+const RECORD_HEADER_LEN: usize = 4 + 1 + 1 + 2;
+/// Most a record can hold, it has to fit in a page with its header
+pub const RECORD_MAX: usize = PAGE_SIZE - RECORD_HEADER_LEN;
+
+/// A payload saved with `write_record`
+pub struct Record {
+    pub version: u8,
+    len: usize,
+    payload: [u8; RECORD_MAX],
+}
+
+impl Record {
+    pub fn payload(&self) -> &[u8] {
+        &self.payload[..self.len]
+    }
+}
+
+/// Saves `payload` to the sector at `offset`, behind a header with `magic`,
+/// `version` and a checksum for `read_record` to check
+pub fn write_record(offset: u32, magic: [u8; 4], version: u8, payload: &[u8]) {
+    assert!(payload.len() <= RECORD_MAX);
+    let mut buf = [0xffu8; PAGE_SIZE];
+    buf[0..4].copy_from_slice(&magic);
+    buf[4] = version;
+    buf[5] = payload.len() as u8;
+    buf[6..8].copy_from_slice(&fletcher16(payload).to_le_bytes());
+    buf[RECORD_HEADER_LEN..RECORD_HEADER_LEN + payload.len()].copy_from_slice(payload);
+    write(offset, &buf);
+}
+
+/// The record saved at `offset`, `None` if there isn't one with `magic` and
+/// a good checksum. Which versions are still readable is up to the caller.
+pub fn read_record(offset: u32, magic: [u8; 4]) -> Option<Record> {
+    let mut buf = [0u8; PAGE_SIZE];
+    read(offset, &mut buf);
+    let len = buf[5] as usize;
+    if buf[0..4] != magic || len > RECORD_MAX {
+        return None;
+    }
+    let mut payload = [0u8; RECORD_MAX];
+    payload[..len].copy_from_slice(&buf[RECORD_HEADER_LEN..RECORD_HEADER_LEN + len]);
+    if u16::from_le_bytes([buf[6], buf[7]]) != fletcher16(&payload[..len]) {
+        return None;
+    }
+    Some(Record {
+        version: buf[4],
+        len,
+        payload,
+    })
+}
+
+/// Reads `buf.len()` bytes starting at `offset` bytes into flash
+pub fn read(offset: u32, buf: &mut [u8]) {
+    let src = (XIP_BASE + offset) as *const u8;
+    for (i, byte) in buf.iter_mut().enumerate() {
+        *byte = unsafe { src.add(i).read_volatile() };
+    }
+}
+
+/// Erases the sectors covering `data` and programs it in, starting at
+/// `offset` bytes into flash. `offset` must be sector aligned and `data`
+/// a multiple of the page size. `data` must live in RAM.
+///
+/// Disables interrupts and parks core 1 for the duration (see
+/// `multicore::lockout`), which is a few tens of ms per sector.
+pub fn write(offset: u32, data: &[u8]) {
+    assert!(offset % SECTOR_SIZE == 0 && data.len() % PAGE_SIZE == 0);
+    assert!(offset >= STORAGE_OFFSET && offset as usize + data.len() <= FLASH_SIZE as usize);
+
+    let erase_len = (data.len() + SECTOR_SIZE as usize - 1) / SECTOR_SIZE as usize * SECTOR_SIZE as usize;
+    crate::multicore::lockout(|| unsafe {
+        let rom = RomFns::lookup();
+        let boot2 = Boot2Copy::new();
+        flash_erase_program(&rom, &boot2, offset, erase_len, data.as_ptr(), data.len());
+    });
+}
+
+/// A RAM copy of the second stage bootloader. Calling it after we're done
+/// puts the flash back into the fast QSPI XIP mode it booted in, rather
+/// than the slow generic mode the ROM's `flash_enter_cmd_xip` leaves it in.
+struct Boot2Copy([u32; 64]);
+
+impl Boot2Copy {
+    fn new() -> Boot2Copy {
+        let mut words = [0u32; 64];
+        for (word, chunk) in words.iter_mut().zip(crate::BOOT2.chunks_exact(4)) {
+            *word = u32::from_le_bytes([chunk[0], chunk[1], chunk[2], chunk[3]]);
+        }
+        Boot2Copy(words)
+    }
+}
+
+#[inline(always)]
+unsafe fn flash_cs_force(high: bool) {
+    let level = if high { SS_OUTOVER_HIGH } else { SS_OUTOVER_LOW };
+    let ctrl = QSPI_SS_CTRL.read_volatile();
+    QSPI_SS_CTRL.write_volatile((ctrl & !SS_OUTOVER_MASK) | level);
+}
+
+#[inline(always)]
+unsafe fn enter_xip(rom: &RomFns, boot2: &Boot2Copy) {
+    (rom.flash_flush_cache)();
+    let boot2_entry: extern "C" fn() = core::mem::transmute(boot2.0.as_ptr() as usize + 1);
+    boot2_entry();
+}
+
+/// Clocks `len` bytes out of `buf` to the flash chip while reading the
+/// response back into the same buffer, mirroring `flash_do_cmd` from the
+/// pico-sdk.
+#[inline(never)]
+#[link_section = ".data.ram_func"]
+unsafe fn flash_do_cmd(rom: &RomFns, boot2: &Boot2Copy, buf: *mut u8, len: usize) {
+    (rom.connect_internal_flash)();
+    (rom.flash_exit_xip)();
+    flash_cs_force(false);
+
+    let mut tx_remaining = len;
+    let mut rx_remaining = len;
+    let mut tx_ptr = buf as *const u8;
+    let mut rx_ptr = buf;
+    while tx_remaining > 0 || rx_remaining > 0 {
+        let status = SSI_SR.read_volatile();
+        let can_put = status & SSI_SR_TFNF != 0;
+        let can_get = status & SSI_SR_RFNE != 0;
+        if can_put && tx_remaining > 0 && rx_remaining < tx_remaining + SSI_FIFO_DEPTH {
+            SSI_DR0.write_volatile(tx_ptr.read() as u32);
+            tx_ptr = tx_ptr.add(1);
+            tx_remaining -= 1;
+        }
+        if can_get && rx_remaining > 0 {
+            rx_ptr.write(SSI_DR0.read_volatile() as u8);
+            rx_ptr = rx_ptr.add(1);
+            rx_remaining -= 1;
+        }
+    }
+
+    flash_cs_force(true);
+    enter_xip(rom, boot2);
+}
+
+#[inline(never)]
+#[link_section = ".data.ram_func"]
+unsafe fn flash_erase_program(
+    rom: &RomFns,
+    boot2: &Boot2Copy,
+    offset: u32,
+    erase_len: usize,
+    data: *const u8,
+    len: usize,
+) {
+    (rom.connect_internal_flash)();
+    (rom.flash_exit_xip)();
+    (rom.flash_range_erase)(offset, erase_len, SECTOR_SIZE, CMD_SECTOR_ERASE);
+    (rom.flash_range_program)(offset, data, len);
+    enter_xip(rom, boot2);
+}
@@ -0,0 +1,12 @@
+//! The framebuffer core 1 draws into, see `keezus_ui::framebuffer`. At
+//! 40KB it's kept out of core 1's stack.
+
+pub use keezus_ui::framebuffer::*;
+
+static mut FRAMEBUFFER: FrameBuffer = FrameBuffer::new();
+
+/// Hands out the framebuffer, which starts out black like the display
+/// after `init`. Only call this once, from `init`.
+pub fn take() -> &'static mut FrameBuffer {
+    unsafe { &mut FRAMEBUFFER }
+}
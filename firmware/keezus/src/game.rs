@@ -0,0 +1,42 @@
+//! Game mode's high score. Whether the game is on, and which keys reach it,
+//! is up to `keezus_ui::keys::KeyRouter`, and the game itself runs on core 1.
+//! This keeps the best score in flash, read back from render.rs and saved
+//! when the game is left.
+
+use crate::flash;
+
+const MAGIC: [u8; 4] = *b"KZGM";
+const VERSION: u8 = 1;
+
+pub struct GameMode {
+    /// As saved in flash
+    high_score: u16,
+}
+
+impl GameMode {
+    pub fn load() -> GameMode {
+        let high_score = flash::read_record(flash::GAME_SECTOR, MAGIC)
+            .filter(|record| record.version == VERSION)
+            .and_then(|record| Some(u16::from_le_bytes(record.payload().try_into().ok()?)));
+        GameMode {
+            high_score: high_score.unwrap_or(0),
+        }
+    }
+
+    pub fn high_score(&self) -> u16 {
+        self.high_score
+    }
+
+    /// Saves the high score if it was beaten, call once the game is left
+    pub fn save_high_score(&mut self) {
+        let best = crate::render::high_score();
+        if best > self.high_score {
+            self.high_score = best;
+            self.save();
+        }
+    }
+
+    fn save(&self) {
+        flash::write_record(flash::GAME_SECTOR, MAGIC, VERSION, &self.high_score.to_le_bytes());
+    }
+}
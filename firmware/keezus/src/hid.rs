@@ -0,0 +1,127 @@
+//! A generic USB HID class, modeled after `keyberon::hid::HidClass`.
+//!
+//! Unlike keyberon's class this one keeps track of the HID protocol mode
+//! (boot vs report) and idle rate the host asks for, which BIOS setup
+//! screens and KVM switches rely on. Everything but talking to
+//! `usb_device` is `keezus_protocol::hid::HidInterface`, so the request
+//! handling is tested on the host.
+
+use keezus_protocol::hid::{ControlRequest, HidInterface, Reply};
+use usb_device::class_prelude::*;
+use usb_device::control;
+use usb_device::Result;
+
+pub use keezus_protocol::hid::{HidDevice, Protocol, ProtocolMode, ReportType, Subclass};
+
+const USB_CLASS_HID: u8 = 0x03;
+const HID_DESC_TYPE_HID: u8 = 0x21;
+
+pub struct HidClass<'a, B: UsbBus, D: HidDevice> {
+    hid: HidInterface<D>,
+    interface: InterfaceNumber,
+    endpoint_interrupt_in: EndpointIn<'a, B>,
+}
+
+impl<'a, B: UsbBus, D: HidDevice> HidClass<'a, B, D> {
+    pub fn new(device: D, alloc: &'a UsbBusAllocator<B>, interval_ms: u8) -> Self {
+        let max_packet_size = device.max_packet_size();
+        let interface = alloc.interface();
+        HidClass {
+            hid: HidInterface::new(device, u8::from(interface)),
+            interface,
+            endpoint_interrupt_in: alloc.interrupt(max_packet_size, interval_ms),
+        }
+    }
+
+    pub fn device(&self) -> &D {
+        self.hid.device()
+    }
+
+    pub fn device_mut(&mut self) -> &mut D {
+        self.hid.device_mut()
+    }
+
+    pub fn protocol_mode(&self) -> ProtocolMode {
+        self.hid.state().protocol_mode()
+    }
+
+    /// The idle rate set by the host in milliseconds, 0 meaning infinite
+    pub fn idle_ms(&self) -> u32 {
+        self.hid.state().idle_ms()
+    }
+
+    pub fn write(&mut self, data: &[u8]) -> Result<usize> {
+        self.endpoint_interrupt_in.write(data)
+    }
+
+    /// Writes the device's current input report in whichever format the
+    /// host has selected
+    pub fn write_report(&mut self) -> Result<usize> {
+        let report = self.hid.input_report().ok_or(UsbError::InvalidState)?;
+        self.endpoint_interrupt_in.write(report)
+    }
+}
+
+/// The parts of the setup packet `HidInterface` looks at
+fn control_request(req: &control::Request) -> ControlRequest {
+    use keezus_protocol::hid::{Recipient, RequestType};
+
+    ControlRequest {
+        request_type: match req.request_type {
+            control::RequestType::Standard => RequestType::Standard,
+            control::RequestType::Class => RequestType::Class,
+            _ => RequestType::Vendor,
+        },
+        recipient: match req.recipient {
+            control::Recipient::Device => Recipient::Device,
+            control::Recipient::Interface => Recipient::Interface,
+            control::Recipient::Endpoint => Recipient::Endpoint,
+            _ => Recipient::Other,
+        },
+        request: req.request,
+        value: req.value,
+        index: req.index,
+    }
+}
+
+impl<B: UsbBus, D: HidDevice> UsbClass<B> for HidClass<'_, B, D> {
+    fn get_configuration_descriptors(&self, writer: &mut DescriptorWriter) -> Result<()> {
+        writer.interface(
+            self.interface,
+            USB_CLASS_HID,
+            self.hid.device().subclass() as u8,
+            self.hid.device().protocol() as u8,
+        )?;
+        writer.write(HID_DESC_TYPE_HID, &self.hid.hid_descriptor())?;
+        writer.endpoint(&self.endpoint_interrupt_in)?;
+        Ok(())
+    }
+
+    fn reset(&mut self) {
+        self.hid.reset();
+    }
+
+    fn control_in(&mut self, xfer: ControlIn<B>) {
+        match self.hid.control_in(&control_request(xfer.request())) {
+            Reply::Ignore => {}
+            Reply::Reject => {
+                xfer.reject().ok();
+            }
+            Reply::Accept(data) => {
+                xfer.accept_with(data).ok();
+            }
+        }
+    }
+
+    fn control_out(&mut self, xfer: ControlOut<B>) {
+        match self.hid.control_out(&control_request(xfer.request()), xfer.data()) {
+            Reply::Ignore => {}
+            Reply::Reject => {
+                xfer.reject().ok();
+            }
+            Reply::Accept(_) => {
+                xfer.accept().ok();
+            }
+        }
+    }
+}
@@ -0,0 +1,201 @@
+//! Answers requests from host tools (e.g. keezus-cli) coming in over the
+//! raw HID interface.
+
+use core::sync::atomic::{AtomicBool, Ordering};
+
+use keezus_protocol::{Command, CountChunk, Request, Response, SettingId, StatsSummary, Status, PROTOCOL_VERSION};
+use keezus_ui::bootloader::Prompt;
+use keezus_ui::screens::Notification;
+
+use crate::canvas::Canvas;
+use crate::keymap;
+use crate::settings::Settings;
+use crate::stats::Recorder;
+use crate::{NUM_COLS, NUM_LAYERS, NUM_ROWS};
+
+/// Set by a `Bootloader` request, `handle_event` does the reset
+static BOOTLOADER_REQUESTED: AtomicBool = AtomicBool::new(false);
+
+/// Whether a host tool has asked for the bootloader. It doesn't go through
+/// `BootloaderConfirm` like the key does, asking is confirmation enough,
+/// but the reset still waits for the notification and the response.
+pub fn bootloader_requested() -> bool {
+    BOOTLOADER_REQUESTED.load(Ordering::Relaxed)
+}
+
+fn version_part(part: &str) -> u8 {
+    part.parse().unwrap_or(0)
+}
+
+/// Handles everything but `StreamEvents`, which needs the raw HID
+/// interface itself, and the canvas requests, see `handle_canvas`
+pub fn handle(request: Request, keymap: &mut keymap::Staging, settings: &mut Settings) -> Response {
+    match request {
+        Request::GetVersion => Response::Version {
+            protocol: PROTOCOL_VERSION,
+            major: version_part(env!("CARGO_PKG_VERSION_MAJOR")),
+            minor: version_part(env!("CARGO_PKG_VERSION_MINOR")),
+            patch: version_part(env!("CARGO_PKG_VERSION_PATCH")),
+        },
+        Request::GetKeymapInfo => Response::KeymapInfo {
+            layers: NUM_LAYERS as u8,
+            rows: NUM_ROWS as u8,
+            cols: NUM_COLS as u8,
+        },
+        Request::ReadKeymap { offset, count } => match keymap.read(offset, count) {
+            Some(chunk) => Response::Keymap(chunk),
+            None => Response::Error(Command::ReadKeymap, Status::InvalidArgument),
+        },
+        Request::WriteKeymap(chunk) => match keymap.write(&chunk) {
+            Some(()) => Response::Ack(Command::WriteKeymap),
+            None => Response::Error(Command::WriteKeymap, Status::InvalidArgument),
+        },
+        Request::SaveKeymap => {
+            keymap.save();
+            crate::render::notify(Notification::KeymapSaved);
+            Response::Ack(Command::SaveKeymap)
+        }
+        Request::ResetKeymap => {
+            keymap.reset();
+            Response::Ack(Command::ResetKeymap)
+        }
+        Request::GetSetting(id) => Response::Setting(id, get_setting(settings, id)),
+        Request::SetSetting(id, value) => match set_setting(settings, id, value) {
+            Some(()) => Response::Ack(Command::SetSetting),
+            None => Response::Error(Command::SetSetting, Status::InvalidArgument),
+        },
+        Request::SaveSettings => {
+            settings.save();
+            crate::render::notify(Notification::SettingsSaved);
+            Response::Ack(Command::SaveSettings)
+        }
+        Request::SetScreensaverText(text) => {
+            settings.screensaver_text = text;
+            crate::render::configure(settings);
+            Response::Ack(Command::SetScreensaverText)
+        }
+        Request::GetScreensaverText => Response::ScreensaverText(settings.screensaver_text),
+        Request::Notify(text) => {
+            crate::render::notify(Notification::Text(text));
+            Response::Ack(Command::Notify)
+        }
+        Request::Bootloader => {
+            crate::render::notify(Notification::Bootloader(Prompt::Reboot));
+            BOOTLOADER_REQUESTED.store(true, Ordering::Relaxed);
+            Response::Ack(Command::Bootloader)
+        }
+        Request::SetTime(time) => {
+            crate::rtc::set(&time);
+            Response::Ack(Command::SetTime)
+        }
+        Request::GetTime => match crate::rtc::now() {
+            Some(time) => Response::Time(time),
+            None => Response::Error(Command::GetTime, Status::Failed),
+        },
+        Request::StreamEvents(_) => Response::Error(Command::StreamEvents, Status::Failed),
+        request => Response::Error(request.command(), Status::Failed),
+    }
+}
+
+/// Handles the requests that draw on the host canvas
+pub fn handle_canvas(request: Request, canvas: &mut Canvas) -> Response {
+    let command = request.command();
+    let done = match request {
+        Request::CanvasShow(visible) => {
+            canvas.show(visible);
+            Some(())
+        }
+        Request::CanvasFill(rect, color) => canvas.fill(&rect, color),
+        Request::CanvasBlit(rect) => canvas.blit(&rect),
+        Request::CanvasPixels(run) => {
+            canvas.push_pixels(run.pixels());
+            Some(())
+        }
+        Request::CanvasText(text) => {
+            canvas.text(&text);
+            Some(())
+        }
+        _ => return Response::Error(command, Status::Failed),
+    };
+    match done {
+        Some(()) => Response::Ack(command),
+        None => Response::Error(command, Status::InvalidArgument),
+    }
+}
+
+/// Handles the requests for the typing statistics
+pub fn handle_stats(request: Request, recorder: &mut Recorder, now_us: u64) -> Response {
+    let stats = recorder.stats();
+    match request {
+        Request::GetStats => Response::Stats(StatsSummary {
+            total: stats.total,
+            wpm: stats.wpm(),
+            keys: stats.keys.len() as u8,
+            days: stats.days.len() as u8,
+            today: stats.today,
+        }),
+        Request::ReadKeyCounts { offset, count } => match CountChunk::from_counts(&stats.keys, offset, count) {
+            Some(chunk) => Response::KeyCounts(chunk),
+            None => Response::Error(Command::ReadKeyCounts, Status::InvalidArgument),
+        },
+        Request::ReadDayCounts { offset, count } => match CountChunk::from_counts(&stats.days, offset, count) {
+            Some(chunk) => Response::DayCounts(chunk),
+            None => Response::Error(Command::ReadDayCounts, Status::InvalidArgument),
+        },
+        Request::ResetStats => {
+            recorder.reset(now_us);
+            Response::Ack(Command::ResetStats)
+        }
+        request => Response::Error(request.command(), Status::Failed),
+    }
+}
+
+fn get_setting(settings: &Settings, id: SettingId) -> u16 {
+    match id {
+        SettingId::PollIntervalMs => settings.poll_interval_ms as u16,
+        SettingId::Brightness => settings.brightness as u16,
+        SettingId::DisplayTimeoutMin => settings.display_timeout_min as u16,
+        SettingId::Orientation => settings.orientation as u16,
+        SettingId::PanelOffsetX => settings.panel_offset_x as u16,
+        SettingId::PanelOffsetY => settings.panel_offset_y as u16,
+        SettingId::ScreensaverMin => settings.screensaver_min as u16,
+        SettingId::Screensaver => settings.screensaver as u16,
+    }
+}
+
+fn set_setting(settings: &mut Settings, id: SettingId, value: u16) -> Option<()> {
+    let value = id.check(value)?;
+    match id {
+        SettingId::PollIntervalMs => settings.poll_interval_ms = value,
+        // The display ones apply straight away
+        SettingId::Brightness => {
+            settings.brightness = value;
+            crate::backlight::configure(settings);
+        }
+        SettingId::DisplayTimeoutMin => {
+            settings.display_timeout_min = value;
+            crate::backlight::configure(settings);
+        }
+        SettingId::Orientation => {
+            settings.orientation = value;
+            crate::render::configure(settings);
+        }
+        SettingId::PanelOffsetX => {
+            settings.panel_offset_x = value;
+            crate::render::configure(settings);
+        }
+        SettingId::PanelOffsetY => {
+            settings.panel_offset_y = value;
+            crate::render::configure(settings);
+        }
+        SettingId::ScreensaverMin => {
+            settings.screensaver_min = value;
+            crate::render::configure(settings);
+        }
+        SettingId::Screensaver => {
+            settings.screensaver = value;
+            crate::render::configure(settings);
+        }
+    }
+    Some(())
+}
@@ -0,0 +1,114 @@
+//! The keyboard HID device.
+//!
+//! In report protocol the keyboard sends an NKRO bitmap report, in boot
+//! protocol it falls back to the standard 8 byte 6KRO boot report that
+//! BIOSes and KVM switches understand. Both are built by
+//! `keezus_protocol::hid::KeyboardReports`.
+
+use keezus_protocol::hid::{KeyboardReports, NKRO_MAX_USAGE, NKRO_REPORT_LEN};
+use keyberon::key_code::KeyCode;
+
+use crate::hid::{HidDevice, Protocol, ProtocolMode, ReportType, Subclass};
+
+pub const LED_NUM_LOCK: u8 = 1 << 0;
+pub const LED_CAPS_LOCK: u8 = 1 << 1;
+pub const LED_SCROLL_LOCK: u8 = 1 << 2;
+
+#[rustfmt::skip]
+const REPORT_DESCRIPTOR: &[u8] = &[
+    0x05, 0x01, // Usage Page (Generic Desktop)
+    0x09, 0x06, // Usage (Keyboard)
+    0xA1, 0x01, // Collection (Application)
+    // Modifiers
+    0x05, 0x07, //   Usage Page (Key Codes)
+    0x19, 0xE0, //   Usage Minimum (224)
+    0x29, 0xE7, //   Usage Maximum (231)
+    0x15, 0x00, //   Logical Minimum (0)
+    0x25, 0x01, //   Logical Maximum (1)
+    0x75, 0x01, //   Report Size (1)
+    0x95, 0x08, //   Report Count (8)
+    0x81, 0x02, //   Input (Data, Variable, Absolute)
+    // LEDs
+    0x05, 0x08, //   Usage Page (LEDs)
+    0x19, 0x01, //   Usage Minimum (1)
+    0x29, 0x05, //   Usage Maximum (5)
+    0x95, 0x05, //   Report Count (5)
+    0x75, 0x01, //   Report Size (1)
+    0x91, 0x02, //   Output (Data, Variable, Absolute)
+    0x95, 0x01, //   Report Count (1)
+    0x75, 0x03, //   Report Size (3)
+    0x91, 0x01, //   Output (Constant)
+    // Key bitmap
+    0x05, 0x07, //   Usage Page (Key Codes)
+    0x19, 0x00, //   Usage Minimum (0)
+    0x29, NKRO_MAX_USAGE, // Usage Maximum (223)
+    0x15, 0x00, //   Logical Minimum (0)
+    0x25, 0x01, //   Logical Maximum (1)
+    0x75, 0x01, //   Report Size (1)
+    0x95, NKRO_MAX_USAGE + 1, // Report Count (224)
+    0x81, 0x02, //   Input (Data, Variable, Absolute)
+    0xC0,       // End Collection
+];
+
+pub struct Keyboard {
+    reports: KeyboardReports,
+    leds: u8,
+}
+
+impl Keyboard {
+    pub fn new() -> Keyboard {
+        Keyboard {
+            reports: KeyboardReports::new(),
+            leds: 0,
+        }
+    }
+
+    /// Updates both report formats from the currently pressed keycodes.
+    /// Returns true if anything changed.
+    pub fn set_keycodes(&mut self, keycodes: &[KeyCode]) -> bool {
+        self.reports.set_usages(keycodes.iter().map(|&kc| kc as u8))
+    }
+
+    pub fn report(&self, mode: ProtocolMode) -> &[u8] {
+        self.reports.report(mode)
+    }
+
+    /// LED state last sent by the host, see the `LED_*` masks
+    pub fn leds(&self) -> u8 {
+        self.leds
+    }
+}
+
+impl HidDevice for Keyboard {
+    fn subclass(&self) -> Subclass {
+        Subclass::BootInterface
+    }
+
+    fn protocol(&self) -> Protocol {
+        Protocol::Keyboard
+    }
+
+    fn max_packet_size(&self) -> u16 {
+        NKRO_REPORT_LEN as u16
+    }
+
+    fn report_descriptor(&self) -> &[u8] {
+        REPORT_DESCRIPTOR
+    }
+
+    fn set_report(&mut self, report_type: ReportType, report_id: u8, data: &[u8]) -> bool {
+        if report_type == ReportType::Output && report_id == 0 && data.len() == 1 {
+            self.leds = data[0];
+            return true;
+        }
+        false
+    }
+
+    fn get_report(&mut self, mode: ProtocolMode, report_type: ReportType, _report_id: u8) -> Option<&[u8]> {
+        match report_type {
+            ReportType::Input => Some(self.report(mode)),
+            ReportType::Output => Some(core::slice::from_ref(&self.leds)),
+            _ => None,
+        }
+    }
+}
@@ -0,0 +1,139 @@
+//! The keymap the layout runs on: `keezus_protocol::keymap::DEFAULT_KEYMAP`,
+//! with any entries uploaded by a host tool (see host.rs) applied on top.
+//!
+//! Uploaded entries are kept in flash and only picked up at boot, as the
+//! layout holds on to the keymap for the lifetime of the firmware.
+
+use keyberon::action::Action;
+use keyberon::key_code::KeyCode;
+use keezus_protocol::keymap::{self, position, DEFAULT_KEYMAP, KEYMAP_LEN};
+use keezus_protocol::{KeyAction, KeymapChunk};
+
+use crate::layout::{CustomActions, CUSTOM_ACTIONS};
+use crate::{flash, NUM_COLS, NUM_LAYERS, NUM_ROWS};
+
+pub type Layers = keyberon::layout::Layers<NUM_COLS, NUM_ROWS, NUM_LAYERS, CustomActions>;
+
+const MAGIC: [u8; 4] = *b"KZKM";
+/// magic, layers, rows, cols, one spare byte
+const HEADER_LEN: usize = 4 + 4;
+const STORAGE_LEN: usize = (HEADER_LEN + KEYMAP_LEN * 2 + flash::PAGE_SIZE - 1) / flash::PAGE_SIZE * flash::PAGE_SIZE;
+
+static mut KEYMAP: Option<Layers> = None;
+
+/// Builds the keymap from the default one and whatever has been saved to
+/// flash. Only call this once, from `init`.
+pub fn load() -> &'static Layers {
+    let mut layers: Layers = [[[Action::NoOp; NUM_COLS]; NUM_ROWS]; NUM_LAYERS];
+    for (index, &action) in DEFAULT_KEYMAP.iter().enumerate() {
+        let (layer, row, col) = position(index);
+        layers[layer][row][col] = from_protocol(action).unwrap_or(Action::NoOp);
+    }
+
+    let mut saved = [0u8; STORAGE_LEN];
+    flash::read(flash::KEYMAP_SECTOR, &mut saved);
+    if header_matches(&saved) {
+        for (index, raw) in saved[HEADER_LEN..].chunks_exact(2).take(KEYMAP_LEN).enumerate() {
+            let action = KeyAction::from_u16(u16::from_le_bytes([raw[0], raw[1]]));
+            if let Some(action) = from_protocol(action) {
+                let (layer, row, col) = position(index);
+                layers[layer][row][col] = action;
+            }
+        }
+    }
+
+    unsafe {
+        KEYMAP = Some(layers);
+        KEYMAP.as_ref().unwrap()
+    }
+}
+
+fn header_matches(buf: &[u8]) -> bool {
+    buf[0..4] == MAGIC
+        && buf[4] as usize == NUM_LAYERS
+        && buf[5] as usize == NUM_ROWS
+        && buf[6] as usize == NUM_COLS
+}
+
+pub fn to_protocol(action: &Action<CustomActions>) -> KeyAction {
+    match action {
+        Action::NoOp => KeyAction::NoOp,
+        Action::Trans => KeyAction::Trans,
+        Action::KeyCode(kc) => KeyAction::KeyCode(*kc as u8),
+        Action::Layer(layer) => KeyAction::Layer(*layer as u8),
+        Action::DefaultLayer(layer) => KeyAction::DefaultLayer(*layer as u8),
+        Action::Custom(custom) => match CUSTOM_ACTIONS.iter().position(|c| c == custom) {
+            Some(index) => KeyAction::Custom(index as u8),
+            None => KeyAction::Other,
+        },
+        _ => KeyAction::Other,
+    }
+}
+
+/// `None` for `Other` and anything that doesn't fit this keyboard
+pub fn from_protocol(action: KeyAction) -> Option<Action<CustomActions>> {
+    Some(match action {
+        KeyAction::NoOp => Action::NoOp,
+        KeyAction::Trans => Action::Trans,
+        KeyAction::KeyCode(kc) => Action::KeyCode(keycode_from_u8(kc)?),
+        KeyAction::Layer(layer) if (layer as usize) < NUM_LAYERS => Action::Layer(layer as usize),
+        KeyAction::DefaultLayer(layer) if (layer as usize) < NUM_LAYERS => Action::DefaultLayer(layer as usize),
+        KeyAction::Custom(index) => Action::Custom(*CUSTOM_ACTIONS.get(index as usize)?),
+        _ => return None,
+    })
+}
+
+fn keycode_from_u8(kc: u8) -> Option<KeyCode> {
+    // KeyCode is repr(u8) and contiguous over these ranges
+    let valid = kc <= KeyCode::ExSel as u8 || (KeyCode::LCtrl as u8..=KeyCode::MediaCalc as u8).contains(&kc);
+    if valid {
+        Some(unsafe { core::mem::transmute::<u8, KeyCode>(kc) })
+    } else {
+        None
+    }
+}
+
+/// The keymap as host tools see it, which only reaches flash through
+/// `save`
+pub struct Staging {
+    staging: keymap::Staging,
+}
+
+impl Staging {
+    pub fn new(layers: &Layers) -> Staging {
+        let mut entries = [KeyAction::Other; KEYMAP_LEN];
+        for (index, entry) in entries.iter_mut().enumerate() {
+            let (layer, row, col) = position(index);
+            *entry = to_protocol(&layers[layer][row][col]);
+        }
+        Staging {
+            staging: keymap::Staging::new(entries),
+        }
+    }
+
+    pub fn read(&self, offset: u16, count: u8) -> Option<KeymapChunk> {
+        self.staging.read(offset, count)
+    }
+
+    pub fn write(&mut self, chunk: &KeymapChunk) -> Option<()> {
+        self.staging.write(chunk)
+    }
+
+    pub fn save(&self) {
+        let mut buf = [0xffu8; STORAGE_LEN];
+        buf[0..4].copy_from_slice(&MAGIC);
+        buf[4] = NUM_LAYERS as u8;
+        buf[5] = NUM_ROWS as u8;
+        buf[6] = NUM_COLS as u8;
+        for (entry, raw) in self.staging.entries().iter().zip(buf[HEADER_LEN..].chunks_exact_mut(2)) {
+            raw.copy_from_slice(&entry.to_u16().to_le_bytes());
+        }
+        flash::write(flash::KEYMAP_SECTOR, &buf);
+    }
+
+    /// Forgets the saved keymap, going back to the default one
+    pub fn reset(&mut self) {
+        flash::write(flash::KEYMAP_SECTOR, &[0xffu8; flash::PAGE_SIZE]);
+        self.staging.reset();
+    }
+}
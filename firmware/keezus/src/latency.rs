@@ -0,0 +1,86 @@
+//! Measures the time from the key matrix changing to the HID report for
+//! that change being handed to the USB peripheral. Includes debouncing.
+//!
+//! Host tools read it with `GetLatency`, e.g. `keezus-cli latency`.
+
+use keezus_protocol::LatencySummary;
+
+pub struct LatencyStats {
+    /// Timer value (us) when the raw matrix first differed from the
+    /// debounced state
+    pending_since: Option<u64>,
+    /// The debouncer has reported the pending change, so the next report
+    /// written will include it
+    emitted: bool,
+    pub samples: u32,
+    pub min_us: u32,
+    pub max_us: u32,
+    total_us: u64,
+}
+
+impl LatencyStats {
+    pub const fn new() -> LatencyStats {
+        LatencyStats {
+            pending_since: None,
+            emitted: false,
+            samples: 0,
+            min_us: u32::MAX,
+            max_us: 0,
+            total_us: 0,
+        }
+    }
+
+    /// Called every scan with whether the raw matrix differs from the
+    /// debounced one, and whether the debouncer produced any events
+    pub fn on_scan(&mut self, now_us: u64, raw_changed: bool, events: bool) {
+        if events {
+            self.emitted = true;
+        }
+        if raw_changed {
+            if self.pending_since.is_none() {
+                self.pending_since = Some(now_us);
+            }
+        } else if !self.emitted {
+            // Just a bounce that never made it through the debouncer
+            self.pending_since = None;
+        }
+    }
+
+    /// Called after a report has been written. Returns true when a new
+    /// sample was recorded.
+    pub fn on_report(&mut self, now_us: u64) -> bool {
+        if !self.emitted {
+            return false;
+        }
+        self.emitted = false;
+        let since = match self.pending_since.take() {
+            Some(since) => since,
+            None => return false,
+        };
+
+        let latency = now_us.saturating_sub(since).min(u32::MAX as u64) as u32;
+        self.samples = self.samples.wrapping_add(1);
+        self.min_us = self.min_us.min(latency);
+        self.max_us = self.max_us.max(latency);
+        self.total_us += latency as u64;
+        true
+    }
+
+    pub fn average_us(&self) -> u32 {
+        if self.samples == 0 {
+            0
+        } else {
+            (self.total_us / self.samples as u64) as u32
+        }
+    }
+
+    pub fn summary(&self) -> LatencySummary {
+        LatencySummary {
+            samples: self.samples,
+            // Still u32::MAX until the first sample
+            min_us: if self.samples == 0 { 0 } else { self.min_us },
+            average_us: self.average_us(),
+            max_us: self.max_us,
+        }
+    }
+}
@@ -1,36 +1,31 @@
+//! The firmware's custom actions. The keymap itself is
+//! `keezus_protocol::keymap::DEFAULT_KEYMAP`, shared with the host tools,
+//! see keymap.rs for how it becomes the layout.
 
-use keyberon::action::{k, Action, Action::*};
-use keyberon::key_code::KeyCode::*;
-
-use crate::{NUM_COLS, NUM_ROWS, NUM_LAYERS};
-#[allow(unused_macros)]
-
-// Shift + KeyCode
-macro_rules! s {
-    ($k:ident) => {
-        m(&[LShift, $k])
-    };
-}
+use keezus_protocol::keymap;
 
 #[derive(Debug, Clone, Copy, Eq, PartialEq)]
 pub enum CustomActions {
     Bootloader,
+    /// Steps through the USB polling rates and resets the board to apply it
+    CycleReportRate,
+    /// Steps through the screens on the display
+    NextScreen,
+    /// Display backlight one level brighter or dimmer, saved on release
+    BrightnessUp,
+    BrightnessDown,
+    /// Keys go to a game on the display instead of the host until it's
+    /// pressed again
+    GameMode,
 }
 
-#[allow(dead_code)]
-const BOOTLOADER: Action<CustomActions> = Action::Custom(CustomActions::Bootloader);
-
-#[rustfmt::skip]
-pub static LAYERS: keyberon::layout::Layers<NUM_COLS, NUM_ROWS, NUM_LAYERS, CustomActions> = [
-    /* QWERTY */
-    /* 
-        All Trans keys are placeholders to even out the layout
-        All k(No) keys are functional
-    */
-    [
-        [k(Q),    k(W),    k(E),   k(R),   k(T),     k(Y),   k(U),   k(I),     k(O),     k(P)],
-        [k(A),    k(S),    k(D),   k(F),   k(G),     k(H),   k(J),   k(K),     k(L),     k(SColon)],
-        [k(Z),    k(X),    k(C),   k(V),   k(B),     k(N),   k(M),   k(Comma), k(Dot),   k(Slash)],
-        [ k(LGui), k(LAlt), Trans,  Trans,  k(Space), Trans,  Trans,  k(RAlt),  k(RCtrl), k(No),],
-    ] 
-];
\ No newline at end of file
+/// Custom actions by index, as seen by host tools, in the order of the
+/// `keezus_protocol::keymap::CUSTOM_*` indices
+pub static CUSTOM_ACTIONS: [CustomActions; keymap::CUSTOM_ACTIONS] = [
+    CustomActions::Bootloader,
+    CustomActions::CycleReportRate,
+    CustomActions::NextScreen,
+    CustomActions::BrightnessUp,
+    CustomActions::BrightnessDown,
+    CustomActions::GameMode,
+];
@@ -0,0 +1,161 @@
+//! Sends framebuffer windows to the display with DMA, so drawing doesn't
+//! keep the CPU busy for the length of an SPI transfer.
+//!
+//! st7735.rs still sets up each window (a handful of command bytes, sent
+//! blocking), then the pixels go from one of two window buffers straight
+//! into SPI0's TX FIFO. While one buffer is being sent the next window is
+//! copied into the other. The renderer on core 1 has
+//! nothing better to do than `poll` for the end of each transfer, so there
+//! is no interrupt.
+//!
+//! rp2040-hal doesn't have a DMA driver yet, so this goes to the registers
+//! directly.
+
+use rp2040_hal::pac;
+
+use crate::framebuffer::{FrameBuffer, PixelSink, Window, MAX_WINDOW_PIXELS};
+
+const CHANNEL: usize = 0;
+/// DMA request signal for SPI0 TX
+const DREQ_SPI0_TX: u32 = 16;
+
+// CH0_CTRL_TRIG fields
+const CTRL_EN: u32 = 1 << 0;
+const CTRL_DATA_SIZE_HALFWORD: u32 = 1 << 2;
+const CTRL_INCR_READ: u32 = 1 << 4;
+const CTRL_CHAIN_TO_SHIFT: u32 = 11;
+const CTRL_TREQ_SEL_SHIFT: u32 = 15;
+
+/// The panel's data/command select line, low for commands
+const DC_PIN: u32 = 16;
+
+/// Where the DMA reads from, two so one can be filled while the other is
+/// being sent
+static mut BUFFERS: [[u16; MAX_WINDOW_PIXELS]; 2] = [[0; MAX_WINDOW_PIXELS]; 2];
+
+pub struct LcdDma {
+    /// Buffer currently being sent
+    active: Option<usize>,
+    /// A window copied out and waiting for the active one to finish
+    queued: Option<(usize, Window)>,
+}
+
+/// Takes the DMA block out of reset. Only call this once, from `init`.
+pub fn init(resets: &mut pac::RESETS) -> LcdDma {
+    resets.reset.modify(|_, w| w.dma().clear_bit());
+    while resets.reset_done.read().dma().bit_is_clear() {}
+
+    spi().sspdmacr.modify(|_, w| w.txdmae().set_bit());
+
+    LcdDma {
+        active: None,
+        queued: None,
+    }
+}
+
+fn dma() -> &'static pac::dma::RegisterBlock {
+    unsafe { &*pac::DMA::ptr() }
+}
+
+fn spi() -> &'static pac::spi0::RegisterBlock {
+    unsafe { &*pac::SPI0::ptr() }
+}
+
+/// Switches SPI0 between the 8 bit frames the driver uses and 16 bit ones
+/// for pixels, which go out high byte first as the panel wants
+fn set_frame_bits(bits: u8) {
+    spi().sspcr0.modify(|_, w| unsafe { w.dss().bits(bits - 1) });
+}
+
+impl LcdDma {
+    pub fn busy(&self) -> bool {
+        self.active.is_some()
+    }
+
+    /// Starts sending the framebuffer's dirty tiles, if nothing is being
+    /// sent already
+    pub fn start<S: PixelSink>(&mut self, framebuffer: &mut FrameBuffer, display: &mut S) -> Result<(), S::Error> {
+        if self.busy() {
+            return Ok(());
+        }
+        if let Some(window) = fill(0, framebuffer) {
+            self.send(0, window, display)?;
+            self.queued = fill(1, framebuffer).map(|window| (1, window));
+        }
+        Ok(())
+    }
+
+    /// Once the current transfer is done, moves on to the queued window
+    /// and refills the buffer that just went out
+    pub fn poll<S: PixelSink>(&mut self, framebuffer: &mut FrameBuffer, display: &mut S) -> Result<(), S::Error> {
+        let done = match self.active {
+            Some(done) if dma().ch[CHANNEL].ch_ctrl_trig.read().busy().bit_is_clear() => done,
+            _ => return Ok(()),
+        };
+        self.active = None;
+
+        // The channel is done once the last pixel is in the FIFO, let it
+        // shift out before the driver toggles DC for the next command
+        while spi().sspsr.read().bsy().bit_is_set() {}
+        while spi().sspsr.read().rne().bit_is_set() {
+            let _ = spi().sspdr.read();
+        }
+        set_frame_bits(8);
+
+        if let Some((buffer, window)) = self.queued.take() {
+            self.send(buffer, window, display)?;
+            self.queued = fill(done, framebuffer).map(|window| (done, window));
+        }
+        Ok(())
+    }
+
+    fn send<S: PixelSink>(&mut self, buffer: usize, window: Window, display: &mut S) -> Result<(), S::Error> {
+        window.select(display)?;
+        set_frame_bits(16);
+
+        let ch = &dma().ch[CHANNEL];
+        let source = unsafe { BUFFERS[buffer].as_ptr() };
+        ch.ch_read_addr.write(|w| unsafe { w.bits(source as u32) });
+        ch.ch_write_addr.write(|w| unsafe { w.bits(&spi().sspdr as *const _ as u32) });
+        ch.ch_trans_count.write(|w| unsafe { w.bits(window.pixel_count() as u32) });
+        ch.ch_ctrl_trig.write(|w| unsafe {
+            w.bits(
+                CTRL_EN
+                    | CTRL_DATA_SIZE_HALFWORD
+                    | CTRL_INCR_READ
+                    // Chaining to itself means no chaining
+                    | (CHANNEL as u32) << CTRL_CHAIN_TO_SHIFT
+                    | DREQ_SPI0_TX << CTRL_TREQ_SEL_SHIFT,
+            )
+        });
+
+        self.active = Some(buffer);
+        Ok(())
+    }
+}
+
+/// Sends a command byte without parameters, e.g. sleep in/out, without
+/// going through st7735.rs and so without needing the display. Only call it while nothing is being
+/// sent.
+pub fn send_command(command: u8) {
+    let sio = unsafe { &*pac::SIO::ptr() };
+    sio.gpio_out_clr.write(|w| unsafe { w.bits(1 << DC_PIN) });
+    spi().sspdr.write(|w| unsafe { w.data().bits(command as u16) });
+    while spi().sspsr.read().bsy().bit_is_set() {}
+    while spi().sspsr.read().rne().bit_is_set() {
+        let _ = spi().sspdr.read();
+    }
+    // st7735.rs leaves it high for pixel data
+    sio.gpio_out_set.write(|w| unsafe { w.bits(1 << DC_PIN) });
+}
+
+/// Copies the next dirty window into a buffer
+fn fill(buffer: usize, framebuffer: &mut FrameBuffer) -> Option<Window> {
+    let window = framebuffer.take_dirty_window()?;
+    // Only ever called for the buffer that isn't being sent
+    let buffer = unsafe { &mut BUFFERS[buffer] };
+    for (dst, src) in buffer.iter_mut().zip(framebuffer.window_pixels(window)) {
+        *dst = src;
+    }
+    Some(window)
+}
@@ -0,0 +1,55 @@
+//! Short labels for keys, for drawing them on the display. The labels
+//! themselves come from `keezus_ui::legend`, this only gets keyberon's
+//! actions down to the `KeyAction`s it labels.
+
+use keyberon::action::Action;
+use keyberon::key_code::KeyCode;
+use keyberon::key_code::KeyCode::{LShift, RShift};
+use keezus_ui::legend::{self, KeymapLegends};
+
+use crate::keymap::{to_protocol, Layers};
+use crate::layout::CustomActions;
+
+use crate::{NUM_COLS, NUM_LAYERS, NUM_ROWS};
+
+static mut KEYMAP_LEGENDS: KeymapLegends = [[[""; NUM_COLS]; NUM_ROWS]; NUM_LAYERS];
+
+/// Works out the legends for the keymap the layout runs on. Only call this
+/// once, from `init`.
+pub fn keymap(layers: &Layers) -> &'static KeymapLegends {
+    let legends = unsafe { &mut KEYMAP_LEGENDS };
+    for (layer, rows) in layers.iter().enumerate() {
+        for (row, actions) in rows.iter().enumerate() {
+            for (col, action) in actions.iter().enumerate() {
+                // Transparent keys do whatever the base layer does
+                legends[layer][row][col] = match action {
+                    Action::Trans if layer > 0 => self::action(&layers[0][row][col]),
+                    action => self::action(action),
+                };
+            }
+        }
+    }
+    legends
+}
+
+pub fn action(action: &Action<CustomActions>) -> &'static str {
+    match action {
+        Action::MultipleKeyCodes(kcs) => {
+            let shifted = kcs.iter().any(|&kc| kc == LShift || kc == RShift);
+            match kcs.iter().rev().find(|&&kc| legend::modifier_bit(kc as u8).is_none()) {
+                Some(&kc) if shifted => legend::shifted(kc as u8).unwrap_or_else(|| keycode(kc)),
+                Some(&kc) => keycode(kc),
+                None => "Mods",
+            }
+        }
+        Action::MultipleActions(actions) => actions.iter().map(self::action).find(|l| !l.is_empty()).unwrap_or(""),
+        Action::HoldTap { tap, .. } => self::action(tap),
+        // Anything the protocol can't express comes back as `Other`, `?`
+        action => legend::action(to_protocol(action)),
+    }
+}
+
+/// At most five characters, `?` for keys without one
+fn keycode(kc: KeyCode) -> &'static str {
+    legend::usage(kc as u8)
+}
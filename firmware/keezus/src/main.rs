@@ -2,16 +2,33 @@
 #![no_std]
 
 mod layout;
+mod backlight;
+mod canvas;
 mod delay;
+mod flash;
+mod framebuffer;
+mod game;
+mod hid;
+mod host;
+mod keyboard;
+mod keymap;
+mod latency;
+mod lcd_dma;
+mod legend;
+mod multicore;
+mod pet;
+mod rawhid;
+mod render;
+mod rtc;
+mod settings;
+mod st7735;
+mod stats;
+mod usb;
+
+// The matrix size is shared with the display, see keezus-ui
+use keezus_ui::{NUM_COLS, NUM_ROWS};
+use keezus_protocol::keymap::LAYERS as NUM_LAYERS;
 
-const NUM_COLS: usize = 10;
-const NUM_ROWS: usize = 4;
-const NUM_LAYERS: usize = 1;
-
-pub struct Graphics{
-    x: i32,
-    y: i32,
-}  
 
 /// The linker will place this boot block at the start of our program image. We
 /// need this to help the ROM bootloader get our code up and running.
@@ -40,7 +57,7 @@ mod app {
         pac::{I2C0, PIO0, RESETS, SPI0, CorePeripherals},
         pio::{PIOExt, SM0, SM1},
         sio::Sio,
-        timer::{Alarm3, Timer, Alarm, Alarm2},
+        timer::{Alarm3, Timer, Alarm},
         usb::UsbBus,
         watchdog::Watchdog,
     };
@@ -49,26 +66,24 @@ mod app {
         timer::CountDown,
     };
 
-    // lcd traits
-    use embedded_graphics::image::{Image, ImageRaw, ImageRawLE};
-    use embedded_graphics::prelude::*;
-    use embedded_graphics::pixelcolor::Rgb565;
-    use embedded_graphics::geometry::Point;
-    use st7735_lcd;
-    use st7735_lcd::Orientation;
     use embedded_time::rate::Hertz;
 
     use core::iter::once;
 
     use crate::delay::RP2040TimerDelay;
+    use keezus_ui::UiEvent;
+    use keezus_ui::bootloader::{BootloaderConfirm, Prompt};
+    use keezus_ui::keys::KeyRouter;
+    use keezus_ui::screens::Notification;
     use crate::{NUM_COLS, NUM_ROWS, NUM_LAYERS};
-    use crate::Graphics;
 
 
     use crate::layout as kb_layout;
     use keyberon::debounce::Debouncer;
-    use keyberon::key_code;
+    use keyberon::key_code::KeyCode;
     use keyberon::layout::{ Event, Layout};
+    use keezus_protocol::keymap::LAYER_NAMES;
+    use keezus_protocol::{Request, Response, REPORT_LEN};
 
     use usb_device::class::UsbClass;
     use usb_device::class_prelude::UsbBusAllocator;
@@ -81,22 +96,22 @@ mod app {
     use asm_delay::AsmDelay;
     use asm_delay::bitrate::U32BitrateExt;
 
-    // const SCAN_TIME_US: u32 = 1000;
-    const SCAN_TIME_US: u32 = 2000;
-
-    const DISPLAY_UPDATE_TIME_US: u32 = 1700;
-    // const DISPLAY_UPDATE_TIME_US: u32 = 3400;
+    // The matrix scan period follows the USB polling interval in the settings
+    const DEBOUNCE_TIME_US: u32 = 20_000;
 
     const EXTERNAL_XTAL_FREQ_HZ: u32 = 12_000_000u32;
     static mut USB_BUS: Option<UsbBusAllocator<UsbBus>> = None;
+    static mut USB_SERIAL: [u8; crate::usb::SERIAL_LEN] = [0; crate::usb::SERIAL_LEN];
 
-    const SCREEN_WIDTH: u32 = 128;
-    const SCREEN_HEIGHT: u32 = 160;
+    /// Upper bound on keycodes held at once, the layout can emit more than
+    /// one per key so this is a bit more than the 40 keys on the board
+    const MAX_KEYCODES: usize = 64;
   
     #[shared]
     struct Shared {
         usb_dev: usb_device::device::UsbDevice<'static, UsbBus>,
-        usb_class: keyberon::Class<'static, UsbBus, ()>,
+        usb_class: crate::hid::HidClass<'static, UsbBus, crate::keyboard::Keyboard>,
+        raw_hid: crate::hid::HidClass<'static, UsbBus, crate::rawhid::RawHid>,
         timer: Timer,
         alarm: Alarm3,
         #[lock_free]
@@ -104,17 +119,22 @@ mod app {
         layout: Layout<NUM_COLS, NUM_ROWS, NUM_LAYERS, kb_layout::CustomActions>,
         #[lock_free]
         debouncer: Debouncer<[[bool; NUM_COLS]; NUM_ROWS]>,
+        settings: crate::settings::Settings,
+        latency: crate::latency::LatencyStats,
+        stats: crate::stats::Recorder,
         #[lock_free]
         watchdog: Watchdog,
-        #[lock_free]
-        display: st7735_lcd::ST7735<rp2040_hal::Spi<rp2040_hal::spi::Enabled,SPI0,8> , rp2040_hal::gpio::Pin<Gpio16,rp2040_hal::gpio::Output<rp2040_hal::gpio::PushPull>> , rp2040_hal::gpio::Pin<Gpio14,rp2040_hal::gpio::Output<rp2040_hal::gpio::PushPull>>>,
-        displayAlarm: Alarm2,
-        #[lock_free]
-        graphics: Graphics
     }
 
     #[local]
-    struct Local {}
+    struct Local {
+        scan_time_us: u32,
+        keymap_staging: crate::keymap::Staging,
+        ui_events: crate::render::UiEvents,
+        keymap: &'static crate::keymap::Layers,
+        pet: crate::pet::Keeper,
+        game: crate::game::GameMode,
+    }
 
 
     #[init]
@@ -135,6 +155,8 @@ mod app {
         .ok()
         .unwrap();
 
+        crate::rtc::init(&mut resets, clocks.rtc_clock.freq().0);
+
         let sio = Sio::new(c.device.SIO);
         let pins = rp2040_hal::gpio::Pins::new(
             c.device.IO_BANK0,
@@ -143,17 +165,26 @@ mod app {
             &mut resets,
         );
 
+        let settings = crate::settings::Settings::load();
+        let keymap = crate::keymap::load();
+        let scan_time_us = settings.scan_time_us();
+
         let mut timer = Timer::new(c.device.TIMER, &mut resets);
         let mut alarm = timer.alarm_3().unwrap();
-        let _ = alarm.schedule(SCAN_TIME_US.microseconds());
+        let _ = alarm.schedule(scan_time_us.microseconds());
         alarm.enable_interrupt();
-        let mut displayAlarm = timer.alarm_2().unwrap();
-        let _ = displayAlarm.schedule(DISPLAY_UPDATE_TIME_US.microseconds());
-        displayAlarm.enable_interrupt();
 
 
         let (mut pio, sm0, sm1, _, _) = c.device.PIO0.split(&mut resets);
 
+        // Needs to happen while interrupts are still off, as XIP is
+        // disabled while the flash chip is being talked to
+        let flash_id = crate::flash::unique_id();
+        let serial = unsafe {
+            crate::usb::serial_from_id(&flash_id, &mut USB_SERIAL);
+            core::str::from_utf8_unchecked(&USB_SERIAL)
+        };
+
         let usb_bus = UsbBusAllocator::new(UsbBus::new(
             c.device.USBCTRL_REGS,
             c.device.USBCTRL_DPRAM,
@@ -166,8 +197,14 @@ mod app {
             USB_BUS = Some(usb_bus);
         }
 
-        let usb_class = keyberon::new_class(unsafe { USB_BUS.as_ref().unwrap() }, ());
-        let usb_dev = keyberon::new_device(unsafe { USB_BUS.as_ref().unwrap() });
+        let usb_class = crate::hid::HidClass::new(
+            crate::keyboard::Keyboard::new(),
+            unsafe { USB_BUS.as_ref().unwrap() },
+            settings.poll_interval_ms,
+        );
+        // Raw HID interval doesn't matter much, it's not latency sensitive
+        let raw_hid = crate::hid::HidClass::new(crate::rawhid::RawHid::new(), unsafe { USB_BUS.as_ref().unwrap() }, 10);
+        let usb_dev = crate::usb::new_device(unsafe { USB_BUS.as_ref().unwrap() }, serial);
 
         let matrix = keyberon::matrix::Matrix::new(
             [
@@ -217,7 +254,6 @@ mod app {
         //let _spi_miso = pins.gpio4.into_mode::<rp2040_hal::gpio::FunctionSpi>();
         let spi = rp2040_hal::Spi::<_, _, 8>::new(c.device.SPI0);
 
-        let mut lcd_led = pins.gpio15.into_push_pull_output();
         let dc = pins.gpio16.into_push_pull_output();
         let rst = pins.gpio14.into_push_pull_output();
 
@@ -229,7 +265,7 @@ mod app {
             &embedded_hal::spi::MODE_0,
         );
 
-        let mut display = st7735_lcd::ST7735::new(spi, dc, rst, true, false, SCREEN_WIDTH, SCREEN_HEIGHT);
+        let mut display = crate::st7735::St7735::new(spi, dc, rst, true, false);
         
 
         // Cannot use SYST as RTIC has already taken this
@@ -249,21 +285,19 @@ mod app {
         // lcd_led.set_high().unwrap();
 
         display.init(&mut delay).unwrap();
-        display.set_orientation(&Orientation::PortraitSwapped).unwrap();
-        display.clear(Rgb565::BLACK).unwrap();
-        display.set_offset(0, 0);
-
-        // let image_raw: ImageRawLE<Rgb565> =
-        //     ImageRaw::new(include_bytes!("../assets/ferris.raw"), 86);
-
-        // let image: Image<_> = Image::new(&image_raw, Point::new(24, 28));
-
-        // image.draw(&mut display).unwrap();
-        
-        // Wait until the background and image have been rendered otherwise
-        // the screen will show random pixels for a brief moment
-
-        lcd_led.set_high().unwrap();
+        crate::framebuffer::clear_panel(&mut display, crate::framebuffer::WIDTH, crate::framebuffer::HEIGHT).unwrap();
+        // Core 1 sets the orientation and offsets up from here
+        crate::render::configure(&settings);
+        let lcd_dma = crate::lcd_dma::init(&mut resets);
+
+        // The backlight stays off until core 1 takes over the display, by
+        // which time it has been cleared rather than showing random pixels
+        let mut pwm_slices = rp2040_hal::pwm::Slices::new(c.device.PWM, &mut resets);
+        pwm_slices.pwm7.enable();
+        let mut lcd_led = pwm_slices.pwm7.channel_b;
+        lcd_led.output_to(pins.gpio15);
+        crate::backlight::configure(&settings);
+        let backlight = crate::backlight::Backlight::new(lcd_led);
 
         // start watchdog after initialization
         // It needs to be fairly high though to account for screen drawing etc
@@ -271,149 +305,329 @@ mod app {
         watchdog.start(1_000_000.microseconds());
 
 
+        // From here on the display belongs to core 1
+        let pet = crate::pet::Keeper::load(timer.get_counter());
+        let mut ui = keezus_ui::UiState::new(LAYER_NAMES[0], settings.poll_interval_ms);
+        ui.pet = pet.pet();
+        let game = crate::game::GameMode::load();
+        ui.high_score = game.high_score();
+        let stats = crate::stats::Recorder::load(timer.get_counter());
+        let screens = keezus_ui::Screens::new(crate::legend::keymap(keymap));
+        let mut psm = c.device.PSM;
+        let mut ppb = c.device.PPB;
+        let mut fifo = sio.fifo;
+        let ui_events = crate::render::start(display, lcd_dma, backlight, screens, ui, &mut psm, &mut ppb, &mut fifo);
+
         (
             Shared {
                 usb_dev,
                 usb_class,
+                raw_hid,
                 timer,
                 alarm,
                 matrix: matrix.unwrap(),
-                debouncer: Debouncer::new([[false; NUM_COLS]; NUM_ROWS], [[false; NUM_COLS]; NUM_ROWS], 10),
-                layout: Layout::new(&kb_layout::LAYERS),
+                debouncer: Debouncer::new(
+                    [[false; NUM_COLS]; NUM_ROWS],
+                    [[false; NUM_COLS]; NUM_ROWS],
+                    (DEBOUNCE_TIME_US / scan_time_us) as u16,
+                ),
+                settings,
+                latency: crate::latency::LatencyStats::new(),
+                stats,
+                layout: Layout::new(keymap),
                 watchdog,
-                display,
-                displayAlarm,
-                graphics: crate::Graphics{x:0,y:0}
             },
-            Local {},
+            Local {
+                scan_time_us,
+                keymap_staging: crate::keymap::Staging::new(keymap),
+                ui_events,
+                keymap,
+                pet,
+                game,
+            },
             init::Monotonics(),
         )
     }
 
-    #[task(binds = USBCTRL_IRQ, priority = 4, shared = [usb_dev, usb_class])]
+    #[task(binds = USBCTRL_IRQ, priority = 4, shared = [usb_dev, usb_class, raw_hid])]
     fn usb_rx(c: usb_rx::Context) {
         let usb = c.shared.usb_dev;
         let kb = c.shared.usb_class;
-        (usb, kb).lock(|usb, kb| {
-            if usb.poll(&mut [kb]) {
-                kb.poll();
+        let raw = c.shared.raw_hid;
+        (usb, kb, raw).lock(|usb, kb, raw| {
+            usb.poll(&mut [kb, raw]);
+            // Another go at a response the IN endpoint was too busy for,
+            // this interrupt comes round again once the endpoint is free
+            if let Some(response) = raw.device_mut().take_response() {
+                if raw.write(&response).is_err() {
+                    raw.device_mut().keep_response(response);
+                }
+            }
+            if let Some(request) = raw.device_mut().take_request() {
+                // Dropped if the previous ones haven't been handled yet, the
+                // host will time out and retry
+                let _ = handle_host_request::spawn(request);
             }
         });
     }
 
-    #[task(priority = 2, capacity = 8, shared = [usb_dev, usb_class, layout])]
+    #[task(priority = 3, capacity = 4, shared = [raw_hid, settings, timer, stats, latency], local = [keymap_staging])]
+    fn handle_host_request(mut c: handle_host_request::Context, packet: [u8; REPORT_LEN]) {
+        let response = match Request::decode(&packet) {
+            Ok(Request::StreamEvents(enable)) => {
+                c.shared.raw_hid.lock(|raw| raw.device_mut().set_stream_events(enable));
+                Response::Ack(keezus_protocol::Command::StreamEvents)
+            }
+            Ok(request @ (Request::CanvasShow(_)
+            | Request::CanvasFill(..)
+            | Request::CanvasBlit(_)
+            | Request::CanvasPixels(_)
+            | Request::CanvasText(_))) => {
+                crate::canvas::with(|canvas| crate::host::handle_canvas(request, canvas))
+            }
+            Ok(request @ (Request::GetStats
+            | Request::ReadKeyCounts { .. }
+            | Request::ReadDayCounts { .. }
+            | Request::ResetStats)) => {
+                let now = c.shared.timer.lock(|t| t.get_counter());
+                c.shared.stats.lock(|stats| crate::host::handle_stats(request, stats, now))
+            }
+            Ok(Request::GetLatency) => Response::Latency(c.shared.latency.lock(|stats| stats.summary())),
+            Ok(request) => {
+                let keymap = c.local.keymap_staging;
+                c.shared.settings.lock(|settings| crate::host::handle(request, keymap, settings))
+            }
+            Err(e) => match keezus_protocol::Command::from_u8(packet[0]) {
+                Ok(command) => Response::Error(command, e.into()),
+                // Nothing sensible to answer with, let the host time out
+                Err(_) => return,
+            },
+        };
+
+        let mut buf = [0u8; REPORT_LEN];
+        response.encode(&mut buf);
+        c.shared.raw_hid.lock(|raw| {
+            // Most likely a key event still in the endpoint, usb_rx sends
+            // the response once it's gone
+            if raw.write(&buf).is_err() {
+                raw.device_mut().keep_response(buf);
+            }
+        });
+    }
+
+    #[task(
+        priority = 2,
+        capacity = 8,
+        shared = [usb_dev, usb_class, raw_hid, layout, timer, settings, latency, stats],
+        local = [
+            ui_events,
+            keymap,
+            pet,
+            game,
+            router: KeyRouter = KeyRouter::new(),
+            last_report_us: u64 = 0,
+            bootloader: BootloaderConfirm = BootloaderConfirm::new(),
+            restart_at_us: Option<u64> = None,
+            bootloader_at_us: Option<u64> = None,
+        ]
+    )]
     fn handle_event(mut c: handle_event::Context, event: Option<Event>) {
         let mut layout = c.shared.layout;
         match event {
-            None => {
-                if let keyberon::layout::CustomEvent::Press(event) = layout.lock(|l| l.tick()) {
-                    match event {
-                        kb_layout::CustomActions::Bootloader => {
-                            rp2040_hal::rom_data::reset_to_usb_boot(0, 0);
-                        }
-                    };
-                }
-            }
+            None => match layout.lock(|l| l.tick()) {
+                keyberon::layout::CustomEvent::Press(event) => match event {
+                    // Only once it's confirmed, see below
+                    kb_layout::CustomActions::Bootloader => {
+                        let now = c.shared.timer.lock(|t| t.get_counter());
+                        c.local.bootloader.press(now);
+                    }
+                    kb_layout::CustomActions::NextScreen => {
+                        c.local.ui_events.enqueue(UiEvent::NextScreen).ok();
+                    }
+                    kb_layout::CustomActions::GameMode => {
+                        let ui_events = &mut *c.local.ui_events;
+                        c.local.router.enter_game(|event| {
+                            ui_events.enqueue(event).ok();
+                        });
+                    }
+                    kb_layout::CustomActions::BrightnessUp => {
+                        let level = c.shared.settings.lock(|s| {
+                            s.brightness = (s.brightness + 1).min(keezus_protocol::BRIGHTNESS_LEVELS);
+                            crate::backlight::configure(s);
+                            s.brightness
+                        });
+                        c.local.ui_events.enqueue(UiEvent::Notify(Notification::Brightness(level))).ok();
+                    }
+                    kb_layout::CustomActions::BrightnessDown => {
+                        let level = c.shared.settings.lock(|s| {
+                            s.brightness = s.brightness.saturating_sub(1).max(1);
+                            crate::backlight::configure(s);
+                            s.brightness
+                        });
+                        c.local.ui_events.enqueue(UiEvent::Notify(Notification::Brightness(level))).ok();
+                    }
+                    _ => (),
+                },
+                keyberon::layout::CustomEvent::Release(event) => match event {
+                    kb_layout::CustomActions::Bootloader => {
+                        let now = c.shared.timer.lock(|t| t.get_counter());
+                        c.local.bootloader.release(now);
+                    }
+                    // Done on release so the key isn't seen as pressed
+                    // again after the reset
+                    kb_layout::CustomActions::CycleReportRate => {
+                        let ms = c.shared.settings.lock(|s| {
+                            s.poll_interval_ms = s.next_poll_interval_ms();
+                            s.save();
+                            s.poll_interval_ms
+                        });
+                        // The endpoint interval is only read by the host on
+                        // enumeration, so reset to apply it. Not straight
+                        // away, or the board just seems to crash
+                        c.local.ui_events.enqueue(UiEvent::Notify(Notification::ReportRate(ms))).ok();
+                        let now = c.shared.timer.lock(|t| t.get_counter());
+                        *c.local.restart_at_us = Some(now + keezus_ui::screens::NOTIFICATION_US);
+                    }
+                    // Saved once the key is let go, rather than on every
+                    // step while it's being tapped
+                    kb_layout::CustomActions::BrightnessUp | kb_layout::CustomActions::BrightnessDown => {
+                        c.shared.settings.lock(|s| s.save());
+                    }
+                    _ => (),
+                },
+                keyberon::layout::CustomEvent::NoEvent => (),
+            },
             Some(e) => {
-                layout.lock(|l| l.event(e));
+                let (row, col) = e.coord();
+                let layer = layout.lock(|l| l.current_layer());
+                let action = crate::keymap::to_protocol(&c.local.keymap[layer][row as usize][col as usize]);
+                // Dropped if the display is falling behind, which can leave a
+                // key lit up until it's pressed again
+                let ui_events = &mut *c.local.ui_events;
+                let route = c.local.router.key(action, row, col, e.is_press(), |event| {
+                    ui_events.enqueue(event).ok();
+                });
+                if route.left_game {
+                    c.local.game.save_high_score();
+                }
+                if route.layout {
+                    layout.lock(|l| l.event(e));
+                }
+                if route.count {
+                    let now = c.shared.timer.lock(|t| t.get_counter());
+                    c.shared.stats.lock(|stats| stats.key_pressed(row, col, now));
+                    if let Some(pet) = c.local.pet.key_pressed() {
+                        ui_events.enqueue(UiEvent::Pet(pet)).ok();
+                    }
+                }
+                if !route.host {
+                    return;
+                }
+                c.shared.raw_hid.lock(|raw| {
+                    if raw.device().stream_events() {
+                        let mut buf = [0u8; REPORT_LEN];
+                        Response::KeyEvent { row, col, pressed: e.is_press() }.encode(&mut buf);
+                        // Best effort, events are dropped if the host isn't keeping up
+                        raw.write(&buf).ok();
+                    }
+                });
                 return;
             }
         }       
 
-        let report: key_code::KbHidReport = layout.lock(|l| l.keycodes().collect());
-        if !c
-            .shared  
+        let now = c.shared.timer.lock(|t| t.get_counter());
+        if let Some(pet) = c.local.pet.update(now) {
+            c.local.ui_events.enqueue(UiEvent::Pet(pet)).ok();
+        }
+        c.shared.stats.lock(|stats| stats.update(now));
+        if c.local.restart_at_us.is_some_and(|at| now >= at) {
+            cortex_m::peripheral::SCB::sys_reset();
+        }
+        if crate::host::bootloader_requested() && c.local.bootloader_at_us.is_none() {
+            *c.local.bootloader_at_us = Some(now + keezus_ui::screens::NOTIFICATION_US);
+        }
+        if c.local.bootloader_at_us.is_some_and(|at| now >= at) {
+            rp2040_hal::rom_data::reset_to_usb_boot(0, 0);
+        }
+        match c.local.bootloader.update(now) {
+            Some(Prompt::Reboot) => rp2040_hal::rom_data::reset_to_usb_boot(0, 0),
+            Some(prompt) => {
+                c.local.ui_events.enqueue(UiEvent::Notify(Notification::Bootloader(prompt))).ok();
+            }
+            None => (),
+        }
+
+        let (keycodes, layer): (heapless::Vec<KeyCode, MAX_KEYCODES>, usize) =
+            layout.lock(|l| (l.keycodes().take(MAX_KEYCODES).collect(), l.current_layer()));
+        let (changed, idle_ms, leds) = c
+            .shared
             .usb_class
-            .lock(|k| k.device_mut().set_keyboard_report(report.clone()))
-        {
+            .lock(|k| (k.device_mut().set_keycodes(&keycodes), k.idle_ms(), k.device().leds()));
+
+        // Only what the screens show, worked out here so core 1 never has
+        // to touch the layout
+        let ui_events = c.local.ui_events;
+        let caps_lock = leds & crate::keyboard::LED_CAPS_LOCK != 0;
+        let usages = keycodes.iter().map(|&kc| kc as u8);
+        c.local.router.layout_ticked(usages, layer, caps_lock, |event| {
+            ui_events.enqueue(event).ok();
+        });
+
+        // With a non zero idle rate (SET_IDLE) the host expects the report
+        // to be repeated even if nothing changed
+        let idle_expired = idle_ms != 0 && now - *c.local.last_report_us >= idle_ms as u64 * 1000;
+        if !changed && !idle_expired {
             return;
         }
         if c.shared.usb_dev.lock(|d| d.state()) != UsbDeviceState::Configured {
             return;
         }
-        while let Ok(0) = c.shared.usb_class.lock(|k| k.write(report.as_bytes())) {}
-    }
-
-    #[task(binds = TIMER_IRQ_2, priority = 1, shared = [ display, displayAlarm, graphics ])]
-    fn screen_update_irq(c: screen_update_irq::Context) {
-        // please ignore some of this sloppy code
-        // i am a good coder irl i pinky promise
-
-        let mut alarm = c.shared.displayAlarm;
-
-        let display = c.shared.display;
-        let graphics = c.shared.graphics;
-
-        let image_raw: ImageRawLE<Rgb565> =
-        ImageRaw::new(include_bytes!("../assets/ferris.raw"), 86);
-
-        let image: Image<_> = Image::new(&image_raw, Point::new(0, graphics.y));
-        
-        //display.clear(Rgb565::BLACK).unwrap();
-
-        //let style = embedded_graphics::mono_font::MonoTextStyle::new(, Rgb565::WHITE);
-        let styleBlack = embedded_graphics::mono_font::MonoTextStyle::new(&embedded_graphics::mono_font::ascii::FONT_8X13_BOLD, Rgb565::BLACK);
-
-        let textStyleWhite = embedded_graphics::mono_font::MonoTextStyleBuilder::new()
-            .font(&embedded_graphics::mono_font::ascii::FONT_8X13_BOLD)
-            .text_color(Rgb565::WHITE)
-            .background_color(Rgb565::BLACK)
-            .build();
-        let textStyleRed = embedded_graphics::mono_font::MonoTextStyleBuilder::new()
-            .font(&embedded_graphics::mono_font::jis_x0201::FONT_10X20)
-            .text_color(Rgb565::RED)
-            .background_color(Rgb565::BLACK)
-            .build();
-
-        let FONT_BUFFER = 13;
-        // let NUM_LINES = 9;
-        let NUM_LINES = 8;
-        let TOTAL_HEIGHT = SCREEN_HEIGHT as i32+FONT_BUFFER;
-
-        //embedded_graphics::text::Text::new("Hello Rust!", Point::new(20, graphics.y-1), styleBlack).draw(display);
-        for i in 0..NUM_LINES {
-            //graphics.y += (( i/NUM_LINES) * 200);
-            let newY = (graphics.y+(((SCREEN_HEIGHT as i32+FONT_BUFFER)/(NUM_LINES))*i))%(SCREEN_HEIGHT as i32+FONT_BUFFER);// %(SCREEN_HEIGHT as i32+FONT_BUFFER);
-            let style = if i == 0 {textStyleRed} else {textStyleWhite};
-            //let text1 = if i == 0 {"! 「システム"} else {"! SYSTEM"}; 
-            let text1 = if i == 0 {"! ｼｽﾃﾑ"} else {"! SYSTEM"}; 
-            //let text2 = if i == 0 {"パニック」!"} else {"PANIC !"};
-            let text2 = if i == 0 {"ﾊﾟﾆｯｸ!"} else {"PANIC !"};
-            // let text = if i%2==0 {"hocus"} else {"pocus"};
-            embedded_graphics::text::Text::new(text1, Point::new(0, TOTAL_HEIGHT-newY), style).draw(display);
-            embedded_graphics::text::Text::new(text2, Point::new(70, newY), style).draw(display);
-        }
-
-        
-        graphics.y = (graphics.y + 1)%(SCREEN_HEIGHT as i32+FONT_BUFFER);
-
-        //image.draw(display).unwrap();
-
-        alarm.lock(|a| {
-            a.clear_interrupt();
-            let _ = a.schedule(DISPLAY_UPDATE_TIME_US.microseconds());
+        *c.local.last_report_us = now;
+        while let Ok(0) = c.shared.usb_class.lock(|k| k.write_report()) {}
+
+        let now = c.shared.timer.lock(|t| t.get_counter());
+        c.shared.latency.lock(|stats| {
+            if stats.on_report(now) && stats.samples % 256 == 0 {
+                defmt::info!(
+                    "matrix to report latency: min {}us avg {}us max {}us",
+                    stats.min_us,
+                    stats.average_us(),
+                    stats.max_us
+                );
+            }
         });
-
     }
 
-
-    #[task(binds = TIMER_IRQ_3, priority = 2, shared = [ matrix, debouncer, timer, alarm, watchdog, usb_dev, usb_class])]
+    #[task(
+        binds = TIMER_IRQ_3,
+        priority = 2,
+        shared = [ matrix, debouncer, timer, alarm, watchdog, usb_dev, usb_class, latency],
+        local = [scan_time_us]
+    )]
     fn scan_timer_irq(mut c: scan_timer_irq::Context) {
 
 
         c.shared.watchdog.feed();
 
-        for event in c.shared.debouncer.events(c.shared.matrix.get().unwrap()) {
+        let raw = c.shared.matrix.get().unwrap();
+        let raw_changed = raw != *c.shared.debouncer.get();
+        let mut events = false;
+        for event in c.shared.debouncer.events(raw) {
+            events = true;
             handle_event::spawn(Some(event)).unwrap();
         }
 
+        let now = c.shared.timer.lock(|t| t.get_counter());
+        c.shared.latency.lock(|stats| stats.on_scan(now, raw_changed, events));
+
         handle_event::spawn(None).unwrap();
 
         let mut alarm = c.shared.alarm;
+        let scan_time_us = *c.local.scan_time_us;
 
         alarm.lock(|a| {
             a.clear_interrupt();
-            let _ = a.schedule(SCAN_TIME_US.microseconds());
+            let _ = a.schedule(scan_time_us.microseconds());
         });
     }
 }
\ No newline at end of file
@@ -0,0 +1,80 @@
+//! Keeping core 1 out of the way of flash writes.
+//!
+//! Core 1 runs the display (see render.rs) straight from flash, which
+//! vanishes while core 0 erases or programs it. Before doing so core 0
+//! asks core 1 over the SIO FIFO to park itself in a RAM loop, and lets it
+//! go again afterwards.
+//!
+//! The FIFO is only used for this once core 1 is up, the UI has its own
+//! queue. Core 0 has several priorities that write flash, so the whole
+//! handshake runs with interrupts off: a task preempting another halfway
+//! through would take its `PAUSED`, or have its `PAUSE` swallowed, and
+//! leave one of them waiting on the FIFO forever.
+
+use core::sync::atomic::{AtomicBool, Ordering};
+
+// SIO FIFO registers, banked per core
+const SIO_FIFO_ST: *const u32 = 0xd000_0050 as *const u32;
+const SIO_FIFO_WR: *mut u32 = 0xd000_0054 as *mut u32;
+const SIO_FIFO_RD: *const u32 = 0xd000_0058 as *const u32;
+
+const FIFO_ST_VLD: u32 = 1 << 0;
+const FIFO_ST_RDY: u32 = 1 << 1;
+
+const PAUSE: u32 = 0x5041_5553;
+const PAUSED: u32 = 0x5041_5344;
+const RESUME: u32 = 0x5245_534d;
+
+static CORE1_RUNNING: AtomicBool = AtomicBool::new(false);
+
+/// Call once core 1 has been launched and is polling `poll_lockout`
+pub fn core1_started() {
+    CORE1_RUNNING.store(true, Ordering::Release);
+}
+
+#[inline(always)]
+unsafe fn fifo_push(word: u32) {
+    while SIO_FIFO_ST.read_volatile() & FIFO_ST_RDY == 0 {}
+    SIO_FIFO_WR.write_volatile(word);
+}
+
+#[inline(always)]
+unsafe fn fifo_pop() -> u32 {
+    while SIO_FIFO_ST.read_volatile() & FIFO_ST_VLD == 0 {}
+    SIO_FIFO_RD.read_volatile()
+}
+
+/// Runs `f` on core 0 with core 1 parked in RAM and interrupts disabled.
+/// Not reentrant: `f` must not call `lockout` itself, core 1 would never
+/// answer the second `PAUSE`.
+pub fn lockout<R>(f: impl FnOnce() -> R) -> R {
+    cortex_m::interrupt::free(|_| {
+        if !CORE1_RUNNING.load(Ordering::Acquire) {
+            return f();
+        }
+        unsafe {
+            fifo_push(PAUSE);
+            while fifo_pop() != PAUSED {}
+        }
+        let result = f();
+        unsafe { fifo_push(RESUME) };
+        result
+    })
+}
+
+/// Called from core 1's main loop, parks it if core 0 asked for it
+pub fn poll_lockout() {
+    unsafe {
+        if SIO_FIFO_ST.read_volatile() & FIFO_ST_VLD != 0 && SIO_FIFO_RD.read_volatile() == PAUSE {
+            cortex_m::interrupt::free(|_| wait_for_resume());
+        }
+    }
+}
+
+/// Must not touch flash until core 0 says so, hence RAM and raw registers
+#[inline(never)]
+#[link_section = ".data.ram_func"]
+unsafe fn wait_for_resume() {
+    fifo_push(PAUSED);
+    while fifo_pop() != RESUME {}
+}
@@ -0,0 +1,68 @@
+//! Keeps the virtual pet from `keezus_ui::pet` alive on core 0: counts
+//! keypresses into it, ticks it along with the timer and saves it to flash
+//! every so often, so it's still there after the board has been unplugged.
+//! A save waits for a tick without any typing, so the pause it causes isn't
+//! felt.
+
+use keezus_ui::pet::{Pet, TICK_US};
+
+use crate::flash;
+
+const MAGIC: [u8; 4] = *b"KZPT";
+const VERSION: u8 = 1;
+/// Ticks between saves, the pet can lose this much of its life to a
+/// power cut
+const SAVE_EVERY_TICKS: u16 = 10;
+
+pub struct Keeper {
+    pet: Pet,
+    next_tick_us: u64,
+    ticks_since_save: u16,
+    typed_this_tick: bool,
+}
+
+impl Keeper {
+    /// Picks up the pet saved in flash, or hatches a new one
+    pub fn load(now_us: u64) -> Keeper {
+        let pet = flash::read_record(flash::PET_SECTOR, MAGIC)
+            .filter(|record| record.version == VERSION)
+            .and_then(|record| Pet::from_bytes(record.payload()));
+        Keeper {
+            pet: pet.unwrap_or_default(),
+            next_tick_us: now_us + TICK_US,
+            ticks_since_save: 0,
+            typed_this_tick: false,
+        }
+    }
+
+    pub fn pet(&self) -> Pet {
+        self.pet
+    }
+
+    /// Returns the pet if it changed in a way that should be shown now
+    pub fn key_pressed(&mut self) -> Option<Pet> {
+        self.typed_this_tick = true;
+        self.pet.key_pressed().then_some(self.pet)
+    }
+
+    /// Ticks the pet if it's time, returning it if it did
+    pub fn update(&mut self, now_us: u64) -> Option<Pet> {
+        if now_us < self.next_tick_us {
+            return None;
+        }
+        self.next_tick_us += TICK_US;
+        self.pet.tick();
+
+        self.ticks_since_save = self.ticks_since_save.saturating_add(1);
+        if self.ticks_since_save >= SAVE_EVERY_TICKS && !self.typed_this_tick {
+            self.save();
+        }
+        self.typed_this_tick = false;
+        Some(self.pet)
+    }
+
+    fn save(&mut self) {
+        flash::write_record(flash::PET_SECTOR, MAGIC, VERSION, &self.pet.to_bytes());
+        self.ticks_since_save = 0;
+    }
+}
@@ -0,0 +1,103 @@
+//! The vendor defined raw HID interface host tools talk to, see the
+//! keezus-protocol crate for what goes over it.
+//!
+//! There is no interrupt OUT endpoint, hosts send their reports with
+//! SET_REPORT on the control pipe (which is what hidapi falls back to).
+
+use keezus_protocol::REPORT_LEN;
+
+use crate::hid::{HidDevice, Protocol, ProtocolMode, ReportType, Subclass};
+
+#[rustfmt::skip]
+const REPORT_DESCRIPTOR: &[u8] = &[
+    0x06, 0x60, 0xFF, // Usage Page (Vendor Defined 0xFF60)
+    0x09, 0x61,       // Usage (0x61)
+    0xA1, 0x01,       // Collection (Application)
+    0x09, 0x62,       //   Usage (0x62)
+    0x15, 0x00,       //   Logical Minimum (0)
+    0x26, 0xFF, 0x00, //   Logical Maximum (255)
+    0x95, REPORT_LEN as u8, // Report Count
+    0x75, 0x08,       //   Report Size (8)
+    0x81, 0x02,       //   Input (Data, Variable, Absolute)
+    0x09, 0x63,       //   Usage (0x63)
+    0x15, 0x00,       //   Logical Minimum (0)
+    0x26, 0xFF, 0x00, //   Logical Maximum (255)
+    0x95, REPORT_LEN as u8, // Report Count
+    0x75, 0x08,       //   Report Size (8)
+    0x91, 0x02,       //   Output (Data, Variable, Absolute)
+    0xC0,             // End Collection
+];
+
+pub struct RawHid {
+    request: Option<[u8; REPORT_LEN]>,
+    /// A response the IN endpoint had no room for yet
+    response: Option<[u8; REPORT_LEN]>,
+    stream_events: bool,
+}
+
+impl RawHid {
+    pub fn new() -> RawHid {
+        RawHid {
+            request: None,
+            response: None,
+            stream_events: false,
+        }
+    }
+
+    /// The last report the host sent, if it hasn't been handled yet
+    pub fn take_request(&mut self) -> Option<[u8; REPORT_LEN]> {
+        self.request.take()
+    }
+
+    /// Holds on to a response until the IN endpoint is free, replacing any
+    /// older one, which the host has stopped waiting for if it has sent
+    /// another request
+    pub fn keep_response(&mut self, response: [u8; REPORT_LEN]) {
+        self.response = Some(response);
+    }
+
+    pub fn take_response(&mut self) -> Option<[u8; REPORT_LEN]> {
+        self.response.take()
+    }
+
+    /// Whether key events should be sent to the host as they happen
+    pub fn stream_events(&self) -> bool {
+        self.stream_events
+    }
+
+    pub fn set_stream_events(&mut self, enable: bool) {
+        self.stream_events = enable;
+    }
+}
+
+impl HidDevice for RawHid {
+    fn subclass(&self) -> Subclass {
+        Subclass::None
+    }
+
+    fn protocol(&self) -> Protocol {
+        Protocol::None
+    }
+
+    fn max_packet_size(&self) -> u16 {
+        REPORT_LEN as u16
+    }
+
+    fn report_descriptor(&self) -> &[u8] {
+        REPORT_DESCRIPTOR
+    }
+
+    fn set_report(&mut self, report_type: ReportType, _report_id: u8, data: &[u8]) -> bool {
+        if report_type != ReportType::Output || data.is_empty() || data.len() > REPORT_LEN {
+            return false;
+        }
+        let mut request = [0u8; REPORT_LEN];
+        request[..data.len()].copy_from_slice(data);
+        self.request = Some(request);
+        true
+    }
+
+    fn get_report(&mut self, _mode: ProtocolMode, _report_type: ReportType, _report_id: u8) -> Option<&[u8]> {
+        None
+    }
+}
@@ -0,0 +1,295 @@
+//! Everything the display shows, run on core 1 so drawing never competes
+//! with matrix scanning and USB on core 0.
+//!
+//! Core 0 hands over the display and friends in `init` and from then on
+//! only talks to core 1 through the `UiEvent` queue, the host canvas (see
+//! canvas.rs), the display and screensaver settings (see backlight.rs and
+//! `configure`), notifications from host requests (see `notify`), the
+//! typing statistics (see stats.rs) and the flash lockout (see
+//! multicore.rs).
+
+use core::sync::atomic::{AtomicU16, AtomicU8, Ordering};
+
+use heapless::spsc::{Consumer, Producer, Queue};
+use keezus_protocol::ShortText;
+use keezus_ui::screens::{Notification, ScreensaverConfig};
+use keezus_ui::{Screens, UiEvent, UiState, DISPLAY_UPDATE_TIME_US};
+use rp2040_hal::gpio::bank0::{Gpio14, Gpio16};
+use rp2040_hal::gpio::{Output, Pin, PushPull};
+use rp2040_hal::multicore::{Multicore, Stack};
+use rp2040_hal::pac;
+use rp2040_hal::sio::SioFifo;
+
+use crate::backlight::Backlight;
+use crate::canvas;
+use crate::framebuffer::{self, FrameBuffer, PixelSink};
+use crate::lcd_dma::LcdDma;
+use crate::settings::Settings;
+use crate::st7735::{Orientation, St7735};
+
+pub type Display = St7735<
+    rp2040_hal::Spi<rp2040_hal::spi::Enabled, pac::SPI0, 8>,
+    Pin<Gpio16, Output<PushPull>>,
+    Pin<Gpio14, Output<PushPull>>,
+>;
+
+/// Bands of the host canvas sent to the display per frame
+const CANVAS_BANDS_PER_FRAME: usize = 2;
+
+const UI_QUEUE_LEN: usize = 32;
+pub type UiEvents = Producer<'static, UiEvent, UI_QUEUE_LEN>;
+
+static mut UI_QUEUE: Queue<UiEvent, UI_QUEUE_LEN> = Queue::new();
+static mut CORE1_STACK: Stack<2048> = Stack::new();
+static mut RENDERER: Option<Renderer> = None;
+
+static ORIENTATION: AtomicU8 = AtomicU8::new(0);
+static OFFSET_X: AtomicU8 = AtomicU8::new(0);
+static OFFSET_Y: AtomicU8 = AtomicU8::new(0);
+static SCREENSAVER_MIN: AtomicU8 = AtomicU8::new(0);
+static SCREENSAVER: AtomicU8 = AtomicU8::new(0);
+/// Too big for an atomic, behind `TextLock`
+static mut SCREENSAVER_TEXT: ShortText = ShortText::EMPTY;
+/// Waiting for core 1 to pick up, behind `NotificationLock`
+static mut NOTIFICATION: Option<Notification> = None;
+/// Best game score seen on the display, only ever written by core 1
+static HIGH_SCORE: AtomicU16 = AtomicU16::new(0);
+
+type TextLock = rp2040_hal::sio::Spinlock3;
+type NotificationLock = rp2040_hal::sio::Spinlock4;
+
+/// Passes the orientation, panel offsets and screensaver settings on to
+/// core 1, call whenever they change. Only from `init` and host requests,
+/// so core 0 never spins on the text lock it already holds.
+pub fn configure(settings: &Settings) {
+    ORIENTATION.store(settings.orientation, Ordering::Relaxed);
+    OFFSET_X.store(settings.panel_offset_x, Ordering::Relaxed);
+    OFFSET_Y.store(settings.panel_offset_y, Ordering::Relaxed);
+    SCREENSAVER_MIN.store(settings.screensaver_min, Ordering::Relaxed);
+    SCREENSAVER.store(settings.screensaver, Ordering::Relaxed);
+    let _lock = TextLock::claim();
+    unsafe { SCREENSAVER_TEXT = settings.screensaver_text };
+}
+
+fn screensaver() -> ScreensaverConfig {
+    let text = {
+        let _lock = TextLock::claim();
+        unsafe { SCREENSAVER_TEXT }
+    };
+    ScreensaverConfig {
+        after_min: SCREENSAVER_MIN.load(Ordering::Relaxed),
+        which: SCREENSAVER.load(Ordering::Relaxed),
+        text,
+    }
+}
+
+/// Pops a notification up over the display. For host requests, which
+/// can't get at the `UiEvent` queue, and only from them so core 0 never
+/// spins on the lock it already holds. `handle_event` sends
+/// `UiEvent::Notify` instead.
+pub fn notify(notification: Notification) {
+    let _lock = NotificationLock::claim();
+    unsafe { NOTIFICATION = Some(notification) };
+}
+
+fn take_notification() -> Option<Notification> {
+    let _lock = NotificationLock::claim();
+    unsafe { NOTIFICATION.take() }
+}
+
+/// The best score from game mode since boot, see game.rs
+pub fn high_score() -> u16 {
+    HIGH_SCORE.load(Ordering::Relaxed)
+}
+
+/// How the panel is set up
+#[derive(Clone, Copy, PartialEq, Eq)]
+struct Panel {
+    /// Even ones are portrait, odd ones landscape
+    orientation: u8,
+    offset: (u8, u8),
+}
+
+impl Panel {
+    /// What the settings ask for, except that the host canvas is always
+    /// shown in portrait
+    fn wanted(canvas_visible: bool) -> Panel {
+        let orientation = ORIENTATION.load(Ordering::Relaxed) % keezus_protocol::ORIENTATIONS;
+        Panel {
+            orientation: if canvas_visible { orientation & !1 } else { orientation },
+            offset: (OFFSET_X.load(Ordering::Relaxed), OFFSET_Y.load(Ordering::Relaxed)),
+        }
+    }
+
+    fn size(&self) -> (usize, usize) {
+        match self.orientation & 1 {
+            0 => (framebuffer::WIDTH, framebuffer::HEIGHT),
+            _ => (framebuffer::HEIGHT, framebuffer::WIDTH),
+        }
+    }
+
+    fn st7735(&self) -> Orientation {
+        match self.orientation {
+            0 => Orientation::Portrait,
+            1 => Orientation::Landscape,
+            2 => Orientation::PortraitSwapped,
+            _ => Orientation::LandscapeSwapped,
+        }
+    }
+}
+
+struct Renderer {
+    display: Display,
+    framebuffer: &'static mut FrameBuffer,
+    lcd_dma: LcdDma,
+    backlight: Backlight,
+    screens: Screens,
+    state: UiState,
+    events: Consumer<'static, UiEvent, UI_QUEUE_LEN>,
+    canvas_shown: bool,
+    /// Set up on the first frame
+    panel: Option<Panel>,
+    band: [u16; canvas::WIDTH * canvas::BAND_HEIGHT],
+}
+
+/// Reads the free running microsecond timer without owning it
+fn now_us() -> u64 {
+    let timer = unsafe { &*pac::TIMER::ptr() };
+    loop {
+        let high = timer.timerawh.read().bits();
+        let low = timer.timerawl.read().bits();
+        if timer.timerawh.read().bits() == high {
+            return (high as u64) << 32 | low as u64;
+        }
+    }
+}
+
+/// Launches core 1 with everything it needs to drive the display, and
+/// returns the sending end of its event queue. Only call this once, from
+/// `init`.
+#[allow(clippy::too_many_arguments)]
+pub fn start(
+    display: Display,
+    lcd_dma: LcdDma,
+    backlight: Backlight,
+    screens: Screens,
+    state: UiState,
+    psm: &mut pac::PSM,
+    ppb: &mut pac::PPB,
+    fifo: &mut SioFifo,
+) -> UiEvents {
+    let (producer, consumer) = unsafe { UI_QUEUE.split() };
+    unsafe {
+        RENDERER = Some(Renderer {
+            display,
+            framebuffer: crate::framebuffer::take(),
+            lcd_dma,
+            backlight,
+            screens,
+            state,
+            events: consumer,
+            canvas_shown: false,
+            panel: None,
+            band: [0; canvas::WIDTH * canvas::BAND_HEIGHT],
+        });
+    }
+
+    let mut multicore = Multicore::new(psm, ppb, fifo);
+    let core1 = &mut multicore.cores()[1];
+    core1.spawn(core1_main, unsafe { &mut CORE1_STACK.mem }).unwrap();
+    crate::multicore::core1_started();
+
+    producer
+}
+
+fn core1_main() -> ! {
+    let renderer = unsafe { RENDERER.as_mut().unwrap() };
+    let mut next_frame = now_us();
+    loop {
+        crate::multicore::poll_lockout();
+        let now = now_us();
+        while let Some(event) = renderer.events.dequeue() {
+            if let UiEvent::Key { .. } = event {
+                renderer.backlight.activity(now);
+            }
+            renderer.state.apply(event);
+        }
+        if let Some(notification) = take_notification() {
+            renderer.state.apply(UiEvent::Notify(notification));
+        }
+
+        if renderer.lcd_dma.busy() {
+            renderer.lcd_dma.poll(renderer.framebuffer, &mut renderer.display).ok();
+            continue;
+        }
+        // Nothing is drawn while the panel sleeps, it keeps what it showed
+        if !renderer.backlight.poll(now) {
+            continue;
+        }
+        if now < next_frame {
+            continue;
+        }
+        next_frame = now + DISPLAY_UPDATE_TIME_US;
+        renderer.frame(now);
+    }
+}
+
+impl Renderer {
+    fn frame(&mut self, now: u64) {
+        let canvas_visible = canvas::with(|canvas| canvas.visible());
+        let panel = Panel::wanted(canvas_visible);
+        if canvas_visible != self.canvas_shown || Some(panel) != self.panel {
+            if Some(panel) != self.panel {
+                self.set_up_panel(panel);
+            }
+            if canvas_visible {
+                // Always portrait
+                framebuffer::clear_panel(&mut self.display, framebuffer::WIDTH, framebuffer::HEIGHT).unwrap();
+                canvas::with(|canvas| canvas.invalidate());
+            } else {
+                self.screens.redraw();
+                self.framebuffer.invalidate();
+            }
+            self.canvas_shown = canvas_visible;
+        }
+
+        if canvas_visible {
+            // Core 0 draws into the canvas on host requests, only hold it
+            // long enough to copy a band out
+            for _ in 0..CANVAS_BANDS_PER_FRAME {
+                let band = &mut self.band;
+                let y = match canvas::with(|canvas| canvas.take_dirty_band(band)) {
+                    Some(y) => y,
+                    None => break,
+                };
+                let (y, last_row) = (y as u16, (y + canvas::BAND_HEIGHT - 1) as u16);
+                self.display
+                    .set_pixels(0, y, canvas::WIDTH as u16 - 1, last_row, band.iter().copied())
+                    .unwrap();
+            }
+            return;
+        }
+
+        self.state.now_us = now;
+        self.state.time = crate::rtc::now();
+        self.state.stats = crate::stats::latest();
+        self.state.screensaver = screensaver();
+        self.screens.tick(&self.state, self.framebuffer).ok();
+        // No compare and swap on the M0+, but core 1 is the only writer
+        let best = self.screens.high_score().max(HIGH_SCORE.load(Ordering::Relaxed));
+        HIGH_SCORE.store(best, Ordering::Relaxed);
+        if !self.framebuffer.is_clean() {
+            self.lcd_dma.start(self.framebuffer, &mut self.display).ok();
+        }
+    }
+
+    /// Screens lay themselves out from the framebuffer's size, so they
+    /// follow along on their next redraw
+    fn set_up_panel(&mut self, panel: Panel) {
+        self.display.set_orientation(panel.st7735()).unwrap();
+        self.display.set_offset(panel.offset.0 as u16, panel.offset.1 as u16);
+        let (width, height) = panel.size();
+        self.framebuffer.resize(width, height);
+        self.panel = Some(panel);
+    }
+}
@@ -0,0 +1,68 @@
+//! The RP2040's real time clock, which the host sets over raw HID. It runs
+//! from clk_rtc and forgets the time whenever the board resets, so `now` is
+//! `None` until the host has set it.
+//!
+//! rp2040-hal doesn't have an RTC driver yet, so this goes to the registers
+//! directly. Core 1 reads the time while drawing and core 0 sets it, but a
+//! read of the two time registers is a single snapshot as long as RTC_0 is
+//! read first, so there's nothing to lock.
+
+use keezus_protocol::DateTime;
+use rp2040_hal::pac;
+
+/// Takes the RTC out of reset and makes it count seconds of clk_rtc, which
+/// runs at `clock_hz`. Only call this once, from `init`.
+pub fn init(resets: &mut pac::RESETS, clock_hz: u32) {
+    resets.reset.modify(|_, w| w.rtc().clear_bit());
+    while resets.reset_done.read().rtc().bit_is_clear() {}
+
+    rtc().clkdiv_m1.write(|w| unsafe { w.bits(clock_hz - 1) });
+}
+
+fn rtc() -> &'static pac::rtc::RegisterBlock {
+    unsafe { &*pac::RTC::ptr() }
+}
+
+/// Sets the clock and starts it if it wasn't running. `time` has to be
+/// valid, see `DateTime::is_valid`.
+pub fn set(time: &DateTime) {
+    let rtc = rtc();
+    // The setup registers are only loaded while the clock is stopped
+    rtc.ctrl.modify(|_, w| w.rtc_enable().clear_bit());
+    while rtc.ctrl.read().rtc_active().bit_is_set() {}
+
+    rtc.setup_0.write(|w| unsafe { w.year().bits(time.year).month().bits(time.month).day().bits(time.day) });
+    rtc.setup_1.write(|w| unsafe {
+        w.dotw()
+            .bits(time.weekday)
+            .hour()
+            .bits(time.hour)
+            .min()
+            .bits(time.minute)
+            .sec()
+            .bits(time.second)
+    });
+    rtc.ctrl.modify(|_, w| w.load().set_bit());
+    rtc.ctrl.modify(|_, w| w.rtc_enable().set_bit());
+    while rtc.ctrl.read().rtc_active().bit_is_clear() {}
+}
+
+/// The current time, if it has been set since the board powered up
+pub fn now() -> Option<DateTime> {
+    let rtc = rtc();
+    if rtc.ctrl.read().rtc_active().bit_is_clear() {
+        return None;
+    }
+    // Reading RTC_0 latches RTC_1, so the two halves always match
+    let low = rtc.rtc_0.read();
+    let high = rtc.rtc_1.read();
+    Some(DateTime {
+        year: high.year().bits(),
+        month: high.month().bits(),
+        day: high.day().bits(),
+        weekday: low.dotw().bits(),
+        hour: low.hour().bits(),
+        minute: low.min().bits(),
+        second: low.sec().bits(),
+    })
+}
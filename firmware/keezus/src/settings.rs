@@ -0,0 +1,138 @@
+//! User settings persisted in flash.
+//!
+//! The layout is a small header followed by the settings fields in a fixed
+//! order. Fields are only ever appended, so a payload written by an older
+//! firmware is still readable and any missing fields fall back to their
+//! defaults.
+
+use keezus_protocol::{SettingId, ShortText, BRIGHTNESS_LEVELS};
+use keezus_ui::screens::DEFAULT_AFTER_MIN;
+
+use crate::flash;
+
+const MAGIC: [u8; 4] = *b"KZST";
+const VERSION: u8 = 1;
+
+/// Supported USB polling intervals, the matrix is scanned at the same rate
+pub use keezus_protocol::POLL_INTERVALS_MS;
+const DEFAULT_POLL_INTERVAL_MS: u8 = 2;
+const DEFAULT_DISPLAY_TIMEOUT_MIN: u8 = 5;
+/// Portrait, upside down, the way the board has always had it
+const DEFAULT_ORIENTATION: u8 = 2;
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub struct Settings {
+    /// HID endpoint interval, also used as the matrix scan period
+    pub poll_interval_ms: u8,
+    /// Backlight level, 1 to `BRIGHTNESS_LEVELS`
+    pub brightness: u8,
+    /// Minutes without a keypress before the display dims, then sleeps. 0
+    /// keeps it on.
+    pub display_timeout_min: u8,
+    /// Below `ORIENTATIONS`, see `render::configure`
+    pub orientation: u8,
+    /// Start of the visible area in the panel's memory
+    pub panel_offset_x: u8,
+    pub panel_offset_y: u8,
+    /// Minutes on the status screen without a keypress before the
+    /// screensaver starts, 0 for never
+    pub screensaver_min: u8,
+    /// Below `SCREENSAVERS`, 0 takes each in turn
+    pub screensaver: u8,
+    /// The scrolling text screensaver's text, empty for the default
+    pub screensaver_text: ShortText,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Settings {
+            poll_interval_ms: DEFAULT_POLL_INTERVAL_MS,
+            brightness: BRIGHTNESS_LEVELS,
+            display_timeout_min: DEFAULT_DISPLAY_TIMEOUT_MIN,
+            orientation: DEFAULT_ORIENTATION,
+            panel_offset_x: 0,
+            panel_offset_y: 0,
+            screensaver_min: DEFAULT_AFTER_MIN,
+            screensaver: 0,
+            screensaver_text: ShortText::EMPTY,
+        }
+    }
+}
+
+impl Settings {
+    /// Reads the settings from flash, falling back to the defaults if
+    /// nothing valid has been saved yet
+    pub fn load() -> Settings {
+        flash::read_record(flash::SETTINGS_SECTOR, MAGIC)
+            .filter(|record| record.version <= VERSION)
+            .map(|record| Settings::from_payload(record.payload()))
+            .unwrap_or_default()
+    }
+
+    pub fn save(&self) {
+        let mut payload = [0u8; flash::RECORD_MAX];
+        let len = self.to_payload(&mut payload);
+        flash::write_record(flash::SETTINGS_SECTOR, MAGIC, VERSION, &payload[..len]);
+    }
+
+    pub fn scan_time_us(&self) -> u32 {
+        self.poll_interval_ms as u32 * 1000
+    }
+
+    /// The next entry in `POLL_INTERVALS_MS`, wrapping around
+    pub fn next_poll_interval_ms(&self) -> u8 {
+        let current = POLL_INTERVALS_MS.iter().position(|&ms| ms == self.poll_interval_ms).unwrap_or(0);
+        POLL_INTERVALS_MS[(current + 1) % POLL_INTERVALS_MS.len()]
+    }
+
+    fn from_payload(payload: &[u8]) -> Settings {
+        let mut settings = Settings::default();
+        let mut fields = payload.iter().copied();
+
+        // The fields go in `SettingId` order, anything out of range keeps
+        // its default
+        let settings_fields = [
+            &mut settings.poll_interval_ms,
+            &mut settings.brightness,
+            &mut settings.display_timeout_min,
+            &mut settings.orientation,
+            &mut settings.panel_offset_x,
+            &mut settings.panel_offset_y,
+            &mut settings.screensaver_min,
+            &mut settings.screensaver,
+        ];
+        for ((id, setting), value) in SettingId::ALL.iter().zip(settings_fields).zip(fields.by_ref()) {
+            if let Some(value) = id.check(value as u16) {
+                *setting = value;
+            }
+        }
+        // The text is its length then its bytes
+        if let Some(len) = fields.next() {
+            let text = &payload[payload.len() - fields.len()..];
+            if let Some(text) = text.get(..len as usize).and_then(|text| core::str::from_utf8(text).ok()) {
+                settings.screensaver_text = ShortText::new(text);
+            }
+        }
+
+        settings
+    }
+
+    /// Returns the number of bytes written
+    fn to_payload(&self, buf: &mut [u8]) -> usize {
+        let fields = [
+            self.poll_interval_ms,
+            self.brightness,
+            self.display_timeout_min,
+            self.orientation,
+            self.panel_offset_x,
+            self.panel_offset_y,
+            self.screensaver_min,
+            self.screensaver,
+        ];
+        buf[..fields.len()].copy_from_slice(&fields);
+        let text = self.screensaver_text.as_str().as_bytes();
+        buf[fields.len()] = text.len() as u8;
+        buf[fields.len() + 1..fields.len() + 1 + text.len()].copy_from_slice(text);
+        fields.len() + 1 + text.len()
+    }
+}
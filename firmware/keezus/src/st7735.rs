@@ -0,0 +1,166 @@
+//! Just enough of an ST7735 driver for the display: the init sequence,
+//! orientation, offsets and address windows. Pixels mostly go out with
+//! DMA (see lcd_dma.rs), which only needs an address window set up first.
+//!
+//! The init sequence is the usual one for the red tab 1.8" panels, the
+//! same the st7735-lcd crate sends.
+
+use embedded_hal::blocking::delay::DelayMs;
+use embedded_hal::blocking::spi;
+use embedded_hal::digital::v2::OutputPin;
+
+use crate::framebuffer::PixelSink;
+
+const SWRESET: u8 = 0x01;
+pub const SLPIN: u8 = 0x10;
+pub const SLPOUT: u8 = 0x11;
+const INVOFF: u8 = 0x20;
+const INVON: u8 = 0x21;
+const DISPON: u8 = 0x29;
+const CASET: u8 = 0x2a;
+const RASET: u8 = 0x2b;
+const RAMWR: u8 = 0x2c;
+const MADCTL: u8 = 0x36;
+const COLMOD: u8 = 0x3a;
+const FRMCTR1: u8 = 0xb1;
+const FRMCTR2: u8 = 0xb2;
+const FRMCTR3: u8 = 0xb3;
+const INVCTR: u8 = 0xb4;
+const PWCTR1: u8 = 0xc0;
+const PWCTR2: u8 = 0xc1;
+const PWCTR3: u8 = 0xc2;
+const PWCTR4: u8 = 0xc3;
+const PWCTR5: u8 = 0xc4;
+const VMCTR1: u8 = 0xc5;
+
+/// MADCTL bit for panels wired BGR
+const MADCTL_BGR: u8 = 0x08;
+/// COLMOD value for 16 bit RGB565
+const COLMOD_16BIT: u8 = 0x05;
+
+/// MADCTL row/column order for each way up
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u8)]
+pub enum Orientation {
+    Portrait = 0x00,
+    Landscape = 0x60,
+    PortraitSwapped = 0xc0,
+    LandscapeSwapped = 0xa0,
+}
+
+pub struct St7735<SPI, DC, RST> {
+    spi: SPI,
+    dc: DC,
+    rst: RST,
+    /// False for panels wired BGR
+    rgb: bool,
+    inverted: bool,
+    /// Where the visible area starts in the panel's memory
+    dx: u16,
+    dy: u16,
+}
+
+impl<SPI, DC, RST> St7735<SPI, DC, RST>
+where
+    SPI: spi::Write<u8>,
+    DC: OutputPin,
+    RST: OutputPin,
+{
+    pub fn new(spi: SPI, dc: DC, rst: RST, rgb: bool, inverted: bool) -> Self {
+        St7735 {
+            spi,
+            dc,
+            rst,
+            rgb,
+            inverted,
+            dx: 0,
+            dy: 0,
+        }
+    }
+
+    /// Resets the panel and sets it up for RGB565 in portrait
+    pub fn init<D: DelayMs<u8>>(&mut self, delay: &mut D) -> Result<(), ()> {
+        self.rst.set_high().map_err(|_| ())?;
+        delay.delay_ms(10);
+        self.rst.set_low().map_err(|_| ())?;
+        delay.delay_ms(10);
+        self.rst.set_high().map_err(|_| ())?;
+
+        self.command(SWRESET, &[])?;
+        delay.delay_ms(200);
+        self.command(SLPOUT, &[])?;
+        delay.delay_ms(200);
+        self.command(FRMCTR1, &[0x01, 0x2c, 0x2d])?;
+        self.command(FRMCTR2, &[0x01, 0x2c, 0x2d])?;
+        self.command(FRMCTR3, &[0x01, 0x2c, 0x2d, 0x01, 0x2c, 0x2d])?;
+        self.command(INVCTR, &[0x07])?;
+        self.command(PWCTR1, &[0xa2, 0x02, 0x84])?;
+        self.command(PWCTR2, &[0xc5])?;
+        self.command(PWCTR3, &[0x0a, 0x00])?;
+        self.command(PWCTR4, &[0x8a, 0x2a])?;
+        self.command(PWCTR5, &[0x8a, 0xee])?;
+        self.command(VMCTR1, &[0x0e])?;
+        self.command(if self.inverted { INVON } else { INVOFF }, &[])?;
+        self.set_orientation(Orientation::Portrait)?;
+        self.command(COLMOD, &[COLMOD_16BIT])?;
+        self.command(DISPON, &[])?;
+        delay.delay_ms(200);
+        Ok(())
+    }
+
+    pub fn set_orientation(&mut self, orientation: Orientation) -> Result<(), ()> {
+        let bgr = if self.rgb { 0 } else { MADCTL_BGR };
+        self.command(MADCTL, &[orientation as u8 | bgr])
+    }
+
+    /// Applies to the address windows set up from here on
+    pub fn set_offset(&mut self, dx: u16, dy: u16) {
+        self.dx = dx;
+        self.dy = dy;
+    }
+
+    /// Sends a command and its parameters, leaving DC high for whatever
+    /// data follows
+    fn command(&mut self, command: u8, params: &[u8]) -> Result<(), ()> {
+        self.dc.set_low().map_err(|_| ())?;
+        self.spi.write(&[command]).map_err(|_| ())?;
+        self.dc.set_high().map_err(|_| ())?;
+        if !params.is_empty() {
+            self.spi.write(params).map_err(|_| ())?;
+        }
+        Ok(())
+    }
+
+    /// Sets the window the next pixels go to and starts a memory write.
+    /// Coordinates are inclusive.
+    fn set_address_window(&mut self, sx: u16, sy: u16, ex: u16, ey: u16) -> Result<(), ()> {
+        let (sx, ex, sy, ey) = (sx + self.dx, ex + self.dx, sy + self.dy, ey + self.dy);
+        let [sx_hi, sx_lo] = sx.to_be_bytes();
+        let [ex_hi, ex_lo] = ex.to_be_bytes();
+        self.command(CASET, &[sx_hi, sx_lo, ex_hi, ex_lo])?;
+        let [sy_hi, sy_lo] = sy.to_be_bytes();
+        let [ey_hi, ey_lo] = ey.to_be_bytes();
+        self.command(RASET, &[sy_hi, sy_lo, ey_hi, ey_lo])?;
+        self.command(RAMWR, &[])
+    }
+}
+
+impl<SPI, DC, RST> PixelSink for St7735<SPI, DC, RST>
+where
+    SPI: spi::Write<u8>,
+    DC: OutputPin,
+    RST: OutputPin,
+{
+    type Error = ();
+
+    fn set_pixels<P>(&mut self, sx: u16, sy: u16, ex: u16, ey: u16, colors: P) -> Result<(), ()>
+    where
+        P: IntoIterator<Item = u16>,
+    {
+        self.set_address_window(sx, sy, ex, ey)?;
+        for color in colors {
+            self.spi.write(&color.to_be_bytes()).map_err(|_| ())?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,117 @@
+//! Typing statistics from `keezus_ui::stats`, kept on core 0: counts the
+//! presses that reach the layout, follows the wall clock for the daily
+//! counts and saves the counts to flash now and then. The host reads them
+//! through host.rs.
+//!
+//! Core 1 shows them on the stats screen from a copy published here every
+//! so often, behind a hardware spinlock. Only `Recorder::update`, from
+//! handle_event, publishes, so core 0 has one context using the lock like
+//! canvas.rs asks for.
+
+use keezus_ui::stats::Stats;
+
+use crate::flash;
+
+const MAGIC: [u8; 4] = *b"KZTS";
+const VERSION: u8 = 1;
+
+/// How often core 1 gets a fresh copy
+const PUBLISH_EVERY_US: u64 = 100_000;
+/// How often the day is looked up on the clock
+const CHECK_DAY_EVERY_US: u64 = 1_000_000;
+/// Counts are saved at most this often, a power cut loses at most this
+/// much typing
+const SAVE_EVERY_US: u64 = 10 * 60 * 1_000_000;
+/// and only once the typing has stopped for this long
+const SAVE_AFTER_IDLE_US: u64 = 5_000_000;
+
+static mut PUBLISHED: Stats = Stats::new();
+
+type StatsLock = rp2040_hal::sio::Spinlock2;
+
+/// The counts as last published, for core 1
+pub fn latest() -> Stats {
+    let _lock = StatsLock::claim();
+    unsafe { PUBLISHED }
+}
+
+fn publish(stats: &Stats) {
+    let _lock = StatsLock::claim();
+    unsafe { PUBLISHED = *stats };
+}
+
+pub struct Recorder {
+    stats: Stats,
+    /// Counts changed since the last save
+    dirty: bool,
+    last_press_us: u64,
+    last_save_us: u64,
+    next_publish_us: u64,
+    next_day_check_us: u64,
+}
+
+impl Recorder {
+    /// Picks up the counts saved in flash, or starts from nothing
+    pub fn load(now_us: u64) -> Recorder {
+        let stats = flash::read_record(flash::STATS_SECTOR, MAGIC)
+            .filter(|record| record.version == VERSION)
+            .and_then(|record| Stats::from_bytes(record.payload()))
+            .unwrap_or_default();
+        publish(&stats);
+        Recorder {
+            stats,
+            dirty: false,
+            last_press_us: now_us,
+            last_save_us: now_us,
+            next_publish_us: now_us,
+            next_day_check_us: now_us,
+        }
+    }
+
+    pub fn stats(&self) -> &Stats {
+        &self.stats
+    }
+
+    pub fn key_pressed(&mut self, row: u8, col: u8, now_us: u64) {
+        self.stats.key_pressed(row, col);
+        self.dirty = true;
+        self.last_press_us = now_us;
+    }
+
+    /// Clears the counts, in flash too
+    pub fn reset(&mut self, now_us: u64) {
+        self.stats.reset();
+        self.save(now_us);
+    }
+
+    /// Call on every tick of the layout
+    pub fn update(&mut self, now_us: u64) {
+        self.stats.tick(now_us);
+
+        if now_us >= self.next_day_check_us {
+            self.next_day_check_us = now_us + CHECK_DAY_EVERY_US;
+            let day = crate::rtc::now().map(|time| time.to_unix().div_euclid(86_400) as u32);
+            // A new day is saved straight away so yesterday's counts stay
+            // with yesterday
+            if day.is_some_and(|day| self.stats.set_day(day)) {
+                self.save(now_us);
+            }
+        }
+
+        let idle = now_us >= self.last_press_us + SAVE_AFTER_IDLE_US;
+        if self.dirty && idle && now_us >= self.last_save_us + SAVE_EVERY_US {
+            self.save(now_us);
+        }
+
+        if now_us >= self.next_publish_us {
+            self.next_publish_us = now_us + PUBLISH_EVERY_US;
+            publish(&self.stats);
+        }
+    }
+
+    fn save(&mut self, now_us: u64) {
+        flash::write_record(flash::STATS_SECTOR, MAGIC, VERSION, &self.stats.to_bytes());
+        self.dirty = false;
+        self.last_save_us = now_us;
+    }
+}
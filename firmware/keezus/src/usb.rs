@@ -0,0 +1,37 @@
+//! USB device identity for the keezyboost40.
+//!
+//! The VID/PID default to the shared V-USB keyboard pair that keyberon uses,
+//! and can be overridden at build time with the `KEEZUS_USB_VID` and
+//! `KEEZUS_USB_PID` environment variables (either exported in the shell or
+//! set in the `[env]` section of `.cargo/config.toml`). See `build.rs`.
+
+use usb_device::bus::{UsbBus, UsbBusAllocator};
+use usb_device::device::{UsbDevice, UsbDeviceBuilder, UsbVidPid};
+
+use crate::flash::UNIQUE_ID_LEN;
+
+include!(concat!(env!("OUT_DIR"), "/usb_ids.rs"));
+
+pub const MANUFACTURER: &str = "sporewoh";
+pub const PRODUCT: &str = "keezyboost40";
+
+pub const SERIAL_LEN: usize = UNIQUE_ID_LEN * 2;
+
+/// Formats the flash unique ID as an upper case hex string, which is what
+/// the host will see as the device's serial number.
+pub fn serial_from_id(id: &[u8; UNIQUE_ID_LEN], buf: &mut [u8; SERIAL_LEN]) {
+    const HEX: &[u8; 16] = b"0123456789ABCDEF";
+    for (byte, out) in id.iter().zip(buf.chunks_exact_mut(2)) {
+        out[0] = HEX[(byte >> 4) as usize];
+        out[1] = HEX[(byte & 0xf) as usize];
+    }
+}
+
+pub fn new_device<'a, B: UsbBus>(bus: &'a UsbBusAllocator<B>, serial: &'a str) -> UsbDevice<'a, B> {
+    UsbDeviceBuilder::new(bus, UsbVidPid(USB_VID, USB_PID))
+        .manufacturer(MANUFACTURER)
+        .product(PRODUCT)
+        .serial_number(serial)
+        .device_release(USB_DEVICE_RELEASE)
+        .build()
+}